@@ -2,10 +2,28 @@
 
 set -eu
 
+# Must come first. Docker itself fails with the same status that a
+# program can exit with, but only before this has been printed.
+echo "playground: the container started" >&2
+
 timeout=${PLAYGROUND_TIMEOUT:-10}
+grace=${PLAYGROUND_TIMEOUT_GRACE:-0}
 
 modify-cargo-toml
 
 # Don't use `exec` here. The shell is what prints out the useful
 # "Killed" message
-timeout --signal=KILL ${timeout} "$@"
+if [ "${grace}" -eq 0 ]; then
+    timeout --signal=KILL "${timeout}" "$@"
+else
+    # Ask nicely first so that the program can flush its output,
+    # then kill it if it's still running once the grace period is over
+    status=0
+    timeout --signal=TERM --kill-after="${grace}" "${timeout}" "$@" || status=$?
+
+    if [ "${status}" -eq 124 ]; then
+        echo "Terminated after running for ${timeout} seconds" >&2
+    fi
+
+    exit "${status}"
+fi
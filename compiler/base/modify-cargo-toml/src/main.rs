@@ -28,6 +28,10 @@ fn main() {
         cargo_toml = remove_dependencies(cargo_toml);
     }
 
+    if let Ok(crates) = env::var("PLAYGROUND_ALLOWED_CRATES") {
+        cargo_toml = retain_dependencies(cargo_toml, &crates);
+    }
+
     if let Ok(crate_type) = env::var("PLAYGROUND_CRATE_TYPE") {
         cargo_toml = set_crate_type(cargo_toml, &crate_type);
     }
@@ -101,6 +105,31 @@ fn remove_dependencies(cargo_toml: Value) -> Value {
     })
 }
 
+// The crates that weren't allowed can't be found by the compiler,
+// however the code refers to them. Cargo treats `-` and `_` in their
+// names as the same.
+fn retain_dependencies(cargo_toml: Value, crates: &str) -> Value {
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct CargoToml {
+        dependencies: BTreeMap<String, Value>,
+        #[serde(flatten)]
+        other: Other,
+    }
+
+    let normalize = |name: &str| name.trim().replace('-', "_");
+    let allowed: Vec<_> = crates.split(',').map(normalize).collect();
+
+    modify(cargo_toml, |mut cargo_toml: CargoToml| {
+        cargo_toml.dependencies = cargo_toml
+            .dependencies
+            .into_iter()
+            .filter(|(name, _)| allowed.contains(&normalize(name)))
+            .collect();
+        cargo_toml
+    })
+}
+
 fn set_crate_type(cargo_toml: Value, crate_type: &str) -> Value {
     #[derive(Debug, Serialize, Deserialize)]
     #[serde(rename_all = "kebab-case")]
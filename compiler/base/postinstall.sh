@@ -2,6 +2,10 @@
 
 set -eu
 
+function install_musl_target() {
+    rustup target add x86_64-unknown-linux-musl
+}
+
 function install_wasm_target() {
     rustup target add wasm32-unknown-unknown
 }
@@ -20,8 +24,15 @@ function install_wasm_gc() {
     cargo install wasm-gc
 }
 
+function install_llvm_tools() {
+    rustup component add llvm-tools-preview
+}
+
+(install_musl_target)
+
 if [[ $1 == "nightly" ]]; then
     (install_wasm_target)
     (install_wasm2wat)
     (install_wasm_gc)
+    (install_llvm_tools)
 fi
@@ -0,0 +1,68 @@
+// Preloaded into the user's program when deterministic execution is
+// requested. Every request for random bytes, through the `getrandom`
+// wrapper or the raw system call that Rust's standard library and the
+// `getrandom` crate make, is answered from a generator seeded by
+// `PLAYGROUND_SEED` instead of by the kernel. `HashMap`'s keys and
+// `rand::thread_rng` are then the same on every run with the same seed.
+
+#define _GNU_SOURCE
+#include <dlfcn.h>
+#include <stdarg.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/syscall.h>
+#include <sys/types.h>
+#include <unistd.h>
+
+static uint64_t state;
+static int seeded;
+
+// SplitMix64; only the repeatability matters, not the quality
+static uint64_t next(void) {
+    uint64_t z = (state += 0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9;
+    z = (z ^ (z >> 27)) * 0x94d049bb133111eb;
+    return z ^ (z >> 31);
+}
+
+static ssize_t fill(void *buf, size_t len) {
+    if (!seeded) {
+        const char *seed = getenv("PLAYGROUND_SEED");
+        state = seed ? strtoull(seed, NULL, 10) : 0;
+        seeded = 1;
+    }
+
+    unsigned char *out = buf;
+    for (size_t i = 0; i < len; i += sizeof(uint64_t)) {
+        uint64_t value = next();
+        size_t n = len - i < sizeof(value) ? len - i : sizeof(value);
+        memcpy(out + i, &value, n);
+    }
+
+    return len;
+}
+
+ssize_t getrandom(void *buf, size_t len, unsigned int flags) {
+    (void)flags;
+    return fill(buf, len);
+}
+
+long syscall(long number, ...) {
+    static long (*real_syscall)(long, ...);
+
+    va_list args;
+    va_start(args, number);
+    long a = va_arg(args, long), b = va_arg(args, long), c = va_arg(args, long);
+    long d = va_arg(args, long), e = va_arg(args, long), f = va_arg(args, long);
+    va_end(args);
+
+    if (number == SYS_getrandom) {
+        return fill((void *)a, (size_t)b);
+    }
+
+    if (!real_syscall) {
+        real_syscall = (long (*)(long, ...))dlsym(RTLD_NEXT, "syscall");
+    }
+    return real_syscall(number, a, b, c, d, e, f);
+}
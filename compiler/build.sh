@@ -5,6 +5,7 @@ set -euv -o pipefail
 # For Tari stuff, only nightly will work
 channels_to_build=nightly
 tools_to_build="${TOOLS_TO_BUILD-rustfmt clippy}"
+toolchains="${TOOLCHAINS-1.42.0}"
 perform_push="${PERFORM_PUSH-false}"
 
 repository="quay.io/tarilabs"
@@ -20,6 +21,7 @@ for channel in $channels_to_build; do
     docker build -t "${full_name}" \
            --cache-from "${full_name}" \
            --build-arg channel="${channel}" \
+           --build-arg toolchains="${toolchains}" \
            .
     docker tag "${full_name}" "${image_name}"
 
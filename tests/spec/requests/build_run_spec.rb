@@ -0,0 +1,48 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "build and run endpoint", type: :request do
+  let(:build_run_uri) { URI.join(Capybara.app_host, '/build-run') }
+
+  let(:request) {
+    {
+      channel: 'stable',
+      mode: 'debug',
+      crateType: 'bin',
+      tests: false,
+    }
+  }
+
+  def post_build_run(body)
+    Net::HTTP.start(build_run_uri.host, build_run_uri.port) do |http|
+      request = Net::HTTP::Post.new(build_run_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "runs the program once it compiles" do
+    response = post_build_run(request.merge(code: 'fn main() { println!("Hello, build!"); }'))
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body['phase']).to eq('run')
+    expect(body['success']).to be true
+    expect(body['compile']['success']).to be true
+    expect(body['run']['stdout']).to include('Hello, build!')
+  end
+
+  it "stops before running when the code does not compile" do
+    response = post_build_run(request.merge(code: 'fn main() { println!("Hello, build!") oops }'))
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body['phase']).to eq('compile')
+    expect(body['success']).to be false
+    expect(body['compile']['success']).to be false
+    expect(body['compile']['stderr']).to include('error')
+    expect(body).not_to have_key('run')
+  end
+end
@@ -0,0 +1,52 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "check endpoint", type: :request do
+  let(:check_uri) { URI.join(Capybara.app_host, '/check') }
+
+  # Clippy sets the `clippy` cfg, so only the plain compile has to
+  # expand the macro, which takes a few seconds
+  let(:slow_to_compile) {
+    <<~EOF
+    macro_rules! slow {
+        () => {};
+        ($head:tt $($tail:tt)*) => { slow!($($tail)*); slow!($($tail)*); };
+    }
+
+    #[cfg(not(any(clippy, feature = "cargo-clippy")))]
+    const _: () = { slow!(a a a a a a a a a a a a a a a a a a); };
+
+    fn main() {}
+    EOF
+  }
+
+  # Yields each line of the response along with when it arrived
+  def post_check(body)
+    Net::HTTP.start(check_uri.host, check_uri.port, read_timeout: 60) do |http|
+      request = Net::HTTP::Post.new(check_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request) do |response|
+        buffer = ''
+        response.read_body do |chunk|
+          buffer << chunk
+          while (line = buffer.slice!(/\A.*\n/))
+            yield JSON.parse(line), Process.clock_gettime(Process::CLOCK_MONOTONIC)
+          end
+        end
+      end
+    end
+  end
+
+  it "sends the clippy result before the compile result is finished" do
+    arrivals = {}
+    post_check(code: slow_to_compile) do |part, arrived|
+      arrivals[part['part']] = arrived
+    end
+
+    expect(arrivals.keys).to contain_exactly('compile', 'clippy', 'format')
+    expect(arrivals['clippy']).to be < arrivals['compile']
+    expect(arrivals['compile'] - arrivals['clippy']).to be > 1
+  end
+end
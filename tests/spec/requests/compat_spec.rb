@@ -0,0 +1,42 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "compatibility endpoint", type: :request do
+  let(:compat_uri) { URI.join(Capybara.app_host, '/compat') }
+
+  def post_compat(body)
+    Net::HTTP.start(compat_uri.host, compat_uri.port) do |http|
+      request = Net::HTTP::Post.new(compat_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "passes on every channel for stable code" do
+    response = post_compat(code: 'fn main() { println!("Hello, world!"); }')
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body).to include('stable' => true, 'beta' => true, 'nightly' => true)
+  end
+
+  it "only passes on nightly for code using unstable features" do
+    code = <<~EOF
+      #![feature(never_type)]
+
+      fn main() {
+          let _never: ! = panic!();
+      }
+    EOF
+
+    response = post_compat(code: code)
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body).to include('stable' => false, 'beta' => false, 'nightly' => true)
+    expect(body['channels']['stable']['stderr']).to include('E0554')
+    expect(body['channels']['nightly']['version']).to include('nightly')
+  end
+end
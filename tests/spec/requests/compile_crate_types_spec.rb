@@ -0,0 +1,53 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "compile crate types endpoint", type: :request do
+  let(:crate_types_uri) { URI.join(Capybara.app_host, '/compile/crate-types') }
+
+  let(:request) {
+    {
+      target: 'llvm-ir',
+      channel: 'stable',
+      mode: 'release',
+      tests: false,
+      crateType: 'lib',
+      crateTypes: ['rlib', 'cdylib'],
+      code: '#[no_mangle] pub extern "C" fn add_one(x: i32) -> i32 { x + 1 }',
+    }
+  }
+
+  def post_crate_types(body)
+    Net::HTTP.start(crate_types_uri.host, crate_types_uri.port) do |http|
+      request = Net::HTTP::Post.new(crate_types_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "compiles the code as each crate type" do
+    response = post_crate_types(request)
+
+    expect(response.code).to eq('200')
+    results = JSON.parse(response.body)['results']
+    expect(results.keys).to contain_exactly('rlib', 'cdylib')
+    results.each_value do |result|
+      expect(result['success']).to be true
+      expect(result['code']).to include('add_one')
+    end
+  end
+
+  it "rejects a crate type that cannot be compiled to the target" do
+    response = post_crate_types(request.merge(target: 'asm', crateTypes: ['rlib', 'proc-macro']))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/proc-macro/)
+  end
+
+  it "rejects an empty list of crate types" do
+    response = post_crate_types(request.merge(crateTypes: []))
+
+    expect(response.code).to eq('400')
+  end
+end
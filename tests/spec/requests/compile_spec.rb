@@ -0,0 +1,247 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "compile endpoint", type: :request do
+  let(:compile_uri) { URI.join(Capybara.app_host, '/compile') }
+
+  let(:request) {
+    {
+      target: 'asm',
+      channel: 'stable',
+      mode: 'debug',
+      crateType: 'bin',
+      tests: false,
+      code: 'fn main() {}',
+    }
+  }
+
+  def post_compile(body, headers = {})
+    Net::HTTP.start(compile_uri.host, compile_uri.port) do |http|
+      request = Net::HTTP::Post.new(compile_uri, headers)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "rejects generating assembly for a proc-macro crate" do
+    response = post_compile(request.merge(crateType: 'proc-macro'))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/proc-macro/)
+  end
+
+  it "describes errors as problem details when the client accepts them" do
+    response = post_compile(request.merge(crateType: 'proc-macro'), 'Accept' => 'application/problem+json')
+
+    expect(response.code).to eq('400')
+    expect(response['Content-Type']).to start_with('application/problem+json')
+
+    problem = JSON.parse(response.body)
+    expect(problem).to include('type' => 'about:blank', 'title' => 'Bad Request', 'status' => 400)
+    expect(problem['detail']).to match(/proc-macro/)
+    expect(problem).not_to have_key('error')
+  end
+
+  it "reports clippy lints along with the compilation" do
+    code = "fn main() {\n    let v = vec![1];\n    if v.len() == 0 {}\n}\n"
+    response = post_compile(request.merge(target: 'llvm-ir', withClippy: true, code: code))
+
+    expect(response.code).to eq('200')
+    json = JSON.parse(response.body)
+    expect(json['success']).to be true
+    expect(json['code']).to include('define')
+    expect(json['stderr']).to include('clippy::len_zero')
+  end
+
+  it "returns the output as a separate part when the client accepts multipart responses" do
+    response = post_compile(request.merge(target: 'llvm-ir'), 'Accept' => 'multipart/mixed')
+
+    expect(response.code).to eq('200')
+    expect(response['Content-Type']).to start_with('multipart/mixed')
+    boundary = response['Content-Type'][/boundary=(\w+)/, 1]
+
+    parts = response.body.split("--#{boundary}")[1..-2].map do |part|
+      headers, body = part.split("\r\n\r\n", 2)
+      [headers.strip, body.chomp("\r\n")]
+    end
+    expect(parts.length).to eq(2)
+
+    diagnostics_headers, diagnostics = parts[0]
+    expect(diagnostics_headers).to include('application/json')
+    expect(JSON.parse(diagnostics)['success']).to be true
+    expect(JSON.parse(diagnostics)).not_to have_key('code')
+
+    output_headers, output = parts[1]
+    expect(output_headers).to include('application/octet-stream')
+    expect(output_headers).to include('filename="playground.ll"')
+    expect(output).to include('define')
+  end
+
+  it "sends the WebAssembly module itself as the multipart output" do
+    code = '#[no_mangle] pub extern "C" fn answer() -> u32 { 42 }'
+    wasm_request = request.merge(target: 'wasm', channel: 'nightly', crateType: 'cdylib', code: code)
+    response = post_compile(wasm_request, 'Accept' => 'multipart/mixed')
+
+    expect(response.code).to eq('200')
+    boundary = response['Content-Type'][/boundary=(\w+)/, 1]
+    output_headers, output = response.body.b.split("--#{boundary}".b)[2].split("\r\n\r\n".b, 2)
+
+    expect(output_headers).to include('filename="playground.wasm"')
+    expect(output).to start_with("\0asm".b)
+  end
+
+  it "leaves out the multipart output when the build fails" do
+    response = post_compile(request.merge(target: 'llvm-ir', code: 'fn main() { missing() }'), 'Accept' => 'multipart/mixed')
+
+    expect(response.code).to eq('200')
+    boundary = response['Content-Type'][/boundary=(\w+)/, 1]
+    expect(response.body.split("--#{boundary}")[1..-2].length).to eq(1)
+  end
+
+  it "lists the active cfg values" do
+    response = post_compile(request.merge(target: 'print-cfg'))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['code']).to include('target_pointer_width')
+  end
+
+  it "warns about assembly options sent for another target" do
+    response = post_compile(request.merge(target: 'llvm-ir', assemblyFlavor: 'intel'))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['warnings']).to include('assemblyFlavor ignored for non-asm target')
+  end
+
+  it "lists the available target features" do
+    response = post_compile(request.merge(target: 'print-target-features'))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['code']).to match(/^\s*sse2\s/)
+  end
+
+  it "lists the files the crate depends on" do
+    response = post_compile(request.merge(target: 'dep-info'))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['code']).to include('src/main.rs')
+  end
+
+  it "rejects pass timings on the stable channel" do
+    response = post_compile(request.merge(channel: 'stable', timePasses: true))
+
+    expect(response.code).to eq('400')
+  end
+
+  it "rejects unknown lint groups" do
+    response = post_compile(request.merge(denyLintGroups: ['future_incompatible', 'not_a_group']))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/not_a_group/)
+  end
+
+  it "rejects compiling tests to WebAssembly" do
+    response = post_compile(request.merge(target: 'wasm', channel: 'nightly', tests: true))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/WebAssembly/)
+  end
+
+  it "issues a session for incremental compilation" do
+    response = post_compile(request.merge(incremental: true))
+
+    expect(response.code).to eq('200')
+    session = JSON.parse(response.body)['session']
+    expect(session).to match(/\A\h{32}\z/)
+
+    response = post_compile(request.merge(incremental: true, session: session))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['session']).to eq(session)
+  end
+
+  it "does not resume a session the playground did not issue" do
+    response = post_compile(request.merge(incremental: true, session: '0123456789abcdef0123456789abcdef'))
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)['session']).not_to eq('0123456789abcdef0123456789abcdef')
+  end
+
+  it "rejects a session that is not a plain name" do
+    response = post_compile(request.merge(incremental: true, session: '../escape'))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/session/)
+  end
+
+  it "rejects code that is only whitespace" do
+    response = post_compile(request.merge(code: " \n\t "))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/empty/)
+  end
+
+  it "rejects choosing a MIR stage outside of nightly" do
+    response = post_compile(request.merge(target: 'mir', mirStage: 'optimized'))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/nightly/)
+  end
+
+  it "rejects dumping MIR after a pass that is not known" do
+    response = post_compile(request.merge(target: 'mir', channel: 'nightly', mirPhase: 'NotAPass'))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/ConstProp/)
+  end
+
+  it "streams the progress of the build before the result" do
+    body = request.merge(target: 'llvm-ir', code: 'fn main() { let _: u8 = rand::random(); }')
+    response = post_compile(body, 'Accept' => 'text/event-stream')
+
+    expect(response.code).to eq('200')
+    expect(response['Content-Type']).to start_with('text/event-stream')
+
+    events = response.body.split("\n\n").map do |event|
+      fields = event.lines.map { |line| line.chomp.split(': ', 2) }.to_h
+      [fields['event'], JSON.parse(fields['data'])]
+    end
+
+    names = events.map(&:first)
+    expect(names.last).to eq('result')
+    expect(names.index('progress')).to be < names.index('result')
+    expect(events.map(&:last)).to include(a_hash_including('target' => 'rand'))
+    expect(events.last.last['success']).to be true
+  end
+
+  context "with a pinned toolchain" do
+    let(:version_uri) { URI.join(Capybara.app_host, '/meta/version/nightly') }
+
+    it "compiles with that toolchain, which the version endpoint reports" do
+      response = post_compile(request.merge(channel: 'nightly', target: 'llvm-ir', toolchainVersion: '1.42.0'))
+
+      expect(response.code).to eq('200')
+      expect(JSON.parse(response.body)['success']).to be true
+
+      uri = version_uri.dup
+      uri.query = URI.encode_www_form(toolchainVersion: '1.42.0')
+      version = JSON.parse(Net::HTTP.get_response(uri).body)
+      expect(version['version']).to eq('1.42.0')
+    end
+
+    it "rejects a version that isn't exact" do
+      response = post_compile(request.merge(toolchainVersion: 'nightly'))
+
+      expect(response.code).to eq('400')
+      expect(JSON.parse(response.body)['error']).to match(/exact version/)
+    end
+
+    it "rejects a version that isn't installed" do
+      response = post_compile(request.merge(toolchainVersion: '1.0.0'))
+
+      expect(response.code).to eq('400')
+      expect(JSON.parse(response.body)['error']).to match(/not installed/)
+    end
+  end
+end
@@ -0,0 +1,32 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "compiles endpoint", type: :request do
+  let(:compiles_uri) { URI.join(Capybara.app_host, '/compiles') }
+
+  def post_compiles(body)
+    Net::HTTP.start(compiles_uri.host, compiles_uri.port) do |http|
+      request = Net::HTTP::Post.new(compiles_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "reports that valid code compiles" do
+    response = post_compiles(code: 'fn main() { println!("Hello, world!"); }')
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body).to eq('compiles' => true)
+  end
+
+  it "reports that code with a type error does not compile" do
+    response = post_compiles(code: 'fn main() { let _: u8 = "one"; }')
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body).to eq('compiles' => false)
+  end
+end
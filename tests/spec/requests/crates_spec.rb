@@ -0,0 +1,29 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "Listing the available crates", type: :request do
+  let(:crates_uri) { URI.join(Capybara.app_host, '/meta/crates') }
+
+  it "can be sorted by name" do
+    uri = crates_uri.dup
+    uri.query = URI.encode_www_form(sort: 'name')
+
+    response = Net::HTTP.get_response(uri)
+    names = JSON.parse(response.body)['crates'].map { |c| c['name'] }
+
+    expect(names).to_not be_empty
+    expect(names).to eq(names.sort)
+  end
+
+  it "lists the exact locked versions" do
+    uri = URI.join(Capybara.app_host, '/meta/crates/versions')
+
+    response = Net::HTTP.get_response(uri)
+    crates = JSON.parse(response.body)['crates']
+    rand = crates.find { |c| c['name'] == 'rand' }
+
+    expect(rand['version']).to match(/\A\d+\.\d+\.\d+/)
+    expect(rand['source']).to start_with('registry+')
+  end
+end
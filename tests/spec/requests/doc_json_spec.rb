@@ -0,0 +1,61 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "rustdoc JSON endpoint", type: :request do
+  let(:doc_json_uri) { URI.join(Capybara.app_host, '/doc/json') }
+
+  let(:request) {
+    {
+      channel: 'nightly',
+      crateType: 'lib',
+      code: <<~EOF,
+      pub struct Point { pub x: i32, pub y: i32 }
+
+      pub fn origin() -> Point { Point { x: 0, y: 0 } }
+      EOF
+    }
+  }
+
+  def post_doc_json(body)
+    Net::HTTP.start(doc_json_uri.host, doc_json_uri.port) do |http|
+      request = Net::HTTP::Post.new(doc_json_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  let(:nightly_features) {
+    uri = URI.join(Capybara.app_host, '/meta/capabilities')
+    JSON.parse(Net::HTTP.get(uri))['nightly']['features']
+  }
+
+  it "includes the items of the crate's root module" do
+    skip "the nightly compiler has no JSON output" unless nightly_features.include?('doc-json')
+
+    response = post_doc_json(request)
+
+    expect(response.code).to eq('200')
+    body = JSON.parse(response.body)
+    expect(body['success']).to be true
+    expect(body['formatVersion']).to eq(body['doc']['format_version'])
+    expect(body['rootItems']).to include('Point', 'origin')
+  end
+
+  it "is only available on the nightly channel" do
+    response = post_doc_json(request.merge(channel: 'stable'))
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/nightly/)
+  end
+
+  it "is refused when the nightly compiler has no JSON output" do
+    skip "the nightly compiler has JSON output" if nightly_features.include?('doc-json')
+
+    response = post_doc_json(request)
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/doc-json/)
+  end
+end
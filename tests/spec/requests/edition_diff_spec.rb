@@ -0,0 +1,42 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "edition-diff endpoint", type: :request do
+  let(:edition_diff_uri) { URI.join(Capybara.app_host, '/edition-diff') }
+
+  let(:request) {
+    {
+      channel: 'stable',
+      mode: 'debug',
+      crateType: 'bin',
+      tests: false,
+      code: 'fn main() { let async = 1; println!("{}", async); }',
+    }
+  }
+
+  def post_edition_diff(body)
+    Net::HTTP.start(edition_diff_uri.host, edition_diff_uri.port) do |http|
+      request = Net::HTTP::Post.new(edition_diff_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "reports code that only works with the 2015 edition" do
+    response = post_edition_diff(request)
+
+    expect(response.code).to eq('200')
+    json = JSON.parse(response.body)
+    expect(json['divergent']).to be true
+    expect(json['runs'].map { |r| [r['edition'], r['success']] }).to eq([['2015', true], ['2018', false]])
+    expect(json['summary']).to match(/succeeds with the 2015 edition but fails with the 2018 edition/i)
+  end
+
+  it "rejects an unknown edition" do
+    response = post_edition_diff(request.merge(editions: ['2015', '2048']))
+
+    expect(response.code).to eq('400')
+  end
+end
@@ -0,0 +1,46 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "repeated execution endpoint", type: :request do
+  let(:repeat_uri) { URI.join(Capybara.app_host, '/execute/repeat') }
+
+  let(:request) {
+    {
+      channel: 'stable',
+      mode: 'debug',
+      crateType: 'bin',
+      tests: false,
+      code: 'fn main() { println!("seed={}", std::env::var("PLAYGROUND_SEED").unwrap()); }',
+    }
+  }
+
+  def post_repeat(body)
+    Net::HTTP.start(repeat_uri.host, repeat_uri.port) do |http|
+      request = Net::HTTP::Post.new(repeat_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "runs the program once per requested time with its own seed" do
+    response = post_repeat(request.merge(times: 2))
+    expect(response.code).to eq('200')
+
+    body = JSON.parse(response.body)
+    expect(body['runs'].length).to eq(2)
+    expect(body['passed']).to eq(2)
+    expect(body['failed']).to eq(0)
+
+    body['runs'].each do |run|
+      expect(run['stdout']).to include("seed=#{run['seed']}")
+    end
+  end
+
+  it "rejects running the program too many times" do
+    response = post_repeat(request.merge(times: 1000))
+
+    expect(response.code).to eq('400')
+  end
+end
@@ -0,0 +1,66 @@
+require 'json'
+require 'net/http'
+require 'securerandom'
+require 'spec_helper'
+
+RSpec.feature "execute endpoint", type: :request do
+  let(:execute_uri) { URI.join(Capybara.app_host, '/execute') }
+  let(:cancel_uri) { URI.join(Capybara.app_host, '/execute/cancel') }
+
+  def get_execute(params)
+    uri = execute_uri.dup
+    uri.query = URI.encode_www_form(params)
+    Net::HTTP.get_response(uri)
+  end
+
+  def post_cancel(body)
+    Net::HTTP.start(cancel_uri.host, cancel_uri.port) do |http|
+      request = Net::HTTP::Post.new(cancel_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "runs code from the query string" do
+    response = get_execute(code: 'fn main() { println!("Hello, query!"); }')
+
+    expect(response.code).to eq('200')
+    json = JSON.parse(response.body)
+    expect(json['success']).to be true
+    expect(json['stdout']).to include('Hello, query!')
+  end
+
+  it "requires the code" do
+    response = get_execute(channel: 'stable')
+
+    expect(response.code).to eq('400')
+  end
+
+  it "returns only the requested fields" do
+    response = get_execute(code: 'fn main() {}', fields: 'success')
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)).to eq('success' => true)
+  end
+
+  it "rejects overly long query strings" do
+    response = get_execute(code: "fn main() {}\n" + ' ' * 10_000)
+
+    expect(response.code).to eq('414')
+  end
+
+  it "reports that an unknown request was not cancelled" do
+    response = post_cancel(requestId: SecureRandom.uuid)
+
+    expect(response.code).to eq('200')
+    expect(JSON.parse(response.body)).to eq('cancelled' => false)
+  end
+
+  it "rejects request ids that could be guessed" do
+    response = post_cancel(requestId: 'not-running')
+
+    expect(response.code).to eq('400')
+    expect(JSON.parse(response.body)['error']).to match(/UUID/)
+  end
+end
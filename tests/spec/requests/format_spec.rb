@@ -0,0 +1,57 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "format endpoint", type: :request do
+  let(:format_uri) { URI.join(Capybara.app_host, '/format') }
+
+  def post_format(body)
+    Net::HTTP.start(format_uri.host, format_uri.port) do |http|
+      request = Net::HTTP::Post.new(format_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "formats each of the submitted files" do
+    response = post_format(files: [
+      { name: 'main.rs', code: 'mod shapes; fn main(  ) { println!("{}", shapes::AREA) ; }' },
+      { name: 'shapes.rs', code: 'pub const AREA:u32=4;' },
+    ])
+
+    expect(response.code).to eq('200')
+    body = JSON.parse(response.body)
+    expect(body['success']).to be true
+
+    files = body['files'].map { |file| [file['name'], file] }.to_h
+    expect(files.keys).to contain_exactly('main.rs', 'shapes.rs')
+    expect(files['main.rs']['success']).to be true
+    expect(files['main.rs']['code']).to include('fn main() {')
+    expect(files['shapes.rs']['success']).to be true
+    expect(files['shapes.rs']['code']).to eq("pub const AREA: u32 = 4;\n")
+  end
+
+  it "reports the files that cannot be formatted" do
+    response = post_format(files: [
+      { name: 'main.rs', code: 'fn main(  ) {}' },
+      { name: 'broken.rs', code: 'fn broken( {' },
+    ])
+
+    expect(response.code).to eq('200')
+    body = JSON.parse(response.body)
+    expect(body['success']).to be false
+
+    files = body['files'].map { |file| [file['name'], file['success']] }.to_h
+    expect(files).to eq('main.rs' => true, 'broken.rs' => false)
+  end
+
+  it "rejects a file submitted twice" do
+    response = post_format(files: [
+      { name: 'main.rs', code: 'fn main() {}' },
+      { name: 'main.rs', code: 'fn main() {}' },
+    ])
+
+    expect(response.code).to eq('400')
+  end
+end
@@ -0,0 +1,132 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "Metadata endpoints", type: :request do
+  def get_json(path)
+    response = Net::HTTP.get_response(URI.join(Capybara.app_host, path))
+    expect(response.code).to eq('200')
+    JSON.parse(response.body)
+  end
+
+  describe "the permitted nightly flags" do
+    it "lists the flags used by the sandbox" do
+      flags = get_json('/meta/nightly-flags')['flags']
+
+      expect(flags).to include('-Zasm-comments')
+    end
+  end
+
+  describe "the installed components" do
+    it "includes rustfmt on stable" do
+      components = get_json('/meta/components')['stable']
+
+      expect(components['rustfmt']).to be true
+    end
+  end
+
+  describe "the default snippet" do
+    it "is a complete program" do
+      code = get_json('/meta/default-snippet')['code']
+
+      expect(code).to include('fn main()')
+    end
+  end
+
+  describe "hashing a snippet" do
+    def post_hash(code)
+      uri = URI.join(Capybara.app_host, '/meta/hash')
+      response = Net::HTTP.post(uri, JSON.generate(code: code), 'Content-Type' => 'application/json')
+      expect(response.code).to eq('200')
+      JSON.parse(response.body)['hash']
+    end
+
+    it "is the same for the same code" do
+      expect(post_hash("fn main() {}\n")).to eq(post_hash("fn main() {}\n"))
+    end
+
+    it "ignores the style of line endings" do
+      expect(post_hash("fn main() {\r\n}\r\n")).to eq(post_hash("fn main() {\n}\n"))
+    end
+  end
+
+  describe "measuring a snippet" do
+    it "counts the functions" do
+      uri = URI.join(Capybara.app_host, '/meta/metrics')
+      code = "fn helper() -> u8 { 1 }\n\nfn main() {\n    helper();\n}\n"
+      response = Net::HTTP.post(uri, JSON.generate(code: code), 'Content-Type' => 'application/json')
+
+      expect(response.code).to eq('200')
+      json = JSON.parse(response.body)
+      expect(json['fns']).to eq(2)
+      expect(json['lines']).to eq(5)
+      expect(json['codeLines']).to eq(4)
+    end
+  end
+
+  describe "finding the entry point" do
+    def post_has_main(code)
+      uri = URI.join(Capybara.app_host, '/meta/has-main')
+      response = Net::HTTP.post(uri, JSON.generate(code: code), 'Content-Type' => 'application/json')
+      expect(response.code).to eq('200')
+      JSON.parse(response.body)
+    end
+
+    it "reports a main function" do
+      expect(post_has_main("fn main() {}\n")).to eq('hasMain' => true, 'noMain' => false)
+    end
+
+    it "reports code without a main function" do
+      expect(post_has_main("fn helper() -> u8 { 1 }\n")).to eq('hasMain' => false, 'noMain' => false)
+    end
+  end
+
+  describe "validating dependencies" do
+    def post_dependencies(dependencies)
+      uri = URI.join(Capybara.app_host, '/meta/validate-deps')
+      response = Net::HTTP.post(uri, JSON.generate(dependencies: dependencies), 'Content-Type' => 'application/json')
+      expect(response.code).to eq('200')
+      JSON.parse(response.body)
+    end
+
+    it "resolves the crates that the playground provides" do
+      json = post_dependencies(%{rand = "*"})
+
+      expect(json['success']).to be true
+      expect(json['resolved'].map { |dep| dep['name'] }).to eq(['rand'])
+    end
+
+    it "reports crates that do not exist" do
+      json = post_dependencies(%{this-crate-does-not-exist = "1.0"})
+
+      expect(json['success']).to be false
+      expect(json['errors'].first['name']).to eq('this-crate-does-not-exist')
+      expect(json['errors'].first['error']).to include('no crate named')
+    end
+  end
+
+  describe "content negotiation" do
+    def get_crates(headers = {})
+      uri = URI.join(Capybara.app_host, '/meta/crates')
+      Net::HTTP.start(uri.host, uri.port) do |http|
+        http.request(Net::HTTP::Get.new(uri, headers))
+      end
+    end
+
+    it "returns JSON by default" do
+      response = get_crates
+
+      expect(response['Content-Type']).to start_with('application/json')
+      expect(JSON.parse(response.body)['crates']).to_not be_empty
+    end
+
+    it "returns MessagePack when the client accepts it" do
+      response = get_crates('Accept' => 'application/msgpack')
+
+      expect(response['Content-Type']).to start_with('application/msgpack')
+      # A single-entry map keyed by "crates"
+      expect(response.body.bytes.first).to eq(0x81)
+      expect(response.body).to include('crates')
+    end
+  end
+end
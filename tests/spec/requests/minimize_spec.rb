@@ -0,0 +1,61 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "minimize endpoint", type: :request do
+  let(:minimize_uri) { URI.join(Capybara.app_host, '/minimize') }
+
+  let(:code) {
+    <<~EOF
+      fn helper() -> u8 {
+          42
+      }
+
+      fn unrelated(x: u8) -> u8 {
+          x + 1
+      }
+
+      fn main() {
+          let v = vec![1, 2, 3];
+          let total: i32 = v.iter().sum::<i32>() + "";
+          println!("{}", total);
+      }
+    EOF
+  }
+
+  let(:request) {
+    {
+      channel: 'stable',
+      mode: 'debug',
+      crateType: 'bin',
+      tests: false,
+      predicate: 'error[E0277]',
+      code: code,
+    }
+  }
+
+  def post_minimize(body)
+    Net::HTTP.start(minimize_uri.host, minimize_uri.port) do |http|
+      request = Net::HTTP::Post.new(minimize_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      http.request(request)
+    end
+  end
+
+  it "removes unused helper functions but keeps the failing code" do
+    response = post_minimize(request)
+
+    expect(response.code).to eq('200')
+    json = JSON.parse(response.body)
+    expect(json['code']).to_not include('fn helper')
+    expect(json['code']).to_not include('fn unrelated')
+    expect(json['code']).to include('v.iter().sum::<i32>() + ""')
+  end
+
+  it "rejects code that does not fail as described" do
+    response = post_minimize(request.merge(code: 'fn main() {}'))
+
+    expect(response.code).to eq('400')
+  end
+end
@@ -0,0 +1,38 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "Sharing a permalink", type: :request do
+  let(:permalink_uri) { URI.join(Capybara.app_host, '/meta/permalink/') }
+
+  let(:options) {
+    {
+      'target' => 'mir',
+      'assemblyFlavor' => nil,
+      'demangleAssembly' => nil,
+      'processAssembly' => nil,
+      'channel' => 'nightly',
+      'mode' => 'release',
+      'edition' => '2015',
+      'crateType' => 'lib',
+      'tests' => false,
+      'backtrace' => false,
+    }
+  }
+  let(:code) { "pub fn shared() {}\n" }
+
+  it "round-trips the code and every option" do
+    created = Net::HTTP.start(permalink_uri.host, permalink_uri.port) do |http|
+      request = Net::HTTP::Post.new(permalink_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(options.merge('code' => code))
+      JSON.parse(http.request(request).body)
+    end
+
+    loaded = JSON.parse(Net::HTTP.get(URI.join(permalink_uri, created['id'])))
+
+    expect(loaded['id']).to eq(created['id'])
+    expect(loaded['code']).to eq(code)
+    expect(loaded['options']).to eq(options)
+  end
+end
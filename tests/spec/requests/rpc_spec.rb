@@ -0,0 +1,41 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "JSON-RPC endpoint", type: :request do
+  let(:rpc_uri) { URI.join(Capybara.app_host, '/rpc') }
+
+  def post_rpc(body)
+    Net::HTTP.start(rpc_uri.host, rpc_uri.port) do |http|
+      request = Net::HTTP::Post.new(rpc_uri)
+      request['content-type'] = 'application/json'
+      request.body = JSON.generate(body)
+      JSON.parse(http.request(request).body)
+    end
+  end
+
+  it "compiles code through the envelope" do
+    response = post_rpc(
+      method: 'compile',
+      params: {
+        target: 'llvm-ir',
+        channel: 'stable',
+        mode: 'debug',
+        crateType: 'bin',
+        tests: false,
+        code: 'fn main() {}',
+      },
+    )
+
+    expect(response).not_to have_key('error')
+    expect(response['result']['success']).to be true
+    expect(response['result']['code']).to match(/define/)
+  end
+
+  it "reports unknown methods" do
+    response = post_rpc(method: 'teleport', params: {})
+
+    expect(response['error']['code']).to eq(-32601)
+    expect(response['error']['message']).to match(/teleport/)
+  end
+end
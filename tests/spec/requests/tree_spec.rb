@@ -0,0 +1,20 @@
+require 'json'
+require 'net/http'
+require 'spec_helper'
+
+RSpec.feature "Listing the crate dependencies as JSON", type: :request do
+  let(:tree_uri) { URI.join(Capybara.app_host, '/tree/json') }
+
+  it "starts the graph at the playground crate" do
+    response = Net::HTTP.get_response(tree_uri)
+
+    expect(response.code).to eq('200')
+    tree = JSON.parse(response.body)
+    names = tree['nodes'].map { |n| n['name'] }
+    expect(names).to include('playground', 'rand')
+
+    root = tree['root']
+    expect(tree['nodes'][root]['name']).to eq('playground')
+    expect(tree['edges']).to include(a_hash_including('from' => root, 'kind' => 'normal'))
+  end
+end
@@ -570,7 +570,7 @@ export function performGistSave(): ThunkAction {
 
     const { code, configuration: { channel, mode, edition }, output: { execute: { stdout, stderr } } } = getState();
 
-    return jsonPost(routes.meta.gist, { code })
+    return jsonPost(routes.meta.gist, { code, channel, mode, edition })
       .then(json => dispatch(receiveGistSaveSuccess({ ...json, code, stdout, stderr, channel, mode, edition })));
     // TODO: Failure case
   };
@@ -17,6 +17,29 @@ pub fn demangle_asm(block: &str) -> String {
     }).to_string()
 }
 
+lazy_static! {
+    // Example:.Lfunc_end7:
+    // Finds label declarations
+    // Include in results only if it is referenced by an opcode, or is a function
+    static ref LABEL_DECL_REGEX: Regex = Regex::new(r"^([a-zA-Z_.<][a-zA-Z0-9$&_.,<>\[\]{}:' ]*):(\s+#.*)?$").unwrap();
+}
+lazy_static! {
+    // Example:    .type main,@function
+    // Note: this is a type of directive
+    // Never include in results, but is used to find and include functions
+    static ref FUNCTION_REGEX: Regex = Regex::new(r"^\s+\.type\s*(.*),@function$").unwrap();
+}
+lazy_static! {
+    // Example:    mov rax, rdx
+    // Always inlude in results
+    static ref OPCODE_REGEX: Regex = Regex::new(r"^\s+[a-zA-Z]+.*[^:]$").unwrap();
+}
+lazy_static! {
+    // Example:    .size main, .Lfunc_end7-main
+    // Marks the end of a function
+    static ref SIZE_REGEX: Regex = Regex::new(r"^\s+\.size\s*(.*),").unwrap();
+}
+
 enum LineType<'a> {
     Opcode,
     LabelDecl(&'a str),
@@ -32,17 +55,6 @@ pub fn filter_asm(block: &str) -> String {
 
     use self::LineType::*;
 
-    lazy_static! {
-        // Example:    mov rax, rdx
-        // Always inlude in results
-        static ref OPCODE_REGEX: Regex = Regex::new(r"^\s+[a-zA-Z]+.*[^:]$").unwrap();
-    }
-    lazy_static! {
-        // Example:.Lfunc_end7:
-        // Finds label declarations
-        // Include in results only if it is referenced by an opcode, or is a function
-        static ref LABEL_DECL_REGEX: Regex = Regex::new(r"^([a-zA-Z_.<][a-zA-Z0-9$&_.,<>\[\]{}:' ]*):(\s+#.*)?$").unwrap();
-    }
     lazy_static! {
         // Example:    mov lea rdi, [rip + str.0] // str.0 is the referenced label
         // Find labels used as operands for an opcode
@@ -54,12 +66,6 @@ pub fn filter_asm(block: &str) -> String {
         // Include in results if it is part of a used label, may contain label references
         static ref DATA_REGEX: Regex = Regex::new(r"^\s+\.(string|asciz|ascii|[1248]?byte|short|word|long|quad|value|zero)").unwrap();
     }
-    lazy_static! {
-        // Example:    .type main,@function
-        // Note: this is a type of directive
-        // Never include in results, but is used to find and include functions
-        static ref FUNCTION_REGEX: Regex = Regex::new(r"^\s+\.type\s*(.*),@function$").unwrap();
-    }
     lazy_static! {
         // Example:    .p2align 4, 0x90
         // Note: this will also match entries found by DATA_REGEX and FUNCTION_REGEX
@@ -149,6 +155,137 @@ pub fn filter_asm(block: &str) -> String {
     filtered_asm
 }
 
+// The user's `main`, rather than the `main` that starts the runtime
+const USER_ENTRY_POINT: &str = "playground::main";
+const ENTRY_POINT: &str = "main";
+
+// Keeps only the entry point and the functions that it calls
+// directly, then removes unused labels and directives like `filter_asm`
+pub fn entry_point_asm(block: &str) -> String {
+    lazy_static! {
+        // Example:    call playground::helper
+        // Example:    jmp qword ptr [rip + foo@GOTPCREL]
+        // Calls, including tail calls, to other functions
+        static ref CALL_REGEX: Regex = Regex::new(r"^\s+(call|jmp)[a-z]*\s+(.*)$").unwrap();
+    }
+
+    let lines: Vec<_> = block.lines().collect();
+    let functions = functions(&lines);
+
+    let entry_point = functions
+        .iter()
+        .find(|&&function| format!("{:#}", demangle(function)) == USER_ENTRY_POINT)
+        .or_else(|| functions.get(ENTRY_POINT));
+    let entry_point = match entry_point {
+        Some(&entry_point) => entry_point,
+        None => return filter_asm(block),
+    };
+
+    let owners = owners(&lines, &functions);
+    let mut call_graph = DiGraphMap::new();
+
+    for (line, &owner) in lines.iter().zip(&owners) {
+        if let (Some(caller), Some(operand)) = (owner, CALL_REGEX.captures(line).and_then(|cap| cap.get(2))) {
+            for &callee in functions.iter().filter(|&&callee| mentions(operand.as_str(), callee)) {
+                call_graph.add_edge(caller, callee, ());
+            }
+        }
+    }
+
+    let mut kept_functions: HashSet<_> = call_graph.neighbors(entry_point).collect();
+    kept_functions.insert(entry_point);
+
+    let mut kept = String::new();
+    for (line, owner) in lines.iter().zip(owners) {
+        if owner.into_iter().all(|owner| kept_functions.contains(owner)) {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+
+    filter_asm(&kept)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionCount {
+    /// Demangled, even when the assembly is not
+    pub function: String,
+    pub count: usize,
+}
+
+// Counts the instructions of each function in the order that they
+// appear. Labels and directives are not instructions.
+pub fn instruction_counts(block: &str) -> Vec<InstructionCount> {
+    let lines: Vec<_> = block.lines().collect();
+    let functions = functions(&lines);
+    let mut counts: Vec<InstructionCount> = Vec::new();
+
+    for (line, owner) in lines.iter().zip(owners(&lines, &functions)) {
+        let owner = match owner {
+            Some(owner) => owner,
+            None => continue,
+        };
+        let function = format!("{:#}", demangle(owner));
+
+        let count = match counts.last_mut() {
+            Some(count) if count.function == function => count,
+            _ => {
+                counts.push(InstructionCount { function, count: 0 });
+                counts.last_mut().expect("a count was just added")
+            }
+        };
+
+        if OPCODE_REGEX.is_match(line) {
+            count.count += 1;
+        }
+    }
+
+    counts
+}
+
+fn functions<'a>(lines: &[&'a str]) -> HashSet<&'a str> {
+    lines
+        .iter()
+        .filter_map(|line| FUNCTION_REGEX.captures(line).and_then(|cap| cap.get(1)))
+        .map(|function| function.as_str())
+        .collect()
+}
+
+// Which function, if any, each line is part of
+fn owners<'a>(lines: &[&'a str], functions: &HashSet<&'a str>) -> Vec<Option<&'a str>> {
+    let mut owners = Vec::with_capacity(lines.len());
+    let mut current_function = None;
+
+    for line in lines {
+        if let Some(label) = LABEL_DECL_REGEX.captures(line).and_then(|cap| cap.get(1)) {
+            if functions.contains(label.as_str()) {
+                current_function = Some(label.as_str());
+            }
+        }
+
+        owners.push(current_function);
+
+        if let Some(sized) = SIZE_REGEX.captures(line).and_then(|cap| cap.get(1)) {
+            if current_function == Some(sized.as_str()) {
+                current_function = None;
+            }
+        }
+    }
+
+    owners
+}
+
+// The name must not be part of a longer symbol
+fn mentions(operand: &str, name: &str) -> bool {
+    let is_symbol = |c: char| c.is_alphanumeric() || "_.$:".contains(c);
+
+    operand.match_indices(name).any(|(idx, _)| {
+        let before = operand[..idx].chars().next_back();
+        let after = operand[idx + name.len()..].chars().next();
+        !before.into_iter().chain(after).any(is_symbol)
+    })
+}
+
 #[cfg(test)]
 mod test {
     #[test]
@@ -248,4 +385,80 @@ mod test {
         assert_eq!(super::filter_asm("# %bb.0:\n subq $24, %rsp\n"),
         "# %bb.0:\n subq $24, %rsp\n")
     }
+
+    #[test]
+    fn entry_point_and_direct_callees_kept() {
+        let asm = "	.type	playground::deeper,@function
+playground::deeper:
+	ret
+.Lfunc_end0:
+	.size	playground::deeper, .Lfunc_end0-playground::deeper
+
+	.type	playground::helper,@function
+playground::helper:
+	call	playground::deeper
+	ret
+.Lfunc_end1:
+	.size	playground::helper, .Lfunc_end1-playground::helper
+
+	.type	playground::helper_unused,@function
+playground::helper_unused:
+	ret
+.Lfunc_end2:
+	.size	playground::helper_unused, .Lfunc_end2-playground::helper_unused
+
+	.type	playground::main,@function
+playground::main:
+	call	playground::helper
+	jmp	qword ptr [rip + std::io::stdio::_print@GOTPCREL]
+.Lfunc_end3:
+	.size	playground::main, .Lfunc_end3-playground::main
+
+	.type	std::io::stdio::_print,@function
+std::io::stdio::_print:
+	ret
+.Lfunc_end4:
+	.size	std::io::stdio::_print, .Lfunc_end4-std::io::stdio::_print
+
+	.type	main,@function
+main:
+	call	std::rt::lang_start
+	ret
+.Lfunc_end5:
+	.size	main, .Lfunc_end5-main
+";
+
+        assert_eq!(super::entry_point_asm(asm), "\
+\nplayground::helper:\n\tcall\tplayground::deeper\n\tret\n\
+\nplayground::main:\n\tcall\tplayground::helper\n\tjmp\tqword ptr [rip + std::io::stdio::_print@GOTPCREL]\n\
+\nstd::io::stdio::_print:\n\tret\n");
+    }
+
+    #[test]
+    fn instructions_are_counted_per_function() {
+        let asm = "	.type	_ZN10playground3add17h0123456789abcdefE,@function
+_ZN10playground3add17h0123456789abcdefE:
+	.cfi_startproc
+	leal	(%rdi,%rsi), %eax
+	retq
+.Lfunc_end0:
+	.size	_ZN10playground3add17h0123456789abcdefE, .Lfunc_end0-_ZN10playground3add17h0123456789abcdefE
+	.cfi_endproc
+
+	.type	main,@function
+main:
+.LBB1_1:
+	pushq	%rax
+	callq	_ZN10playground3add17h0123456789abcdefE
+	popq	%rcx
+	retq
+.Lfunc_end1:
+	.size	main, .Lfunc_end1-main
+";
+
+        assert_eq!(super::instruction_counts(asm), [
+            super::InstructionCount { function: "playground::add".into(), count: 2 },
+            super::InstructionCount { function: "main".into(), count: 4 },
+        ]);
+    }
 }
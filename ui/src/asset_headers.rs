@@ -0,0 +1,65 @@
+// Adds the operator's own headers to the files that make up the UI,
+// such as those needed for cross-origin isolation.
+
+use iron::{prelude::*, status, AfterMiddleware};
+
+pub struct AssetHeaders(Vec<(String, String)>);
+
+impl AssetHeaders {
+    pub fn new(headers: Vec<(String, String)>) -> Self {
+        AssetHeaders(headers)
+    }
+}
+
+impl AfterMiddleware for AssetHeaders {
+    fn after(&self, _req: &mut Request<'_, '_>, mut res: Response) -> IronResult<Response> {
+        if let Some(status::Ok) | Some(status::PartialContent) = res.status {
+            for (name, value) in &self.0 {
+                res.headers.set_raw(name.clone(), vec![value.clone().into_bytes()]);
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iron::{Chain, Iron};
+    use std::{io::prelude::*, net::TcpStream};
+
+    #[test]
+    fn configured_headers_are_added_to_the_index() {
+        let handler = |req: &mut Request<'_, '_>| {
+            if req.url.path() == [""] {
+                Ok(Response::with((status::Ok, "<!doctype html>")))
+            } else {
+                Ok(Response::with(status::NotFound))
+            }
+        };
+        let mut chain = Chain::new(handler);
+        chain.link_after(AssetHeaders::new(vec![
+            ("Cross-Origin-Opener-Policy".into(), "same-origin".into()),
+        ]));
+
+        let mut listening = Iron::new(chain).http("127.0.0.1:0").expect("Unable to start server");
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(listening.socket).unwrap();
+            write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let index = get("/");
+        assert!(index.contains("Cross-Origin-Opener-Policy: same-origin\r\n"), "response was: {}", index);
+
+        let missing = get("/missing.js");
+        assert!(!missing.contains("Cross-Origin-Opener-Policy"), "response was: {}", missing);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+}
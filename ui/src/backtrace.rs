@@ -0,0 +1,98 @@
+// Turns the backtrace that a panicking program prints into frames
+// that the editor can link to the user's code.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use rustc_demangle::demangle;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub index: u32,
+    pub symbol: String,
+    /// `file:line`, when the debug info knows where the frame is
+    pub location: Option<String>,
+}
+
+/// The frames of every backtrace in the output. The standard library
+/// prints the location from the debug info under each symbol, so
+/// release builds usually have few locations.
+pub fn parse(stderr: &str) -> Vec<Frame> {
+    lazy_static! {
+        // Full backtraces include the address before the symbol
+        static ref FRAME_REGEX: Regex = Regex::new(r"^\s*(\d+):\s+(?:0x[0-9a-f]+ - )?(.+)$").unwrap();
+        static ref LOCATION_REGEX: Regex = Regex::new(r"^\s+at (?:\./)?(.+?):(\d+)(?::\d+)?$").unwrap();
+        static ref HASH_REGEX: Regex = Regex::new(r"::h[0-9a-f]{16}$").unwrap();
+    }
+
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut in_backtrace = false;
+
+    for line in stderr.lines() {
+        if line.trim_end() == "stack backtrace:" {
+            in_backtrace = true;
+            continue;
+        }
+        if !in_backtrace {
+            continue;
+        }
+
+        if let Some(caps) = FRAME_REGEX.captures(line) {
+            let symbol = format!("{:#}", demangle(&caps[2]));
+            frames.push(Frame {
+                index: caps[1].parse().unwrap_or(0),
+                symbol: HASH_REGEX.replace(&symbol, "").into_owned(),
+                location: None,
+            });
+        } else if let Some(caps) = LOCATION_REGEX.captures(line) {
+            // Inlined frames can list several locations; the first
+            // is the innermost
+            if let Some(frame) = frames.last_mut().filter(|frame| frame.location.is_none()) {
+                frame.location = Some(format!("{}:{}", &caps[1], &caps[2]));
+            }
+        } else {
+            in_backtrace = false;
+        }
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames_are_paired_with_their_locations() {
+        let stderr = r#"thread 'main' panicked at src/main.rs:3:20:
+called `Option::unwrap()` on a `None` value
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/abc/library/std/src/panicking.rs:652:5
+   1: core::panicking::panic
+   2: playground::trigger_the_problem
+             at ./src/main.rs:3:20
+   3: playground::main
+             at ./src/main.rs:7:9
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+"#;
+
+        assert_eq!(parse(stderr), [
+            Frame { index: 0, symbol: "rust_begin_unwind".into(), location: Some("/rustc/abc/library/std/src/panicking.rs:652".into()) },
+            Frame { index: 1, symbol: "core::panicking::panic".into(), location: None },
+            Frame { index: 2, symbol: "playground::trigger_the_problem".into(), location: Some("src/main.rs:3".into()) },
+            Frame { index: 3, symbol: "playground::main".into(), location: Some("src/main.rs:7".into()) },
+        ]);
+    }
+
+    #[test]
+    fn full_backtraces_are_demangled() {
+        let stderr = "stack backtrace:
+   0:     0x55d8c3b0c2e3 - _ZN10playground19trigger_the_problem17h0123456789abcdefE
+                               at src/main.rs:3
+   1:     0x55d8c3b0c300 - playground::main::hfedcba9876543210
+";
+
+        let symbols: Vec<_> = parse(stderr).into_iter().map(|frame| frame.symbol).collect();
+        assert_eq!(symbols, ["playground::trigger_the_problem", "playground::main"]);
+    }
+}
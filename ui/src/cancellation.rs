@@ -0,0 +1,138 @@
+// Lets a client stop a request that it started earlier, identified by
+// an id that the client chose when starting it. Anyone who knows the id
+// can cancel the request, so it has to be a random UUID that nobody
+// else could guess or claim first.
+
+use lazy_static::lazy_static;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+#[derive(Debug, Default)]
+pub struct Cancellations {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+/// Removes the id from the running requests when dropped
+#[derive(Debug)]
+pub struct Registration<'a> {
+    id: String,
+    cancelled: Arc<AtomicBool>,
+    owner: &'a Cancellations,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AlreadyRunning;
+
+impl Cancellations {
+    pub fn register(&self, id: &str) -> Result<Registration<'_>, AlreadyRunning> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+
+        if running.contains_key(id) {
+            return Err(AlreadyRunning);
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(id.into(), cancelled.clone());
+
+        Ok(Registration { id: id.into(), cancelled, owner: self })
+    }
+
+    /// Returns if a request with the id was running
+    pub fn cancel(&self, id: &str) -> bool {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+
+        match running.get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl Registration<'_> {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let mut running = self.owner.running.lock().unwrap_or_else(|e| e.into_inner());
+        running.remove(&self.id);
+    }
+}
+
+/// A version 4 UUID, such as `0f8d2a3e-6c1b-4e5f-9a7d-3b2c1d0e4f5a`
+pub fn is_valid_id(id: &str) -> bool {
+    const GROUPS: &[usize] = &[8, 4, 4, 4, 12];
+
+    let groups: Vec<_> = id.split('-').collect();
+    let well_formed = groups.len() == GROUPS.len() &&
+        groups.iter().zip(GROUPS).all(|(group, &len)| group.len() == len && group.bytes().all(|b| b.is_ascii_hexdigit()));
+
+    well_formed && groups[2].starts_with('4') && groups[3].starts_with(|c| "89abAB".contains(c))
+}
+
+pub fn global() -> &'static Cancellations {
+    lazy_static! {
+        static ref CANCELLATIONS: Cancellations = Cancellations::default();
+    }
+
+    &CANCELLATIONS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn running_requests_can_be_cancelled() {
+        let cancellations = Cancellations::default();
+        let registration = cancellations.register("abc").unwrap();
+
+        assert!(!registration.is_cancelled());
+        assert!(cancellations.cancel("abc"));
+        assert!(registration.is_cancelled());
+    }
+
+    #[test]
+    fn finished_requests_cannot_be_cancelled() {
+        let cancellations = Cancellations::default();
+        drop(cancellations.register("abc").unwrap());
+
+        assert!(!cancellations.cancel("abc"));
+    }
+
+    #[test]
+    fn ids_are_unique_while_running() {
+        let cancellations = Cancellations::default();
+        let registration = cancellations.register("abc").unwrap();
+
+        assert_eq!(cancellations.register("abc").unwrap_err(), AlreadyRunning);
+
+        drop(registration);
+        assert!(cancellations.register("abc").is_ok());
+    }
+
+    #[test]
+    fn ids_must_be_random_uuids() {
+        assert!(is_valid_id("0f8d2a3e-6c1b-4e5f-9a7d-3b2c1d0e4f5a"));
+        assert!(is_valid_id("0F8D2A3E-6C1B-4E5F-BA7D-3B2C1D0E4F5A"));
+
+        assert!(!is_valid_id("report-42"));
+        assert!(!is_valid_id("00000000-0000-0000-0000-000000000000"));
+        assert!(!is_valid_id("0f8d2a3e6c1b4e5f9a7d3b2c1d0e4f5a"));
+        assert!(!is_valid_id("0f8d2a3e-6c1b-4e5f-9a7d-3b2c1d0e4f5g"));
+    }
+}
@@ -0,0 +1,41 @@
+// Responses that clients and proxies cache are serialized the same way
+// each time their content is the same, so that the body can be
+// compared or hashed as a whole. Object keys are sorted, as a `Value`
+// keeps them in a BTreeMap unless serde_json's `preserve_order` feature
+// is enabled; lists keep their order, so callers sort those that have
+// no meaningful order.
+
+use serde::Serialize;
+use serde_json::Value;
+
+pub fn sorted<T: Serialize>(value: &T) -> serde_json::Result<Value> {
+    serde_json::to_value(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_derive::Serialize;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Channel {
+        toolchains: Vec<String>,
+        components: HashMap<String, bool>,
+    }
+
+    #[test]
+    fn the_same_content_serializes_identically() {
+        let names: Vec<_> = (0..32).map(|n| format!("component-{}", n)).collect();
+        let channel = |names: &mut dyn Iterator<Item = &String>| Channel {
+            toolchains: vec!["1.42.0".into()],
+            components: names.map(|name| (name.clone(), true)).collect(),
+        };
+
+        let forward = serde_json::to_vec(&sorted(&channel(&mut names.iter())).unwrap()).unwrap();
+        let backward = serde_json::to_vec(&sorted(&channel(&mut names.iter().rev())).unwrap()).unwrap();
+
+        assert_eq!(forward, backward);
+        assert!(forward.starts_with(br#"{"components":{"component-0":true,"component-1":true,"component-10":true"#));
+    }
+}
@@ -0,0 +1,105 @@
+// Reads the `Cargo.lock` of the playground crate to find the exact
+// versions of its dependencies, including any build metadata.
+
+use serde_derive::Deserialize;
+
+const ROOT_PACKAGE: &str = "playground";
+
+#[derive(Debug, Deserialize)]
+struct Lock {
+    #[serde(default)]
+    package: Vec<Package>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// Such as `registry+https://github.com/rust-lang/crates.io-index`
+    pub source: Option<String>,
+    // Entries are like `rand 0.7.3`, with a version and source only
+    // when there are several packages of the name
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Lockfile {
+    packages: Vec<Package>,
+}
+
+impl Lockfile {
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        let Lock { package } = toml::from_str(contents)?;
+        Ok(Lockfile { packages: package })
+    }
+
+    /// The package that the playground crate depends on. When several
+    /// versions of the crate are locked, the playground's own entry
+    /// decides between them.
+    pub fn resolve(&self, name: &str) -> Option<&Package> {
+        let mut candidates = self.packages.iter().filter(|p| p.name == name);
+        let first = candidates.next()?;
+        if candidates.next().is_none() {
+            return Some(first);
+        }
+
+        let root = self.packages.iter().find(|p| p.name == ROOT_PACKAGE)?;
+        let version = root.dependencies.iter().find_map(|dependency| {
+            let mut parts = dependency.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(n), Some(version)) if n == name => Some(version),
+                _ => None,
+            }
+        })?;
+
+        self.packages.iter().find(|p| p.name == name && p.version == version)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOCKFILE: &str = r#"
+[[package]]
+name = "playground"
+version = "0.0.1"
+dependencies = [
+ "rand 0.7.3",
+ "zstd-sys",
+]
+
+[[package]]
+name = "rand"
+version = "0.6.5"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "rand"
+version = "0.7.3"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "zstd-sys"
+version = "1.4.15+zstd.1.4.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    #[test]
+    fn versions_include_build_metadata() {
+        let lock = Lockfile::parse(LOCKFILE).unwrap();
+        let zstd = lock.resolve("zstd-sys").expect("zstd-sys is locked");
+
+        assert_eq!(zstd.version, "1.4.15+zstd.1.4.4");
+        assert_eq!(zstd.source.as_deref(), Some("registry+https://github.com/rust-lang/crates.io-index"));
+    }
+
+    #[test]
+    fn the_playground_chooses_between_versions() {
+        let lock = Lockfile::parse(LOCKFILE).unwrap();
+
+        assert_eq!(lock.resolve("rand").map(|p| p.version.as_str()), Some("0.7.3"));
+        assert!(lock.resolve("serde").is_none());
+    }
+}
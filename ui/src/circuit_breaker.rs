@@ -0,0 +1,191 @@
+// Stops sending requests to a dependency that keeps failing so that
+// callers don't each have to wait for it to fail again
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Requests are allowed
+    Closed,
+    /// Requests are rejected until the cooldown expires
+    Open,
+    /// A single probe request has been allowed to see if the
+    /// dependency has recovered
+    HalfOpen,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub state: State,
+    pub consecutive_failures: u32,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold: threshold.max(1),
+            cooldown,
+            inner: Mutex::default(),
+        }
+    }
+
+    /// The outcome of an allowed request is given to `Attempt::record`.
+    pub fn allow_request(&self) -> Option<Attempt<'_>> {
+        let mut inner = self.lock();
+
+        match inner.open_until {
+            None => Some(Attempt { breaker: Some(self), probe: false }),
+            Some(_) if inner.probing => None,
+            Some(open_until) if Instant::now() < open_until => None,
+            Some(_) => {
+                inner.probing = true;
+                Some(Attempt { breaker: Some(self), probe: true })
+            }
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut inner = self.lock();
+        inner.probing = false;
+
+        if success {
+            if inner.open_until.is_some() {
+                log::info!("Closing the circuit breaker");
+            }
+            inner.consecutive_failures = 0;
+            inner.open_until = None;
+        } else {
+            inner.consecutive_failures = inner.consecutive_failures.saturating_add(1);
+            if inner.consecutive_failures >= self.threshold {
+                if inner.open_until.is_none() {
+                    log::warn!("Opening the circuit breaker after {} consecutive failures", inner.consecutive_failures);
+                }
+                inner.open_until = Some(Instant::now() + self.cooldown);
+            }
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        let inner = self.lock();
+
+        let state = match inner.open_until {
+            None => State::Closed,
+            Some(_) if inner.probing => State::HalfOpen,
+            Some(_) => State::Open,
+        };
+
+        Status { state, consecutive_failures: inner.consecutive_failures }
+    }
+
+    // The state is always left consistent, so a panic elsewhere
+    // shouldn't take the breaker down with it.
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A request that the breaker allowed. If it is dropped without its
+/// outcome being recorded, such as when the request panicked, another
+/// probe is allowed instead of the breaker staying half-open.
+#[derive(Debug)]
+pub struct Attempt<'a> {
+    breaker: Option<&'a CircuitBreaker>,
+    probe: bool,
+}
+
+impl Attempt<'_> {
+    pub fn record(mut self, success: bool) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.record(success);
+        }
+    }
+}
+
+impl Drop for Attempt<'_> {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.breaker.take() {
+            if self.probe {
+                breaker.lock().probing = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const ONE_HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn requests_fail_fast_after_repeated_failures() {
+        let breaker = CircuitBreaker::new(3, ONE_HOUR);
+
+        for _ in 0..3 {
+            breaker.allow_request().expect("The request was not allowed").record(false);
+        }
+
+        assert!(breaker.allow_request().is_none());
+        assert_eq!(breaker.status(), Status { state: State::Open, consecutive_failures: 3 });
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(3, ONE_HOUR);
+
+        breaker.record(false);
+        breaker.record(false);
+        breaker.record(true);
+        breaker.record(false);
+
+        assert!(breaker.allow_request().is_some());
+        assert_eq!(breaker.status(), Status { state: State::Closed, consecutive_failures: 1 });
+    }
+
+    #[test]
+    fn a_single_probe_is_allowed_after_the_cooldown() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(0));
+        breaker.record(false);
+
+        let probe = breaker.allow_request().expect("The probe was not allowed");
+        assert!(breaker.allow_request().is_none());
+        assert_eq!(breaker.status().state, State::HalfOpen);
+
+        probe.record(true);
+
+        assert!(breaker.allow_request().is_some());
+        assert_eq!(breaker.status().state, State::Closed);
+    }
+
+    #[test]
+    fn a_probe_that_panics_allows_another() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(0));
+        breaker.record(false);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _probe = breaker.allow_request().expect("The probe was not allowed");
+            panic!("The request failed unexpectedly");
+        }));
+
+        assert!(panicked.is_err());
+        assert_eq!(breaker.status().state, State::Open);
+        assert!(breaker.allow_request().is_some());
+    }
+}
@@ -0,0 +1,118 @@
+// Reads the lint listing that `clippy-driver -W help` prints, which
+// is the same table that rustc prints for its own lints.
+
+use std::collections::BTreeMap;
+
+const CLIPPY_PREFIX: &str = "clippy::";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+    pub name: String,
+    /// `allow`, `warn`, or `deny`
+    pub level: String,
+    pub groups: Vec<String>,
+    pub description: String,
+}
+
+enum Section {
+    Lints,
+    Groups,
+}
+
+/// Only Clippy's lints are kept. Names use underscores, as they do in
+/// attributes, even though the listing uses dashes.
+pub fn parse(listing: &str) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut section = Section::Lints;
+
+    for line in listing.lines() {
+        if line.starts_with("Lint groups") {
+            section = Section::Groups;
+            continue;
+        } else if line.starts_with("Lint checks") {
+            section = Section::Lints;
+            continue;
+        }
+
+        let mut columns = line.split_whitespace();
+        let name = match columns.next() {
+            Some(name) if name.starts_with(CLIPPY_PREFIX) => normalize(name),
+            _ => continue,
+        };
+
+        match section {
+            Section::Lints => {
+                let level = columns.next().unwrap_or_default().to_string();
+                let description = columns.collect::<Vec<_>>().join(" ");
+                lints.push(Lint { name, level, groups: Vec::new(), description });
+            }
+            Section::Groups => {
+                let members = columns.collect::<Vec<_>>().join(" ");
+                for member in members.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+                    groups.entry(normalize(member)).or_default().push(name.clone());
+                }
+            }
+        }
+    }
+
+    for lint in &mut lints {
+        if let Some(groups) = groups.remove(&lint.name) {
+            lint.groups = groups;
+        }
+    }
+
+    lints
+}
+
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LISTING: &str = "
+Available lint options:
+    -W <foo>           Warn about <foo>
+
+Lint checks provided by rustc:
+
+                   name  default  meaning
+                   ----  -------  -------
+          unused-imports  warn     imports that are never used
+
+Lint checks provided by plugins loaded by this crate:
+
+                   name  default  meaning
+                   ----  -------  -------
+  clippy::needless-return  warn     using a return statement like `return expr;` where an expression would suffice
+         clippy::eq-op  deny     equal operands on both sides of a comparison or bitwise combination
+
+Lint groups provided by plugins loaded by this crate:
+
+                   name  sub-lints
+                   ----  ---------
+             clippy::all  clippy::needless-return, clippy::eq-op
+           clippy::style  clippy::needless-return
+";
+
+    #[test]
+    fn clippy_lints_are_listed_with_their_groups() {
+        assert_eq!(parse(LISTING), [
+            Lint {
+                name: "clippy::needless_return".into(),
+                level: "warn".into(),
+                groups: vec!["clippy::all".into(), "clippy::style".into()],
+                description: "using a return statement like `return expr;` where an expression would suffice".into(),
+            },
+            Lint {
+                name: "clippy::eq_op".into(),
+                level: "deny".into(),
+                groups: vec!["clippy::all".into()],
+                description: "equal operands on both sides of a comparison or bitwise combination".into(),
+            },
+        ]);
+    }
+}
@@ -0,0 +1,34 @@
+// Identifies snippets by their contents so that clients can spot
+// duplicates without the code being stored anywhere
+
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+/// A hex-encoded SHA-256 of the code. Line endings are normalized
+/// first, as they depend on the platform that the code was typed on.
+pub fn hash(code: &str) -> String {
+    let code = code.replace("\r\n", "\n").replace('\r', "\n");
+    let digest = Sha256::digest(code.as_bytes());
+
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        write!(&mut hex, "{:02x}", byte).expect("Unable to write to a string");
+    }
+    hex
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line_endings_do_not_change_the_hash() {
+        assert_eq!(hash("fn main() {\r\n}\r\n"), hash("fn main() {\n}\n"));
+        assert_eq!(hash("fn main() {\r}\r"), hash("fn main() {\n}\n"));
+    }
+
+    #[test]
+    fn hash_is_hex_encoded_sha256() {
+        assert_eq!(hash(""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+    }
+}
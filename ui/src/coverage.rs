@@ -0,0 +1,64 @@
+// Reads the line coverage that `llvm-cov export --format=lcov`
+// records for the user's crate
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LineCoverage {
+    pub line: u32,
+    pub hits: u64,
+}
+
+/// Only the user's source file is reported; the standard library and
+/// the dependencies are not instrumented but may still be mentioned.
+pub fn parse_lcov(lcov: &str) -> Vec<LineCoverage> {
+    let mut in_user_file = false;
+    let mut lines = Vec::new();
+
+    for record in lcov.lines() {
+        let mut parts = record.splitn(2, ':');
+
+        match (parts.next(), parts.next()) {
+            (Some("SF"), Some(path)) => {
+                in_user_file = path.ends_with("src/main.rs") || path.ends_with("src/lib.rs");
+            }
+            (Some("DA"), Some(data)) if in_user_file => {
+                // <line>,<hits>[,<checksum>]
+                let mut fields = data.split(',');
+                let line = fields.next().and_then(|l| l.parse().ok());
+                let hits = fields.next().and_then(|h| h.parse().ok());
+
+                if let (Some(line), Some(hits)) = (line, hits) {
+                    lines.push(LineCoverage { line, hits });
+                }
+            }
+            (Some("end_of_record"), None) => in_user_file = false,
+            _ => {}
+        }
+    }
+
+    lines.sort_by_key(|l| l.line);
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn only_the_users_file_is_reported() {
+        let lcov = "SF:/playground/src/lib.rs\n\
+                    FN:1,_RNvCs_10playground3add\n\
+                    DA:1,2\n\
+                    DA:2,2\n\
+                    DA:5,0\n\
+                    end_of_record\n\
+                    SF:/rustc/library/core/src/fmt/mod.rs\n\
+                    DA:10,7\n\
+                    end_of_record\n";
+
+        assert_eq!(parse_lcov(lcov), [
+            LineCoverage { line: 1, hits: 2 },
+            LineCoverage { line: 2, hits: 2 },
+            LineCoverage { line: 5, hits: 0 },
+        ]);
+    }
+}
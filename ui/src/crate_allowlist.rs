@@ -0,0 +1,98 @@
+// Restricts which of the provided crates code may use, such as for a
+// deployment used in teaching. The sandbox enforces this by only giving
+// Cargo the allowed dependencies. Looking at the paths in the code
+// first only gives a clearer error for the usual ways of naming a
+// crate; macros and renaming imports get past it.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::BTreeSet;
+
+use crate::sandbox::CrateInformation;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allowlist(BTreeSet<String>);
+
+impl Allowlist {
+    /// A comma-separated list of crate names
+    pub fn parse(s: &str) -> Self {
+        let names = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(normalize)
+            .collect();
+
+        Allowlist(names)
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.0.contains(&normalize(name))
+    }
+
+    /// Names that aren't among the available crates, such as `std` or
+    /// the code's own modules, are never rejected.
+    pub fn first_disallowed(&self, code: &str, available: &[CrateInformation]) -> Option<String> {
+        let available: BTreeSet<_> = available.iter().map(|krate| normalize(&krate.name)).collect();
+
+        referenced_crates(code)
+            .into_iter()
+            .filter(|name| available.contains(name))
+            .find(|name| !self.allows(name))
+    }
+}
+
+fn referenced_crates(code: &str) -> BTreeSet<String> {
+    lazy_static! {
+        static ref EXTERN_CRATE_REGEX: Regex = Regex::new(r"\bextern\s+crate\s+(\w+)").unwrap();
+        static ref USE_REGEX: Regex = Regex::new(r"\buse\s+(?:::)?(\w+)").unwrap();
+        static ref PATH_ROOT_REGEX: Regex = Regex::new(r"(?:^|[^\w:])(?:::)?(\w+)\s*::").unwrap();
+    }
+
+    [&*EXTERN_CRATE_REGEX, &*USE_REGEX, &*PATH_ROOT_REGEX]
+        .iter()
+        .flat_map(|regex| regex.captures_iter(code))
+        .map(|caps| normalize(&caps[1]))
+        .collect()
+}
+
+// Cargo treats `-` and `_` in crate names as the same
+fn normalize(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn available(names: &[&str]) -> Vec<CrateInformation> {
+        names
+            .iter()
+            .map(|&name| CrateInformation { name: name.into(), version: "1.0.0".into(), id: name.into() })
+            .collect()
+    }
+
+    #[test]
+    fn crates_that_are_not_allowed_are_rejected() {
+        let allowlist = Allowlist::parse("rand, lazy-static");
+        let available = available(&["rand", "lazy_static", "regex"]);
+
+        let code = "use rand::Rng;\nfn main() { let re = regex::Regex::new(\"a\"); }";
+        assert_eq!(allowlist.first_disallowed(code, &available), Some("regex".into()));
+
+        let code = "extern crate regex;\nfn main() {}";
+        assert_eq!(allowlist.first_disallowed(code, &available), Some("regex".into()));
+    }
+
+    #[test]
+    fn allowed_crates_and_other_paths_are_accepted() {
+        let allowlist = Allowlist::parse("rand,lazy_static");
+        let available = available(&["rand", "lazy-static", "regex"]);
+
+        let code = "#[macro_use] extern crate lazy_static;\n\
+                    use std::collections::HashMap;\n\
+                    mod regex_like { pub fn f() {} }\n\
+                    fn main() { let x: u8 = ::rand::random(); regex_like::f(); }";
+        assert_eq!(allowlist.first_disallowed(code, &available), None);
+    }
+}
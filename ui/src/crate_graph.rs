@@ -0,0 +1,262 @@
+// Builds a dependency graph from the output of `cargo metadata`
+
+use petgraph::{
+    dot::{Config, Dot},
+    prelude::*,
+};
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+    resolve: Option<Resolve>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Package {
+    id: String,
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Resolve {
+    nodes: Vec<Node>,
+    root: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    id: String,
+    #[serde(default)]
+    dependencies: Vec<String>,
+    /// Only reported by newer versions of Cargo
+    #[serde(default)]
+    deps: Vec<NodeDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeDep {
+    pkg: String,
+    #[serde(default)]
+    dep_kinds: Vec<DepKindInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DepKindInfo {
+    kind: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CrateNode {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DependencyKind {
+    Normal,
+    Development,
+    Build,
+}
+
+impl DependencyKind {
+    fn from_cargo(kind: Option<&str>) -> Self {
+        match kind {
+            Some("dev") => DependencyKind::Development,
+            Some("build") => DependencyKind::Build,
+            _ => DependencyKind::Normal,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CrateEdge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: DependencyKind,
+}
+
+/// A crate depending on another in more than one way, such as both
+/// normally and to build, has an edge for each kind.
+#[derive(Debug, Clone)]
+pub struct CrateGraph {
+    graph: DiGraph<CrateNode, DependencyKind>,
+    root: Option<NodeIndex>,
+}
+
+impl CrateGraph {
+    pub fn from_metadata(json: &[u8]) -> serde_json::Result<Self> {
+        let metadata: Metadata = serde_json::from_slice(json)?;
+
+        let mut graph = DiGraph::new();
+        let mut indices = HashMap::new();
+
+        for package in metadata.packages {
+            let node = CrateNode { name: package.name, version: package.version };
+            indices.insert(package.id, graph.add_node(node));
+        }
+
+        let (nodes, root) = match metadata.resolve {
+            Some(resolve) => (resolve.nodes, resolve.root),
+            None => (Vec::new(), None),
+        };
+
+        for node in nodes {
+            let from = match indices.get(&node.id) {
+                Some(&idx) => idx,
+                None => continue,
+            };
+
+            for (dep, kind) in dependencies(&node) {
+                if let Some(&to) = indices.get(dep) {
+                    graph.add_edge(from, to, kind);
+                }
+            }
+        }
+
+        let root = root.and_then(|id| indices.get(&id).cloned());
+
+        Ok(CrateGraph { graph, root })
+    }
+
+    /// Where `CrateEdge` and `root` point to
+    pub fn nodes(&self) -> impl Iterator<Item = &CrateNode> {
+        self.graph.raw_nodes().iter().map(|n| &n.weight)
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = CrateEdge> + '_ {
+        self.graph.edge_references().map(|e| CrateEdge {
+            from: e.source().index(),
+            to: e.target().index(),
+            kind: *e.weight(),
+        })
+    }
+
+    /// The playground crate, when Cargo reports it
+    pub fn root(&self) -> Option<usize> {
+        self.root.map(NodeIndex::index)
+    }
+
+    pub fn to_dot(&self) -> String {
+        let labelled = self.graph.map(|_, n| format!("{} {}", n.name, n.version), |_, _| "");
+        format!("{}", Dot::with_config(&labelled, &[Config::EdgeNoLabel]))
+    }
+}
+
+// Each dependency is listed once in `deps` with all of the ways it is
+// used, while the older `dependencies` only has the package ids.
+fn dependencies(node: &Node) -> Vec<(&str, DependencyKind)> {
+    if node.deps.is_empty() {
+        return node.dependencies.iter().map(|id| (&**id, DependencyKind::Normal)).collect();
+    }
+
+    let mut dependencies = Vec::new();
+    for dep in &node.deps {
+        let mut kinds: Vec<_> = dep.dep_kinds.iter().map(|k| DependencyKind::from_cargo(k.kind.as_deref())).collect();
+        if kinds.is_empty() {
+            kinds.push(DependencyKind::Normal);
+        }
+        // Dependencies for several targets repeat their kind
+        kinds.sort();
+        kinds.dedup();
+
+        dependencies.extend(kinds.into_iter().map(|kind| (&*dep.pkg, kind)));
+    }
+    dependencies
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TWO_DEPENDENCIES: &str = r#"{
+        "packages": [
+            { "id": "playground 0.0.1", "name": "playground", "version": "0.0.1" },
+            { "id": "rand 0.7.3", "name": "rand", "version": "0.7.3" },
+            { "id": "regex 1.3.5", "name": "regex", "version": "1.3.5" }
+        ],
+        "resolve": {
+            "root": "playground 0.0.1",
+            "nodes": [
+                { "id": "playground 0.0.1", "dependencies": ["rand 0.7.3", "regex 1.3.5"] },
+                { "id": "rand 0.7.3", "dependencies": [] },
+                { "id": "regex 1.3.5", "dependencies": [] }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn dot_contains_dependencies() {
+        let graph = CrateGraph::from_metadata(TWO_DEPENDENCIES.as_bytes()).unwrap();
+        let dot = graph.to_dot();
+
+        assert!(dot.starts_with("digraph {"), "was: {}", dot);
+        assert!(dot.contains(r#""rand 0.7.3""#), "was: {}", dot);
+        assert!(dot.contains(r#""regex 1.3.5""#), "was: {}", dot);
+        assert_eq!(dot.matches("->").count(), 2, "was: {}", dot);
+    }
+
+    #[test]
+    fn edges_are_listed_from_the_root() {
+        let graph = CrateGraph::from_metadata(TWO_DEPENDENCIES.as_bytes()).unwrap();
+
+        let names: Vec<_> = graph.nodes().map(|n| format!("{} {}", n.name, n.version)).collect();
+        assert_eq!(names, ["playground 0.0.1", "rand 0.7.3", "regex 1.3.5"]);
+
+        let root = graph.root().expect("The root is missing");
+        assert_eq!(names[root], "playground 0.0.1");
+
+        let mut edges: Vec<_> = graph.edges().collect();
+        edges.sort_by_key(|e| e.to);
+        assert_eq!(edges, [
+            CrateEdge { from: root, to: 1, kind: DependencyKind::Normal },
+            CrateEdge { from: root, to: 2, kind: DependencyKind::Normal },
+        ]);
+    }
+
+    #[test]
+    fn edges_have_the_kind_of_dependency() {
+        let metadata = r#"{
+            "packages": [
+                { "id": "playground 0.0.1", "name": "playground", "version": "0.0.1" },
+                { "id": "cc 1.0.50", "name": "cc", "version": "1.0.50" },
+                { "id": "libc 0.2.68", "name": "libc", "version": "0.2.68" }
+            ],
+            "resolve": {
+                "root": "playground 0.0.1",
+                "nodes": [
+                    {
+                        "id": "playground 0.0.1",
+                        "dependencies": ["cc 1.0.50", "libc 0.2.68"],
+                        "deps": [
+                            { "name": "cc", "pkg": "cc 1.0.50", "dep_kinds": [{ "kind": "build", "target": null }] },
+                            {
+                                "name": "libc",
+                                "pkg": "libc 0.2.68",
+                                "dep_kinds": [
+                                    { "kind": null, "target": null },
+                                    { "kind": "dev", "target": null },
+                                    { "kind": "dev", "target": "cfg(unix)" }
+                                ]
+                            }
+                        ]
+                    },
+                    { "id": "cc 1.0.50", "dependencies": [], "deps": [] },
+                    { "id": "libc 0.2.68", "dependencies": [], "deps": [] }
+                ]
+            }
+        }"#;
+
+        let graph = CrateGraph::from_metadata(metadata.as_bytes()).unwrap();
+
+        let mut edges: Vec<_> = graph.edges().map(|e| (e.to, e.kind)).collect();
+        edges.sort();
+        assert_eq!(edges, [
+            (1, DependencyKind::Build),
+            (2, DependencyKind::Normal),
+            (2, DependencyKind::Development),
+        ]);
+    }
+}
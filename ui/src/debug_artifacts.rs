@@ -0,0 +1,135 @@
+// Keeps tarballs of build directories around long enough for an
+// operator to download each of them once
+
+use lazy_static::lazy_static;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const TOKEN_LEN: usize = 32;
+const TIME_TO_LIVE: Duration = Duration::from_secs(15 * 60);
+const TAKEN_EXTENSION: &str = "taken";
+
+/// Only available when `PLAYGROUND_DEBUG_ARTIFACTS` is set, as the
+/// tarballs expose the internals of the sandbox.
+pub fn store() -> Option<&'static Store> {
+    lazy_static! {
+        static ref STORE: Option<Store> = env::var_os("PLAYGROUND_DEBUG_ARTIFACTS")
+            .map(|_| Store::new(env::temp_dir().join("playground-debug-artifacts"), TIME_TO_LIVE));
+    }
+
+    STORE.as_ref()
+}
+
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+    time_to_live: Duration,
+}
+
+impl Store {
+    pub fn new(dir: PathBuf, time_to_live: Duration) -> Self {
+        Store { dir, time_to_live }
+    }
+
+    pub fn save(&self, tarball: &[u8]) -> io::Result<String> {
+        fs::create_dir_all(&self.dir)?;
+        self.evict();
+
+        let token: String = thread_rng().sample_iter(&Alphanumeric).take(TOKEN_LEN).collect();
+        fs::write(self.dir.join(&token), tarball)?;
+
+        Ok(token)
+    }
+
+    pub fn take(&self, token: &str) -> io::Result<Option<Vec<u8>>> {
+        if token.len() != TOKEN_LEN || !token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Ok(None);
+        }
+
+        // Renaming is atomic, so only one request can claim the tarball
+        let path = self.dir.join(token);
+        let taken = path.with_extension(TAKEN_EXTENSION);
+        match fs::rename(&path, &taken) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let expired = self.is_expired(&taken);
+        let tarball = fs::read(&taken);
+        fs::remove_file(&taken)?;
+
+        if expired {
+            Ok(None)
+        } else {
+            tarball.map(Some)
+        }
+    }
+
+    fn is_expired(&self, path: &Path) -> bool {
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .map_or(true, |age| age >= self.time_to_live)
+    }
+
+    fn evict(&self) {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Unable to read debug artifact directory: {}", e);
+                return;
+            }
+        };
+
+        for path in entries.flat_map(|entry| entry).map(|entry| entry.path()) {
+            if self.is_expired(&path) {
+                if let Err(e) = fs::remove_file(&path) {
+                    log::warn!("Unable to evict debug artifact {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn artifacts_can_only_be_taken_once() {
+        let dir = TempDir::new("debug-artifacts").unwrap();
+        let store = Store::new(dir.path().into(), TIME_TO_LIVE);
+
+        let token = store.save(b"tarball").unwrap();
+
+        assert_eq!(store.take(&token).unwrap(), Some(b"tarball".to_vec()));
+        assert_eq!(store.take(&token).unwrap(), None);
+    }
+
+    #[test]
+    fn expired_artifacts_are_not_returned() {
+        let dir = TempDir::new("debug-artifacts").unwrap();
+        let store = Store::new(dir.path().into(), Duration::from_secs(0));
+
+        let token = store.save(b"tarball").unwrap();
+
+        assert_eq!(store.take(&token).unwrap(), None);
+    }
+
+    #[test]
+    fn tokens_cannot_escape_the_store() {
+        let dir = TempDir::new("debug-artifacts").unwrap();
+        let store = Store::new(dir.path().join("store"), TIME_TO_LIVE);
+        fs::write(dir.path().join("secret"), "secret").unwrap();
+
+        assert_eq!(store.take("../secret").unwrap(), None);
+    }
+}
@@ -0,0 +1,35 @@
+// The code shown to someone who opens the playground without loading
+// a gist or permalink
+
+use std::{fs, io, path::Path};
+
+const BUILT_IN: &str = r#"fn main() {
+    println!("Hello, world!");
+}"#;
+
+pub fn load(path: Option<&Path>) -> io::Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(path),
+        None => Ok(BUILT_IN.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn the_configured_snippet_is_used() {
+        let dir = TempDir::new("default-snippet").unwrap();
+        let path = dir.path().join("welcome.rs");
+        fs::write(&path, "fn main() { println!(\"Welcome!\"); }").unwrap();
+
+        assert_eq!(load(Some(&path)).unwrap(), "fn main() { println!(\"Welcome!\"); }");
+    }
+
+    #[test]
+    fn hello_world_is_used_by_default() {
+        assert!(load(None).unwrap().contains("Hello, world!"));
+    }
+}
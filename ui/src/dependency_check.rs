@@ -0,0 +1,212 @@
+// Checks a list of dependencies against the crates that the
+// playground provides. The containers have no network access, so
+// these are the only crates that can ever be resolved.
+
+use semver::{Version, VersionReq};
+use serde_derive::Deserialize;
+use snafu::{ResultExt, Snafu};
+use std::collections::BTreeMap;
+use toml::Value;
+
+use crate::sandbox::CrateInformation;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    Resolved { name: String, version: String },
+    Failed { name: String, reason: String },
+}
+
+/// Accepts the entries of a `[dependencies]` table, with or without
+/// the header itself.
+pub fn parse(fragment: &str) -> Result<BTreeMap<String, Value>, toml::de::Error> {
+    let table: BTreeMap<String, Value> = toml::from_str(fragment)?;
+
+    match table.get("dependencies") {
+        Some(Value::Table(dependencies)) => Ok(dependencies.clone().into_iter().collect()),
+        _ => Ok(table),
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum LockfileError {
+    #[snafu(display("{}", source))]
+    LockfileSyntax { source: toml::de::Error },
+    #[snafu(display("no package is the crate itself, as none is without a source"))]
+    RootPackageMissing,
+    #[snafu(display("the dependency {:?} is not one of the locked packages", dependency))]
+    LockedPackageMissing { dependency: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    source: Option<String>,
+    /// Such as `rand`, `rand 0.7.3` or `rand 0.7.3 (registry+...)`
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// The crate's direct dependencies, each pinned to its locked version.
+/// Packages without a source are the crate and its workspace, so only
+/// their dependencies that have a source are included.
+pub fn from_lockfile(lockfile: &str) -> Result<BTreeMap<String, Value>, LockfileError> {
+    let lockfile: Lockfile = toml::from_str(lockfile).context(LockfileSyntax)?;
+    let roots: Vec<_> = lockfile.package.iter().filter(|package| package.source.is_none()).collect();
+
+    if roots.is_empty() {
+        return RootPackageMissing.fail();
+    }
+
+    let mut dependencies = BTreeMap::new();
+
+    for dependency in roots.iter().flat_map(|root| &root.dependencies) {
+        let mut parts = dependency.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let version = parts.next();
+
+        let package = lockfile
+            .package
+            .iter()
+            .find(|package| package.name == name && version.iter().all(|&v| package.version == v))
+            .ok_or_else(|| LockfileError::LockedPackageMissing { dependency: dependency.clone() })?;
+
+        if package.source.is_some() {
+            dependencies.insert(package.name.clone(), Value::String(format!("={}", package.version)));
+        }
+    }
+
+    Ok(dependencies)
+}
+
+pub fn resolve(dependencies: &BTreeMap<String, Value>, available: &[CrateInformation]) -> Vec<Resolution> {
+    dependencies
+        .iter()
+        .map(|(name, spec)| match resolve_one(name, spec, available) {
+            Ok(version) => Resolution::Resolved { name: name.clone(), version },
+            Err(reason) => Resolution::Failed { name: name.clone(), reason },
+        })
+        .collect()
+}
+
+fn resolve_one(name: &str, spec: &Value, available: &[CrateInformation]) -> Result<String, String> {
+    let (package, requirement) = match spec {
+        Value::String(requirement) => (name, requirement.as_str()),
+        Value::Table(table) => {
+            if table.contains_key("git") || table.contains_key("path") {
+                return Err("only crates from crates.io are available".into());
+            }
+            let package = table.get("package").and_then(Value::as_str).unwrap_or(name);
+            let requirement = table.get("version").and_then(Value::as_str).unwrap_or("*");
+            (package, requirement)
+        }
+        _ => return Err("expected a version requirement or a table".into()),
+    };
+
+    let requirement = VersionReq::parse(requirement)
+        .map_err(|e| format!("the version requirement {:?} is invalid: {}", requirement, e))?;
+
+    // Cargo treats `-` and `_` in crate names as the same
+    let normalize = |name: &str| name.replace('-', "_");
+    let candidates: Vec<_> = available
+        .iter()
+        .filter(|krate| normalize(&krate.name) == normalize(package))
+        .collect();
+
+    if candidates.is_empty() {
+        return Err(format!("no crate named `{}` is available", package));
+    }
+
+    candidates
+        .iter()
+        .filter_map(|krate| Version::parse(&krate.version).ok())
+        .find(|version| requirement.matches(version))
+        .map(|version| version.to_string())
+        .ok_or_else(|| {
+            let versions: Vec<_> = candidates.iter().map(|krate| krate.version.as_str()).collect();
+            format!("`{}` {} is not available; the available versions are {}", package, requirement, versions.join(", "))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn available() -> Vec<CrateInformation> {
+        vec![
+            CrateInformation { name: "rand".into(), version: "0.7.3".into(), id: "rand".into() },
+            CrateInformation { name: "serde_json".into(), version: "1.0.57".into(), id: "serde_json".into() },
+        ]
+    }
+
+    #[test]
+    fn provided_crates_resolve_to_their_versions() {
+        let dependencies = parse("[dependencies]\nrand = \"0.7\"\nserde-json = { version = \"1\" }\n").unwrap();
+
+        assert_eq!(resolve(&dependencies, &available()), [
+            Resolution::Resolved { name: "rand".into(), version: "0.7.3".into() },
+            Resolution::Resolved { name: "serde-json".into(), version: "1.0.57".into() },
+        ]);
+    }
+
+    #[test]
+    fn locked_dependencies_resolve_to_their_exact_versions() {
+        let lockfile = r#"
+            [[package]]
+            name = "getrandom"
+            version = "0.1.14"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "playground"
+            version = "0.0.1"
+            dependencies = [
+             "rand 0.7.3 (registry+https://github.com/rust-lang/crates.io-index)",
+            ]
+
+            [[package]]
+            name = "rand"
+            version = "0.7.3"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            dependencies = [
+             "getrandom",
+            ]
+        "#;
+
+        let dependencies = from_lockfile(lockfile).unwrap();
+
+        assert_eq!(resolve(&dependencies, &available()), [
+            Resolution::Resolved { name: "rand".into(), version: "0.7.3".into() },
+        ]);
+    }
+
+    #[test]
+    fn lockfiles_need_the_crate_itself() {
+        let lockfile = "[[package]]\nname = \"rand\"\nversion = \"0.7.3\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n";
+
+        assert!(from_lockfile(lockfile).is_err());
+        assert!(from_lockfile("[[package]\n").is_err());
+    }
+
+    #[test]
+    fn unknown_crates_and_versions_do_not_resolve() {
+        let dependencies = parse("not-a-real-crate = \"1\"\nrand = \"0.8\"\n").unwrap();
+
+        assert_eq!(resolve(&dependencies, &available()), [
+            Resolution::Failed {
+                name: "not-a-real-crate".into(),
+                reason: "no crate named `not-a-real-crate` is available".into(),
+            },
+            Resolution::Failed {
+                name: "rand".into(),
+                reason: "`rand` ^0.8 is not available; the available versions are 0.7.3".into(),
+            },
+        ]);
+    }
+}
@@ -0,0 +1,140 @@
+// Separates Cargo's progress messages about dependencies from the
+// output that concerns the user's own crate, or removes them all
+
+const USER_CRATE: &str = "playground";
+
+const STATUS_VERBS: &[&str] = &[
+    "Blocking",
+    "Checking",
+    "Compiling",
+    "Downloaded",
+    "Downloading",
+    "Fresh",
+    "Updating",
+];
+
+// Only shown for the user's own crate
+const BUILD_VERBS: &[&str] = &["Finished", "Running"];
+
+// Cargo right-aligns its verbs in this many columns
+const STATUS_WIDTH: usize = 12;
+
+/// Returns the dependency log and the remaining stderr.
+pub fn split(stderr: &str) -> (String, String) {
+    let mut dependencies = String::new();
+    let mut remaining = String::new();
+
+    for (line, from_cargo) in lines_from_cargo(stderr) {
+        let output = if from_cargo && is_dependency_status(line) { &mut dependencies } else { &mut remaining };
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    (dependencies, remaining)
+}
+
+/// Leaves only the diagnostics and the program's own output
+pub fn strip_status(stderr: &str) -> String {
+    let mut remaining = String::new();
+
+    for (line, from_cargo) in lines_from_cargo(stderr) {
+        if !(from_cargo && is_status(line)) {
+            remaining.push_str(line);
+            remaining.push('\n');
+        }
+    }
+
+    remaining
+}
+
+// Everything after Cargo's `Running` line was printed by the program
+fn lines_from_cargo(stderr: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut running = false;
+
+    stderr.lines().map(move |line| {
+        let from_cargo = !running;
+        running |= line.trim_start().starts_with("Running ");
+        (line, from_cargo)
+    })
+}
+
+// The program may print anything, so the verb has to be aligned
+// exactly as Cargo does it
+fn is_status(line: &str) -> bool {
+    let verb = match line.get(..STATUS_WIDTH) {
+        Some(verb) => verb.trim_start(),
+        None => return false,
+    };
+
+    line[STATUS_WIDTH..].starts_with(' ') &&
+        (STATUS_VERBS.contains(&verb) || BUILD_VERBS.contains(&verb))
+}
+
+fn is_dependency_status(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+
+    match (words.next(), words.next()) {
+        (Some(verb), Some(subject)) => STATUS_VERBS.contains(&verb) && subject != USER_CRATE,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BUILD_WITH_DEPENDENCIES: &str = "    Updating crates.io index
+   Compiling libc v0.2.66
+   Compiling rand v0.7.3
+   Compiling playground v0.0.1 (/playground)
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+  |
+2 |     let x = 1;
+  |         ^ help: consider prefixing with an underscore: `_x`
+
+    Finished dev [unoptimized + debuginfo] target(s) in 4.21s
+     Running `target/debug/playground`
+";
+
+    #[test]
+    fn user_warnings_are_not_moved_to_the_dependency_log() {
+        let (dependencies, stderr) = split(BUILD_WITH_DEPENDENCIES);
+
+        assert_eq!(dependencies, "    Updating crates.io index\n   Compiling libc v0.2.66\n   Compiling rand v0.7.3\n");
+        assert!(stderr.starts_with("   Compiling playground v0.0.1"), "stderr was: {}", stderr);
+        assert!(stderr.contains("warning: unused variable: `x`"), "stderr was: {}", stderr);
+        assert!(stderr.contains("Finished"), "stderr was: {}", stderr);
+    }
+
+    #[test]
+    fn program_output_resembling_a_dependency_is_kept() {
+        let output = format!("{}   Compiling foo v1.0\n", BUILD_WITH_DEPENDENCIES);
+        let (dependencies, stderr) = split(&output);
+
+        assert!(!dependencies.contains("foo"), "dependencies were: {}", dependencies);
+        assert!(stderr.ends_with("     Running `target/debug/playground`\n   Compiling foo v1.0\n"), "stderr was: {}", stderr);
+        assert!(strip_status(&output).ends_with("\n   Compiling foo v1.0\n"));
+    }
+
+    #[test]
+    fn all_progress_messages_can_be_stripped() {
+        let stderr = format!("{}thread 'main' panicked at 'explicit panic', src/main.rs:3:5\n", BUILD_WITH_DEPENDENCIES);
+
+        assert_eq!(strip_status(&stderr), "warning: unused variable: `x`
+ --> src/main.rs:2:9
+  |
+2 |     let x = 1;
+  |         ^ help: consider prefixing with an underscore: `_x`
+
+thread 'main' panicked at 'explicit panic', src/main.rs:3:5
+");
+    }
+
+    #[test]
+    fn program_output_resembling_progress_is_kept() {
+        let stderr = "Compiling the report\n  Finished: 3 of 4\n";
+
+        assert_eq!(strip_status(stderr), stderr);
+    }
+}
@@ -0,0 +1,519 @@
+// Reads the diagnostics and build messages that Cargo emits with
+// `--message-format=json`
+
+use serde_derive::Deserialize;
+
+pub const MAX_SUMMARY_LINES: usize = 100;
+
+// Cargo's package ids start with the name, such as
+// `playground 0.0.1 (path+file:///playground)`
+const PLAYGROUND_PACKAGE_ID: &str = "playground ";
+
+// The user's files, once made relative to the project
+const USER_SOURCE_DIR: &str = "src/";
+
+#[derive(Debug, Deserialize)]
+struct CargoMessage {
+    reason: String,
+    package_id: Option<String>,
+    message: Option<Diagnostic>,
+    target: Option<CargoTarget>,
+    #[serde(default)]
+    filenames: Vec<String>,
+    executable: Option<String>,
+    #[serde(default)]
+    fresh: bool,
+    success: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoTarget {
+    name: String,
+    #[serde(default)]
+    kind: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Diagnostic {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    level: String,
+    code: Option<DiagnosticCode>,
+    #[serde(default)]
+    spans: Vec<DiagnosticSpan>,
+    #[serde(default)]
+    children: Vec<Diagnostic>,
+    rendered: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticCode {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiagnosticSpan {
+    #[serde(default)]
+    file_name: String,
+    #[serde(default)]
+    is_primary: bool,
+    line_start: u32,
+    line_end: u32,
+    column_start: u32,
+    column_end: u32,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub line_start: u32,
+    pub column_start: u32,
+    pub line_end: u32,
+    pub column_end: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: String,
+}
+
+/// One of the compiler's messages, without the notes and help that
+/// are part of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub message: String,
+    /// Such as `E0308` or `clippy::eq_op`
+    pub code: Option<String>,
+    /// The primary span, when there is one
+    pub span: Option<Span>,
+    pub rendered: Option<String>,
+}
+
+/// The messages by their level. Notes and help that the compiler
+/// gives on their own, rather than as part of another message, are
+/// both `notes`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Groups {
+    pub errors: Vec<Message>,
+    pub warnings: Vec<Message>,
+    pub notes: Vec<Message>,
+}
+
+/// Something that Cargo built, including the dependencies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub target: String,
+    /// Such as `bin` or `lib`
+    pub kinds: Vec<String>,
+    /// Paths within the container
+    pub filenames: Vec<String>,
+    pub executable: Option<String>,
+    /// Cargo reused it from an earlier build
+    pub fresh: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BuildMessages {
+    pub artifacts: Vec<Artifact>,
+    /// From the `build-finished` message, which older versions of Cargo
+    /// don't send
+    pub succeeded: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    /// The diagnostics as a human would have seen them
+    pub rendered: String,
+    pub suggestions: Vec<Suggestion>,
+    /// Anything that was not a Cargo message
+    pub other: String,
+    /// The Cargo messages, one per line, exactly as they were emitted
+    pub raw: String,
+    pub build: BuildMessages,
+    pub groups: Groups,
+    pub first_error: Option<String>,
+    /// Not counting the compiler's closing tallies, such as "aborting
+    /// due to 2 previous errors"
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// Enough to show when there isn't room for all of the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// As the compiler rendered it
+    pub first_error: Option<String>,
+    pub errors: usize,
+    pub warnings: usize,
+    pub last_lines: Vec<String>,
+}
+
+impl Diagnostics {
+    /// At most `MAX_SUMMARY_LINES` are taken from the end of `stderr`
+    pub fn summarize(&self, stderr: &str, lines: usize) -> Summary {
+        let all_lines: Vec<_> = stderr.lines().collect();
+        let start = all_lines.len().saturating_sub(lines.min(MAX_SUMMARY_LINES));
+
+        Summary {
+            first_error: self.first_error.clone(),
+            errors: self.errors,
+            warnings: self.warnings,
+            last_lines: all_lines[start..].iter().map(|&line| line.to_string()).collect(),
+        }
+    }
+}
+
+pub fn parse(stdout: &str) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+
+    for line in stdout.lines() {
+        let message = match serde_json::from_str::<CargoMessage>(line) {
+            Ok(message) => message,
+            Err(_) => {
+                diagnostics.other.push_str(line);
+                diagnostics.other.push('\n');
+                continue;
+            }
+        };
+
+        diagnostics.raw.push_str(line);
+        diagnostics.raw.push('\n');
+
+        match &*message.reason {
+            "compiler-message" => {}
+            "compiler-artifact" => {
+                diagnostics.build.artifacts.extend(into_artifact(message));
+                continue;
+            }
+            "build-finished" => {
+                diagnostics.build.succeeded = message.success;
+                continue;
+            }
+            _ => continue,
+        }
+
+        if let Some(message) = message.message {
+            if let Some(rendered) = &message.rendered {
+                diagnostics.rendered.push_str(rendered);
+            }
+            count(&message, &mut diagnostics);
+            group(&message, &mut diagnostics.groups);
+            collect_suggestions(&message, &mut diagnostics.suggestions);
+        }
+    }
+
+    diagnostics
+}
+
+/// For reporting progress one line at a time, as Cargo emits them
+pub fn artifact(line: &str) -> Option<Artifact> {
+    let message = serde_json::from_str::<CargoMessage>(line).ok()?;
+    if message.reason != "compiler-artifact" {
+        return None;
+    }
+
+    into_artifact(message)
+}
+
+fn into_artifact(message: CargoMessage) -> Option<Artifact> {
+    let target = message.target?;
+    Some(Artifact {
+        target: target.name,
+        kinds: target.kind,
+        filenames: message.filenames,
+        executable: message.executable,
+        fresh: message.fresh,
+    })
+}
+
+fn is_tally(diagnostic: &Diagnostic) -> bool {
+    diagnostic.message.starts_with("aborting due to") ||
+        diagnostic.message.ends_with("warning emitted") ||
+        diagnostic.message.ends_with("warnings emitted")
+}
+
+fn count(diagnostic: &Diagnostic, diagnostics: &mut Diagnostics) {
+    if is_tally(diagnostic) {
+        return;
+    }
+
+    match &*diagnostic.level {
+        "error" | "error: internal compiler error" => {
+            diagnostics.errors += 1;
+            if diagnostics.first_error.is_none() {
+                diagnostics.first_error = diagnostic.rendered.clone();
+            }
+        }
+        "warning" => diagnostics.warnings += 1,
+        _ => {}
+    }
+}
+
+fn group(diagnostic: &Diagnostic, groups: &mut Groups) {
+    if is_tally(diagnostic) {
+        return;
+    }
+
+    let group = match &*diagnostic.level {
+        "error" | "error: internal compiler error" => &mut groups.errors,
+        "warning" => &mut groups.warnings,
+        "note" | "help" | "failure-note" => &mut groups.notes,
+        _ => return,
+    };
+
+    group.push(Message {
+        message: diagnostic.message.clone(),
+        code: diagnostic.code.as_ref().map(|code| code.code.clone()),
+        span: diagnostic.spans.iter().find(|span| span.is_primary).map(|span| Span {
+            line_start: span.line_start,
+            column_start: span.column_start,
+            line_end: span.line_end,
+            column_end: span.column_end,
+        }),
+        rendered: diagnostic.rendered.clone(),
+    });
+}
+
+fn collect_suggestions(diagnostic: &Diagnostic, suggestions: &mut Vec<Suggestion>) {
+    for span in &diagnostic.spans {
+        if let Some(replacement) = &span.suggested_replacement {
+            suggestions.push(Suggestion {
+                span: Span {
+                    line_start: span.line_start,
+                    column_start: span.column_start,
+                    line_end: span.line_end,
+                    column_end: span.column_end,
+                },
+                replacement: replacement.clone(),
+                applicability: span.suggestion_applicability.clone().unwrap_or_else(|| "Unspecified".into()),
+            });
+        }
+    }
+
+    for child in &diagnostic.children {
+        collect_suggestions(child, suggestions);
+    }
+}
+
+/// Drops the warnings that aren't about the user's code, such as
+/// lints reported for another package or within a dependency's macros.
+/// Errors are always kept, as is everything else Cargo emitted.
+pub fn retain_user_code(stdout: &str) -> String {
+    let mut retained = String::new();
+
+    for line in stdout.lines() {
+        let is_other_code = match serde_json::from_str::<CargoMessage>(line) {
+            Ok(message) => message.reason == "compiler-message" && !is_user_code(&message),
+            Err(_) => false,
+        };
+
+        if !is_other_code {
+            retained.push_str(line);
+            retained.push('\n');
+        }
+    }
+
+    retained
+}
+
+// Diagnostics without a primary span, such as the closing tallies,
+// belong to the package that they were emitted for. Only warnings
+// are attributed; an error stops the build wherever it came from.
+fn is_user_code(message: &CargoMessage) -> bool {
+    match &message.message {
+        Some(diagnostic) if diagnostic.level == "warning" => {}
+        _ => return true,
+    }
+
+    if let Some(id) = &message.package_id {
+        if !id.starts_with(PLAYGROUND_PACKAGE_ID) {
+            return false;
+        }
+    }
+
+    let mut primary = message.message.iter().flat_map(|d| &d.spans).filter(|span| span.is_primary).peekable();
+    if primary.peek().is_none() {
+        return true;
+    }
+
+    primary.any(|span| super::project_paths::normalize(&span.file_name).starts_with(USER_SOURCE_DIR))
+}
+
+/// Places the rendered diagnostics after Cargo's `Compiling` lines,
+/// which is where they appear when Cargo renders them itself.
+pub fn splice_rendered(cargo_stderr: &str, rendered: &str) -> String {
+    let lines: Vec<_> = cargo_stderr.lines().collect();
+    let split = lines
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("Compiling "))
+        .map_or(0, |idx| idx + 1);
+
+    let mut stderr = String::new();
+    for line in &lines[..split] {
+        stderr.push_str(line);
+        stderr.push('\n');
+    }
+    stderr.push_str(rendered);
+    for line in &lines[split..] {
+        stderr.push_str(line);
+        stderr.push('\n');
+    }
+    stderr
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MISTYPED_METHOD: &str = r#"{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"no method named `pusj` found for struct `std::vec::Vec<{integer}>` in the current scope","code":{"code":"E0599","explanation":null},"level":"error","spans":[{"file_name":"src/main.rs","byte_start":43,"byte_end":47,"line_start":3,"line_end":3,"column_start":7,"column_end":11,"is_primary":true,"text":[],"label":"help: there is a method with a similar name: `push`","suggested_replacement":"push","suggestion_applicability":"MaybeIncorrect","expansion":null}],"children":[],"rendered":"error[E0599]: no method named `pusj` found\n"}}
+{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"aborting due to previous error","code":null,"level":"error","spans":[],"children":[],"rendered":"error: aborting due to previous error\n\n"}}
+"#;
+
+    #[test]
+    fn suggestion_for_a_mistyped_method() {
+        let diagnostics = parse(MISTYPED_METHOD);
+
+        assert_eq!(diagnostics.suggestions, vec![Suggestion {
+            span: Span { line_start: 3, column_start: 7, line_end: 3, column_end: 11 },
+            replacement: "push".into(),
+            applicability: "MaybeIncorrect".into(),
+        }]);
+        assert!(diagnostics.rendered.starts_with("error[E0599]"), "was: {}", diagnostics.rendered);
+        assert!(diagnostics.other.is_empty());
+    }
+
+    #[test]
+    fn only_diagnostics_for_the_users_code_are_retained() {
+        let dependency = r#"{"reason":"compiler-message","package_id":"itoa 0.4.5 (registry+https://github.com/rust-lang/crates.io-index)","message":{"message":"this looks like you are swapping elements","code":{"code":"clippy::manual_swap","explanation":null},"level":"warning","spans":[{"file_name":"/playground/.cargo/registry/src/github.com-1ecc6299db9ec823/itoa-0.4.5/src/lib.rs","byte_start":10,"byte_end":20,"line_start":2,"line_end":2,"column_start":5,"column_end":15,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"warning: this looks like you are swapping elements\n"}}
+"#;
+        let macro_from_dependency = r#"{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"this comparison involving the minimum or maximum element","code":null,"level":"warning","spans":[{"file_name":"/playground/.cargo/registry/src/github.com-1ecc6299db9ec823/lazy_static-1.4.0/src/lib.rs","byte_start":10,"byte_end":20,"line_start":2,"line_end":2,"column_start":5,"column_end":15,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"warning: this comparison involving the minimum or maximum element\n"}}
+"#;
+        let stdout = format!("{}{}{}", dependency, macro_from_dependency, MISTYPED_METHOD);
+
+        let retained = retain_user_code(&stdout);
+        assert_eq!(retained, MISTYPED_METHOD);
+
+        let diagnostics = parse(&retained);
+        assert_eq!(diagnostics.errors, 1);
+        assert_eq!(diagnostics.warnings, 0);
+    }
+
+    #[test]
+    fn errors_outside_the_users_code_are_retained() {
+        let macro_from_dependency = r#"{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"mismatched types","code":{"code":"E0308","explanation":null},"level":"error","spans":[{"file_name":"/playground/.cargo/registry/src/github.com-1ecc6299db9ec823/lazy_static-1.4.0/src/lib.rs","byte_start":10,"byte_end":20,"line_start":2,"line_end":2,"column_start":5,"column_end":15,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"error[E0308]: mismatched types\n"}}
+"#;
+        let ice = r#"{"reason":"compiler-message","package_id":"itoa 0.4.5 (registry+https://github.com/rust-lang/crates.io-index)","message":{"message":"unexpected panic","code":null,"level":"error: internal compiler error","spans":[],"children":[],"rendered":"error: internal compiler error: unexpected panic\n"}}
+"#;
+        let stdout = format!("{}{}", macro_from_dependency, ice);
+
+        assert_eq!(retain_user_code(&stdout), stdout);
+    }
+
+    #[test]
+    fn messages_are_grouped_by_level() {
+        let warning = r#"{"reason":"compiler-message","package_id":"playground 0.0.1 (path+file:///playground)","message":{"message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/main.rs","byte_start":16,"byte_end":17,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":"warning: unused variable: `x`\n"}}
+"#;
+        let groups = parse(&format!("{}{}", warning, MISTYPED_METHOD)).groups;
+
+        assert_eq!(groups.errors.len(), 1, "errors were: {:?}", groups.errors);
+        assert_eq!(groups.errors[0].code.as_deref(), Some("E0599"));
+        assert_eq!(groups.errors[0].span, Some(Span { line_start: 3, column_start: 7, line_end: 3, column_end: 11 }));
+        assert_eq!(groups.warnings.len(), 1, "warnings were: {:?}", groups.warnings);
+        assert_eq!(groups.warnings[0].message, "unused variable: `x`");
+        assert!(groups.notes.is_empty());
+    }
+
+    #[test]
+    fn raw_diagnostics_are_the_cargo_messages() {
+        let stdout = format!("{}hello from a build script\n", MISTYPED_METHOD);
+        let diagnostics = parse(&stdout);
+
+        assert_eq!(diagnostics.raw, MISTYPED_METHOD);
+        for line in diagnostics.raw.lines() {
+            let message: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(message["reason"], "compiler-message");
+        }
+        assert_eq!(diagnostics.other, "hello from a build script\n");
+    }
+
+    #[test]
+    fn the_summary_counts_every_error() {
+        let message = |level: &str, message: &str| {
+            let rendered = format!("{}: {}\n", level, message);
+            serde_json::json!({
+                "reason": "compiler-message",
+                "message": { "message": message, "level": level, "spans": [], "children": [], "rendered": rendered },
+            }).to_string()
+        };
+
+        let mut stdout = String::new();
+        for n in 0..25 {
+            stdout.push_str(&message("error", &format!("cannot find value `x{}` in this scope", n)));
+            stdout.push('\n');
+        }
+        stdout.push_str(&message("warning", "unused variable: `y`"));
+        stdout.push('\n');
+        stdout.push_str(&message("error", "aborting due to 25 previous errors"));
+        stdout.push('\n');
+
+        let diagnostics = parse(&stdout);
+        let stderr = "   Compiling playground v0.0.1 (/playground)\nerror: aborting due to 25 previous errors\n\nerror: could not compile `playground`.\n";
+        let summary = diagnostics.summarize(stderr, 2);
+
+        assert_eq!(summary.errors, 25);
+        assert_eq!(summary.warnings, 1);
+        assert_eq!(summary.first_error.as_deref(), Some("error: cannot find value `x0` in this scope\n"));
+        assert_eq!(summary.last_lines, ["", "error: could not compile `playground`."]);
+    }
+
+    #[test]
+    fn artifacts_are_reported_with_their_paths() {
+        let stdout = r#"{"reason":"compiler-artifact","package_id":"itoa 0.4.5 (registry+https://github.com/rust-lang/crates.io-index)","target":{"kind":["lib"],"crate_types":["lib"],"name":"itoa","src_path":"/cargo/itoa/src/lib.rs","edition":"2015","doctest":true},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/playground/target/debug/deps/libitoa-b0d7f8e4a0b5c1e2.rlib"],"executable":null,"fresh":true}
+{"reason":"compiler-artifact","package_id":"playground 0.0.1 (path+file:///playground)","target":{"kind":["bin"],"crate_types":["bin"],"name":"playground","src_path":"/playground/src/main.rs","edition":"2018","doctest":false},"profile":{"opt_level":"0","debuginfo":2,"debug_assertions":true,"overflow_checks":true,"test":false},"features":[],"filenames":["/playground/target/debug/playground"],"executable":"/playground/target/debug/playground","fresh":false}
+{"reason":"build-finished","success":true}
+"#;
+
+        let build = parse(stdout).build;
+
+        assert_eq!(build.artifacts, [
+            Artifact {
+                target: "itoa".into(),
+                kinds: vec!["lib".into()],
+                filenames: vec!["/playground/target/debug/deps/libitoa-b0d7f8e4a0b5c1e2.rlib".into()],
+                executable: None,
+                fresh: true,
+            },
+            Artifact {
+                target: "playground".into(),
+                kinds: vec!["bin".into()],
+                filenames: vec!["/playground/target/debug/playground".into()],
+                executable: Some("/playground/target/debug/playground".into()),
+                fresh: false,
+            },
+        ]);
+        assert_eq!(build.succeeded, Some(true));
+    }
+
+    #[test]
+    fn artifacts_are_read_one_line_at_a_time() {
+        let line = r#"{"reason":"compiler-artifact","package_id":"itoa 0.4.5 (registry+https://github.com/rust-lang/crates.io-index)","target":{"kind":["lib"],"crate_types":["lib"],"name":"itoa","src_path":"/cargo/itoa/src/lib.rs","edition":"2015","doctest":true},"filenames":[],"executable":null,"fresh":true}"#;
+
+        let itoa = artifact(line).expect("no artifact");
+        assert_eq!((itoa.target.as_str(), itoa.fresh), ("itoa", true));
+
+        assert_eq!(artifact(r#"{"reason":"build-finished","success":true}"#), None);
+        assert_eq!(artifact("   Compiling itoa v0.4.5"), None);
+    }
+
+    #[test]
+    fn rendered_diagnostics_follow_the_compiling_line() {
+        let cargo_stderr = "   Compiling playground v0.0.1 (/playground)\nerror: could not compile `playground`.\n";
+        let stderr = splice_rendered(cargo_stderr, "error[E0599]: oops\n");
+
+        assert_eq!(stderr, "   Compiling playground v0.0.1 (/playground)\nerror[E0599]: oops\nerror: could not compile `playground`.\n");
+    }
+}
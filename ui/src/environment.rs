@@ -0,0 +1,160 @@
+// Environment variables that a client asks to set for the program it
+// executes. Only the names an operator has allowed may be set, and
+// names that change how the program is built, linked or run, such as
+// `PATH` or `LD_PRELOAD`, can never be set even if they are allowed.
+
+use std::{collections::BTreeSet, fmt};
+
+pub const MAX_VARIABLES: usize = 32;
+pub const MAX_VALUE_LEN: usize = 1024;
+const MAX_NAME_LEN: usize = 64;
+
+const RESERVED_NAMES: &[&str] = &[
+    "BASHOPTS",
+    "BASH_ENV",
+    "ENV",
+    "GCONV_PATH",
+    "GLIBC_TUNABLES",
+    "HOME",
+    "HOSTNAME",
+    "IFS",
+    "LANG",
+    "PATH",
+    "PWD",
+    "SHELL",
+    "SHELLOPTS",
+    "TMPDIR",
+    "TZ",
+    "USER",
+];
+
+const RESERVED_PREFIXES: &[&str] = &["CARGO", "DYLD_", "LC_", "LD_", "MALLOC_", "PLAYGROUND_", "RUST"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allowlist(BTreeSet<String>);
+
+impl Allowlist {
+    /// A comma-separated list of variable names
+    pub fn parse(s: &str) -> Self {
+        let names = s
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(Into::into)
+            .collect();
+
+        Allowlist(names)
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rejection {
+    InvalidName,
+    Reserved,
+    NotAllowed,
+    ValueTooLong,
+    ValueContainsNul,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::Rejection::*;
+
+        match self {
+            InvalidName => write!(f, "use up to {} letters, digits or '_', not starting with a digit", MAX_NAME_LEN),
+            Reserved => write!(f, "the name is reserved"),
+            NotAllowed => write!(f, "the name is not allowed on this playground"),
+            ValueTooLong => write!(f, "the value is longer than {} bytes", MAX_VALUE_LEN),
+            ValueContainsNul => write!(f, "the value contains a NUL byte"),
+        }
+    }
+}
+
+/// No name may be set unless it is allowed, and reserved names are
+/// rejected even then in case the allowlist is too generous.
+pub fn check(name: &str, value: &str, allowlist: Option<&Allowlist>) -> Result<(), Rejection> {
+    if !is_valid_name(name) {
+        return Err(Rejection::InvalidName);
+    }
+    if is_reserved(name) {
+        return Err(Rejection::Reserved);
+    }
+    match allowlist {
+        Some(allowlist) if allowlist.allows(name) => {}
+        _ => return Err(Rejection::NotAllowed),
+    }
+    if value.len() > MAX_VALUE_LEN {
+        return Err(Rejection::ValueTooLong);
+    }
+    if value.contains('\0') {
+        return Err(Rejection::ValueContainsNul);
+    }
+
+    Ok(())
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut bytes = name.bytes();
+
+    let starts_well = match bytes.next() {
+        Some(b) => b.is_ascii_alphabetic() || b == b'_',
+        None => false,
+    };
+    starts_well && name.len() <= MAX_NAME_LEN && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+// Names are case-sensitive, but a program may look them up either way
+fn is_reserved(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+
+    RESERVED_NAMES.contains(&&*name) || RESERVED_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allowlisted_variables_are_accepted() {
+        let allowlist = Allowlist::parse("GREETING, _log_level");
+
+        assert_eq!(check("GREETING", "Hello, world!", Some(&allowlist)), Ok(()));
+        assert_eq!(check("_log_level", "", Some(&allowlist)), Ok(()));
+    }
+
+    #[test]
+    fn reserved_variables_are_rejected_even_when_allowlisted() {
+        let allowlist = Allowlist::parse("PATH, LD_PRELOAD, RUSTFLAGS, playground_timeout, BASH_ENV, MALLOC_CHECK_");
+
+        assert_eq!(check("PATH", "/tmp", Some(&allowlist)), Err(Rejection::Reserved));
+        assert_eq!(check("LD_PRELOAD", "/tmp/evil.so", Some(&allowlist)), Err(Rejection::Reserved));
+        assert_eq!(check("RUSTFLAGS", "-Ctarget-cpu=native", Some(&allowlist)), Err(Rejection::Reserved));
+        assert_eq!(check("playground_timeout", "100", Some(&allowlist)), Err(Rejection::Reserved));
+        assert_eq!(check("BASH_ENV", "/tmp/evil.sh", Some(&allowlist)), Err(Rejection::Reserved));
+        assert_eq!(check("MALLOC_CHECK_", "3", Some(&allowlist)), Err(Rejection::Reserved));
+    }
+
+    #[test]
+    fn malformed_variables_are_rejected() {
+        let allowlist = Allowlist::parse("LONG, NUL");
+
+        assert_eq!(check("", "value", Some(&allowlist)), Err(Rejection::InvalidName));
+        assert_eq!(check("1ST", "value", Some(&allowlist)), Err(Rejection::InvalidName));
+        assert_eq!(check("A=B", "value", Some(&allowlist)), Err(Rejection::InvalidName));
+        assert_eq!(check("LONG", &"x".repeat(MAX_VALUE_LEN + 1), Some(&allowlist)), Err(Rejection::ValueTooLong));
+        assert_eq!(check("NUL", "a\0b", Some(&allowlist)), Err(Rejection::ValueContainsNul));
+    }
+
+    #[test]
+    fn only_allowlisted_variables_are_accepted() {
+        let allowlist = Allowlist::parse("GREETING, LOG_LEVEL");
+
+        assert_eq!(check("LOG_LEVEL", "debug", Some(&allowlist)), Ok(()));
+        assert_eq!(check("OTHER", "value", Some(&allowlist)), Err(Rejection::NotAllowed));
+        assert_eq!(check("GREETING", "Hello, world!", None), Err(Rejection::NotAllowed));
+    }
+}
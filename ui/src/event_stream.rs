@@ -0,0 +1,78 @@
+// Server-sent events, written as a task produces them. Each event has
+// a name and one line of JSON as its data.
+
+use iron::response::WriteBody;
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+pub struct Events(Sender<Vec<u8>>);
+
+impl Events {
+    /// Returns false once the client has gone away
+    pub fn send(&self, name: &str, data: &impl Serialize) -> bool {
+        // Serializing our own responses can't fail in practice
+        let data = match serde_json::to_string(data) {
+            Ok(data) => data,
+            Err(e) => {
+                log::error!("Unable to serialize the {} event: {}", name, e);
+                return true;
+            }
+        };
+
+        self.0.send(format!("event: {}\ndata: {}\n\n", name, data).into_bytes()).is_ok()
+    }
+}
+
+pub struct EventStream {
+    events: Receiver<Vec<u8>>,
+}
+
+impl EventStream {
+    /// The stream ends when the task returns
+    pub fn spawn<F>(task: F) -> Self
+    where
+        F: FnOnce(Events) + Send + 'static,
+    {
+        let (tx, events) = mpsc::channel();
+        thread::spawn(move || task(Events(tx)));
+
+        EventStream { events }
+    }
+}
+
+impl WriteBody for EventStream {
+    fn write_body(&mut self, res: &mut dyn Write) -> io::Result<()> {
+        for event in self.events.iter() {
+            res.write_all(&event)?;
+            res.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn events_are_written_in_the_order_they_are_sent() {
+        let mut stream = EventStream::spawn(|events| {
+            events.send("progress", &json!({ "target": "rand" }));
+            events.send("result", &json!({ "success": true }));
+        });
+
+        let mut body = Vec::new();
+        stream.write_body(&mut body).unwrap();
+
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "event: progress\ndata: {\"target\":\"rand\"}\n\nevent: result\ndata: {\"success\":true}\n\n",
+        );
+    }
+}
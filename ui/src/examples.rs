@@ -0,0 +1,117 @@
+// Example snippets that the frontend can offer to load. Each directory
+// within the configured one is a category and each `.rs` file in it is
+// an example, titled after its file name.
+
+use std::{ffi::OsStr, fs, io, path::Path};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    pub examples: Vec<Example>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    pub title: String,
+    pub code: String,
+}
+
+/// There are no examples unless a directory is configured. Categories
+/// and examples are sorted by name; empty categories are left out.
+pub fn load(path: Option<&Path>) -> io::Result<Vec<Category>> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut categories = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        let examples = load_category(&entry.path())?;
+        if !examples.is_empty() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            categories.push(Category { name, examples });
+        }
+    }
+
+    categories.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(categories)
+}
+
+fn load_category(path: &Path) -> io::Result<Vec<Example>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(path)? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new("rs")) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    files
+        .iter()
+        .map(|path| {
+            let code = fs::read_to_string(path)?;
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            Ok(Example { title: title(&stem), code })
+        })
+        .collect()
+}
+
+// `shared_state.rs` becomes "Shared state"
+fn title(stem: &str) -> String {
+    let words = stem.replace(&['_', '-'][..], " ");
+    let mut chars = words.trim().chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn configured_examples_are_grouped_by_category() {
+        let dir = TempDir::new("examples").unwrap();
+        fs::create_dir(dir.path().join("concurrency")).unwrap();
+        fs::create_dir(dir.path().join("basics")).unwrap();
+        fs::create_dir(dir.path().join("empty")).unwrap();
+        fs::write(dir.path().join("basics/hello_world.rs"), "fn main() {}").unwrap();
+        fs::write(dir.path().join("basics/README.md"), "Not an example").unwrap();
+        fs::write(dir.path().join("concurrency/shared-state.rs"), "use std::sync::Mutex;").unwrap();
+        fs::write(dir.path().join("concurrency/channels.rs"), "use std::sync::mpsc;").unwrap();
+        fs::write(dir.path().join("stray.rs"), "fn main() {}").unwrap();
+
+        let example = |title: &str, code: &str| Example { title: title.into(), code: code.into() };
+
+        assert_eq!(load(Some(dir.path())).unwrap(), [
+            Category {
+                name: "basics".into(),
+                examples: vec![example("Hello world", "fn main() {}")],
+            },
+            Category {
+                name: "concurrency".into(),
+                examples: vec![
+                    example("Channels", "use std::sync::mpsc;"),
+                    example("Shared state", "use std::sync::Mutex;"),
+                ],
+            },
+        ]);
+    }
+
+    #[test]
+    fn there_are_no_examples_by_default() {
+        assert_eq!(load(None).unwrap(), []);
+    }
+}
@@ -5,23 +5,68 @@ use hubcaps::{
 };
 use hyper;
 use hyper_tls;
-use std::collections::HashMap;
-use tokio1::{prelude::Future, runtime::current_thread::Runtime};
+use snafu::Snafu;
+use std::{collections::HashMap, iter, sync::Mutex, time::Duration};
+use tokio1::{prelude::Future, runtime::current_thread::Runtime, timer::timeout, util::FutureExt};
 
 const FILENAME: &str = "playground.rs";
+const METADATA_FILENAME: &str = "playground.json";
 const DESCRIPTION: &str = "Code shared from the Rust Playground";
+const USER_AGENT: &str = "The Rust Playground";
+const SCOPES_HEADER: &str = "x-oauth-scopes";
+const GIST_SCOPE: &str = "gist";
+const GITHUB_HOST: &str = "https://api.github.com";
 
+// GitHub normally answers well within this, so waiting any longer
+// only delays telling the user that it can't be reached
+const GITHUB_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("GitHub could not be reached"))]
+    Unavailable,
+    #[snafu(display("Too many requests to GitHub are already in progress"))]
+    Busy,
+    #[snafu(display("GitHub was unable to handle the request: {}", source))]
+    Request { source: hubcaps::Error },
+}
+
+impl From<timeout::Error<hubcaps::Error>> for Error {
+    fn from(other: timeout::Error<hubcaps::Error>) -> Self {
+        match other.into_inner() {
+            Some(source) => match source.kind() {
+                hubcaps::ErrorKind::Http(e) if e.is_connect() => Error::Unavailable,
+                _ => Error::Request { source },
+            },
+            // The time ran out or the timer itself failed
+            None => Error::Unavailable,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Gist {
     pub id: String,
     pub url: String,
     pub code: String,
+    pub metadata: Option<String>,
 }
 
 impl From<gists::Gist> for Gist {
     fn from(other: gists::Gist) -> Self {
+        let mut metadata = None;
+
         let mut files: Vec<_> = other.files
             .into_iter()
             .map(|(name, file)| (name, file.content.unwrap_or_default()))
+            .filter_map(|(name, content)| {
+                if name == METADATA_FILENAME {
+                    metadata = Some(content);
+                    None
+                } else {
+                    Some((name, content))
+                }
+            })
             .collect();
 
         files.sort_by(|(name1, _), (name2, _)| name1.cmp(name2));
@@ -40,20 +85,95 @@ impl From<gists::Gist> for Gist {
             id: other.id,
             url: other.html_url,
             code: code,
+            metadata,
+        }
+    }
+}
+
+/// Caps on what a single gist may contain. GitHub has its own limits,
+/// but only reports them as a generic validation failure.
+#[derive(Debug, Copy, Clone)]
+pub struct Limits {
+    pub max_files: usize,
+    pub max_bytes: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Files { count: usize, max: usize },
+    Bytes { count: usize, max: usize },
+}
+
+impl Limits {
+    /// Checks the files that `create_with_metadata` would upload
+    pub fn check(&self, code: &str, metadata: Option<&str>) -> Result<(), LimitExceeded> {
+        let files: Vec<_> = iter::once(code).chain(metadata).collect();
+
+        let count = files.len();
+        if count > self.max_files {
+            return Err(LimitExceeded::Files { count, max: self.max_files });
+        }
+
+        let count = files.iter().map(|file| file.len()).sum();
+        if count > self.max_bytes {
+            return Err(LimitExceeded::Bytes { count, max: self.max_bytes });
+        }
+
+        Ok(())
+    }
+}
+
+/// Caps how many requests to GitHub are in progress at once, as they
+/// all share the server's token and its rate limit. Requests beyond
+/// the cap are turned away instead of waiting, as each waiting request
+/// would hold one of the server's threads.
+#[derive(Debug)]
+pub struct Concurrency {
+    max: usize,
+    running: Mutex<usize>,
+}
+
+impl Concurrency {
+    pub fn new(max: usize) -> Self {
+        Concurrency { max: max.max(1), running: Mutex::new(0) }
+    }
+
+    pub fn run<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let _running = self.start().ok_or(Error::Busy)?;
+        f()
+    }
+
+    fn start(&self) -> Option<Running<'_>> {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        if *running >= self.max {
+            return None;
         }
+        *running += 1;
+        Some(Running(self))
     }
 }
 
-pub fn create(token: String, code: String) -> Gist {
+// Finishes the request even when it panics
+struct Running<'a>(&'a Concurrency);
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        let mut running = self.0.running.lock().unwrap_or_else(|e| e.into_inner());
+        *running -= 1;
+    }
+}
+
+/// Creates a gist that additionally stores arbitrary metadata (such
+/// as the options used to run the code) in a separate file.
+pub fn create_with_metadata(token: String, code: String, metadata: Option<String>) -> Result<Gist, Error> {
     Runtime::new()
         .expect("unable to create runtime")
-        .block_on(create_future(token, code))
-        .expect("Unable to create gist")
-    // TODO: Better reporting of failures
+        .block_on(create_future(GITHUB_HOST, token, code, metadata).timeout(GITHUB_TIMEOUT))
+        .map_err(Into::into)
 }
 
-pub fn create_future(token: String, code: String) -> impl Future<Item = Gist, Error = hubcaps::Error> {
-    let github = github(token);
+pub fn create_future(host: &str, token: String, code: String, metadata: Option<String>) -> impl Future<Item = Gist, Error = hubcaps::Error> {
+    let github = github(host, token);
 
     let file = Content {
         filename: None,
@@ -63,6 +183,14 @@ pub fn create_future(token: String, code: String) -> impl Future<Item = Gist, Er
     let mut files = HashMap::new();
     files.insert(FILENAME.into(), file);
 
+    if let Some(metadata) = metadata {
+        let file = Content {
+            filename: None,
+            content: metadata,
+        };
+        files.insert(METADATA_FILENAME.into(), file);
+    }
+
     let options = GistOptions {
         description: Some(DESCRIPTION.into()),
         public: Some(false),
@@ -75,16 +203,20 @@ pub fn create_future(token: String, code: String) -> impl Future<Item = Gist, Er
         .map(Into::into)
 }
 
-pub fn load(token: String, id: &str) -> Gist {
+pub fn load(token: String, id: &str) -> Result<Gist, Error> {
+    load_within(GITHUB_HOST, token, id, GITHUB_TIMEOUT)
+    // TODO: Better reporting of a 404
+}
+
+fn load_within(host: &str, token: String, id: &str, time_limit: Duration) -> Result<Gist, Error> {
     Runtime::new()
         .expect("unable to create runtime")
-        .block_on(load_future(token, id))
-        .expect("Unable to load gist")
-    // TODO: Better reporting of a 404
+        .block_on(load_future(host, token, id).timeout(time_limit))
+        .map_err(Into::into)
 }
 
-pub fn load_future(token: String, id: &str) -> impl Future<Item = Gist, Error = ::hubcaps::Error> {
-    let github = github(token);
+pub fn load_future(host: &str, token: String, id: &str) -> impl Future<Item = Gist, Error = ::hubcaps::Error> {
+    let github = github(host, token);
 
     github
         .gists()
@@ -92,11 +224,192 @@ pub fn load_future(token: String, id: &str) -> impl Future<Item = Gist, Error =
         .map(Into::into)
 }
 
+/// The OAuth scopes that GitHub reports for the token. Tokens that
+/// don't use OAuth scopes (such as fine-grained tokens) report none.
+pub fn token_scopes(token: String) -> Result<Option<Vec<String>>, timeout::Error<hyper::Error>> {
+    token_scopes_within(GITHUB_HOST, token, GITHUB_TIMEOUT)
+}
+
+fn token_scopes_within(host: &str, token: String, time_limit: Duration) -> Result<Option<Vec<String>>, timeout::Error<hyper::Error>> {
+    Runtime::new()
+        .expect("unable to create runtime")
+        .block_on(token_scopes_future(host, token).timeout(time_limit))
+}
+
+pub fn token_scopes_future(host: &str, token: String) -> impl Future<Item = Option<Vec<String>>, Error = hyper::Error> {
+    let https = HubcapConnector::new(1).expect("Unable to create TLS connector");
+    let client = hyper::Client::builder().build::<_, hyper::Body>(https);
+
+    let request = hyper::Request::get(host)
+        .header("Authorization", format!("token {}", token))
+        .header("User-Agent", USER_AGENT)
+        .body(hyper::Body::empty())
+        .expect("Unable to build token scope request");
+
+    client
+        .request(request)
+        .map(|response| {
+            response
+                .headers()
+                .get(SCOPES_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(parse_scopes)
+        })
+}
+
+fn parse_scopes(header: &str) -> Vec<String> {
+    header
+        .split(',')
+        .map(str::trim)
+        .filter(|scope| !scope.is_empty())
+        .map(Into::into)
+        .collect()
+}
+
+pub fn has_gist_scope(scopes: &[String]) -> bool {
+    scopes.iter().any(|scope| scope == GIST_SCOPE)
+}
+
 type HubcapConnector = hyper_tls::HttpsConnector<hyper::client::HttpConnector>;
 
-fn github(token: String) -> Github<HubcapConnector> {
-    Github::new(
-        String::from("The Rust Playground"),
+fn github(host: &str, token: String) -> Github<HubcapConnector> {
+    Github::host(
+        host,
+        String::from(USER_AGENT),
         Some(Credentials::Token(token)),
     )
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn token_with_gist_scope() {
+        let scopes = parse_scopes("gist, read:user");
+        assert!(has_gist_scope(&scopes));
+    }
+
+    #[test]
+    fn token_without_gist_scope() {
+        let scopes = parse_scopes("repo, read:user");
+        assert!(!has_gist_scope(&scopes));
+    }
+
+    #[test]
+    fn requests_beyond_the_concurrency_are_turned_away() {
+        use std::sync::{mpsc, Arc, Barrier};
+        use std::thread;
+
+        let concurrency = Arc::new(Concurrency::new(2));
+        let started = Arc::new(Barrier::new(3));
+        let (finish, finished) = mpsc::channel::<()>();
+        let finished = Arc::new(Mutex::new(finished));
+
+        let running: Vec<_> = (0..2)
+            .map(|_| {
+                let (concurrency, started, finished) = (concurrency.clone(), started.clone(), finished.clone());
+                thread::spawn(move || {
+                    concurrency.run(|| {
+                        started.wait();
+                        finished.lock().unwrap().recv().unwrap();
+                        Ok(())
+                    })
+                })
+            })
+            .collect();
+
+        started.wait();
+        match concurrency.run(|| Ok(())) {
+            Err(Error::Busy) => {}
+            other => panic!("A third request should have been turned away, but was {:?}", other),
+        }
+
+        finish.send(()).unwrap();
+        finish.send(()).unwrap();
+        for thread in running {
+            thread.join().unwrap().unwrap();
+        }
+
+        concurrency.run(|| Ok(())).expect("A finished request should make room for another");
+    }
+
+    #[test]
+    fn metadata_counts_toward_the_limits() {
+        let limits = Limits { max_files: 1, max_bytes: 100 };
+
+        assert_eq!(limits.check("fn main() {}", None), Ok(()));
+        assert_eq!(limits.check("fn main() {}", Some("{}")), Err(LimitExceeded::Files { count: 2, max: 1 }));
+
+        let limits = Limits { max_files: 2, max_bytes: 14 };
+        assert_eq!(limits.check("fn main() {}", Some("{\"a\":1}")), Err(LimitExceeded::Bytes { count: 19, max: 14 }));
+    }
+
+    #[test]
+    fn unreachable_github_fails_promptly() {
+        // Reserved for documentation, so nothing ever answers
+        let host = "https://192.0.2.1";
+        let start = std::time::Instant::now();
+
+        let result = load_within(host, "token".into(), "abc", Duration::from_millis(500));
+
+        match result {
+            Err(Error::Unavailable) => {}
+            other => panic!("GitHub was not reported as unavailable: {:?}", other),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Answers a single request the way GitHub does, reporting the
+    /// token's scopes in a header
+    fn mock_github(scopes: &'static str) -> String {
+        use std::io::{Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\n{}: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                SCOPES_HEADER, scopes,
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        host
+    }
+
+    #[test]
+    fn token_scopes_reported_by_github_without_gist_are_detected() {
+        let host = mock_github("repo, read:user");
+
+        let scopes = token_scopes_within(&host, "token".into(), Duration::from_secs(5))
+            .expect("The mock GitHub did not answer")
+            .expect("The scopes header was not read");
+
+        assert_eq!(scopes, ["repo", "read:user"]);
+        assert!(!has_gist_scope(&scopes));
+    }
+
+    #[test]
+    fn token_scopes_reported_by_github_with_gist_are_accepted() {
+        let host = mock_github("gist");
+
+        let scopes = token_scopes_within(&host, "token".into(), Duration::from_secs(5))
+            .expect("The mock GitHub did not answer")
+            .expect("The scopes header was not read");
+
+        assert!(has_gist_scope(&scopes));
+    }
+
+    #[test]
+    fn token_without_any_scope() {
+        let scopes = parse_scopes("");
+        assert!(scopes.is_empty());
+        assert!(!has_gist_scope(&scopes));
+    }
+}
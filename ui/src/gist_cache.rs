@@ -0,0 +1,160 @@
+// Keeps recently loaded gists in memory. Shared gists are rarely
+// edited, so the same link being opened many times only needs to
+// reach GitHub once.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::gist::Gist;
+
+#[derive(Debug)]
+pub struct GistCache {
+    capacity: usize,
+    time_to_live: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: HashMap<String, Entry>,
+    // Increases on every use; the entry with the smallest value is
+    // the least recently used
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    gist: Gist,
+    loaded_at: Instant,
+    last_used: u64,
+}
+
+impl GistCache {
+    pub fn new(capacity: usize, time_to_live: Duration) -> Self {
+        GistCache { capacity, time_to_live, inner: Mutex::default() }
+    }
+
+    /// Returns the cached gist unless it has expired, otherwise calls
+    /// `load` and caches its result. Failures are not cached.
+    pub fn get_or_load<E>(&self, id: &str, now: Instant, load: impl FnOnce() -> Result<Gist, E>) -> Result<Gist, E> {
+        if let Some(gist) = self.get(id, now) {
+            return Ok(gist);
+        }
+
+        // The lock isn't held while loading so that a slow response
+        // from GitHub doesn't hold up every other gist
+        let gist = load()?;
+        self.insert(id, gist.clone(), now);
+        Ok(gist)
+    }
+
+    fn get(&self, id: &str, now: Instant) -> Option<Gist> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let clock = inner.clock;
+
+        let entry = inner.entries.get_mut(id)?;
+        if now.duration_since(entry.loaded_at) < self.time_to_live {
+            entry.last_used = clock;
+            return Some(entry.gist.clone());
+        }
+
+        inner.entries.remove(id);
+        None
+    }
+
+    fn insert(&self, id: &str, gist: Gist, now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.clock += 1;
+        let last_used = inner.clock;
+
+        if !inner.entries.contains_key(id) && inner.entries.len() >= self.capacity {
+            let oldest = inner.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(id, _)| id.clone());
+
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+
+        inner.entries.insert(id.into(), Entry { gist, loaded_at: now, last_used });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn gist(id: &str) -> Gist {
+        Gist {
+            id: id.into(),
+            url: format!("https://gist.github.com/{}", id),
+            code: "fn main() {}".into(),
+            metadata: None,
+        }
+    }
+
+    fn load(cache: &GistCache, id: &str, now: Instant, calls: &Cell<u32>) -> Gist {
+        let loaded: Result<_, ()> = cache.get_or_load(id, now, || {
+            calls.set(calls.get() + 1);
+            Ok(gist(id))
+        });
+        loaded.unwrap()
+    }
+
+    #[test]
+    fn a_second_load_does_not_reach_github() {
+        let cache = GistCache::new(10, HOUR);
+        let calls = Cell::new(0);
+        let now = Instant::now();
+
+        load(&cache, "abc", now, &calls);
+        let gist = load(&cache, "abc", now + Duration::from_secs(1), &calls);
+
+        assert_eq!(gist.id, "abc");
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn expired_gists_are_loaded_again() {
+        let cache = GistCache::new(10, HOUR);
+        let calls = Cell::new(0);
+        let now = Instant::now();
+
+        load(&cache, "abc", now, &calls);
+        load(&cache, "abc", now + HOUR, &calls);
+
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn the_least_recently_used_gist_is_evicted() {
+        let cache = GistCache::new(2, HOUR);
+        let calls = Cell::new(0);
+        let now = Instant::now();
+
+        load(&cache, "a", now, &calls);
+        load(&cache, "b", now, &calls);
+        load(&cache, "a", now, &calls);
+        load(&cache, "c", now, &calls);
+        assert_eq!(calls.get(), 3);
+
+        load(&cache, "a", now, &calls);
+        assert_eq!(calls.get(), 3);
+
+        load(&cache, "b", now, &calls);
+        assert_eq!(calls.get(), 4);
+    }
+}
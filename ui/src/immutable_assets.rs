@@ -0,0 +1,87 @@
+// UI files whose names contain a hash of their contents, such as
+// `app.0123abcd.js`, are never changed in place; a new build gives them
+// a new name instead. Browsers may keep them for as long as they like.
+
+use iron::{
+    headers::{CacheControl, CacheDirective},
+    prelude::*,
+    status, AfterMiddleware,
+};
+use regex::Regex;
+
+/// A dot, at least 8 hex digits, and then the extension
+pub const DEFAULT_PATTERN: &str = r"\.[0-9a-f]{8,}\.[0-9A-Za-z]+$";
+
+const ONE_YEAR_IN_SECONDS: u32 = 60 * 60 * 24 * 365;
+
+/// Replaces the `Cache-Control` of the files whose names match the
+/// pattern. It is linked after the shorter-lived caching so that it
+/// takes precedence.
+pub struct ImmutableAssets(Regex);
+
+impl ImmutableAssets {
+    pub fn new(pattern: Regex) -> Self {
+        ImmutableAssets(pattern)
+    }
+
+    fn is_hashed(&self, req: &Request<'_, '_>) -> bool {
+        req.url.path().last().into_iter().any(|name| self.0.is_match(name))
+    }
+}
+
+impl AfterMiddleware for ImmutableAssets {
+    fn after(&self, req: &mut Request<'_, '_>, mut res: Response) -> IronResult<Response> {
+        if let Some(status::Ok) | Some(status::PartialContent) | Some(status::NotModified) = res.status {
+            if self.is_hashed(req) {
+                res.headers.set(CacheControl(vec![
+                    CacheDirective::Public,
+                    CacheDirective::MaxAge(ONE_YEAR_IN_SECONDS),
+                    CacheDirective::Extension("immutable".into(), None),
+                ]));
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iron::{Chain, Iron};
+    use std::{io::prelude::*, net::TcpStream};
+
+    #[test]
+    fn only_hashed_files_are_immutable() {
+        let handler = |_: &mut Request<'_, '_>| {
+            let mut res = Response::with((status::Ok, "body"));
+            res.headers.set(CacheControl(vec![CacheDirective::Public, CacheDirective::MaxAge(86400)]));
+            Ok(res)
+        };
+        let mut chain = Chain::new(handler);
+        chain.link_after(ImmutableAssets::new(Regex::new(DEFAULT_PATTERN).unwrap()));
+
+        let mut listening = Iron::new(chain).http("127.0.0.1:0").expect("Unable to start server");
+
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(listening.socket).unwrap();
+            write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let hashed = get("/assets/app.0123abcd.js");
+        assert!(
+            hashed.contains("Cache-Control: public, max-age=31536000, immutable\r\n"),
+            "response was: {}", hashed,
+        );
+
+        let plain = get("/assets/app.js");
+        assert!(plain.contains("Cache-Control: public, max-age=86400\r\n"), "response was: {}", plain);
+        assert!(!plain.contains("immutable"), "response was: {}", plain);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+// Follows each line of the code through formatting so that the editor
+// can keep the cursor on the same line of code.
+
+// Beyond this many comparisons, lines are mapped proportionally
+pub const MAX_COMPARISONS: usize = 4_000_000;
+
+/// The formatted line number (1-based) of each original line, in
+/// order. Lines are matched ignoring all whitespace, since formatting
+/// mostly changes indentation and spacing. Lines without a match, such
+/// as those joined with another line, follow the previous match.
+pub fn map(original: &str, formatted: &str) -> Vec<u32> {
+    let original: Vec<_> = original.lines().map(normalize).collect();
+    let formatted: Vec<_> = formatted.lines().map(normalize).collect();
+
+    // Usually only a small part of the code changes
+    let prefix = original.iter().zip(&formatted).take_while(|(a, b)| a == b).count();
+    let suffix = original[prefix..].iter().rev()
+        .zip(formatted[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let original_middle = &original[prefix..original.len() - suffix];
+    let formatted_middle = &formatted[prefix..formatted.len() - suffix];
+
+    let middle = if original_middle.len().saturating_mul(formatted_middle.len()) <= MAX_COMPARISONS {
+        follow_previous_match(matching_lines(original_middle, formatted_middle), formatted_middle.len())
+    } else {
+        proportional(original_middle.len(), formatted_middle.len())
+    };
+
+    let prefix_lines = (0..prefix).map(|idx| idx + 1);
+    let middle_lines = middle.into_iter().map(|idx| prefix + idx + 1);
+    let suffix_lines = (0..suffix).map(|idx| formatted.len() - suffix + idx + 1);
+
+    prefix_lines.chain(middle_lines).chain(suffix_lines).map(|line| line as u32).collect()
+}
+
+fn normalize(line: &str) -> String {
+    line.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// The 0-based index in `changed` that each line of `original` is
+/// paired with by the longest common subsequence of the two, if any.
+/// This makes `original.len() * changed.len()` comparisons, so callers
+/// check that against `MAX_COMPARISONS` first.
+pub fn matching_lines<T: PartialEq>(original: &[T], changed: &[T]) -> Vec<Option<usize>> {
+    let (n, m) = (original.len(), changed.len());
+
+    // lengths[i][j] is the length of the LCS of original[i..] and changed[j..]
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if original[i] == changed[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut mapping = Vec::with_capacity(n);
+    let (mut i, mut j) = (0, 0);
+
+    while i < n {
+        if j < m && original[i] == changed[j] {
+            mapping.push(Some(j));
+            i += 1;
+            j += 1;
+        } else if j < m && lengths[i][j + 1] >= lengths[i + 1][j] {
+            j += 1;
+        } else {
+            mapping.push(None);
+            i += 1;
+        }
+    }
+
+    mapping
+}
+
+fn follow_previous_match(mapping: Vec<Option<usize>>, formatted: usize) -> Vec<usize> {
+    let mut next_unmatched = 0;
+
+    mapping
+        .into_iter()
+        .map(|matched| match matched {
+            Some(idx) => {
+                next_unmatched = idx + 1;
+                idx
+            }
+            None => next_unmatched.min(formatted.saturating_sub(1)),
+        })
+        .collect()
+}
+
+fn proportional(original: usize, formatted: usize) -> Vec<usize> {
+    (0..original)
+        .map(|idx| (idx * formatted / original.max(1)).min(formatted.saturating_sub(1)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn an_inserted_line_shifts_the_following_lines() {
+        let original = "fn main() {\n    let a = 1;\n    let b = 2;\n}\n";
+        let formatted = "fn main() {\n    let a = 1;\n\n    let b = 2;\n}\n";
+
+        assert_eq!(map(original, formatted), [1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn reindented_and_split_lines_are_followed() {
+        let original = "fn main() { let a = 1;\nprintln!(\"{}\", a); }\nfn other() {}\n";
+        let formatted = "fn main() {\n    let a = 1;\n    println!(\"{}\", a);\n}\nfn other() {}\n";
+
+        assert_eq!(map(original, formatted), [1, 1, 5]);
+    }
+}
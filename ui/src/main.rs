@@ -2,658 +2,3677 @@
 
 use corsware::{AllowedOrigins, CorsMiddleware, UniCase};
 use iron::{
-    headers::ContentType,
+    headers::{Accept, Authorization, Bearer, ContentType, Vary},
     method::Method::{Get, Post},
     modifiers::Header,
     prelude::*,
     status,
 };
 use lazy_static::lazy_static;
-use mount::Mount;
 use playground_middleware::{
     Cache, FileLogger, GuessContentType, ModifyWith, Prefix, Rewrite, Staticfile, StatisticLogger,
 };
+use rand::Rng;
 use router::Router;
+use routes::{RecordedRouter, Routes};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_derive::{Deserialize, Serialize};
 use snafu::{ResultExt, Snafu};
 use std::{
     any::Any,
+    collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
     env,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
-    time::{Duration, Instant},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
 };
 
-use crate::sandbox::Sandbox;
+use crate::{asset_headers::AssetHeaders, circuit_breaker::CircuitBreaker, gist_cache::GistCache, immutable_assets::ImmutableAssets, quota::DailyQuota, sandbox::Sandbox, slow_requests::SlowRequestLogger};
 
 const DEFAULT_ADDRESS: &str = "127.0.0.1";
 const DEFAULT_PORT: u16 = 5000;
 const DEFAULT_LOG_FILE: &str = "access-log.csv";
 
 mod asm_cleanup;
+mod asset_headers;
+mod backtrace;
+mod cancellation;
+mod canonical;
+mod cargo_lock;
+mod circuit_breaker;
+mod clippy_lints;
+mod content_hash;
+mod coverage;
+mod crate_allowlist;
+mod crate_graph;
+mod debug_artifacts;
+mod default_snippet;
+mod dependency_check;
+mod dependency_log;
+mod diagnostics;
+mod environment;
+mod event_stream;
+mod examples;
 mod gist;
+mod gist_cache;
+mod immutable_assets;
+mod line_map;
+mod metrics;
+mod minimize;
+mod miri_errors;
+mod multipart;
+mod patch;
+mod project_paths;
+mod prelude;
+mod profile;
+mod quota;
+mod recent_failures;
+mod response_fields;
+mod response_limit;
+mod result_stream;
+mod routes;
+mod rustdoc_json;
 mod sandbox;
+mod slow_requests;
+mod startup;
+mod strace;
+mod time_passes;
+mod warm_pool;
+mod wasm_sections;
 
 const ONE_HOUR_IN_SECONDS: u32 = 60 * 60;
 const ONE_DAY_IN_SECONDS: u64 = 60 * 60 * 24;
 const ONE_YEAR_IN_SECONDS: u64 = 60 * 60 * 24 * 365;
 
 const SANDBOX_CACHE_TIME_TO_LIVE_IN_SECONDS: u64 = ONE_HOUR_IN_SECONDS as u64;
+// Components and targets only change when the containers are rebuilt
+const CAPABILITIES_CACHE_TIME_TO_LIVE_IN_SECONDS: u64 = ONE_DAY_IN_SECONDS;
+
+const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_IN_SECONDS: u64 = 30;
+
+const DEFAULT_GIST_CACHE_SIZE: usize = 1000;
+const DEFAULT_GIST_CACHE_TIME_TO_LIVE_IN_SECONDS: u64 = 24 * 60 * 60;
+const DEFAULT_MAX_GIST_FILES: usize = 10;
+// GitHub truncates the content of larger files
+const DEFAULT_MAX_GIST_BYTES: usize = 1024 * 1024;
+const DEFAULT_MAX_CONCURRENT_GISTS: usize = 10;
+
+const DEFAULT_MAX_EXECUTE_REPEAT: u32 = 10;
+
+const MAX_FORMAT_FILES: usize = 20;
+
+// Gist ids are 32 characters and asset names are short, so this only
+// turns away abuse
+const DEFAULT_MAX_PATH_LEN: usize = 256;
+
+const QUOTA_REMAINING_HEADER: &str = "X-Quota-Remaining";
+
+// Not a standard status, but commonly used (such as by nginx) when
+// the client gave up on the request
+const CLIENT_CLOSED_REQUEST: u16 = 499;
 
 fn main() {
+    if let Err(e) = run() {
+        eprintln!("Unable to start the playground: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> startup::Result<()> {
     // Dotenv may be unable to load environment variables, but that's ok in production
     println!("Loading environment");
 
     let _ = dotenv::dotenv();
+
+    if let Some(path) = env::var_os("PLAYGROUND_CONFIG").map(PathBuf::from) {
+        let values = startup::config_file(&path)?;
+        for (key, value) in startup::config_overrides(values, |key| env::var_os(key).is_some()) {
+            env::set_var(key, value);
+        }
+    }
+
     openssl_probe::init_ssl_cert_env_vars();
     env_logger::init();
 
-    let root: PathBuf = env::var_os("PLAYGROUND_UI_ROOT").expect("Must specify PLAYGROUND_UI_ROOT").into();
-    let gh_token = env::var("PLAYGROUND_GITHUB_TOKEN").expect("Must specify PLAYGROUND_GITHUB_TOKEN");
+    let root = startup::ui_root(env::var_os("PLAYGROUND_UI_ROOT"))?;
+    let gh_token = env::var("PLAYGROUND_GITHUB_TOKEN").map_err(|_| startup::Error::VariableMissing {
+        name: "PLAYGROUND_GITHUB_TOKEN",
+        purpose: "a GitHub API token that can read and write gists",
+    })?;
 
     let address = env::var("PLAYGROUND_UI_ADDRESS").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string());
     let port = env::var("PLAYGROUND_UI_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(DEFAULT_PORT);
     let logfile = env::var("PLAYGROUND_LOG_FILE").unwrap_or_else(|_| DEFAULT_LOG_FILE.to_string());
     let cors_enabled = env::var_os("PLAYGROUND_CORS_ENABLED").is_some();
+    let allow_null_origin = env::var("PLAYGROUND_ALLOW_NULL_ORIGIN").map(|v| v != "false").unwrap_or(true);
+    let require_gist_scope = env::var_os("PLAYGROUND_GITHUB_TOKEN_REQUIRE_GIST_SCOPE").is_some();
+    let default_snippet_path = env::var_os("PLAYGROUND_DEFAULT_SNIPPET").map(PathBuf::from);
+    let examples_path = env::var_os("PLAYGROUND_EXAMPLES_DIR").map(PathBuf::from);
+    let daily_quota = env::var("PLAYGROUND_DAILY_QUOTA").ok().and_then(|q| q.parse().ok()).map(DailyQuota::new).map(Arc::new);
+    let max_connections = env::var("PLAYGROUND_MAX_CONNECTIONS").ok().and_then(|m| m.parse().ok());
+    let max_path_len = env::var("PLAYGROUND_MAX_PATH_LEN").ok().and_then(|m| m.parse().ok()).unwrap_or(DEFAULT_MAX_PATH_LEN);
+    let admin_token = env::var("PLAYGROUND_ADMIN_TOKEN").ok().filter(|t| !t.is_empty()).map(Arc::new);
+    let evaluate_enabled = env::var("PLAYGROUND_EVALUATE_ENABLED").map(|v| v != "false").unwrap_or(true);
+    let evaluate_content_type = env::var("PLAYGROUND_EVALUATE_CONTENT_TYPE").ok();
+    let slow_request_threshold = env::var("PLAYGROUND_SLOW_REQUEST_MS").ok().and_then(|ms| ms.parse().ok()).map(Duration::from_millis);
+    let asset_headers = startup::asset_headers(env::var("PLAYGROUND_ASSET_HEADERS").ok())?;
+    let hashed_asset_pattern = startup::hashed_asset_pattern(env::var("PLAYGROUND_HASHED_ASSET_PATTERN").ok())?;
+    startup::check_max_threads(env::var("PLAYGROUND_MAX_THREADS").ok())?;
+
+    let default_snippet = default_snippet::load(default_snippet_path.as_deref())
+        .context(startup::DefaultSnippetUnreadable { path: default_snippet_path.unwrap_or_default() })?;
+    let examples = examples::load(examples_path.as_deref())
+        .context(startup::ExamplesUnreadable { path: examples_path.unwrap_or_default() })?;
+
+    println!("Checking GitHub token");
+
+    check_github_token(&gh_token, require_gist_scope);
+
+    if profiling_enabled() {
+        println!("Checking profiling support");
+
+        if !sandbox::profiling_supported() {
+            log::warn!("Docker does not support the PERFMON capability, which needs Docker 20.10 or later; profiling requests will be refused");
+        }
+    }
+
+    // Requests use new containers until these have started
+    thread::spawn(sandbox::fill_warm_pool);
 
     println!("Linking UI files");
 
-    let files = Staticfile::new(&root).expect("Unable to open root directory");
+    if !startup::ui_index_exists(&root) {
+        log::warn!(
+            "The UI root {} does not contain {}; `/` will report that the UI assets were not found. \
+             Build the frontend or set PLAYGROUND_UI_ROOT to the directory containing its output",
+            root.display(),
+            startup::UI_INDEX,
+        );
+    }
+
+    let files = Staticfile::new(&root).context(startup::UiRootUnreadable { path: &root })?;
     let mut files = Chain::new(files);
     let one_day = Duration::new(ONE_DAY_IN_SECONDS, 0);
     let one_year = Duration::new(ONE_YEAR_IN_SECONDS, 0);
 
     files.link_after(ModifyWith::new(Cache::new(one_day)));
     files.link_after(Prefix::new(&["assets"], Cache::new(one_year)));
+    files.link_after(ImmutableAssets::new(hashed_asset_pattern));
     files.link_after(GuessContentType::new(ContentType::html().0));
+    if !asset_headers.is_empty() {
+        files.link_after(AssetHeaders::new(asset_headers));
+    }
 
-    let mut gist_router = Router::new();
+    let mut gist_router = RecordedRouter::new();
     gist_router.post("/", meta_gist_create, "gist_create");
     gist_router.get("/:id", meta_gist_get, "gist_get");
 
+    let mut debug_artifacts_router = RecordedRouter::new();
+    debug_artifacts_router.get("/:token", meta_debug_artifacts, "debug_artifacts_get");
+
+    let mut permalink_router = RecordedRouter::new();
+    permalink_router.post("/", meta_permalink_create, "permalink_create");
+    permalink_router.get("/:id", meta_permalink_get, "permalink_get");
+
     println!("Mounting routes");
 
-    let mut mount = Mount::new();
-    mount.mount("/", files);
-    mount.mount("/compile", compile);
-    mount.mount("/execute", execute);
-    mount.mount("/format", format);
-    mount.mount("/clippy", clippy);
-    mount.mount("/miri", miri);
-    mount.mount("/meta/crates", meta_crates);
-    mount.mount("/meta/version/stable", meta_version_stable);
-    mount.mount("/meta/version/beta", meta_version_beta);
-    mount.mount("/meta/version/nightly", meta_version_nightly);
-    mount.mount("/meta/version/rustfmt", meta_version_rustfmt);
-    mount.mount("/meta/version/clippy", meta_version_clippy);
-    mount.mount("/meta/version/miri", meta_version_miri);
-    mount.mount("/meta/gist", gist_router);
-    mount.mount("/evaluate.json", evaluate);
-
-
-    let mut chain = Chain::new(mount);
-    let file_logger = FileLogger::new(logfile).expect("Unable to create file logger");
+    let mut routes = Routes::new();
+    routes.get("/", PathLimited::new(max_path_len, IndexRequired::new(&root, files)));
+    mount_sandbox_routes(&mut routes, &daily_quota);
+    routes.post("/execute/cancel", execute_cancel);
+    routes.get("/tree/json", tree_json);
+    routes.get("/meta/crates", meta_crates);
+    routes.get("/meta/crates/graph", meta_crates_graph);
+    routes.get("/meta/crates/versions", meta_crates_versions);
+    routes.get("/meta/clippy-lints", meta_clippy_lints);
+    routes.get("/meta/nightly-flags", meta_nightly_flags);
+    routes.get("/meta/default-snippet", move |req: &mut Request<'_, '_>| meta_default_snippet(req, &default_snippet));
+    routes.get("/meta/examples", move |req: &mut Request<'_, '_>| meta_examples(req, &examples));
+    routes.get("/meta/circuit-breaker", meta_circuit_breaker);
+    routes.get("/meta/version/stable", meta_version_stable);
+    routes.get("/meta/version/beta", meta_version_beta);
+    routes.get("/meta/version/nightly", meta_version_nightly);
+    routes.get("/meta/version/rustfmt", meta_version_rustfmt);
+    routes.get("/meta/version/clippy", meta_version_clippy);
+    routes.get("/meta/version/miri", meta_version_miri);
+    routes.get("/meta/capabilities", meta_capabilities);
+    routes.get("/meta/components", meta_components);
+    routes.post("/meta/hash", meta_hash);
+    routes.post("/meta/validate-deps", meta_validate_deps);
+    routes.post("/meta/metrics", meta_metrics);
+    routes.post("/meta/has-main", meta_has_main);
+    routes.mount_router("/meta/gist", gist_router, |router| PathLimited::new(max_path_len, router));
+    routes.mount_router("/meta/permalink", permalink_router, |router| PathLimited::new(max_path_len, router));
+    routes.mount_router("/meta/debug-artifacts", debug_artifacts_router, |router| router);
+    routes.get("/admin/recent-failures", AdminOnly::new(&admin_token, admin_recent_failures));
+    mount_evaluate(&mut routes, evaluate_enabled, QuotaLimited::new(&daily_quota, move |req: &mut Request<'_, '_>| evaluate(req, evaluate_content_type.as_deref())));
+
+    // Mounted last so that the list is complete
+    routes.mount_listing("/admin/routes", |list| {
+        AdminOnly::new(&admin_token, move |req: &mut Request<'_, '_>| admin_routes(req, &list))
+    });
+
+    let mut chain = Chain::new(routes.into_mount());
+    let file_logger = FileLogger::new(&logfile).context(startup::AccessLogUnwritable { path: &logfile })?;
     let logger = StatisticLogger::new(file_logger);
     let rewrite = Rewrite::new(vec![vec!["help".into()]], "/index.html".into());
     let gh_token = GhToken::new(gh_token);
 
     chain.link_around(logger);
+    if let Some(threshold) = slow_request_threshold {
+        chain.link_around(SlowRequestLogger::new(threshold));
+    }
     chain.link_before(rewrite);
     chain.link_before(gh_token);
 
     if cors_enabled {
-        chain.link_around(CorsMiddleware {
-            // A null origin occurs when you make a request from a
-            // page hosted on a filesystem, such as when you read the
-            // Rust book locally
-            allowed_origins: AllowedOrigins::Any { allow_null: true },
-            allowed_headers: vec![UniCase("Content-Type".to_owned())],
-            allowed_methods: vec![Get, Post],
-            exposed_headers: vec![],
-            allow_credentials: false,
-            max_age_seconds: ONE_HOUR_IN_SECONDS,
-            prefer_wildcard: true,
-        });
+        chain.link_around(cors(allow_null_origin));
     }
 
     log::info!("Starting the server on http://{}:{}", address, port);
-    Iron::new(chain).http((&*address, port)).expect("Unable to start server");
-}
+    let _server = server(chain, max_connections)
+        .http((&*address, port))
+        .context(startup::Listen { address: &address, port })?;
 
-#[derive(Debug, Clone)]
-struct GhToken(Arc<String>);
+    // Dropping the server waits for it to stop, which it never does
+    Ok(())
+}
 
-impl GhToken {
-    fn new(token: String) -> Self {
-        GhToken(Arc::new(token))
+fn cors(allow_null_origin: bool) -> CorsMiddleware {
+    CorsMiddleware {
+        // A null origin occurs when you make a request from a page
+        // hosted on a filesystem, such as when you read the Rust book
+        // locally. Any page can also choose to send it, so locked-down
+        // deployments may turn it off.
+        allowed_origins: AllowedOrigins::Any { allow_null: allow_null_origin },
+        allowed_headers: vec![UniCase("Content-Type".to_owned())],
+        allowed_methods: vec![Get, Post],
+        exposed_headers: vec![UniCase(QUOTA_REMAINING_HEADER.to_owned())],
+        allow_credentials: false,
+        max_age_seconds: ONE_HOUR_IN_SECONDS,
+        prefer_wildcard: true,
     }
 }
 
-impl iron::BeforeMiddleware for GhToken {
-    fn before(&self, req: &mut Request<'_, '_>) -> IronResult<()> {
-        req.extensions.insert::<Self>(self.clone());
-        Ok(())
+// Each connection is handled by its own thread until it closes, so
+// limiting the threads stops new connections from being accepted.
+fn server<H: iron::Handler>(handler: H, max_connections: Option<usize>) -> Iron<H> {
+    let mut server = Iron::new(handler);
+    if let Some(max_connections) = max_connections {
+        server.threads = max_connections.max(1);
     }
+    server
 }
 
-impl iron::typemap::Key for GhToken {
-    type Value = Self;
+// When disabled, requests are not found, as they were before the
+// endpoint existed
+fn mount_evaluate<H: iron::Handler>(routes: &mut Routes, enabled: bool, handler: H) {
+    if enabled {
+        routes.post("/evaluate.json", handler);
+    } else {
+        routes.mount_not_found("/evaluate.json");
+    }
 }
 
-fn compile(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: CompileRequest| {
-        let req = req.try_into()?;
-        sandbox
-            .compile(&req)
-            .map(CompileResponse::from)
-            .context(Compilation)
-    })
+// The endpoints that run code in the sandbox, each of which counts
+// against the daily quota
+fn mount_sandbox_routes(routes: &mut Routes, daily_quota: &Option<Arc<DailyQuota>>) {
+    routes.post("/compile", QuotaLimited::new(daily_quota, compile));
+    routes.post("/compile/crate-types", QuotaLimited::new(daily_quota, compile_crate_types).costing(compile_crate_types_cost));
+    routes.mount("/execute", &[Get, Post], QuotaLimited::new(daily_quota, execute));
+    routes.post("/execute/repeat", QuotaLimited::new(daily_quota, execute_repeat).costing(execute_repeat_cost));
+    routes.post("/build-run", QuotaLimited::new(daily_quota, build_run));
+    routes.post("/edition-diff", QuotaLimited::new(daily_quota, edition_diff).costing(|_| EDITION_DIFF_RUNS));
+    routes.post("/minimize", QuotaLimited::new(daily_quota, minimize).costing(|_| MAX_MINIMIZE_ATTEMPTS + 1));
+    routes.post("/format", QuotaLimited::new(daily_quota, format).costing(format_cost));
+    routes.post("/format/check", QuotaLimited::new(daily_quota, format_check));
+    routes.post("/clippy", QuotaLimited::new(daily_quota, clippy));
+    routes.post("/miri", QuotaLimited::new(daily_quota, miri));
+    routes.post("/doc/json", QuotaLimited::new(daily_quota, doc_json));
+    routes.post("/compat", QuotaLimited::new(daily_quota, compat).costing(|_| COMPAT_SANDBOXES));
+    routes.post("/check", QuotaLimited::new(daily_quota, check).costing(|_| CHECK_SANDBOXES));
+    routes.post("/compiles", QuotaLimited::new(daily_quota, compiles));
+    routes.post("/rpc", QuotaLimited::new(daily_quota, rpc));
 }
 
-fn execute(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: ExecuteRequest| {
-        let req = req.try_into()?;
-        sandbox
-            .execute(&req)
-            .map(ExecuteResponse::from)
-            .context(Execution)
-    })
+// A token without the `gist` scope only fails once someone tries to
+// share their code, so point out the problem as early as possible.
+fn check_github_token(token: &str, require_gist_scope: bool) {
+    match gist::token_scopes(token.to_string()) {
+        Ok(Some(ref scopes)) if gist::has_gist_scope(scopes) => {}
+        Ok(Some(scopes)) => {
+            log::error!("The GitHub token does not have the `gist` scope (it has {:?}); creating gists will fail", scopes);
+            if require_gist_scope {
+                std::process::exit(1);
+            }
+        }
+        Ok(None) => log::warn!("GitHub did not report the scopes of the GitHub token"),
+        Err(e) => log::warn!("Unable to check the scopes of the GitHub token: {}", e),
+    }
 }
 
-fn format(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: FormatRequest| {
-        let req = req.try_into()?;
-        sandbox
-            .format(&req)
-            .map(FormatResponse::from)
-            .context(Formatting)
-    })
+// Only the requests that use the sandbox count against the quota, once
+// for each sandbox that they run
+struct QuotaLimited<H> {
+    quota: Option<Arc<DailyQuota>>,
+    cost: fn(&mut Request<'_, '_>) -> u32,
+    handler: H,
 }
 
-fn clippy(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: ClippyRequest| {
-        sandbox
-            .clippy(&req.try_into()?)
-            .map(ClippyResponse::from)
-            .context(Linting)
-    })
-}
+impl<H> QuotaLimited<H> {
+    fn new(quota: &Option<Arc<DailyQuota>>, handler: H) -> Self {
+        QuotaLimited { quota: quota.clone(), cost: |_| 1, handler }
+    }
 
-fn miri(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: MiriRequest| {
-        sandbox
-            .miri(&req.try_into()?)
-            .map(MiriResponse::from)
-            .context(Interpreting)
-    })
+    fn costing(self, cost: fn(&mut Request<'_, '_>) -> u32) -> Self {
+        QuotaLimited { cost, ..self }
+    }
 }
 
-fn meta_crates(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .crates()
-            .map(MetaCratesResponse::from)
-    })
-}
+impl<H: iron::Handler> iron::Handler for QuotaLimited<H> {
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        let quota = match &self.quota {
+            Some(quota) => quota,
+            None => return self.handler.handle(req),
+        };
 
-fn meta_version_stable(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_stable()
-            .map(MetaVersionResponse::from)
-    })
-}
+        let units = (self.cost)(req);
+        let (remaining, mut response) = match quota.take(req.remote_addr.ip(), units, SystemTime::now()) {
+            Ok(remaining) => (remaining, self.handler.handle(req)?),
+            Err(quota::Exhausted) => {
+                let err = QuotaExceeded { limit: quota.limit() }.fail::<()>();
+                (0, serialize_to_response(ResponseFormat::negotiate(req), err)?)
+            }
+        };
 
-fn meta_version_beta(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_beta()
-            .map(MetaVersionResponse::from)
-    })
+        response.headers.set_raw(QUOTA_REMAINING_HEADER, vec![remaining.to_string().into_bytes()]);
+        Ok(response)
+    }
 }
 
-fn meta_version_nightly(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_nightly()
-            .map(MetaVersionResponse::from)
-    })
+/// Turns away paths, such as gist ids and asset names, that are longer
+/// than any the handler serves. Only the part of the path after where
+/// the handler is mounted counts.
+struct PathLimited<H> {
+    max: usize,
+    handler: H,
 }
 
-fn meta_version_rustfmt(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_rustfmt()
-            .map(MetaVersionResponse::from)
-    })
+impl<H> PathLimited<H> {
+    fn new(max: usize, handler: H) -> Self {
+        PathLimited { max, handler }
+    }
 }
 
-fn meta_version_clippy(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_clippy()
-            .map(MetaVersionResponse::from)
-    })
+impl<H: iron::Handler> iron::Handler for PathLimited<H> {
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        let len = req.url.path().join("/").len();
+
+        if len > self.max {
+            let err = PathTooLong { len, max: self.max }.fail::<()>();
+            return serialize_to_response(ResponseFormat::negotiate(req), err);
+        }
+
+        self.handler.handle(req)
+    }
 }
 
-fn meta_version_miri(_req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox_no_request(|sandbox| {
-        cached(sandbox)
-            .version_miri()
-            .map(MetaVersionResponse::from)
-    })
+const UI_ASSETS_NOT_FOUND: &str = r#"<!DOCTYPE html>
+<html>
+  <head><title>UI assets not found</title></head>
+  <body>
+    <h1>UI assets not found</h1>
+    <p>The playground's frontend has not been built, or PLAYGROUND_UI_ROOT does not point to its output.</p>
+  </body>
+</html>
+"#;
+
+/// Explains that the frontend is missing when the index page is
+/// requested but was never built, instead of answering with a bare
+/// 404. The root is checked on each request so that building the
+/// frontend doesn't require a restart.
+struct IndexRequired<H> {
+    root: PathBuf,
+    handler: H,
 }
 
-fn meta_gist_create(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
-    serialize_to_response(deserialize_from_request(req, |r: MetaGistCreateRequest| {
-        let gist = gist::create(token, r.code);
-        Ok(MetaGistResponse::from(gist))
-    }))
+impl<H> IndexRequired<H> {
+    fn new(root: &Path, handler: H) -> Self {
+        IndexRequired { root: root.to_owned(), handler }
+    }
 }
 
-fn meta_gist_get(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    match req.extensions.get::<Router>().unwrap().find("id") {
-        Some(id) => {
-            let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
-            let gist = gist::load(token, id);
-            serialize_to_response(Ok(MetaGistResponse::from(gist)))
-        }
-        None => {
-            Ok(Response::with(status::UnprocessableEntity))
+impl<H: iron::Handler> iron::Handler for IndexRequired<H> {
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        let path = req.url.path();
+        let is_index = path == [""] || path == [startup::UI_INDEX];
+
+        if is_index && !startup::ui_index_exists(&self.root) {
+            return Ok(Response::with((status::ServiceUnavailable, Header(ContentType::html()), UI_ASSETS_NOT_FOUND)));
         }
+
+        self.handler.handle(req)
     }
 }
 
-// This is a backwards compatibilty shim. The Rust homepage and the
-// documentation use this to run code in place.
-fn evaluate(req: &mut Request<'_, '_>) -> IronResult<Response> {
-    with_sandbox(req, |sandbox, req: EvaluateRequest| {
-        let req = req.try_into()?;
-        sandbox
-            .execute(&req)
-            .map(EvaluateResponse::from)
-            .context(Evaluation)
-    })
+/// Requires the request to carry `Authorization: Bearer <token>`
+/// with the configured admin token.
+struct AdminOnly<H> {
+    token: Option<Arc<String>>,
+    handler: H,
 }
 
-fn with_sandbox<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> IronResult<Response>
-where
-    F: FnOnce(Sandbox, Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
-    Resp: Serialize,
-{
-    serialize_to_response(run_handler(req, f))
+impl<H> AdminOnly<H> {
+    fn new(token: &Option<Arc<String>>, handler: H) -> Self {
+        AdminOnly { token: token.clone(), handler }
+    }
 }
 
-fn with_sandbox_no_request<Resp, F>(f: F) -> IronResult<Response>
-where
-    F: FnOnce(Sandbox) -> Result<Resp>,
-    Resp: Serialize,
-{
-    serialize_to_response(run_handler_no_request(f))
+impl<H: iron::Handler> iron::Handler for AdminOnly<H> {
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        let provided = req.headers
+            .get::<Authorization<Bearer>>()
+            .map(|auth| auth.token.as_bytes());
+
+        let allowed = match (&self.token, provided) {
+            (None, _) => AdminDisabled.fail(),
+            (Some(token), Some(provided)) if constant_time_eq(token.as_bytes(), provided) => Ok(()),
+            (Some(_), _) => AdminTokenInvalid.fail(),
+        };
+
+        match allowed {
+            Ok(()) => self.handler.handle(req),
+            Err(e) => serialize_to_response(ResponseFormat::negotiate(req), Err::<(), _>(e)),
+        }
+    }
 }
 
-fn run_handler<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
-where
-    F: FnOnce(Sandbox, Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
-{
-    deserialize_from_request(req, |req| {
-        let sandbox = Sandbox::new().context(SandboxCreation)?;
-        f(sandbox, req)
-    })
+// Comparing every byte keeps the response time from revealing how
+// much of a guessed token was correct
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
-fn deserialize_from_request<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
-where
-    F: FnOnce(Req) -> Result<Resp>,
-    Req: DeserializeOwned + Clone + Any + 'static,
-{
-    let body = req.get::<bodyparser::Struct<Req>>()
-        .context(Deserialization)?;
+#[derive(Debug, Clone)]
+struct GhToken(Arc<String>);
 
-    let req = body.ok_or(Error::RequestMissing)?;
+impl GhToken {
+    fn new(token: String) -> Self {
+        GhToken(Arc::new(token))
+    }
+}
 
-    let resp = f(req)?;
+impl iron::BeforeMiddleware for GhToken {
+    fn before(&self, req: &mut Request<'_, '_>) -> IronResult<()> {
+        req.extensions.insert::<Self>(self.clone());
+        Ok(())
+    }
+}
 
-    Ok(resp)
+impl iron::typemap::Key for GhToken {
+    type Value = Self;
 }
 
-fn run_handler_no_request<Resp, F>(f: F) -> Result<Resp>
-where
-    F: FnOnce(Sandbox) -> Result<Resp>,
-{
-    let sandbox = Sandbox::new().context(SandboxCreation)?;
-    let resp = f(sandbox)?;
-    Ok(resp)
+fn compile(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    if accepts(req, &[MULTIPART_MIME_TYPE]) {
+        return compile_multipart(req);
+    }
+    if accepts(req, &[EVENT_STREAM_MIME_TYPE]) {
+        return compile_events(req);
+    }
+
+    with_sandbox(req, compile_operation)
 }
 
-fn serialize_to_response<Resp>(response: Result<Resp>) -> IronResult<Response>
-where
-    Resp: Serialize,
-{
-    let response = response.and_then(|resp| {
-        let resp = serde_json::ser::to_string(&resp).context(Serialization)?;
-        Ok(resp)
+// Dependencies can take a long time to build, so each crate is
+// reported as a `progress` event once Cargo has built it. The response
+// is sent as a `result` event, or an `error` event if there is none.
+// The build is stopped if the client goes away.
+fn compile_events(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let stream = deserialize_from_request(req, |req: CompileRequest| {
+        Ok(event_stream::EventStream::spawn(move |events| {
+            let mut on_progress = |progress: sandbox::Progress| events.send("progress", &CompileProgress::from(progress));
+            let resp = run_sandboxed(|sandbox| compile_operation_reporting(sandbox, req, Some(&mut on_progress)));
+
+            match resp {
+                Ok(resp) => events.send("result", &resp),
+                Err(e) => events.send("error", &ErrorJson { error: e.to_string() }),
+            };
+        }))
     });
 
-    match response {
-        Ok(body) => Ok(Response::with((status::Ok, Header(ContentType::json()), body))),
-        Err(err) => {
-            let err = ErrorJson { error: err.to_string() };
-            match serde_json::ser::to_string(&err) {
-                Ok(error_str) => Ok(Response::with((status::InternalServerError, Header(ContentType::json()), error_str))),
-                Err(_) => Ok(Response::with((status::InternalServerError, Header(ContentType::json()), FATAL_ERROR_JSON))),
-            }
-        },
+    match stream {
+        Ok(stream) => {
+            let content_type = ContentType(EVENT_STREAM_MIME_TYPE.parse().expect("Invalid event stream MIME type"));
+            let body: Box<dyn iron::response::WriteBody> = Box::new(stream);
+            Ok(Response::with((status::Ok, Header(content_type), vary_on_accept(), body)))
+        }
+        Err(e) => serialize_to_response(format, Err::<(), _>(e)),
     }
 }
 
-#[derive(Debug, Clone)]
-struct SandboxCacheInfo<T> {
-    value: T,
-    time: Instant,
-}
-
-/// Caches the success value of a single operation
-#[derive(Debug)]
-struct SandboxCacheOne<T>(Mutex<Option<SandboxCacheInfo<T>>>);
+// The artifact is sent as a file of its own after the rest of the
+// response, so a client can show the diagnostics and save the output
+// from one request. There is no file when the build failed.
+fn compile_multipart(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let parts = run_handler(req, |sandbox, req: CompileRequest| {
+        let target = parse_target(&req.target)?;
+        let resp = compile_operation(sandbox, CompileRequest { artifact: true, ..req })?;
+        compile_response_parts(target, resp)
+    });
 
-impl<T> Default for SandboxCacheOne<T> {
-    fn default() -> Self { SandboxCacheOne(Mutex::default()) }
+    match parts {
+        Ok(parts) => {
+            let boundary = multipart::boundary();
+            let content_type = multipart::content_type(&boundary).parse().expect("Invalid multipart MIME type");
+            Ok(Response::with((status::Ok, Header(ContentType(content_type)), vary_on_accept(), multipart::encode(&boundary, &parts))))
+        }
+        Err(e) => serialize_to_response(format, Err::<(), _>(e)),
+    }
 }
 
-impl<T> SandboxCacheOne<T>
-where
-    T: Clone
-{
-    fn clone_or_populate<F>(&self, populator: F) -> Result<T>
-    where
-        F: FnOnce() -> sandbox::Result<T>
-    {
-        let mut cache = self.0.lock().map_err(|_| Error::CachePoisoned)?;
+fn compile_response_parts(target: sandbox::CompileTarget, mut resp: CompileResponse) -> Result<Vec<multipart::Part>> {
+    let artifact = resp.artifact.take();
 
-        match cache.clone() {
-            Some(cached) => {
-                if cached.time.elapsed() > Duration::from_secs(SANDBOX_CACHE_TIME_TO_LIVE_IN_SECONDS) {
-                    SandboxCacheOne::populate(&mut *cache, populator)
-                } else {
-                    Ok(cached.value)
-                }
-            },
-            None => {
-                SandboxCacheOne::populate(&mut *cache, populator)
-            }
-        }
+    let mut diagnostics = serde_json::to_value(&resp).context(Serialization)?;
+    if let Some(fields) = diagnostics.as_object_mut() {
+        fields.remove("code");
     }
 
-    fn populate<F>(cache: &mut Option<SandboxCacheInfo<T>>, populator: F) -> Result<T>
-    where
-        F: FnOnce() -> sandbox::Result<T>
-    {
-        let value = populator().context(Caching)?;
-        *cache = Some(SandboxCacheInfo {
-            value: value.clone(),
-            time: Instant::now(),
+    let mut parts = vec![multipart::Part {
+        content_type: ContentType::json().to_string(),
+        file_name: None,
+        body: serde_json::to_vec(&diagnostics).context(Serialization)?,
+    }];
+    if let Some(artifact) = artifact {
+        parts.push(multipart::Part {
+            content_type: OCTET_STREAM_MIME_TYPE.to_string(),
+            file_name: Some(format!("playground.{}", target.artifact_extension())),
+            body: artifact,
         });
-        Ok(value)
     }
+    Ok(parts)
 }
 
-/// Caches the successful results of all sandbox operations that make
-/// sense to cache.
-#[derive(Debug, Default)]
-struct SandboxCache {
-    crates: SandboxCacheOne<Vec<sandbox::CrateInformation>>,
-    version_stable: SandboxCacheOne<sandbox::Version>,
-    version_beta: SandboxCacheOne<sandbox::Version>,
-    version_nightly: SandboxCacheOne<sandbox::Version>,
-    version_clippy: SandboxCacheOne<sandbox::Version>,
-    version_rustfmt: SandboxCacheOne<sandbox::Version>,
-    version_miri: SandboxCacheOne<sandbox::Version>,
+fn compile_operation(sandbox: Sandbox, req: CompileRequest) -> Result<CompileResponse> {
+    compile_operation_reporting(sandbox, req, None)
 }
 
-/// Provides a similar API to the Sandbox that caches the successful results.
-struct CachedSandbox<'a> {
+fn compile_operation_reporting(
     sandbox: Sandbox,
-    cache: &'a SandboxCache,
-}
+    req: CompileRequest,
+    on_progress: Option<&mut dyn FnMut(sandbox::Progress) -> bool>,
+) -> Result<CompileResponse> {
+    let warnings = req.ignored_field_warnings();
+    let separate_deps_log = req.separate_deps_log;
+    let normalize_paths = req.normalize_paths;
+    let use_prelude = req.use_prelude;
+    let unsafe_stats = detect_unsafe(&req.code, req.detect_unsafe);
+    let sandbox = check_allowed_crates(sandbox, &req.code)?;
+    let mut req: sandbox::CompileRequest = req.try_into()?;
+    let sandbox = check_toolchain(sandbox, req.channel, req.toolchain_version.as_deref())?;
+    let insertion = insert_prelude(&mut req.code, use_prelude)?;
+    let resp = match on_progress {
+        Some(on_progress) => sandbox.compile_with_progress(&req, on_progress),
+        None => sandbox.compile(&req),
+    };
+    let resp = resp.context(Compilation)?;
 
-impl<'a> CachedSandbox<'a> {
-    fn crates(&self) -> Result<Vec<sandbox::CrateInformation>> {
-        self.cache.crates.clone_or_populate(|| self.sandbox.crates())
+    let mut resp = finish_compile_response(resp, insertion.as_ref(), separate_deps_log, normalize_paths)?;
+    resp.warnings = warnings;
+    resp.unsafe_stats = unsafe_stats;
+    resp.session = req.incremental_session;
+    Ok(resp)
+}
+
+fn finish_compile_response(
+    mut resp: sandbox::CompileResponse,
+    insertion: Option<&prelude::Insertion>,
+    separate_deps_log: bool,
+    normalize_paths: bool,
+) -> Result<CompileResponse> {
+    let debug_artifacts = match (resp.debug_artifacts.take(), debug_artifacts::store()) {
+        (Some(tarball), Some(store)) => {
+            let token = store.save(&tarball).context(DebugArtifactsSaving)?;
+            Some(DebugArtifacts::new(token))
+        }
+        _ => None,
+    };
+
+    let mut resp = CompileResponse::from(resp);
+    resp.debug_artifacts = debug_artifacts;
+    if let Some(insertion) = insertion {
+        resp.stderr = insertion.correct_rendered(&resp.stderr);
+        for suggestion in &mut resp.suggestions {
+            suggestion.span.line_start = insertion.user_line(suggestion.span.line_start);
+            suggestion.span.line_end = insertion.user_line(suggestion.span.line_end);
+        }
+    }
+    if separate_deps_log {
+        resp.deps_log = Some(split_dependency_log(&mut resp.stderr));
     }
+    if normalize_paths {
+        normalize_compile_paths(&mut resp);
+    }
+    Ok(resp)
+}
 
-    fn version_stable(&self) -> Result<sandbox::Version> {
-        self.cache.version_stable.clone_or_populate(|| {
-            self.sandbox.version(sandbox::Channel::Stable)
-        })
+// The compiled code is left as it is; only what describes the build
+// is rewritten
+fn normalize_compile_paths(resp: &mut CompileResponse) {
+    use crate::project_paths::normalize_in_place;
+
+    normalize_in_place(&mut resp.stdout);
+    normalize_in_place(&mut resp.stderr);
+    if let Some(raw_diagnostics) = &mut resp.raw_diagnostics {
+        normalize_in_place(raw_diagnostics);
+    }
+    if let Some(deps_log) = &mut resp.deps_log {
+        normalize_in_place(deps_log);
+    }
+    if let Some(summary) = &mut resp.diagnostic_summary {
+        summary.first_error.iter_mut().chain(&mut summary.last_lines).for_each(normalize_in_place);
     }
+    if let Some(groups) = &mut resp.diagnostic_groups {
+        let messages = groups.errors.iter_mut().chain(&mut groups.warnings).chain(&mut groups.notes);
+        messages.flat_map(|m| &mut m.rendered).for_each(normalize_in_place);
+    }
+    if let Some(build_messages) = &mut resp.build_messages {
+        for artifact in &mut build_messages.artifacts {
+            artifact.filenames.iter_mut().chain(&mut artifact.executable).for_each(normalize_in_place);
+        }
+    }
+}
 
-    fn version_beta(&self) -> Result<sandbox::Version> {
-        self.cache.version_beta.clone_or_populate(|| {
-            self.sandbox.version(sandbox::Channel::Beta)
-        })
+// Each distinct crate type is compiled separately; a request naming
+// one that doesn't exist is rejected before anything is compiled
+fn compile_crate_types_cost(req: &mut Request<'_, '_>) -> u32 {
+    match req.get::<bodyparser::Struct<CompileCrateTypesRequest>>() {
+        Ok(Some(r)) if r.crate_types.iter().all(|name| parse_crate_type(name).is_ok()) => {
+            r.crate_types.iter().collect::<BTreeSet<_>>().len().max(1) as u32
+        }
+        _ => 1,
     }
+}
 
-    fn version_nightly(&self) -> Result<sandbox::Version> {
-        self.cache.version_nightly.clone_or_populate(|| {
-            self.sandbox.version(sandbox::Channel::Nightly)
-        })
+fn compile_crate_types(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: CompileCrateTypesRequest| {
+        let mut names = Vec::new();
+        for name in req.crate_types {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        if names.is_empty() {
+            CrateTypesMissing.fail()?;
+        }
+
+        let warnings = req.request.ignored_field_warnings();
+        let separate_deps_log = req.request.separate_deps_log;
+        let normalize_paths = req.request.normalize_paths;
+        let use_prelude = req.request.use_prelude;
+        let unsafe_stats = detect_unsafe(&req.request.code, req.request.detect_unsafe);
+        let sandbox = check_allowed_crates(sandbox, &req.request.code)?;
+        let mut base: sandbox::CompileRequest = req.request.try_into()?;
+        let sandbox = check_toolchain(sandbox, base.channel, base.toolchain_version.as_deref())?;
+
+        // Every combination is checked before anything is compiled
+        let requests = names
+            .iter()
+            .map(|name| {
+                let req = sandbox::CompileRequest { crate_type: parse_crate_type(name)?, ..base.clone() };
+                validate_compile_options(&req)?;
+                Ok(req)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let insertion = insert_prelude(&mut base.code, use_prelude)?;
+
+        let results = names
+            .into_iter()
+            .zip(requests)
+            .map(|(name, req)| {
+                let req = sandbox::CompileRequest { code: base.code.clone(), ..req };
+                let resp = sandbox.compile(&req).context(Compilation)?;
+                let mut resp = finish_compile_response(resp, insertion.as_ref(), separate_deps_log, normalize_paths)?;
+                resp.warnings = warnings.clone();
+                resp.unsafe_stats = unsafe_stats.clone();
+                Ok((name, resp))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(CompileCrateTypesResponse { results })
+    })
+}
+
+fn execute(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    if req.method == Get {
+        let format = ResponseFormat::negotiate(req);
+        let fields = requested_fields(req);
+        let response = execute_request_from_query(req)
+            .and_then(|req| run_sandboxed(|sandbox| execute_operation(sandbox, req)))
+            .and_then(|resp| project_fields(resp, &fields));
+        return serialize_to_response(format, response);
     }
 
-    fn version_clippy(&self) -> Result<sandbox::Version> {
-        self.cache.version_clippy.clone_or_populate(|| {
-            self.sandbox.version_clippy()
-        })
+    with_sandbox(req, execute_operation)
+}
+
+// Lets static pages run code from a plain link, much like
+// `evaluate.json` but with the options of `/execute`.
+fn execute_request_from_query(req: &Request<'_, '_>) -> Result<ExecuteRequest> {
+    let url: &iron::url::Url = req.url.as_ref();
+    let len = url.query().map_or(0, str::len);
+    if len > MAX_EXECUTE_QUERY_LEN {
+        QueryTooLong { len, max: MAX_EXECUTE_QUERY_LEN }.fail()?;
     }
 
-    fn version_rustfmt(&self) -> Result<sandbox::Version> {
-        self.cache.version_rustfmt.clone_or_populate(|| {
-            self.sandbox.version_rustfmt()
-        })
+    let param = |name: &str| query_param(req, name);
+    let flag = |name: &str| param(name).as_deref() == Some("true");
+
+    Ok(ExecuteRequest {
+        channel: param("channel").unwrap_or_else(|| "stable".to_string()),
+        mode: param("mode").unwrap_or_else(|| "debug".to_string()),
+        edition: param("edition").unwrap_or_default(),
+        crate_type: param("crateType").unwrap_or_else(|| "bin".to_string()),
+        tests: flag("tests"),
+        backtrace: flag("backtrace"),
+        deterministic: flag("deterministic"),
+        seed: param("seed"),
+        stack_size_mb: None,
+        separate_deps_log: false,
+        strip_cargo_noise: flag("stripCargoNoise"),
+        use_prelude: false,
+        linker: None,
+        incremental: false,
+        session: None,
+        tz: None,
+        lang: None,
+        coverage: false,
+        static_target: false,
+        sanitizer: None,
+        trace: false,
+        profile: false,
+        memory_report: false,
+        toolchain_version: None,
+        request_id: None,
+        detect_unsafe: false,
+        env: Vec::new(),
+        code: param("code").ok_or(Error::QueryParameterMissing { name: "code" })?,
+    })
+}
+
+fn execute_operation(sandbox: Sandbox, req: ExecuteRequest) -> Result<ExecuteResponse> {
+    let separate_deps_log = req.separate_deps_log;
+    let strip_cargo_noise = req.strip_cargo_noise;
+    let use_prelude = req.use_prelude;
+    let unsafe_stats = detect_unsafe(&req.code, req.detect_unsafe);
+    let sandbox = check_allowed_crates(sandbox, &req.code)?;
+    let mut req: sandbox::ExecuteRequest = req.try_into()?;
+    let sandbox = check_toolchain(sandbox, req.channel, req.toolchain_version.as_deref())?;
+    let sandbox = check_coverage(sandbox, &req)?;
+    let insertion = insert_prelude(&mut req.code, use_prelude)?;
+    let mut resp = sandbox
+        .execute(&req)
+        .map(ExecuteResponse::from)
+        .context(Execution)?;
+
+    if let Some(insertion) = insertion {
+        resp.stderr = insertion.correct_execution(&resp.stderr);
+        for line in resp.coverage.iter_mut().flatten() {
+            line.line = insertion.user_line(line.line);
+        }
+        for frame in resp.backtrace.iter_mut().flatten() {
+            frame.location = frame.location.as_deref().map(|location| insertion.correct_locations(location));
+        }
+    }
+    resp.unsafe_stats = unsafe_stats;
+    resp.session = req.incremental_session;
+    if separate_deps_log {
+        resp.deps_log = Some(split_dependency_log(&mut resp.stderr));
     }
+    if strip_cargo_noise {
+        resp.stderr = dependency_log::strip_status(&resp.stderr);
+    }
+    Ok(resp)
+}
 
-    fn version_miri(&self) -> Result<sandbox::Version> {
-        self.cache.version_miri.clone_or_populate(|| {
-            self.sandbox.version_miri()
-        })
+// Scanned before the prelude is inserted so that only the user's own
+// code is counted
+fn detect_unsafe(code: &str, requested: bool) -> Option<UnsafeStats> {
+    if requested {
+        Some(metrics::unsafe_usage(code).into())
+    } else {
+        None
     }
 }
 
-/// A convenience constructor
-fn cached(sandbox: Sandbox) -> CachedSandbox<'static> {
-    lazy_static! {
-        static ref SANDBOX_CACHE: SandboxCache = Default::default();
+fn insert_prelude(code: &mut String, use_prelude: bool) -> Result<Option<prelude::Insertion>> {
+    if !use_prelude {
+        return Ok(None);
     }
 
-    CachedSandbox {
-        sandbox,
-        cache: &SANDBOX_CACHE,
+    let prelude = prelude::configured().ok_or(Error::PreludeUnavailable)?;
+    let (with_prelude, insertion) = prelude::insert(code, prelude);
+    *code = with_prelude;
+    Ok(Some(insertion))
+}
+
+// Leaves the messages about the user's crate in `stderr`
+fn split_dependency_log(stderr: &mut String) -> String {
+    let (deps_log, remaining) = dependency_log::split(stderr);
+    *stderr = remaining;
+    deps_log
+}
+
+fn execute_repeat(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: ExecuteRepeatRequest| {
+        let max = max_execute_repeat();
+        if req.times == 0 || req.times > max {
+            InvalidRepeatCount { value: req.times, max }.fail()?;
+        }
+
+        let strip_cargo_noise = req.request.strip_cargo_noise;
+        let base: sandbox::ExecuteRequest = req.request.try_into()?;
+        let sandbox = check_allowed_crates(sandbox, &base.code)?;
+        let sandbox = check_toolchain(sandbox, base.channel, base.toolchain_version.as_deref())?;
+        let sandbox = check_coverage(sandbox, &base)?;
+        let mut rng = rand::thread_rng();
+
+        let runs = (0..req.times)
+            .map(|_| {
+                let seed = rng.gen();
+                let req = sandbox::ExecuteRequest { seed: Some(seed), ..base.clone() };
+                let resp = sandbox.execute(&req).context(Execution)?;
+                let mut response = ExecuteResponse::from(resp);
+                if strip_cargo_noise {
+                    response.stderr = dependency_log::strip_status(&response.stderr);
+                }
+                Ok(ExecuteRepeatRun { seed, response })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let passed = runs.iter().filter(|run| run.response.success).count();
+        let failed = runs.len() - passed;
+
+        Ok(ExecuteRepeatResponse { runs, passed, failed })
+    })
+}
+
+// Each run uses its own sandbox. Requests that will be turned away
+// are only charged once; the body is kept for the handler to read.
+fn execute_repeat_cost(req: &mut Request<'_, '_>) -> u32 {
+    match req.get::<bodyparser::Struct<ExecuteRepeatRequest>>() {
+        Ok(Some(r)) if r.times > 0 && r.times <= max_execute_repeat() => r.times,
+        _ => 1,
     }
 }
 
-#[derive(Debug, Snafu)]
-pub enum Error {
-    #[snafu(display("Sandbox creation failed: {}", source))]
-    SandboxCreation { source: sandbox::Error },
-    #[snafu(display("Compilation operation failed: {}", source))]
-    Compilation { source: sandbox::Error },
-    #[snafu(display("Execution operation failed: {}", source))]
-    Execution { source: sandbox::Error },
-    #[snafu(display("Evaluation operation failed: {}", source))]
-    Evaluation { source: sandbox::Error },
-    #[snafu(display("Linting operation failed: {}", source))]
-    Linting { source: sandbox::Error },
-    #[snafu(display("Formatting operation failed: {}", source))]
-    Formatting { source: sandbox::Error },
-    #[snafu(display("Interpreting operation failed: {}", source))]
-    Interpreting { source: sandbox::Error },
-    #[snafu(display("Caching operation failed: {}", source))]
-    Caching { source: sandbox::Error },
-    #[snafu(display("Unable to serialize response: {}", source))]
-    Serialization { source: serde_json::Error },
-    #[snafu(display("Unable to deserialize request: {}", source))]
-    Deserialization { source: bodyparser::BodyError },
-    #[snafu(display("The value {:?} is not a valid target", value))]
-    InvalidTarget { value: String },
-    #[snafu(display("The value {:?} is not a valid assembly flavor", value))]
-    InvalidAssemblyFlavor { value: String },
-    #[snafu(display("The value {:?} is not a valid demangle option", value))]
-    InvalidDemangleAssembly { value: String },
-    #[snafu(display("The value {:?} is not a valid assembly processing option", value))]
-    InvalidProcessAssembly { value: String },
-    #[snafu(display("The value {:?} is not a valid channel", value,))]
-    InvalidChannel { value: String },
-    #[snafu(display("The value {:?} is not a valid mode", value))]
-    InvalidMode { value: String },
-    #[snafu(display("The value {:?} is not a valid edition", value))]
-    InvalidEdition { value: String },
-    #[snafu(display("The value {:?} is not a valid crate type", value))]
-    InvalidCrateType { value: String },
-    #[snafu(display("No request was provided"))]
-    RequestMissing,
-    #[snafu(display("The cache has been poisoned"))]
-    CachePoisoned,
+fn execute_cancel(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    serialize_to_response(format, deserialize_from_request(req, |r: ExecuteCancelRequest| {
+        if !cancellation::is_valid_id(&r.request_id) {
+            return InvalidRequestId { value: r.request_id }.fail();
+        }
+        let cancelled = cancellation::global().cancel(&r.request_id);
+        Ok(ExecuteCancelResponse { cancelled })
+    }))
 }
 
-type Result<T, E = Error> = ::std::result::Result<T, E>;
+fn build_run(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, build_run_operation)
+}
 
-const FATAL_ERROR_JSON: &str =
-    r#"{"error": "Multiple cascading errors occurred, abandon all hope"}"#;
+fn build_run_operation(sandbox: Sandbox, req: ExecuteRequest) -> Result<BuildRunResponse> {
+    if req.coverage {
+        IncompatibleOptions { conflict: "coverage cannot be collected when building before running" }.fail()?;
+    }
 
-#[derive(Debug, Clone, Serialize)]
-struct ErrorJson {
-    error: String,
+    let separate_deps_log = req.separate_deps_log;
+    let strip_cargo_noise = req.strip_cargo_noise;
+    let use_prelude = req.use_prelude;
+    let unsafe_stats = detect_unsafe(&req.code, req.detect_unsafe);
+    let sandbox = check_allowed_crates(sandbox, &req.code)?;
+    let mut req: sandbox::ExecuteRequest = req.try_into()?;
+    let sandbox = check_toolchain(sandbox, req.channel, req.toolchain_version.as_deref())?;
+    let insertion = insert_prelude(&mut req.code, use_prelude)?;
+    let mut resp = sandbox
+        .build_and_run(&req)
+        .map(BuildRunResponse::from)
+        .context(Execution)?;
+
+    if let Some(insertion) = insertion {
+        resp.compile.stderr = insertion.correct_rendered(&resp.compile.stderr);
+        if let Some(run) = &mut resp.run {
+            run.stderr = insertion.correct_locations(&run.stderr);
+            for frame in run.backtrace.iter_mut().flatten() {
+                frame.location = frame.location.as_deref().map(|location| insertion.correct_locations(location));
+            }
+        }
+    }
+    resp.unsafe_stats = unsafe_stats;
+    if separate_deps_log {
+        resp.compile.deps_log = Some(split_dependency_log(&mut resp.compile.stderr));
+    }
+    if strip_cargo_noise {
+        resp.compile.stderr = dependency_log::strip_status(&resp.compile.stderr);
+    }
+    Ok(resp)
 }
 
-#[derive(Debug, Clone, Deserialize)]
-struct CompileRequest {
-    target: String,
-    #[serde(rename = "assemblyFlavor")]
-    assembly_flavor: Option<String>,
-    #[serde(rename = "demangleAssembly")]
-    demangle_assembly: Option<String>,
-    #[serde(rename = "processAssembly")]
-    process_assembly: Option<String>,
-    channel: String,
-    mode: String,
-    #[serde(default)]
-    edition: String,
-    #[serde(rename = "crateType")]
-    crate_type: String,
-    tests: bool,
-    #[serde(default)]
-    backtrace: bool,
-    code: String,
+// The code is run once with each of the two editions
+const EDITION_DIFF_RUNS: u32 = 2;
+
+fn edition_diff(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: EditionDiffRequest| {
+        let (first, second) = req.editions;
+        let base: sandbox::ExecuteRequest = req.request.try_into()?;
+        let sandbox = check_allowed_crates(sandbox, &base.code)?;
+        let sandbox = check_coverage(sandbox, &base)?;
+
+        let run = |edition: String| {
+            let parsed = parse_edition(&edition)?.ok_or_else(|| Error::InvalidEdition { value: edition.clone() })?;
+            let req = sandbox::ExecuteRequest { edition: Some(parsed), ..base.clone() };
+            let resp = sandbox.execute(&req).context(Execution)?;
+            Ok(EditionDiffRun { edition, response: resp.into() })
+        };
+
+        let first = run(first)?;
+        let second = run(second)?;
+
+        let divergent = first.response.success != second.response.success ||
+            first.response.stdout != second.response.stdout;
+
+        let summary = match (first.response.success, second.response.success) {
+            (true, false) => format!("Succeeds with the {} edition but fails with the {} edition", first.edition, second.edition),
+            (false, true) => format!("Fails with the {} edition but succeeds with the {} edition", first.edition, second.edition),
+            _ if divergent => format!("The output differs between the {} and {} editions", first.edition, second.edition),
+            _ => format!("Behaves the same with the {} and {} editions", first.edition, second.edition),
+        };
+
+        Ok(EditionDiffResponse { runs: vec![first, second], divergent, summary })
+    })
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct CompileResponse {
-    success: bool,
-    code: String,
-    stdout: String,
-    stderr: String,
+fn minimize(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: MinimizeRequest| {
+        if req.predicate.is_empty() {
+            MinimizePredicateEmpty.fail()?;
+        }
+
+        let base: sandbox::ExecuteRequest = req.request.try_into()?;
+        let sandbox = check_allowed_crates(sandbox, &base.code)?;
+        let sandbox = check_coverage(sandbox, &base)?;
+        let predicate = req.predicate;
+
+        let still_fails = |code: &str| -> Result<bool> {
+            let req = sandbox::ExecuteRequest { code: code.to_string(), ..base.clone() };
+            let resp = sandbox.execute(&req).context(Execution)?;
+            Ok(!resp.success && resp.stderr.contains(&predicate))
+        };
+
+        if !still_fails(&base.code)? {
+            MinimizeNotFailing { predicate: predicate.clone() }.fail()?;
+        }
+
+        let minimized = minimize::minimize(&base.code, MAX_MINIMIZE_ATTEMPTS, still_fails)?;
+        Ok(MinimizeResponse::from(minimized))
+    })
+}
+
+fn format(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, format_operation)
+}
+
+// Each file is formatted in its own sandbox
+fn format_cost(req: &mut Request<'_, '_>) -> u32 {
+    match req.get::<bodyparser::Struct<FormatRequest>>() {
+        Ok(Some(r)) if r.files.len() <= MAX_FORMAT_FILES => r.files.len().max(1) as u32,
+        _ => 1,
+    }
+}
+
+fn format_operation(sandbox: Sandbox, req: FormatRequest) -> Result<FormatResponse> {
+    if !req.files.is_empty() {
+        return format_files(sandbox, req);
+    }
+
+    let original = if req.line_map { Some(req.code.clone()) } else { None };
+    let req = req.try_into()?;
+    let mut resp = sandbox
+        .format(&req)
+        .map(FormatResponse::from)
+        .context(Formatting)?;
+
+    if let (Some(original), true) = (original, resp.success) {
+        resp.line_map = Some(line_map::map(&original, &resp.code));
+    }
+    Ok(resp)
+}
+
+// Each file is formatted on its own, so one that rustfmt cannot parse
+// doesn't stop the others from being formatted
+fn format_files(sandbox: Sandbox, req: FormatRequest) -> Result<FormatResponse> {
+    if !req.code.is_empty() {
+        IncompatibleOptions { conflict: "either the code or a list of files can be formatted, not both" }.fail()?;
+    }
+    if req.files.len() > MAX_FORMAT_FILES {
+        TooManyFormatFiles { count: req.files.len(), max: MAX_FORMAT_FILES }.fail()?;
+    }
+
+    let mut names = BTreeSet::new();
+    for file in &req.files {
+        if !names.insert(&file.name) {
+            DuplicateFormatFile { name: file.name.clone() }.fail()?;
+        }
+    }
+
+    let edition = parse_edition(&req.edition)?;
+    let line_map = req.line_map;
+
+    let files = req.files
+        .into_iter()
+        .map(|file| {
+            let original = if line_map { Some(file.code.clone()) } else { None };
+            let req = sandbox::FormatRequest { code: parse_source(file.code)?, edition };
+            let resp = sandbox.format(&req).context(Formatting)?;
+
+            let line_map = match original {
+                Some(original) if resp.success => Some(line_map::map(&original, &resp.code)),
+                _ => None,
+            };
+
+            Ok(FormattedFile {
+                name: file.name,
+                success: resp.success,
+                code: resp.code,
+                stdout: resp.stdout,
+                stderr: resp.stderr,
+                line_map,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(FormatResponse {
+        success: files.iter().all(|file| file.success),
+        code: String::new(),
+        stdout: String::new(),
+        stderr: String::new(),
+        line_map: None,
+        files: Some(files),
+    })
+}
+
+fn format_check(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: FormatRequest| {
+        let req = req.try_into()?;
+        sandbox
+            .format_check(&req)
+            .map(FormatCheckResponse::from)
+            .context(Formatting)
+    })
+}
+
+fn clippy(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, clippy_operation)
+}
+
+fn clippy_operation(sandbox: Sandbox, req: ClippyRequest) -> Result<ClippyResponse> {
+    let normalize_paths = req.normalize_paths;
+    let sandbox = check_allowed_crates(sandbox, &req.code)?;
+    let req: sandbox::ClippyRequest = req.try_into()?;
+    let mut resp = sandbox.clippy(&req).context(Linting)?;
+
+    if normalize_paths {
+        project_paths::normalize_in_place(&mut resp.stdout);
+        project_paths::normalize_in_place(&mut resp.stderr);
+    }
+
+    let patch = if req.suggestions {
+        let patched = patch::apply(&req.code, &resp.suggestions);
+        Some(patch::unified_diff(req.crate_type.file_name(), &req.code, &patched))
+    } else {
+        None
+    };
+
+    Ok(ClippyResponse { patch, ..resp.into() })
+}
+
+fn miri(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, miri_operation)
+}
+
+fn miri_operation(sandbox: Sandbox, req: MiriRequest) -> Result<MiriResponse> {
+    check_allowed_crates(sandbox, &req.code)?
+        .miri(&req.try_into()?)
+        .map(MiriResponse::from)
+        .context(Interpreting)
+}
+
+fn doc_json(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: DocJsonRequest| {
+        let sandbox = check_allowed_crates(sandbox, &req.code)?;
+        let req: sandbox::DocJsonRequest = req.try_into()?;
+
+        check_feature(sandbox, req.channel, sandbox::FEATURE_DOC_JSON)?
+            .doc_json(&req)
+            .map(DocJsonResponse::from)
+            .context(Compilation)
+    })
+}
+
+// Each channel is checked in its own sandbox
+const COMPAT_SANDBOXES: u32 = 3;
+
+fn compat(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: CompatRequest| {
+        let code = parse_source(req.code)?;
+        let edition = parse_edition(&req.edition)?;
+        let crate_type = parse_crate_type(&req.crate_type)?;
+        let sandbox = check_allowed_crates(sandbox, &code)?;
+
+        // Each channel gets its own sandbox so that they can all build at once
+        let check = |channel| {
+            let req = sandbox::CheckRequest { code: code.clone(), channel, edition, crate_type };
+            thread::spawn(move || run_sandboxed(|sandbox| sandbox.check(&req).context(Compilation)))
+        };
+        let stable = check(sandbox::Channel::Stable);
+        let beta = check(sandbox::Channel::Beta);
+        let nightly = check(sandbox::Channel::Nightly);
+
+        let sandbox = cached(sandbox);
+        let (stable, beta, nightly) = (
+            compat_channel(stable, sandbox.version_stable())?,
+            compat_channel(beta, sandbox.version_beta())?,
+            compat_channel(nightly, sandbox.version_nightly())?,
+        );
+
+        Ok(CompatResponse {
+            stable: stable.success,
+            beta: beta.success,
+            nightly: nightly.success,
+            channels: CompatChannels { stable, beta, nightly },
+        })
+    })
+}
+
+fn compat_channel(
+    check: JoinHandle<Result<sandbox::CheckResponse>>,
+    version: Result<sandbox::Version>,
+) -> Result<CompatChannel> {
+    let check = check.join().map_err(|_| Error::CompatibilityCheckPanicked)??;
+
+    Ok(CompatChannel {
+        success: check.success,
+        version: version?.release,
+        stdout: check.stdout,
+        stderr: check.stderr,
+    })
+}
+
+// Only type checks the code, and leaves out the compiler's output, for
+// clients that check many snippets at once
+fn compiles(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: CheckRequest| {
+        let code = parse_source(req.code)?;
+        let req = sandbox::CheckRequest {
+            channel: parse_channel(&req.channel)?,
+            edition: parse_edition(&req.edition)?,
+            crate_type: parse_crate_type(&req.crate_type)?,
+            code,
+        };
+
+        let check = check_allowed_crates(sandbox, &req.code)?.check(&req).context(Compilation)?;
+        Ok(CompilesResponse { compiles: check.success })
+    })
+}
+
+// Compiling, linting and formatting each run a sandbox
+const CHECK_SANDBOXES: u32 = 3;
+
+// Compiles, lints, and formats the code at once, sending each result
+// as soon as it's ready. Failures of one part don't stop the others.
+fn check(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let stream = deserialize_from_request(req, |req: CheckRequest| {
+        let compile = sandbox::CheckRequest {
+            code: parse_source(req.code.clone())?,
+            channel: parse_channel(&req.channel)?,
+            edition: parse_edition(&req.edition)?,
+            crate_type: parse_crate_type(&req.crate_type)?,
+        };
+        let clippy = ClippyRequest {
+            code: req.code.clone(),
+            edition: req.edition.clone(),
+            crate_type: req.crate_type,
+            patch: false,
+            normalize_paths: false,
+            user_code_only: false,
+        };
+        let format = FormatRequest { code: req.code, edition: req.edition, line_map: false, files: Vec::new() };
+
+        Ok(result_stream::ResultStream::spawn(vec![
+            check_part("compile", move || {
+                run_sandboxed(|sandbox| check_allowed_crates(sandbox, &compile.code)?.check(&compile).context(Compilation))
+                    .map(CheckCompileResponse::from)
+            }),
+            check_part("clippy", move || run_sandboxed(|sandbox| clippy_operation(sandbox, clippy))),
+            check_part("format", move || run_sandboxed(|sandbox| format_operation(sandbox, format))),
+        ]))
+    });
+
+    match stream {
+        Ok(stream) => {
+            let content_type = ContentType(NDJSON_MIME_TYPE.parse().expect("Invalid NDJSON MIME type"));
+            let body: Box<dyn iron::response::WriteBody> = Box::new(stream);
+            Ok(Response::with((status::Ok, Header(content_type), vary_on_accept(), body)))
+        }
+        Err(e) => serialize_to_response(format, Err::<(), _>(e)),
+    }
+}
+
+fn check_part<Resp, F>(part: &'static str, f: F) -> result_stream::Task
+where
+    Resp: Serialize,
+    F: FnOnce() -> Result<Resp> + Send + 'static,
+{
+    Box::new(move || {
+        let line = match f() {
+            Ok(result) => serde_json::to_vec(&CheckPart { part, result: Some(result), error: None }),
+            Err(e) => serde_json::to_vec(&CheckPart::<()> { part, result: None, error: Some(e.to_string()) }),
+        };
+        line.unwrap_or_else(|_| FATAL_ERROR_JSON.into())
+    })
+}
+
+// Always answers with a 200; failures are reported inside of the
+// envelope instead.
+fn rpc(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let response = match deserialize_from_request(req, rpc_dispatch) {
+        Ok(result) => RpcResponse::Result(result),
+        Err(e) => RpcResponse::Error(RpcError { code: e.rpc_code(), message: e.to_string() }),
+    };
+
+    serialize_to_response(format, Ok(response))
+}
+
+fn rpc_dispatch(req: RpcRequest) -> Result<serde_json::Value> {
+    let RpcRequest { method, params } = req;
+
+    match &*method {
+        "compile" => rpc_call(params, compile_operation),
+        "execute" => rpc_call(params, execute_operation),
+        "format" => rpc_call(params, format_operation),
+        "clippy" => rpc_call(params, clippy_operation),
+        "miri" => rpc_call(params, miri_operation),
+        _ => RpcMethodUnknown { method }.fail(),
+    }
+}
+
+fn rpc_call<Req, Resp, F>(params: serde_json::Value, f: F) -> Result<serde_json::Value>
+where
+    F: FnOnce(Sandbox, Req) -> Result<Resp>,
+    Req: DeserializeOwned,
+    Resp: Serialize,
+{
+    let req = serde_json::from_value(params).context(RpcParamsInvalid)?;
+    let resp = run_sandboxed(|sandbox| f(sandbox, req))?;
+    serde_json::to_value(resp).context(Serialization)
+}
+
+fn meta_crates(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let sort = query_param(req, "sort");
+
+    with_sandbox_no_request(req, |sandbox| {
+        let sort = match sort {
+            Some(s) => Some(parse_crate_sort(&s)?),
+            None => None,
+        };
+
+        let mut crates = cached(sandbox).crates()?;
+
+        if let Some(allowlist) = allowed_crates() {
+            crates.retain(|krate| allowlist.allows(&krate.name));
+        }
+
+        // The order in the container can change between builds of it
+        crates.sort_by(|a, b| a.id.cmp(&b.id));
+        if let Some(sort) = sort {
+            sort_crates(&mut crates, sort);
+        }
+
+        canonical::sorted(&MetaCratesResponse::from(crates)).context(Serialization)
+    })
+}
+
+fn meta_crates_versions(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        let cached = cached(sandbox);
+        let mut crates = cached.crates()?;
+        let lock = cached.cargo_lock()?;
+
+        if let Some(allowlist) = allowed_crates() {
+            crates.retain(|krate| allowlist.allows(&krate.name));
+        }
+
+        Ok(MetaCrateVersionsResponse::new(crates, &lock))
+    })
+}
+
+fn meta_clippy_lints(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .clippy_lints()
+            .map(MetaClippyLintsResponse::from)
+    })
+}
+
+fn meta_crates_graph(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let response_format = ResponseFormat::negotiate(req);
+    let format = query_param(req, "format");
+
+    let graph = run_handler_no_request(|sandbox| {
+        let format = parse_graph_format(format.as_ref().map_or("svg", String::as_str))?;
+        let cached = cached(sandbox);
+        let dot = cached.crate_graph()?.to_dot();
+
+        match format {
+            GraphFormat::Dot => Ok((format, dot.into_bytes())),
+            GraphFormat::Rendered(f) => {
+                let image = cached.sandbox.render_graph(&dot, f).context(GraphRendering)?;
+                Ok((format, image))
+            }
+        }
+    });
+
+    match graph {
+        Ok((format, body)) => {
+            let mut response = Response::with((status::Ok, Header(ContentType(format.mime())), body));
+            let disposition = format!("attachment; filename=\"crates.{}\"", format.extension());
+            response.headers.set_raw("Content-Disposition", vec![disposition.into_bytes()]);
+            Ok(response)
+        }
+        Err(e) => serialize_to_response(response_format, Err::<(), _>(e)),
+    }
+}
+
+fn tree_json(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .crate_graph()
+            .map(|graph| TreeJsonResponse::from(&graph))
+    })
+}
+
+fn meta_debug_artifacts(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let token = req.extensions.get::<Router>().unwrap().find("token").unwrap_or("").to_string();
+
+    let tarball = debug_artifacts::store()
+        .ok_or(Error::DebugArtifactsDisabled)
+        .and_then(|store| store.take(&token).context(DebugArtifactsLoading))
+        .and_then(|tarball| tarball.ok_or(Error::DebugArtifactsMissing));
+
+    match tarball {
+        Ok(tarball) => {
+            let mime: iron::mime::Mime = "application/gzip".parse().expect("Invalid tarball MIME type");
+            let mut response = Response::with((status::Ok, Header(ContentType(mime)), tarball));
+            let disposition = format!("attachment; filename=\"{}.tar.gz\"", token);
+            response.headers.set_raw("Content-Disposition", vec![disposition.into_bytes()]);
+            Ok(response)
+        }
+        Err(e) => serialize_to_response(format, Err::<(), _>(e)),
+    }
+}
+
+fn meta_default_snippet(req: &mut Request<'_, '_>, code: &str) -> IronResult<Response> {
+    let code = code.to_string();
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(MetaDefaultSnippetResponse { code }))
+}
+
+fn meta_examples(req: &mut Request<'_, '_>, examples: &[examples::Category]) -> IronResult<Response> {
+    let response = MetaExamplesResponse::from(examples);
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(response))
+}
+
+fn meta_nightly_flags(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let flags = sandbox::NIGHTLY_FLAGS.iter().map(|f| format!("-Z{}", f)).collect();
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(MetaNightlyFlagsResponse { flags }))
+}
+
+fn admin_routes(req: &mut Request<'_, '_>, routes: &[routes::Route]) -> IronResult<Response> {
+    let routes = routes.iter().map(Into::into).collect();
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(AdminRoutesResponse { routes }))
+}
+
+fn admin_recent_failures(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let failures = recent_failures::global().list().into_iter().map(Into::into).collect();
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(AdminRecentFailuresResponse { failures }))
+}
+
+fn meta_circuit_breaker(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let status = sandbox_breaker().status();
+    serialize_to_response(ResponseFormat::negotiate(req), Ok(MetaCircuitBreakerResponse::from(status)))
+}
+
+fn meta_version_stable(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    if let Some(version) = query_param(req, "toolchainVersion") {
+        return meta_version_pinned(req, sandbox::Channel::Stable, version);
+    }
+
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_stable()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+fn meta_version_beta(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    if let Some(version) = query_param(req, "toolchainVersion") {
+        return meta_version_pinned(req, sandbox::Channel::Beta, version);
+    }
+
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_beta()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+fn meta_version_nightly(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    if let Some(version) = query_param(req, "toolchainVersion") {
+        return meta_version_pinned(req, sandbox::Channel::Nightly, version);
+    }
+
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_nightly()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+// Pinned toolchains are rarely asked about, so they aren't cached
+fn meta_version_pinned(req: &Request<'_, '_>, channel: sandbox::Channel, version: String) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        let sandbox = check_toolchain(sandbox, channel, parse_toolchain_version(Some(version.clone()))?.as_deref())?;
+        sandbox
+            .pinned_version(channel, &version)
+            .map(MetaVersionResponse::from)
+            .context(Versioning)
+    })
+}
+
+fn meta_version_rustfmt(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_rustfmt()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+fn meta_version_clippy(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_clippy()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+fn meta_version_miri(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        cached(sandbox)
+            .version_miri()
+            .map(MetaVersionResponse::from)
+    })
+}
+
+fn meta_capabilities(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        let sandbox = cached(sandbox);
+        let capabilities = MetaCapabilitiesResponse {
+            stable: sandbox.capabilities_stable()?.into(),
+            beta: sandbox.capabilities_beta()?.into(),
+            nightly: sandbox.capabilities_nightly()?.into(),
+        };
+        canonical::sorted(&capabilities).context(Serialization)
+    })
+}
+
+// Shares the capabilities' cache, which lists the same components
+fn meta_components(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox_no_request(req, |sandbox| {
+        let sandbox = cached(sandbox);
+        Ok(MetaComponentsResponse {
+            stable: installed_components(&sandbox.capabilities_stable()?),
+            beta: installed_components(&sandbox.capabilities_beta()?),
+            nightly: installed_components(&sandbox.capabilities_nightly()?),
+        })
+    })
+}
+
+fn installed_components(capabilities: &sandbox::Capabilities) -> BTreeMap<&'static str, bool> {
+    sandbox::TOOLCHAIN_COMPONENTS
+        .iter()
+        .map(|&name| (name, capabilities.has_component(name)))
+        .collect()
+}
+
+fn meta_gist_create(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
+    serialize_to_response(format, deserialize_from_request(req, |r: MetaGistCreateRequest| {
+        let metadata = if r.metadata.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&r.metadata).context(Serialization)?)
+        };
+
+        check_gist_limits(gist_limits(), &r.code, metadata.as_deref())?;
+
+        let gist = gist_concurrency().run(|| gist::create_with_metadata(token, r.code, metadata)).map_err(gist_failure)?;
+        Ok(MetaGistResponse::from(gist))
+    }))
+}
+
+fn meta_validate_deps(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    with_sandbox(req, |sandbox, req: MetaValidateDepsRequest| {
+        let dependencies = match (req.dependencies, req.lockfile) {
+            (Some(dependencies), None) => dependency_check::parse(&dependencies).context(InvalidDependencies)?,
+            (None, Some(lockfile)) => dependency_check::from_lockfile(&lockfile).context(InvalidLockfile)?,
+            (Some(_), Some(_)) =>
+                IncompatibleOptions { conflict: "dependencies and a lockfile cannot both be given" }.fail()?,
+            (None, None) => DependenciesMissing.fail()?,
+        };
+        let crates = cached(sandbox).crates()?;
+        let mut resolutions = dependency_check::resolve(&dependencies, &crates);
+        if let Some(allowlist) = allowed_crates() {
+            disallow_resolutions(&mut resolutions, allowlist);
+        }
+        Ok(MetaValidateDepsResponse::from(resolutions))
+    })
+}
+
+fn meta_hash(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    serialize_to_response(format, deserialize_from_request(req, |r: MetaHashRequest| {
+        let hash = content_hash::hash(&r.code);
+        Ok(MetaHashResponse { hash })
+    }))
+}
+
+fn meta_has_main(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    serialize_to_response(format, deserialize_from_request(req, |r: MetaHasMainRequest| {
+        Ok(MetaHasMainResponse::from(metrics::entry_point(&r.code)))
+    }))
+}
+
+fn meta_metrics(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    serialize_to_response(format, deserialize_from_request(req, |r: MetaMetricsRequest| {
+        Ok(MetaMetricsResponse::from(metrics::measure(&r.code)))
+    }))
+}
+
+fn meta_gist_get(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    match req.extensions.get::<Router>().unwrap().find("id") {
+        Some(id) => {
+            let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
+            let gist = gist_cache().get_or_load(id, Instant::now(), || gist_concurrency().run(|| gist::load(token, id))).map_err(gist_failure);
+            serialize_to_response(format, gist.map(MetaGistResponse::from))
+        }
+        None => {
+            Ok(Response::with(status::UnprocessableEntity))
+        }
+    }
+}
+
+fn meta_permalink_create(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
+    serialize_to_response(format, deserialize_from_request(req, |r: MetaPermalinkRequest| {
+        // Ensure that the permalink will be usable once loaded
+        let _: sandbox::CompileRequest = r.clone().try_into()?;
+
+        let metadata = serde_json::to_string(&r.options).context(Serialization)?;
+        check_gist_limits(gist_limits(), &r.code, Some(&metadata))?;
+
+        let gist = gist_concurrency().run(|| gist::create_with_metadata(token, r.code, Some(metadata))).map_err(gist_failure)?;
+        MetaPermalinkResponse::try_from(gist)
+    }))
+}
+
+fn meta_permalink_get(req: &mut Request<'_, '_>) -> IronResult<Response> {
+    let format = ResponseFormat::negotiate(req);
+    match req.extensions.get::<Router>().unwrap().find("id") {
+        Some(id) => {
+            let token = req.extensions.get::<GhToken>().unwrap().0.as_ref().clone();
+            let gist = gist_cache().get_or_load(id, Instant::now(), || gist_concurrency().run(|| gist::load(token, id))).map_err(gist_failure);
+            serialize_to_response(format, gist.and_then(MetaPermalinkResponse::try_from))
+        }
+        None => {
+            Ok(Response::with(status::UnprocessableEntity))
+        }
+    }
+}
+
+// This is a backwards compatibilty shim. The Rust homepage and the
+// documentation use this to run code in place.
+fn evaluate(req: &mut Request<'_, '_>, content_type: Option<&str>) -> IronResult<Response> {
+    let mut response = with_sandbox(req, |sandbox, req: EvaluateRequest| {
+        let sandbox = check_allowed_crates(sandbox, &req.code)?;
+        let req = req.try_into()?;
+        sandbox
+            .execute(&req)
+            .map(EvaluateResponse::from)
+            .context(Evaluation)
+    })?;
+
+    override_content_type(&mut response, content_type);
+    Ok(response)
+}
+
+// Some consumers of `evaluate.json` were written against the old
+// playground, which didn't send a JSON content type. An empty value
+// removes the header.
+fn override_content_type(response: &mut Response, content_type: Option<&str>) {
+    match content_type {
+        None => {}
+        Some("") => {
+            response.headers.remove::<ContentType>();
+        }
+        Some(content_type) => response.headers.set_raw("Content-Type", vec![content_type.as_bytes().to_vec()]),
+    }
+}
+
+fn query_param(req: &Request<'_, '_>, name: &str) -> Option<String> {
+    let url: &iron::url::Url = req.url.as_ref();
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn with_sandbox<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> IronResult<Response>
+where
+    F: FnOnce(Sandbox, Req) -> Result<Resp>,
+    Req: DeserializeOwned + Clone + Any + 'static,
+    Resp: Serialize,
+{
+    let format = ResponseFormat::negotiate(req);
+    let fields = requested_fields(req);
+    let response = run_handler(req, f).and_then(|resp| project_fields(resp, &fields));
+    serialize_to_response(format, response)
+}
+
+fn with_sandbox_no_request<Resp, F>(req: &Request<'_, '_>, f: F) -> IronResult<Response>
+where
+    F: FnOnce(Sandbox) -> Result<Resp>,
+    Resp: Serialize,
+{
+    let fields = requested_fields(req);
+    let response = run_handler_no_request(f).and_then(|resp| project_fields(resp, &fields));
+    serialize_to_response(ResponseFormat::negotiate(req), response)
+}
+
+fn requested_fields(req: &Request<'_, '_>) -> Option<Vec<String>> {
+    query_param(req, "fields").map(|fields| response_fields::parse(&fields))
+}
+
+fn project_fields<Resp>(response: Resp, fields: &Option<Vec<String>>) -> Result<response_fields::Projected<Resp>>
+where
+    Resp: Serialize,
+{
+    response_fields::project(response, fields.as_deref()).context(Serialization)
+}
+
+fn run_handler<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
+where
+    F: FnOnce(Sandbox, Req) -> Result<Resp>,
+    Req: DeserializeOwned + Clone + Any + 'static,
+{
+    deserialize_from_request(req, |req| run_sandboxed(|sandbox| f(sandbox, req)))
+}
+
+fn deserialize_from_request<Req, Resp, F>(req: &mut Request<'_, '_>, f: F) -> Result<Resp>
+where
+    F: FnOnce(Req) -> Result<Resp>,
+    Req: DeserializeOwned + Clone + Any + 'static,
+{
+    let body = req.get::<bodyparser::Struct<Req>>()
+        .context(Deserialization)?;
+
+    let req = body.ok_or(Error::RequestMissing)?;
+
+    let resp = f(req)?;
+
+    Ok(resp)
+}
+
+fn run_handler_no_request<Resp, F>(f: F) -> Result<Resp>
+where
+    F: FnOnce(Sandbox) -> Result<Resp>,
+{
+    run_sandboxed(f)
+}
+
+// When the container runtime is broken, every request would otherwise
+// wait for it to fail on its own.
+fn run_sandboxed<Resp, F>(f: F) -> Result<Resp>
+where
+    F: FnOnce(Sandbox) -> Result<Resp>,
+{
+    let attempt = match sandbox_breaker().allow_request() {
+        Some(attempt) => attempt,
+        None => return SandboxUnavailable.fail(),
+    };
+
+    let resp = Sandbox::new().context(SandboxCreation).and_then(f);
+    let failed = match &resp {
+        Err(e) => e.is_runtime_failure(),
+        Ok(_) => false,
+    };
+    attempt.record(!failed);
+    resp
+}
+
+fn sandbox_breaker() -> &'static CircuitBreaker {
+    lazy_static! {
+        static ref SANDBOX_BREAKER: CircuitBreaker = {
+            let threshold = env::var("PLAYGROUND_CIRCUIT_BREAKER_THRESHOLD")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_THRESHOLD);
+            let cooldown = env::var("PLAYGROUND_CIRCUIT_BREAKER_COOLDOWN")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_IN_SECONDS);
+
+            CircuitBreaker::new(threshold, Duration::from_secs(cooldown))
+        };
+    }
+
+    &SANDBOX_BREAKER
+}
+
+fn gist_cache() -> &'static GistCache {
+    lazy_static! {
+        static ref GIST_CACHE: GistCache = {
+            let size = env::var("PLAYGROUND_GIST_CACHE_SIZE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_GIST_CACHE_SIZE);
+            let time_to_live = env::var("PLAYGROUND_GIST_CACHE_TTL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_GIST_CACHE_TIME_TO_LIVE_IN_SECONDS);
+
+            GistCache::new(size, Duration::from_secs(time_to_live))
+        };
+    }
+
+    &GIST_CACHE
+}
+
+fn gist_concurrency() -> &'static gist::Concurrency {
+    lazy_static! {
+        static ref GIST_CONCURRENCY: gist::Concurrency = {
+            let max = env::var("PLAYGROUND_MAX_CONCURRENT_GISTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_CONCURRENT_GISTS);
+
+            gist::Concurrency::new(max)
+        };
+    }
+
+    &GIST_CONCURRENCY
+}
+
+fn gist_limits() -> gist::Limits {
+    lazy_static! {
+        static ref GIST_LIMITS: gist::Limits = {
+            let max_files = env::var("PLAYGROUND_MAX_GIST_FILES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_GIST_FILES);
+            let max_bytes = env::var("PLAYGROUND_MAX_GIST_BYTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_MAX_GIST_BYTES);
+
+            gist::Limits { max_files, max_bytes }
+        };
+    }
+
+    *GIST_LIMITS
+}
+
+// Unlimited unless configured
+fn response_limit() -> Option<response_limit::Limit> {
+    lazy_static! {
+        static ref RESPONSE_LIMIT: Option<response_limit::Limit> = {
+            let max_kb: Option<usize> = env::var("PLAYGROUND_MAX_RESPONSE_KB")
+                .ok()
+                .and_then(|v| v.parse().ok());
+            let overflow = env::var("PLAYGROUND_OVERSIZED_RESPONSES")
+                .ok()
+                .and_then(|v| response_limit::Overflow::parse(&v))
+                .unwrap_or(response_limit::Overflow::Truncate);
+
+            max_kb.map(|max_kb| response_limit::Limit { max_bytes: max_kb.saturating_mul(1024), overflow })
+        };
+    }
+
+    *RESPONSE_LIMIT
+}
+
+// Every crate is allowed unless configured
+fn allowed_crates() -> Option<&'static crate_allowlist::Allowlist> {
+    lazy_static! {
+        static ref ALLOWED_CRATES: Option<crate_allowlist::Allowlist> = env::var("PLAYGROUND_ALLOWED_CRATES")
+            .ok()
+            .map(|v| crate_allowlist::Allowlist::parse(&v));
+    }
+
+    ALLOWED_CRATES.as_ref()
+}
+
+// No variable may be set unless configured
+fn allowed_environment() -> Option<&'static environment::Allowlist> {
+    lazy_static! {
+        static ref ALLOWED_ENV: Option<environment::Allowlist> = env::var("PLAYGROUND_ALLOWED_ENV")
+            .ok()
+            .map(|v| environment::Allowlist::parse(&v));
+    }
+
+    ALLOWED_ENV.as_ref()
+}
+
+// strace can inspect far more than the program's output, so only
+// operators that want it turn it on
+fn tracing_enabled() -> bool {
+    lazy_static! {
+        static ref TRACING_ENABLED: bool = env::var("PLAYGROUND_TRACE_ENABLED")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+    }
+
+    *TRACING_ENABLED
+}
+
+// So that operators can find the container that ran a reported request
+fn request_ids_logged() -> bool {
+    lazy_static! {
+        static ref REQUEST_IDS_LOGGED: bool = env::var("PLAYGROUND_LOG_REQUEST_IDS")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+    }
+
+    *REQUEST_IDS_LOGGED
+}
+
+fn max_execute_repeat() -> u32 {
+    lazy_static! {
+        static ref MAX_EXECUTE_REPEAT: u32 = env::var("PLAYGROUND_MAX_EXECUTE_REPEAT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_EXECUTE_REPEAT);
+    }
+
+    *MAX_EXECUTE_REPEAT
+}
+
+// Profiling gives the container a capability that no other one has,
+// and perf has had security bugs of its own
+fn profiling_enabled() -> bool {
+    lazy_static! {
+        static ref PROFILING_ENABLED: bool = env::var("PLAYGROUND_PROFILE_ENABLED")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+    }
+
+    *PROFILING_ENABLED
+}
+
+// Gives the sandbox back so that the request can still use it
+fn check_allowed_crates(sandbox: Sandbox, code: &str) -> Result<Sandbox> {
+    let allowlist = match allowed_crates() {
+        Some(allowlist) => allowlist,
+        None => return Ok(sandbox),
+    };
+
+    let cached = cached(sandbox);
+    let crates = cached.crates()?;
+    if let Some(name) = allowlist.first_disallowed(code, &crates) {
+        CrateNotAllowed { name }.fail()?;
+    }
+
+    Ok(cached.sandbox)
+}
+
+// Gives the sandbox back so that the request can still use it
+fn check_toolchain(sandbox: Sandbox, channel: sandbox::Channel, version: Option<&str>) -> Result<Sandbox> {
+    let version = match version {
+        Some(version) => version,
+        None => return Ok(sandbox),
+    };
+
+    let cached = cached(sandbox);
+    if !cached.capabilities_for(channel)?.has_toolchain(version) {
+        ToolchainUnavailable { version }.fail()?;
+    }
+
+    Ok(cached.sandbox)
+}
+
+// Gives the sandbox back so that the request can still use it
+fn check_feature(sandbox: Sandbox, channel: sandbox::Channel, feature: &'static str) -> Result<Sandbox> {
+    let cached = cached(sandbox);
+    if !cached.capabilities_for(channel)?.has_feature(feature) {
+        FeatureUnavailable { feature }.fail()?;
+    }
+
+    Ok(cached.sandbox)
+}
+
+fn check_coverage(sandbox: Sandbox, req: &sandbox::ExecuteRequest) -> Result<Sandbox> {
+    if req.coverage {
+        check_feature(sandbox, req.channel, sandbox::FEATURE_COVERAGE)
+    } else {
+        Ok(sandbox)
+    }
+}
+
+fn disallow_resolutions(resolutions: &mut [dependency_check::Resolution], allowlist: &crate_allowlist::Allowlist) {
+    use dependency_check::Resolution;
+
+    for resolution in resolutions {
+        if let Resolution::Resolved { name, .. } = resolution {
+            if !allowlist.allows(name) {
+                let reason = format!("`{}` is not allowed on this playground", name);
+                *resolution = Resolution::Failed { name: name.clone(), reason };
+            }
+        }
+    }
+}
+
+fn gist_failure(err: gist::Error) -> Error {
+    match err {
+        gist::Error::Unavailable => Error::GistUnavailable,
+        gist::Error::Busy => Error::GistBusy,
+        gist::Error::Request { source } => Error::GistRequest { source },
+    }
+}
+
+fn check_gist_limits(limits: gist::Limits, code: &str, metadata: Option<&str>) -> Result<()> {
+    match limits.check(code, metadata) {
+        Ok(()) => Ok(()),
+        Err(gist::LimitExceeded::Files { count, max }) => GistTooManyFiles { count, max }.fail(),
+        Err(gist::LimitExceeded::Bytes { count, max }) => GistTooLarge { count, max }.fail(),
+    }
+}
+
+fn serialize_to_response<Resp>(format: ResponseFormat, response: Result<Resp>) -> IronResult<Response>
+where
+    Resp: Serialize,
+{
+    let response = response.and_then(|resp| format.serialize_within(&resp, response_limit()));
+
+    match response {
+        Ok(body) => Ok(Response::with((status::Ok, Header(format.content_type()), vary_on_accept(), body))),
+        Err(err) => {
+            let status = err.status();
+            match format.serialize_error(&err) {
+                Ok(error_body) => Ok(Response::with((status, Header(format.error_content_type()), vary_on_accept(), error_body))),
+                Err(_) => Ok(Response::with((status::InternalServerError, Header(ContentType::json()), vary_on_accept(), FATAL_ERROR_JSON))),
+            }
+        },
+    }
+}
+
+const MESSAGE_PACK_MIME_TYPES: &[&str] = &["application/msgpack", "application/x-msgpack"];
+const PROBLEM_JSON_MIME_TYPE: &str = "application/problem+json";
+const MULTIPART_MIME_TYPE: &str = "multipart/mixed";
+const OCTET_STREAM_MIME_TYPE: &str = "application/octet-stream";
+const NDJSON_MIME_TYPE: &str = "application/x-ndjson";
+const EVENT_STREAM_MIME_TYPE: &str = "text/event-stream";
+
+// A quality of zero marks a type that the client does not accept
+fn accepts(req: &Request<'_, '_>, mime_types: &[&str]) -> bool {
+    req.headers.get::<Accept>().map_or(false, |accept| {
+        accept.iter().any(|item| item.quality.0 > 0 && mime_types.contains(&&*item.item.to_string()))
+    })
+}
+
+// The body depends on the `Accept` header, so caches keep one per format
+fn vary_on_accept() -> Header<Vary> {
+    Header(Vary::Items(vec![UniCase("Accept".to_owned())]))
+}
+
+/// How response bodies are encoded. JSON unless the client asks for
+/// MessagePack in the `Accept` header. Clients accepting
+/// `application/problem+json` get errors in the RFC 7807 format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum ResponseFormat {
+    Json,
+    ProblemJson,
+    MessagePack,
+}
+
+impl ResponseFormat {
+    fn negotiate(req: &Request<'_, '_>) -> Self {
+        if accepts(req, MESSAGE_PACK_MIME_TYPES) {
+            ResponseFormat::MessagePack
+        } else if accepts(req, &[PROBLEM_JSON_MIME_TYPE]) {
+            ResponseFormat::ProblemJson
+        } else {
+            ResponseFormat::Json
+        }
+    }
+
+    fn content_type(self) -> ContentType {
+        match self {
+            ResponseFormat::Json | ResponseFormat::ProblemJson => ContentType::json(),
+            ResponseFormat::MessagePack => ContentType(MESSAGE_PACK_MIME_TYPES[0].parse().expect("Invalid MessagePack MIME type")),
+        }
+    }
+
+    fn error_content_type(self) -> ContentType {
+        match self {
+            ResponseFormat::ProblemJson => ContentType(PROBLEM_JSON_MIME_TYPE.parse().expect("Invalid problem JSON MIME type")),
+            _ => self.content_type(),
+        }
+    }
+
+    fn serialize<T: Serialize>(self, value: &T) -> Result<Vec<u8>> {
+        match self {
+            ResponseFormat::Json | ResponseFormat::ProblemJson => serde_json::to_vec(value).context(Serialization),
+            ResponseFormat::MessagePack => rmp_serde::to_vec_named(value).context(MessagePackSerialization),
+        }
+    }
+
+    /// Oversized responses are cut down or rejected, as configured
+    fn serialize_within<T: Serialize>(self, value: &T, limit: Option<response_limit::Limit>) -> Result<Vec<u8>> {
+        let body = self.serialize(value)?;
+        let limit = match limit {
+            Some(limit) if body.len() > limit.max_bytes => limit,
+            _ => return Ok(body),
+        };
+
+        if limit.overflow == response_limit::Overflow::Truncate {
+            let mut value = serde_json::to_value(value).context(Serialization)?;
+            let size = |value: &serde_json::Value| self.serialize(value).map(|body| body.len()).unwrap_or(std::usize::MAX);
+            response_limit::truncate(&mut value, limit.max_bytes, size);
+
+            let truncated = self.serialize(&value)?;
+            if truncated.len() <= limit.max_bytes {
+                return Ok(truncated);
+            }
+        }
+
+        ResponseTooLarge { count: body.len(), max: limit.max_bytes }.fail()
+    }
+
+    fn serialize_error(self, err: &Error) -> Result<Vec<u8>> {
+        match self {
+            ResponseFormat::ProblemJson => self.serialize(&ProblemJson::from(err)),
+            _ => self.serialize(&ErrorJson { error: err.to_string() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SandboxCacheInfo<T> {
+    value: T,
+    time: Instant,
+}
+
+/// Caches the success value of a single operation. The lock is held
+/// while the value is populated, so callers that arrive in the
+/// meantime wait for that one sandbox call instead of making their own.
+#[derive(Debug)]
+struct SandboxCacheOne<T>(Mutex<Option<SandboxCacheInfo<T>>>);
+
+impl<T> Default for SandboxCacheOne<T> {
+    fn default() -> Self { SandboxCacheOne(Mutex::default()) }
+}
+
+impl<T> SandboxCacheOne<T>
+where
+    T: Clone
+{
+    fn clone_or_populate<F>(&self, populator: F) -> Result<T>
+    where
+        F: FnOnce() -> sandbox::Result<T>
+    {
+        self.clone_or_populate_within(Duration::from_secs(SANDBOX_CACHE_TIME_TO_LIVE_IN_SECONDS), populator)
+    }
+
+    fn clone_or_populate_within<F>(&self, time_to_live: Duration, populator: F) -> Result<T>
+    where
+        F: FnOnce() -> sandbox::Result<T>
+    {
+        let mut cache = self.0.lock().map_err(|_| Error::CachePoisoned)?;
+
+        match cache.clone() {
+            Some(cached) => {
+                if cached.time.elapsed() > time_to_live {
+                    SandboxCacheOne::populate(&mut *cache, populator)
+                } else {
+                    Ok(cached.value)
+                }
+            },
+            None => {
+                SandboxCacheOne::populate(&mut *cache, populator)
+            }
+        }
+    }
+
+    fn populate<F>(cache: &mut Option<SandboxCacheInfo<T>>, populator: F) -> Result<T>
+    where
+        F: FnOnce() -> sandbox::Result<T>
+    {
+        let value = populator().context(Caching)?;
+        *cache = Some(SandboxCacheInfo {
+            value: value.clone(),
+            time: Instant::now(),
+        });
+        Ok(value)
+    }
+}
+
+/// Caches the successful results of all sandbox operations that make
+/// sense to cache.
+#[derive(Debug, Default)]
+struct SandboxCache {
+    crates: SandboxCacheOne<Vec<sandbox::CrateInformation>>,
+    cargo_lock: SandboxCacheOne<cargo_lock::Lockfile>,
+    crate_graph: SandboxCacheOne<crate_graph::CrateGraph>,
+    clippy_lints: SandboxCacheOne<Vec<clippy_lints::Lint>>,
+    version_stable: SandboxCacheOne<sandbox::Version>,
+    version_beta: SandboxCacheOne<sandbox::Version>,
+    version_nightly: SandboxCacheOne<sandbox::Version>,
+    version_clippy: SandboxCacheOne<sandbox::Version>,
+    version_rustfmt: SandboxCacheOne<sandbox::Version>,
+    version_miri: SandboxCacheOne<sandbox::Version>,
+    capabilities_stable: SandboxCacheOne<sandbox::Capabilities>,
+    capabilities_beta: SandboxCacheOne<sandbox::Capabilities>,
+    capabilities_nightly: SandboxCacheOne<sandbox::Capabilities>,
+}
+
+/// Provides a similar API to the Sandbox that caches the successful results.
+struct CachedSandbox<'a> {
+    sandbox: Sandbox,
+    cache: &'a SandboxCache,
+}
+
+impl<'a> CachedSandbox<'a> {
+    fn crates(&self) -> Result<Vec<sandbox::CrateInformation>> {
+        self.cache.crates.clone_or_populate(|| self.sandbox.crates())
+    }
+
+    fn cargo_lock(&self) -> Result<cargo_lock::Lockfile> {
+        self.cache.cargo_lock.clone_or_populate(|| self.sandbox.cargo_lock())
+    }
+
+    fn crate_graph(&self) -> Result<crate_graph::CrateGraph> {
+        self.cache.crate_graph.clone_or_populate(|| self.sandbox.crate_graph())
+    }
+
+    fn clippy_lints(&self) -> Result<Vec<clippy_lints::Lint>> {
+        self.cache.clippy_lints.clone_or_populate(|| self.sandbox.clippy_lints())
+    }
+
+    fn version_stable(&self) -> Result<sandbox::Version> {
+        self.cache.version_stable.clone_or_populate(|| {
+            self.sandbox.version(sandbox::Channel::Stable)
+        })
+    }
+
+    fn version_beta(&self) -> Result<sandbox::Version> {
+        self.cache.version_beta.clone_or_populate(|| {
+            self.sandbox.version(sandbox::Channel::Beta)
+        })
+    }
+
+    fn version_nightly(&self) -> Result<sandbox::Version> {
+        self.cache.version_nightly.clone_or_populate(|| {
+            self.sandbox.version(sandbox::Channel::Nightly)
+        })
+    }
+
+    fn version_clippy(&self) -> Result<sandbox::Version> {
+        self.cache.version_clippy.clone_or_populate(|| {
+            self.sandbox.version_clippy()
+        })
+    }
+
+    fn version_rustfmt(&self) -> Result<sandbox::Version> {
+        self.cache.version_rustfmt.clone_or_populate(|| {
+            self.sandbox.version_rustfmt()
+        })
+    }
+
+    fn version_miri(&self) -> Result<sandbox::Version> {
+        self.cache.version_miri.clone_or_populate(|| {
+            self.sandbox.version_miri()
+        })
+    }
+
+    fn capabilities_stable(&self) -> Result<sandbox::Capabilities> {
+        self.capabilities(&self.cache.capabilities_stable, sandbox::Channel::Stable)
+    }
+
+    fn capabilities_beta(&self) -> Result<sandbox::Capabilities> {
+        self.capabilities(&self.cache.capabilities_beta, sandbox::Channel::Beta)
+    }
+
+    fn capabilities_nightly(&self) -> Result<sandbox::Capabilities> {
+        self.capabilities(&self.cache.capabilities_nightly, sandbox::Channel::Nightly)
+    }
+
+    fn capabilities_for(&self, channel: sandbox::Channel) -> Result<sandbox::Capabilities> {
+        match channel {
+            sandbox::Channel::Stable => self.capabilities_stable(),
+            sandbox::Channel::Beta => self.capabilities_beta(),
+            sandbox::Channel::Nightly => self.capabilities_nightly(),
+        }
+    }
+
+    fn capabilities(
+        &self,
+        cache: &SandboxCacheOne<sandbox::Capabilities>,
+        channel: sandbox::Channel,
+    ) -> Result<sandbox::Capabilities> {
+        let time_to_live = Duration::from_secs(CAPABILITIES_CACHE_TIME_TO_LIVE_IN_SECONDS);
+        cache.clone_or_populate_within(time_to_live, || self.sandbox.capabilities(channel))
+    }
+}
+
+/// A convenience constructor
+fn cached(sandbox: Sandbox) -> CachedSandbox<'static> {
+    lazy_static! {
+        static ref SANDBOX_CACHE: SandboxCache = Default::default();
+    }
+
+    CachedSandbox {
+        sandbox,
+        cache: &SANDBOX_CACHE,
+    }
+}
+
+#[derive(Debug, Snafu)]
+pub enum Error {
+    #[snafu(display("The daily quota of {} requests has been used up", limit))]
+    QuotaExceeded { limit: u32 },
+    #[snafu(display("No prelude has been configured"))]
+    PreludeUnavailable,
+    #[snafu(display("The sandbox is temporarily unavailable"))]
+    SandboxUnavailable,
+    #[snafu(display("Sandbox creation failed: {}", source))]
+    SandboxCreation { source: sandbox::Error },
+    #[snafu(display("Compilation operation failed: {}", source))]
+    Compilation { source: sandbox::Error },
+    #[snafu(display("Execution operation failed: {}", source))]
+    Execution { source: sandbox::Error },
+    #[snafu(display("Evaluation operation failed: {}", source))]
+    Evaluation { source: sandbox::Error },
+    #[snafu(display("Linting operation failed: {}", source))]
+    Linting { source: sandbox::Error },
+    #[snafu(display("Formatting operation failed: {}", source))]
+    Formatting { source: sandbox::Error },
+    #[snafu(display("Interpreting operation failed: {}", source))]
+    Interpreting { source: sandbox::Error },
+    #[snafu(display("Graph rendering operation failed: {}", source))]
+    GraphRendering { source: sandbox::Error },
+    #[snafu(display("Caching operation failed: {}", source))]
+    Caching { source: sandbox::Error },
+    #[snafu(display("Version operation failed: {}", source))]
+    Versioning { source: sandbox::Error },
+    #[snafu(display("Checking on one of the channels panicked"))]
+    CompatibilityCheckPanicked,
+    #[snafu(display("Debug artifacts are not enabled on this server"))]
+    DebugArtifactsDisabled,
+    #[snafu(display("Tracing system calls is not enabled on this server"))]
+    TracingDisabled,
+    #[snafu(display("Profiling is not enabled on this server"))]
+    ProfilingDisabled,
+    #[snafu(display("Profiling needs Docker 20.10 or later, which this server does not have"))]
+    ProfilingUnsupported,
+    #[snafu(display("The admin endpoints are not enabled on this server"))]
+    AdminDisabled,
+    #[snafu(display("The admin token is missing or incorrect"))]
+    AdminTokenInvalid,
+    #[snafu(display("Unable to save the debug artifacts: {}", source))]
+    DebugArtifactsSaving { source: std::io::Error },
+    #[snafu(display("Unable to load the debug artifacts: {}", source))]
+    DebugArtifactsLoading { source: std::io::Error },
+    #[snafu(display("The debug artifacts do not exist or have already been downloaded"))]
+    DebugArtifactsMissing,
+    #[snafu(display("Unable to serialize response: {}", source))]
+    Serialization { source: serde_json::Error },
+    #[snafu(display("Unable to serialize response as MessagePack: {}", source))]
+    MessagePackSerialization { source: rmp_serde::encode::Error },
+    #[snafu(display("The response would be {} bytes long, but at most {} are allowed", count, max))]
+    ResponseTooLarge { count: usize, max: usize },
+    #[snafu(display("The gist would have {} files, but at most {} are allowed", count, max))]
+    GistTooManyFiles { count: usize, max: usize },
+    #[snafu(display("The gist would be {} bytes long, but at most {} are allowed", count, max))]
+    GistTooLarge { count: usize, max: usize },
+    #[snafu(display("The gist service is unavailable; GitHub could not be reached"))]
+    GistUnavailable,
+    #[snafu(display("Too many gists are being saved or loaded; please try again shortly"))]
+    GistBusy,
+    #[snafu(display("Unable to use the gist: {}", source))]
+    GistRequest { source: hubcaps::Error },
+    #[snafu(display("The gist does not contain permalink options"))]
+    PermalinkOptionsMissing,
+    #[snafu(display("Unable to read the permalink options: {}", source))]
+    PermalinkOptionsInvalid { source: serde_json::Error },
+    #[snafu(display("Unable to deserialize request: {}", source))]
+    Deserialization { source: bodyparser::BodyError },
+    #[snafu(display("The code cannot be empty"))]
+    InvalidSource,
+    #[snafu(display("The program can be run between 1 and {} times, not {}", max, value))]
+    InvalidRepeatCount { value: u32, max: u32 },
+    #[snafu(display("The predicate cannot be empty"))]
+    MinimizePredicateEmpty,
+    #[snafu(display("At least one crate type must be requested"))]
+    CrateTypesMissing,
+    #[snafu(display("The code does not fail with an error containing {:?}", predicate))]
+    MinimizeNotFailing { predicate: String },
+    #[snafu(display("The value {:?} is not a valid target", value))]
+    InvalidTarget { value: String },
+    #[snafu(display("The value {:?} is not a valid assembly flavor", value))]
+    InvalidAssemblyFlavor { value: String },
+    #[snafu(display("The value {:?} is not a valid demangle option", value))]
+    InvalidDemangleAssembly { value: String },
+    #[snafu(display("The value {:?} is not a valid assembly processing option", value))]
+    InvalidProcessAssembly { value: String },
+    #[snafu(display("The value {:?} is not a valid MIR stage", value))]
+    InvalidMirStage { value: String },
+    #[snafu(display("The value {:?} is not a valid diagnostic format", value))]
+    InvalidDiagnosticFormat { value: String },
+    #[snafu(display("The value {:?} is not a MIR pass that can be dumped; choose one of {}", value, sandbox::MIR_PASSES.join(", ")))]
+    InvalidMirPhase { value: String },
+    #[snafu(display("The value {:?} is not a valid linker", value))]
+    InvalidLinker { value: String },
+    #[snafu(display("The value {:?} is not a valid sanitizer", value))]
+    InvalidSanitizer { value: String },
+    #[snafu(display("The value {:?} is not a valid channel", value,))]
+    InvalidChannel { value: String },
+    #[snafu(display("The value {:?} is not a valid mode", value))]
+    InvalidMode { value: String },
+    #[snafu(display("The value {:?} is not a valid edition", value))]
+    InvalidEdition { value: String },
+    #[snafu(display("The value {:?} is not a valid crate type", value))]
+    InvalidCrateType { value: String },
+    #[snafu(display("The value {:?} is not a valid crate sort order", value))]
+    InvalidCrateSort { value: String },
+    #[snafu(display("The value {:?} is not a valid graph format", value))]
+    InvalidGraphFormat { value: String },
+    #[snafu(display("The value {:?} is not a session issued by this playground", value))]
+    InvalidSession { value: String },
+    #[snafu(display("The value {:?} is not a valid seed", value))]
+    InvalidSeed { value: String },
+    #[snafu(display("The value {:?} is not a valid request id; use a random (version 4) UUID", value))]
+    InvalidRequestId { value: String },
+    #[snafu(display("The value {:?} is not a supported timezone", value))]
+    InvalidTimezone { value: String },
+    #[snafu(display("The value {:?} is not a supported locale", value))]
+    InvalidLocale { value: String },
+    #[snafu(display("The environment variable {:?} cannot be set: {}", name, reason))]
+    InvalidEnvironmentVariable { name: String, reason: environment::Rejection },
+    #[snafu(display("{} environment variables were requested, but at most {} can be set", count, max))]
+    TooManyEnvironmentVariables { count: usize, max: usize },
+    #[snafu(display("{} files were submitted for formatting, but at most {} can be", count, max))]
+    TooManyFormatFiles { count: usize, max: usize },
+    #[snafu(display("The file {:?} was submitted for formatting more than once", name))]
+    DuplicateFormatFile { name: String },
+    #[snafu(display("The value {:?} is not a supported lint group", value))]
+    InvalidLintGroup { value: String },
+    #[snafu(display("Unable to parse the dependencies: {}", source))]
+    InvalidDependencies { source: toml::de::Error },
+    #[snafu(display("Unable to read the lockfile: {}", source))]
+    InvalidLockfile { source: dependency_check::LockfileError },
+    #[snafu(display("Either dependencies or a lockfile must be given"))]
+    DependenciesMissing,
+    #[snafu(display("The crate `{}` is not allowed on this playground", name))]
+    CrateNotAllowed { name: String },
+    #[snafu(display("The toolchain version `{}` must be an exact version, such as `1.42.0`", version))]
+    InvalidToolchainVersion { version: String },
+    #[snafu(display("The toolchain version `{}` is not installed on this playground", version))]
+    ToolchainUnavailable { version: String },
+    #[snafu(display("The `{}` feature is not supported by the compiler on this playground", feature))]
+    FeatureUnavailable { feature: &'static str },
+    #[snafu(display("The requested options are incompatible: {}", conflict))]
+    IncompatibleOptions { conflict: String },
+    #[snafu(display("The method {:?} is not known", method))]
+    RpcMethodUnknown { method: String },
+    #[snafu(display("Unable to deserialize the parameters: {}", source))]
+    RpcParamsInvalid { source: serde_json::Error },
+    #[snafu(display("The query string is {} bytes long, but at most {} are allowed", len, max))]
+    QueryTooLong { len: usize, max: usize },
+    #[snafu(display("The path is {} bytes long, but at most {} are allowed", len, max))]
+    PathTooLong { len: usize, max: usize },
+    #[snafu(display("The query parameter {:?} is required", name))]
+    QueryParameterMissing { name: &'static str },
+    #[snafu(display("No request was provided"))]
+    RequestMissing,
+    #[snafu(display("The cache has been poisoned"))]
+    CachePoisoned,
+}
+
+impl Error {
+    /// Errors caused by the contents of the request are the client's
+    /// fault; everything else is ours.
+    fn status(&self) -> status::Status {
+        use self::Error::*;
+
+        match *self {
+            Execution { source: sandbox::Error::Cancelled } => status::Unregistered(CLIENT_CLOSED_REQUEST),
+            Execution { source: sandbox::Error::RequestIdInUse { .. } } => status::Conflict,
+            Compilation { source: sandbox::Error::MirPassNotRun { .. } } => status::BadRequest,
+            Deserialization { .. } |
+            InvalidSource |
+            InvalidRepeatCount { .. } |
+            MinimizePredicateEmpty |
+            CrateTypesMissing |
+            MinimizeNotFailing { .. } |
+            InvalidTarget { .. } |
+            InvalidAssemblyFlavor { .. } |
+            InvalidDemangleAssembly { .. } |
+            InvalidProcessAssembly { .. } |
+            InvalidMirStage { .. } |
+            InvalidDiagnosticFormat { .. } |
+            InvalidMirPhase { .. } |
+            InvalidLinker { .. } |
+            InvalidSanitizer { .. } |
+            InvalidChannel { .. } |
+            InvalidMode { .. } |
+            InvalidEdition { .. } |
+            InvalidCrateType { .. } |
+            InvalidCrateSort { .. } |
+            InvalidGraphFormat { .. } |
+            InvalidSession { .. } |
+            InvalidSeed { .. } |
+            InvalidRequestId { .. } |
+            InvalidTimezone { .. } |
+            InvalidLocale { .. } |
+            InvalidEnvironmentVariable { .. } |
+            TooManyEnvironmentVariables { .. } |
+            TooManyFormatFiles { .. } |
+            DuplicateFormatFile { .. } |
+            InvalidLintGroup { .. } |
+            InvalidDependencies { .. } |
+            InvalidLockfile { .. } |
+            DependenciesMissing |
+            CrateNotAllowed { .. } |
+            InvalidToolchainVersion { .. } |
+            ToolchainUnavailable { .. } |
+            FeatureUnavailable { .. } |
+            IncompatibleOptions { .. } |
+            RpcMethodUnknown { .. } |
+            RpcParamsInvalid { .. } |
+            QueryParameterMissing { .. } |
+            PreludeUnavailable |
+            RequestMissing => status::BadRequest,
+            QueryTooLong { .. } | PathTooLong { .. } => status::UriTooLong,
+            GistTooManyFiles { .. } | GistTooLarge { .. } => status::PayloadTooLarge,
+            ResponseTooLarge { .. } => status::UnprocessableEntity,
+            PermalinkOptionsMissing | DebugArtifactsMissing => status::NotFound,
+            DebugArtifactsDisabled | TracingDisabled | ProfilingDisabled | ProfilingUnsupported | AdminDisabled => status::Forbidden,
+            AdminTokenInvalid => status::Unauthorized,
+            SandboxUnavailable | GistUnavailable | GistBusy => status::ServiceUnavailable,
+            QuotaExceeded { .. } => status::TooManyRequests,
+            _ => status::InternalServerError,
+        }
+    }
+
+    fn is_runtime_failure(&self) -> bool {
+        use self::Error::*;
+
+        match self {
+            SandboxCreation { source } |
+            Compilation { source } |
+            Execution { source } |
+            Evaluation { source } |
+            Linting { source } |
+            Formatting { source } |
+            Interpreting { source } |
+            GraphRendering { source } |
+            Caching { source } => source.is_runtime_failure(),
+            _ => false,
+        }
+    }
+
+    /// The JSON-RPC error code that is closest to the HTTP status.
+    fn rpc_code(&self) -> i32 {
+        use self::Error::*;
+
+        match *self {
+            Deserialization { .. } | RequestMissing => -32700,
+            RpcMethodUnknown { .. } => -32601,
+            SandboxUnavailable | QuotaExceeded { .. } => -32000,
+            _ if self.status() == status::InternalServerError => -32603,
+            _ => -32602,
+        }
+    }
+}
+
+type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+const FATAL_ERROR_JSON: &str =
+    r#"{"error": "Multiple cascading errors occurred, abandon all hope"}"#;
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorJson {
+    error: String,
+}
+
+/// An RFC 7807 problem detail. Errors aren't documented individually,
+/// so the type is always `about:blank` and the title is the status.
+#[derive(Debug, Clone, Serialize)]
+struct ProblemJson {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+}
+
+impl From<&Error> for ProblemJson {
+    fn from(err: &Error) -> Self {
+        let status = err.status();
+        ProblemJson {
+            kind: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.to_u16(),
+            detail: err.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CompileRequest {
+    target: String,
+    #[serde(rename = "assemblyFlavor")]
+    assembly_flavor: Option<String>,
+    #[serde(rename = "demangleAssembly")]
+    demangle_assembly: Option<String>,
+    #[serde(rename = "processAssembly")]
+    process_assembly: Option<String>,
+    #[serde(default, rename = "mirStage")]
+    mir_stage: Option<String>,
+    /// Shows the MIR right after this pass instead
+    #[serde(default, rename = "mirPhase")]
+    mir_phase: Option<String>,
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
+    #[serde(default, rename = "allFlavors")]
+    all_flavors: bool,
+    #[serde(default)]
+    suggestions: bool,
+    #[serde(default, rename = "rawDiagnostics")]
+    raw_diagnostics: bool,
+    #[serde(default, rename = "buildMessages")]
+    build_messages: bool,
+    /// How many of the last lines of output to summarize
+    #[serde(default, rename = "diagnosticSummary")]
+    diagnostic_summary: Option<usize>,
+    #[serde(default, rename = "diagnosticGroups")]
+    diagnostic_groups: bool,
+    /// `short`, `human`, or `json`; how Cargo prints the messages
+    #[serde(default, rename = "diagnosticFormat")]
+    diagnostic_format: Option<String>,
+    #[serde(default, rename = "instructionCounts")]
+    instruction_counts: bool,
+    #[serde(default, rename = "wasmSections")]
+    wasm_sections: bool,
+    #[serde(default, rename = "codegenUnits")]
+    codegen_units: Option<u32>,
+    #[serde(default)]
+    jobs: Option<u32>,
+    #[serde(default, rename = "debugArtifacts")]
+    debug_artifacts: bool,
+    #[serde(default, rename = "separateDepsLog")]
+    separate_deps_log: bool,
+    /// Shows paths within the project relative to it
+    #[serde(default, rename = "normalizePaths")]
+    normalize_paths: bool,
+    #[serde(default, rename = "usePrelude")]
+    use_prelude: bool,
+    #[serde(default)]
+    linker: Option<String>,
+    #[serde(default)]
+    incremental: bool,
+    #[serde(default)]
+    session: Option<String>,
+    #[serde(default, rename = "timePasses")]
+    time_passes: bool,
+    #[serde(default, rename = "denyLintGroups")]
+    deny_lint_groups: Vec<String>,
+    #[serde(default, rename = "withClippy")]
+    with_clippy: bool,
+    #[serde(default, rename = "detectUnsafe")]
+    detect_unsafe: bool,
+    #[serde(default, rename = "commandLine")]
+    command_line: bool,
+    #[serde(default, rename = "toolchainVersion")]
+    toolchain_version: Option<String>,
+    code: String,
+    /// Only set for multipart responses, which carry the artifact
+    #[serde(skip)]
+    artifact: bool,
+}
+
+impl CompileRequest {
+    // The frontend always sends its current assembly settings, so
+    // these fields are accepted with any target.
+    fn ignored_field_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        if self.target != "asm" {
+            let assembly_fields = [
+                ("assemblyFlavor", &self.assembly_flavor),
+                ("demangleAssembly", &self.demangle_assembly),
+                ("processAssembly", &self.process_assembly),
+            ];
+
+            for (name, value) in &assembly_fields {
+                if value.is_some() {
+                    warnings.push(format!("{} ignored for non-asm target", name));
+                }
+            }
+        }
+
+        if self.target != "mir" && self.mir_stage.is_some() {
+            warnings.push("mirStage ignored for non-mir target".to_string());
+        }
+
+        if self.target != "mir" && self.mir_phase.is_some() {
+            warnings.push("mirPhase ignored for non-mir target".to_string());
+        }
+
+        warnings
+    }
+}
+
+/// Each of the `crateTypes` replaces the `crateType` of the request
+#[derive(Debug, Clone, Deserialize)]
+struct CompileCrateTypesRequest {
+    #[serde(rename = "crateTypes")]
+    crate_types: Vec<String>,
+    #[serde(flatten)]
+    request: CompileRequest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompileCrateTypesResponse {
+    /// Keyed by the crate types as they were requested
+    results: BTreeMap<String, CompileResponse>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompileResponse {
+    success: bool,
+    #[serde(rename = "errorCategory", skip_serializing_if = "Option::is_none")]
+    error_category: Option<ErrorCategory>,
+    /// What went wrong when the compiler crashed; the full report
+    /// stays in `stderr`
+    #[serde(rename = "errorSummary", skip_serializing_if = "Option::is_none")]
+    error_summary: Option<String>,
+    code: String,
+    stdout: String,
+    stderr: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    flavors: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    suggestions: Vec<Suggestion>,
+    /// Cargo's JSON messages, one per line
+    #[serde(rename = "rawDiagnostics", skip_serializing_if = "Option::is_none")]
+    raw_diagnostics: Option<String>,
+    #[serde(rename = "buildMessages", skip_serializing_if = "Option::is_none")]
+    build_messages: Option<BuildMessages>,
+    #[serde(rename = "diagnosticSummary", skip_serializing_if = "Option::is_none")]
+    diagnostic_summary: Option<DiagnosticSummary>,
+    #[serde(rename = "diagnosticGroups", skip_serializing_if = "Option::is_none")]
+    diagnostic_groups: Option<DiagnosticGroups>,
+    #[serde(rename = "instructionCounts", skip_serializing_if = "Option::is_none")]
+    instruction_counts: Option<Vec<InstructionCount>>,
+    #[serde(rename = "wasmSections", skip_serializing_if = "Option::is_none")]
+    wasm_sections: Option<Vec<WasmSection>>,
+    #[serde(rename = "buildInfo")]
+    build_info: BuildInfo,
+    #[serde(rename = "debugArtifacts", skip_serializing_if = "Option::is_none")]
+    debug_artifacts: Option<DebugArtifacts>,
+    /// Problems with the request that didn't stop it from running
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<String>,
+    #[serde(rename = "depsLog", skip_serializing_if = "Option::is_none")]
+    deps_log: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passes: Option<Vec<Pass>>,
+    /// The code was too large to return in full
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    truncated: bool,
+    #[serde(rename = "unsafeStats", skip_serializing_if = "Option::is_none")]
+    unsafe_stats: Option<UnsafeStats>,
+    /// The Cargo invocation that was run, without the container around it
+    #[serde(rename = "commandLine", skip_serializing_if = "Option::is_none")]
+    command_line: Option<String>,
+    /// The incremental session to send with the next request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+    /// Sent as a part of its own in multipart responses
+    #[serde(skip)]
+    artifact: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ErrorCategory {
+    CompilerIce,
+    RecursionLimit,
+    ThreadLimit,
+}
+
+/// Only the user's code is scanned; dependencies are not included
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UnsafeStats {
+    user_unsafe: u32,
+    blocks: u32,
+    functions: u32,
+    impls: u32,
+    traits: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Pass {
+    name: String,
+    duration_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DebugArtifacts {
+    token: String,
+    url: String,
+}
+
+impl DebugArtifacts {
+    fn new(token: String) -> Self {
+        let url = format!("/meta/debug-artifacts/{}", token);
+        DebugArtifacts { token, url }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Suggestion {
+    span: SuggestionSpan,
+    replacement: String,
+    applicability: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SuggestionSpan {
+    line_start: u32,
+    column_start: u32,
+    line_end: u32,
+    column_end: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BuildMessages {
+    artifacts: Vec<Artifact>,
+    /// Missing when Cargo doesn't report it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    succeeded: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DiagnosticSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    first_error: Option<String>,
+    errors: usize,
+    warnings: usize,
+    last_lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DiagnosticGroups {
+    errors: Vec<GroupedDiagnostic>,
+    warnings: Vec<GroupedDiagnostic>,
+    notes: Vec<GroupedDiagnostic>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct GroupedDiagnostic {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<SuggestionSpan>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rendered: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Artifact {
+    target: String,
+    kinds: Vec<String>,
+    filenames: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    executable: Option<String>,
+    fresh: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct InstructionCount {
+    function: String,
+    count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct WasmSection {
+    name: String,
+    bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BuildInfo {
+    resolver: &'static str,
+    profile: &'static str,
+    edition: &'static str,
+    channel: &'static str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExecuteRequest {
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
+    #[serde(default)]
+    deterministic: bool,
+    /// Seeds the program's random numbers when `deterministic` is
+    /// set. A string, as JavaScript numbers cannot hold every `u64`
+    #[serde(default)]
+    seed: Option<String>,
+    #[serde(default, rename = "stackSizeMb")]
+    stack_size_mb: Option<u32>,
+    #[serde(default, rename = "separateDepsLog")]
+    separate_deps_log: bool,
+    /// Removes Cargo's progress messages, such as `Compiling` and
+    /// `Finished`, from stderr
+    #[serde(default, rename = "stripCargoNoise")]
+    strip_cargo_noise: bool,
+    #[serde(default, rename = "usePrelude")]
+    use_prelude: bool,
+    #[serde(default)]
+    linker: Option<String>,
+    #[serde(default)]
+    incremental: bool,
+    #[serde(default)]
+    session: Option<String>,
+    #[serde(default)]
+    tz: Option<String>,
+    #[serde(default)]
+    lang: Option<String>,
+    #[serde(default)]
+    coverage: bool,
+    #[serde(default, rename = "staticTarget")]
+    static_target: bool,
+    #[serde(default)]
+    sanitizer: Option<String>,
+    #[serde(default)]
+    trace: bool,
+    #[serde(default)]
+    profile: bool,
+    #[serde(default, rename = "memoryReport")]
+    memory_report: bool,
+    #[serde(default, rename = "toolchainVersion")]
+    toolchain_version: Option<String>,
+    #[serde(default, rename = "requestId")]
+    request_id: Option<String>,
+    #[serde(default, rename = "detectUnsafe")]
+    detect_unsafe: bool,
+    #[serde(default)]
+    env: Vec<EnvironmentVariable>,
+    code: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EnvironmentVariable {
+    name: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExecuteResponse {
+    success: bool,
+    /// Whether the code built, so that failing to build can be told
+    /// apart from failing when run
+    compiled: bool,
+    #[serde(rename = "testsPassed", skip_serializing_if = "Option::is_none")]
+    tests_passed: Option<bool>,
+    #[serde(rename = "errorCategory", skip_serializing_if = "Option::is_none")]
+    error_category: Option<ErrorCategory>,
+    /// What went wrong when the compiler crashed; the full report
+    /// stays in `stderr`
+    #[serde(rename = "errorSummary", skip_serializing_if = "Option::is_none")]
+    error_summary: Option<String>,
+    stdout: String,
+    stderr: String,
+    #[serde(rename = "depsLog", skip_serializing_if = "Option::is_none")]
+    deps_log: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    coverage: Option<Vec<LineCoverage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    syscalls: Option<Vec<Syscall>>,
+    /// Folded stacks, ready for flame graph tools
+    #[serde(skip_serializing_if = "Option::is_none")]
+    profile: Option<String>,
+    #[serde(rename = "peakRssKb", skip_serializing_if = "Option::is_none")]
+    peak_rss_kb: Option<u64>,
+    #[serde(rename = "unsafeStats", skip_serializing_if = "Option::is_none")]
+    unsafe_stats: Option<UnsafeStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backtrace: Option<Vec<BacktraceFrame>>,
+    /// The incremental session to send with the next request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BacktraceFrame {
+    frame: u32,
+    symbol: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LineCoverage {
+    line: u32,
+    hits: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Syscall {
+    name: String,
+    calls: u64,
+    errors: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExecuteCancelRequest {
+    #[serde(rename = "requestId")]
+    request_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExecuteCancelResponse {
+    /// A request with the id was running
+    cancelled: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BuildRunResponse {
+    /// The last phase that ran; the program only runs if it compiles
+    phase: BuildRunPhase,
+    success: bool,
+    compile: BuildRunCompile,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<ExecuteResponse>,
+    #[serde(rename = "unsafeStats", skip_serializing_if = "Option::is_none")]
+    unsafe_stats: Option<UnsafeStats>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BuildRunPhase {
+    Compile,
+    Run,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct BuildRunCompile {
+    success: bool,
+    stdout: String,
+    stderr: String,
+    #[serde(rename = "depsLog", skip_serializing_if = "Option::is_none")]
+    deps_log: Option<String>,
+}
+
+// Browsers and proxies commonly reject URLs longer than this
+const MAX_EXECUTE_QUERY_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ExecuteRepeatRequest {
+    times: u32,
+    #[serde(flatten)]
+    request: ExecuteRequest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExecuteRepeatResponse {
+    runs: Vec<ExecuteRepeatRun>,
+    passed: usize,
+    failed: usize,
+}
+
+/// The program can read the seed from `PLAYGROUND_SEED`
+#[derive(Debug, Clone, Serialize)]
+struct ExecuteRepeatRun {
+    // JavaScript numbers cannot hold every `u64`
+    #[serde(serialize_with = "serialize_as_string")]
+    seed: u64,
+    #[serde(flatten)]
+    response: ExecuteResponse,
+}
+
+fn serialize_as_string<S>(value: &u64, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_str(value)
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct EditionDiffRequest {
+    #[serde(default = "default_edition_diff")]
+    editions: (String, String),
+    #[serde(flatten)]
+    request: ExecuteRequest,
+}
+
+fn default_edition_diff() -> (String, String) {
+    ("2015".to_string(), "2018".to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EditionDiffResponse {
+    runs: Vec<EditionDiffRun>,
+    /// The editions disagree on whether the code works or on what it
+    /// prints; Cargo's own output is not compared.
+    divergent: bool,
+    summary: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct EditionDiffRun {
+    edition: String,
+    #[serde(flatten)]
+    response: ExecuteResponse,
+}
+
+// Every attempt compiles and runs the code, so this bounds the total
+// time spent on a single request. The original code is run once more
+// to check that it fails.
+const MAX_MINIMIZE_ATTEMPTS: u32 = 25;
+
+/// The predicate is text, such as `error[E0277]`, that the standard
+/// error of the failing code must keep containing.
+#[derive(Debug, Clone, Deserialize)]
+struct MinimizeRequest {
+    predicate: String,
+    #[serde(flatten)]
+    request: ExecuteRequest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MinimizeResponse {
+    code: String,
+    attempts: u32,
+    finished: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FormatRequest {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default, rename = "lineMap")]
+    line_map: bool,
+    /// Formatted instead of `code`
+    #[serde(default)]
+    files: Vec<FormatFile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FormatFile {
+    name: String,
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FormatResponse {
+    success: bool,
+    code: String,
+    stdout: String,
+    stderr: String,
+    /// The formatted line number of each original line
+    #[serde(rename = "lineMap", skip_serializing_if = "Option::is_none")]
+    line_map: Option<Vec<u32>>,
+    /// Only when files were requested; `success` is then whether all of
+    /// them were formatted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<Vec<FormattedFile>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FormattedFile {
+    name: String,
+    success: bool,
+    code: String,
+    stdout: String,
+    stderr: String,
+    #[serde(rename = "lineMap", skip_serializing_if = "Option::is_none")]
+    line_map: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FormatCheckResponse {
+    formatted: bool,
+    diff: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ClippyRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+    #[serde(default)]
+    patch: bool,
+    /// Shows paths within the project relative to it
+    #[serde(default, rename = "normalizePaths")]
+    normalize_paths: bool,
+    /// Leaves out the warnings about dependencies
+    #[serde(default, rename = "userCodeOnly")]
+    user_code_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClippyResponse {
+    success: bool,
+    stdout: String,
+    stderr: String,
+    /// A unified diff that applies every machine-applicable suggestion
+    #[serde(skip_serializing_if = "Option::is_none")]
+    patch: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CompatRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CheckRequest {
+    code: String,
+    #[serde(default = "default_channel")]
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+/// A `progress` event of a streamed compilation
+#[derive(Debug, Clone, Serialize)]
+struct CompileProgress {
+    /// The crate that was built
+    target: String,
+    fresh: bool,
+}
+
+/// One line of the response to `/check`
+#[derive(Debug, Clone, Serialize)]
+struct CheckPart<T> {
+    part: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CheckCompileResponse {
+    success: bool,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompilesResponse {
+    compiles: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompatResponse {
+    stable: bool,
+    beta: bool,
+    nightly: bool,
+    channels: CompatChannels,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompatChannels {
+    stable: CompatChannel,
+    beta: CompatChannel,
+    nightly: CompatChannel,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CompatChannel {
+    success: bool,
+    version: String,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MiriRequest {
+    code: String,
+    #[serde(default)]
+    edition: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct DocJsonRequest {
+    code: String,
+    channel: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(default = "default_crate_type", rename = "crateType")]
+    crate_type: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DocJsonResponse {
+    success: bool,
+    stdout: String,
+    stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root_items: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    doc: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MiriResponse {
+    success: bool,
+    stdout: String,
+    stderr: String,
+    errors: Vec<MiriError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MiriError {
+    kind: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span: Option<MiriSpan>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MiriSpan {
+    file: String,
+    line: u32,
+    column: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CrateInformation {
+    name: String,
+    version: String,
+    id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaCratesResponse {
+    crates: Vec<CrateInformation>,
+}
+
+/// The version is the one in `Cargo.lock`, with any build metadata
+#[derive(Debug, Clone, Serialize)]
+struct CrateVersion {
+    name: String,
+    id: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaCrateVersionsResponse {
+    crates: Vec<CrateVersion>,
+}
+
+/// The nodes' ids are their positions in `nodes`
+#[derive(Debug, Clone, Serialize)]
+struct TreeJsonResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root: Option<usize>,
+    nodes: Vec<TreeNode>,
+    edges: Vec<TreeEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TreeNode {
+    id: usize,
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct TreeEdge {
+    from: usize,
+    to: usize,
+    kind: DependencyKind,
+}
+
+#[derive(Debug, Copy, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum DependencyKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaClippyLintsResponse {
+    lints: Vec<ClippyLint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClippyLint {
+    name: String,
+    level: String,
+    groups: Vec<String>,
+    description: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MetaValidateDepsRequest {
+    /// The entries of a `[dependencies]` table
+    #[serde(default)]
+    dependencies: Option<String>,
+    /// A `Cargo.lock` whose crate's dependencies are used instead
+    #[serde(default)]
+    lockfile: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaValidateDepsResponse {
+    success: bool,
+    resolved: Vec<ResolvedDependency>,
+    errors: Vec<DependencyError>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ResolvedDependency {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DependencyError {
+    name: String,
+    error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaDefaultSnippetResponse {
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaExamplesResponse {
+    categories: Vec<ExampleCategory>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ExampleCategory {
+    name: String,
+    examples: Vec<Example>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Example {
+    title: String,
+    code: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaNightlyFlagsResponse {
+    flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MetaCircuitBreakerResponse {
+    state: &'static str,
+    consecutive_failures: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AdminRoutesResponse {
+    routes: Vec<RouteInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RouteInfo {
+    path: String,
+    methods: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AdminRecentFailuresResponse {
+    failures: Vec<RecentFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RecentFailure {
+    category: &'static str,
+    /// Seconds since the Unix epoch
+    timestamp: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaVersionResponse {
+    version: String,
+    hash: String,
+    date: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaCapabilitiesResponse {
+    stable: MetaChannelCapabilities,
+    beta: MetaChannelCapabilities,
+    nightly: MetaChannelCapabilities,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MetaChannelCapabilities {
+    components: Vec<String>,
+    targets: Vec<String>,
+    toolchains: Vec<String>,
+    features: Vec<String>,
+}
+
+/// Whether each of `TOOLCHAIN_COMPONENTS` is installed
+#[derive(Debug, Clone, Serialize)]
+struct MetaComponentsResponse {
+    stable: BTreeMap<&'static str, bool>,
+    beta: BTreeMap<&'static str, bool>,
+    nightly: BTreeMap<&'static str, bool>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct ExecuteRequest {
-    channel: String,
-    mode: String,
-    #[serde(default)]
-    edition: String,
-    #[serde(rename = "crateType")]
-    crate_type: String,
-    tests: bool,
-    #[serde(default)]
-    backtrace: bool,
+struct MetaHashRequest {
     code: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct ExecuteResponse {
-    success: bool,
-    stdout: String,
-    stderr: String,
+struct MetaHashResponse {
+    hash: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct FormatRequest {
+struct MetaMetricsRequest {
     code: String,
-    #[serde(default)]
-    edition: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct FormatResponse {
-    success: bool,
-    code: String,
-    stdout: String,
-    stderr: String,
+#[serde(rename_all = "camelCase")]
+struct MetaMetricsResponse {
+    lines: usize,
+    code_lines: usize,
+    tokens: usize,
+    fns: usize,
+    structs: usize,
+    impls: usize,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct ClippyRequest {
+struct MetaHasMainRequest {
     code: String,
-    #[serde(default)]
-    edition: String,
-    #[serde(default = "default_crate_type", rename = "crateType")]
-    crate_type: String,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct ClippyResponse {
-    success: bool,
-    stdout: String,
-    stderr: String,
+#[serde(rename_all = "camelCase")]
+struct MetaHasMainResponse {
+    has_main: bool,
+    no_main: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct MiriRequest {
+struct MetaGistCreateRequest {
     code: String,
-    #[serde(default)]
-    edition: String,
+    #[serde(flatten)]
+    metadata: GistMetadata,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct MiriResponse {
-    success: bool,
-    stdout: String,
-    stderr: String,
+struct MetaGistResponse {
+    id: String,
+    url: String,
+    code: String,
+    #[serde(flatten)]
+    metadata: GistMetadata,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct CrateInformation {
-    name: String,
-    version: String,
-    id: String,
+/// The configuration of the run that the gist was created from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct GistMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    channel: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    edition: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct MetaCratesResponse {
-    crates: Vec<CrateInformation>,
+impl GistMetadata {
+    fn is_empty(&self) -> bool {
+        *self == GistMetadata::default()
+    }
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct MetaVersionResponse {
-    version: String,
-    hash: String,
-    date: String,
+/// Everything needed to reproduce a run apart from the code itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PermalinkOptions {
+    target: String,
+    #[serde(rename = "assemblyFlavor")]
+    assembly_flavor: Option<String>,
+    #[serde(rename = "demangleAssembly")]
+    demangle_assembly: Option<String>,
+    #[serde(rename = "processAssembly")]
+    process_assembly: Option<String>,
+    #[serde(default, rename = "mirStage")]
+    mir_stage: Option<String>,
+    /// Shows the MIR right after this pass instead
+    #[serde(default, rename = "mirPhase")]
+    mir_phase: Option<String>,
+    channel: String,
+    mode: String,
+    #[serde(default)]
+    edition: String,
+    #[serde(rename = "crateType")]
+    crate_type: String,
+    tests: bool,
+    #[serde(default)]
+    backtrace: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct MetaGistCreateRequest {
+struct MetaPermalinkRequest {
     code: String,
+    #[serde(flatten)]
+    options: PermalinkOptions,
 }
 
 #[derive(Debug, Clone, Serialize)]
-struct MetaGistResponse {
+struct MetaPermalinkResponse {
     id: String,
     url: String,
     code: String,
+    options: PermalinkOptions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RpcRequest {
+    method: String,
+    #[serde(default)]
+    params: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+enum RpcResponse {
+    Result(serde_json::Value),
+    Error(RpcError),
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RpcError {
+    code: i32,
+    message: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -699,7 +3718,17 @@ impl TryFrom<CompileRequest> for sandbox::CompileRequest {
             _ => target,
         };
 
-        Ok(sandbox::CompileRequest {
+        let target = match (target, me.mir_stage, me.mir_phase) {
+            (sandbox::CompileTarget::Mir(_), Some(_), Some(_)) =>
+                IncompatibleOptions { conflict: "a MIR stage and a MIR phase cannot both be chosen" }.fail()?,
+            (sandbox::CompileTarget::Mir(_), Some(stage), None) =>
+                sandbox::CompileTarget::Mir(Some(parse_mir_stage(&stage)?)),
+            (sandbox::CompileTarget::Mir(_), None, Some(phase)) =>
+                sandbox::CompileTarget::Mir(Some(parse_mir_phase(&phase)?)),
+            _ => target,
+        };
+
+        let req = sandbox::CompileRequest {
             target,
             channel: parse_channel(&me.channel)?,
             mode: parse_mode(&me.mode)?,
@@ -707,18 +3736,237 @@ impl TryFrom<CompileRequest> for sandbox::CompileRequest {
             crate_type: parse_crate_type(&me.crate_type)?,
             tests: me.tests,
             backtrace: me.backtrace,
-            code: me.code,
-        })
+            all_flavors: me.all_flavors,
+            suggestions: me.suggestions,
+            raw_diagnostics: me.raw_diagnostics,
+            build_messages: me.build_messages,
+            diagnostic_summary: me.diagnostic_summary,
+            diagnostic_groups: me.diagnostic_groups,
+            diagnostic_format: parse_diagnostic_format(me.diagnostic_format.as_deref())?,
+            instruction_counts: me.instruction_counts,
+            wasm_sections: me.wasm_sections,
+            debug_artifacts: parse_debug_artifacts(me.debug_artifacts)?,
+            artifact: me.artifact,
+            linker: parse_linker(me.linker.as_deref())?,
+            incremental_session: parse_incremental_session(me.incremental, me.session)?,
+            time_passes: me.time_passes,
+            deny_lint_groups: parse_lint_groups(me.deny_lint_groups)?,
+            with_clippy: me.with_clippy,
+            codegen_units: me.codegen_units,
+            jobs: me.jobs,
+            command_line: me.command_line,
+            toolchain_version: parse_toolchain_version(me.toolchain_version)?,
+            code: parse_source(me.code)?,
+        };
+
+        validate_compile_options(&req)?;
+
+        Ok(req)
+    }
+}
+
+// Rejects combinations that are known to fail in confusing ways
+// inside the sandbox.
+fn validate_compile_options(req: &sandbox::CompileRequest) -> Result<()> {
+    use crate::sandbox::{CompileTarget::*, CrateType::*, JsonMessagesRequest, LibraryType::*};
+
+    match (req.target, req.all_flavors) {
+        (Assembly(..), _) | (_, false) => {}
+        (_, true) =>
+            IncompatibleOptions { conflict: "all assembly flavors can only be requested for the assembly target" }.fail()?,
+    }
+
+    match (req.target, req.instruction_counts) {
+        (Assembly(..), _) | (_, false) => {}
+        (_, true) =>
+            IncompatibleOptions { conflict: "instruction counts can only be requested for the assembly target" }.fail()?,
+    }
+
+    match (req.diagnostic_format, req.wants_json_messages()) {
+        (sandbox::DiagnosticFormat::Human, _) | (_, false) => {}
+        (_, true) =>
+            IncompatibleOptions { conflict: "a diagnostic format cannot be chosen along with structured diagnostics" }.fail()?,
+    }
+
+    match (req.target, req.wasm_sections) {
+        (Wasm, _) | (_, false) => {}
+        (_, true) =>
+            IncompatibleOptions { conflict: "wasm sections can only be requested for the wasm target" }.fail()?,
+    }
+
+    match req.target {
+        Mir(Some(_)) if req.channel != sandbox::Channel::Nightly =>
+            IncompatibleOptions { conflict: "a MIR stage or phase can only be chosen on the nightly channel" }.fail()?,
+        _ => {}
+    }
+
+    match (req.time_passes, req.channel, req.all_flavors) {
+        (false, _, _) | (true, sandbox::Channel::Nightly, false) => {}
+        (true, sandbox::Channel::Nightly, true) =>
+            IncompatibleOptions { conflict: "pass timings cannot be collected for all assembly flavors" }.fail()?,
+        (true, _, _) =>
+            IncompatibleOptions { conflict: "pass timings can only be collected on the nightly channel" }.fail()?,
+    }
+
+    match (req.target, req.crate_type, req.tests) {
+        (Assembly(..), Library(ProcMacro), _) =>
+            IncompatibleOptions { conflict: "assembly cannot be generated for a proc-macro crate" }.fail(),
+        (Wasm, Library(ProcMacro), _) =>
+            IncompatibleOptions { conflict: "a proc-macro crate cannot be compiled to WebAssembly" }.fail(),
+        (Wasm, _, true) =>
+            IncompatibleOptions { conflict: "tests cannot be compiled to WebAssembly" }.fail(),
+        _ => Ok(()),
     }
 }
 
 impl From<sandbox::CompileResponse> for CompileResponse {
     fn from(me: sandbox::CompileResponse) -> Self {
+        let flavors = me.flavors
+            .into_iter()
+            .map(|(flavor, code)| (assembly_flavor_name(flavor).to_string(), code))
+            .collect();
+
         CompileResponse {
             success: me.success,
+            error_category: me.error_category.map(Into::into),
+            error_summary: error_summary(me.error_category, &me.stderr),
             code: me.code,
             stdout: me.stdout,
             stderr: me.stderr,
+            flavors,
+            suggestions: me.suggestions.into_iter().map(Into::into).collect(),
+            raw_diagnostics: me.raw_diagnostics,
+            build_messages: me.build_messages.map(Into::into),
+            diagnostic_summary: me.diagnostic_summary.map(Into::into),
+            diagnostic_groups: me.diagnostic_groups.map(Into::into),
+            instruction_counts: me.instruction_counts.map(|counts| counts.into_iter().map(Into::into).collect()),
+            wasm_sections: me.wasm_sections.map(|sections| sections.into_iter().map(Into::into).collect()),
+            build_info: me.build_info.into(),
+            debug_artifacts: None,
+            warnings: Vec::new(),
+            deps_log: None,
+            passes: me.passes.map(|passes| passes.into_iter().map(Into::into).collect()),
+            truncated: me.truncated,
+            unsafe_stats: None,
+            command_line: me.command_line,
+            session: None,
+            artifact: me.artifact,
+        }
+    }
+}
+
+impl From<sandbox::ErrorCategory> for ErrorCategory {
+    fn from(me: sandbox::ErrorCategory) -> Self {
+        match me {
+            sandbox::ErrorCategory::CompilerIce => ErrorCategory::CompilerIce,
+            sandbox::ErrorCategory::RecursionLimit => ErrorCategory::RecursionLimit,
+            sandbox::ErrorCategory::ThreadLimit => ErrorCategory::ThreadLimit,
+        }
+    }
+}
+
+impl From<metrics::UnsafeUsage> for UnsafeStats {
+    fn from(me: metrics::UnsafeUsage) -> Self {
+        UnsafeStats {
+            user_unsafe: me.total(),
+            blocks: me.blocks,
+            functions: me.functions,
+            impls: me.impls,
+            traits: me.traits,
+        }
+    }
+}
+
+impl From<recent_failures::Failure> for RecentFailure {
+    fn from(me: recent_failures::Failure) -> Self {
+        let timestamp = me.at.duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        RecentFailure { category: me.category.name(), timestamp }
+    }
+}
+
+impl From<time_passes::Pass> for Pass {
+    fn from(me: time_passes::Pass) -> Self {
+        Pass { name: me.name, duration_ms: me.duration_ms }
+    }
+}
+
+impl From<diagnostics::BuildMessages> for BuildMessages {
+    fn from(me: diagnostics::BuildMessages) -> Self {
+        BuildMessages {
+            artifacts: me.artifacts.into_iter().map(Into::into).collect(),
+            succeeded: me.succeeded,
+        }
+    }
+}
+
+impl From<diagnostics::Summary> for DiagnosticSummary {
+    fn from(me: diagnostics::Summary) -> Self {
+        let diagnostics::Summary { first_error, errors, warnings, last_lines } = me;
+        DiagnosticSummary { first_error, errors, warnings, last_lines }
+    }
+}
+
+impl From<diagnostics::Groups> for DiagnosticGroups {
+    fn from(me: diagnostics::Groups) -> Self {
+        let group = |messages: Vec<diagnostics::Message>| messages.into_iter().map(Into::into).collect();
+        DiagnosticGroups { errors: group(me.errors), warnings: group(me.warnings), notes: group(me.notes) }
+    }
+}
+
+impl From<diagnostics::Message> for GroupedDiagnostic {
+    fn from(me: diagnostics::Message) -> Self {
+        let span = me.span.map(|diagnostics::Span { line_start, column_start, line_end, column_end }| {
+            SuggestionSpan { line_start, column_start, line_end, column_end }
+        });
+
+        GroupedDiagnostic { message: me.message, code: me.code, span, rendered: me.rendered }
+    }
+}
+
+impl From<diagnostics::Artifact> for Artifact {
+    fn from(me: diagnostics::Artifact) -> Self {
+        let diagnostics::Artifact { target, kinds, filenames, executable, fresh } = me;
+        Artifact { target, kinds, filenames, executable, fresh }
+    }
+}
+
+impl From<diagnostics::Suggestion> for Suggestion {
+    fn from(me: diagnostics::Suggestion) -> Self {
+        let diagnostics::Span { line_start, column_start, line_end, column_end } = me.span;
+
+        Suggestion {
+            span: SuggestionSpan { line_start, column_start, line_end, column_end },
+            replacement: me.replacement,
+            applicability: me.applicability,
+        }
+    }
+}
+
+impl From<asm_cleanup::InstructionCount> for InstructionCount {
+    fn from(me: asm_cleanup::InstructionCount) -> Self {
+        InstructionCount { function: me.function, count: me.count }
+    }
+}
+
+impl From<wasm_sections::Section> for WasmSection {
+    fn from(me: wasm_sections::Section) -> Self {
+        WasmSection { name: me.name, bytes: me.bytes }
+    }
+}
+
+impl From<&routes::Route> for RouteInfo {
+    fn from(me: &routes::Route) -> Self {
+        RouteInfo { path: me.path.clone(), methods: me.methods.iter().map(ToString::to_string).collect() }
+    }
+}
+
+impl From<sandbox::BuildInfo> for BuildInfo {
+    fn from(me: sandbox::BuildInfo) -> Self {
+        BuildInfo {
+            resolver: me.resolver,
+            profile: me.profile,
+            edition: edition_name(me.edition),
+            channel: channel_name(me.channel),
         }
     }
 }
@@ -727,15 +3975,123 @@ impl TryFrom<ExecuteRequest> for sandbox::ExecuteRequest {
     type Error = Error;
 
     fn try_from(me: ExecuteRequest) -> Result<Self> {
-        Ok(sandbox::ExecuteRequest {
+        let req = sandbox::ExecuteRequest {
             channel: parse_channel(&me.channel)?,
             mode: parse_mode(&me.mode)?,
             edition: parse_edition(&me.edition)?,
             crate_type: parse_crate_type(&me.crate_type)?,
             tests: me.tests,
             backtrace: me.backtrace,
-            code: me.code,
-        })
+            deterministic: me.deterministic,
+            stack_size_mb: me.stack_size_mb,
+            linker: parse_linker(me.linker.as_deref())?,
+            incremental_session: parse_incremental_session(me.incremental, me.session)?,
+            seed: parse_seed(me.seed)?,
+            timezone: parse_timezone(me.tz)?,
+            locale: parse_locale(me.lang)?,
+            coverage: me.coverage,
+            static_target: me.static_target,
+            sanitizer: parse_sanitizer(me.sanitizer.as_deref())?,
+            trace: parse_trace(me.trace)?,
+            profile: parse_profile(me.profile)?,
+            memory_report: me.memory_report,
+            toolchain_version: parse_toolchain_version(me.toolchain_version)?,
+            request_id: parse_request_id(me.request_id)?,
+            log_request_id: request_ids_logged(),
+            env: parse_environment(me.env)?,
+            code: parse_source(me.code)?,
+        };
+
+        validate_execute_options(&req)?;
+
+        Ok(req)
+    }
+}
+
+fn validate_execute_options(req: &sandbox::ExecuteRequest) -> Result<()> {
+    match (req.coverage, req.tests, req.channel) {
+        (false, _, _) | (true, true, sandbox::Channel::Nightly) => {}
+        (true, false, _) =>
+            IncompatibleOptions { conflict: "coverage can only be collected when running tests" }.fail()?,
+        (true, true, _) =>
+            IncompatibleOptions { conflict: "coverage can only be collected on the nightly channel" }.fail()?,
+    }
+
+    match (req.sanitizer, req.channel, req.static_target, req.coverage) {
+        (None, _, _, _) | (Some(_), sandbox::Channel::Nightly, false, false) => {}
+        (Some(_), sandbox::Channel::Nightly, true, _) =>
+            IncompatibleOptions { conflict: "sanitizers cannot be used with the static target" }.fail()?,
+        (Some(_), sandbox::Channel::Nightly, _, true) =>
+            IncompatibleOptions { conflict: "sanitizers cannot be used while collecting coverage" }.fail()?,
+        (Some(_), _, _, _) =>
+            IncompatibleOptions { conflict: "sanitizers are only available on the nightly channel" }.fail()?,
+    }
+
+    // Only one runner can take the place of Cargo's, and it only does
+    // for the host target. Coverage and sanitizers need the program to
+    // themselves.
+    let runners = Runner::chosen(req);
+    if let Some(runner) = runners.first() {
+        let others = [
+            (req.coverage, "collecting coverage"),
+            (req.sanitizer.is_some(), "a sanitizer"),
+            (req.static_target, "the static target"),
+        ];
+        let other = runners
+            .get(1)
+            .map(|other| other.description())
+            .or_else(|| others.iter().find(|&&(chosen, _)| chosen).map(|&(_, name)| name));
+
+        if let Some(other) = other {
+            IncompatibleOptions { conflict: format!("{} cannot be combined with {}", runner.description(), other) }.fail()?;
+        }
+    }
+
+    match (req.static_target, req.coverage, req.linker) {
+        (false, _, _) => Ok(()),
+        (true, true, _) =>
+            IncompatibleOptions { conflict: "coverage cannot be collected for the static target" }.fail(),
+        (true, _, sandbox::Linker::Lld) =>
+            IncompatibleOptions { conflict: "the lld linker cannot be used with the static target" }.fail(),
+        (true, _, sandbox::Linker::Default) => Ok(()),
+    }
+}
+
+/// The ways of running the program that are set as Cargo's runner
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Runner {
+    Deterministic,
+    Trace,
+    Profile,
+    MemoryReport,
+}
+
+impl Runner {
+    fn chosen(req: &sandbox::ExecuteRequest) -> Vec<Runner> {
+        let runners = [
+            (req.deterministic, Runner::Deterministic),
+            (req.trace, Runner::Trace),
+            (req.profile, Runner::Profile),
+            (req.memory_report, Runner::MemoryReport),
+        ];
+
+        runners.iter().filter(|&&(chosen, _)| chosen).map(|&(_, runner)| runner).collect()
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Runner::Deterministic => "deterministic execution",
+            Runner::Trace => "tracing system calls",
+            Runner::Profile => "profiling",
+            Runner::MemoryReport => "reporting memory use",
+        }
+    }
+}
+
+fn error_summary(category: Option<sandbox::ErrorCategory>, stderr: &str) -> Option<String> {
+    match category {
+        Some(sandbox::ErrorCategory::CompilerIce) => sandbox::compiler_ice_summary(stderr),
+        _ => None,
     }
 }
 
@@ -743,9 +4099,89 @@ impl From<sandbox::ExecuteResponse> for ExecuteResponse {
     fn from(me: sandbox::ExecuteResponse) -> Self {
         ExecuteResponse {
             success: me.success,
+            compiled: me.compiled,
+            tests_passed: me.tests_passed,
+            error_category: me.error_category.map(Into::into),
+            error_summary: error_summary(me.error_category, &me.stderr),
             stdout: me.stdout,
             stderr: me.stderr,
+            deps_log: None,
+            coverage: me.coverage.map(|lines| lines.into_iter().map(Into::into).collect()),
+            syscalls: me.syscalls.map(|syscalls| syscalls.into_iter().map(Into::into).collect()),
+            profile: me.profile,
+            peak_rss_kb: me.peak_rss_kb,
+            unsafe_stats: None,
+            backtrace: Some(me.backtrace)
+                .filter(|frames| !frames.is_empty())
+                .map(|frames| frames.into_iter().map(Into::into).collect()),
+            session: None,
+        }
+    }
+}
+
+impl From<backtrace::Frame> for BacktraceFrame {
+    fn from(me: backtrace::Frame) -> Self {
+        BacktraceFrame { frame: me.index, symbol: me.symbol, location: me.location }
+    }
+}
+
+impl From<sandbox::BuildRunResponse> for BuildRunResponse {
+    fn from(me: sandbox::BuildRunResponse) -> Self {
+        let compile = BuildRunCompile {
+            success: me.build.success,
+            stdout: me.build.stdout,
+            stderr: me.build.stderr,
+            deps_log: None,
+        };
+        let run: Option<ExecuteResponse> = me.run.map(Into::into);
+        let (phase, success) = match &run {
+            Some(run) => (BuildRunPhase::Run, run.success),
+            None => (BuildRunPhase::Compile, false),
+        };
+
+        BuildRunResponse {
+            phase,
+            success,
+            compile,
+            run,
+            unsafe_stats: None,
+        }
+    }
+}
+
+impl From<Vec<dependency_check::Resolution>> for MetaValidateDepsResponse {
+    fn from(me: Vec<dependency_check::Resolution>) -> Self {
+        use dependency_check::Resolution;
+
+        let mut resolved = Vec::new();
+        let mut errors = Vec::new();
+
+        for resolution in me {
+            match resolution {
+                Resolution::Resolved { name, version } => resolved.push(ResolvedDependency { name, version }),
+                Resolution::Failed { name, reason } => errors.push(DependencyError { name, error: reason }),
+            }
         }
+
+        MetaValidateDepsResponse { success: errors.is_empty(), resolved, errors }
+    }
+}
+
+impl From<sandbox::Progress> for CompileProgress {
+    fn from(me: sandbox::Progress) -> Self {
+        CompileProgress { target: me.target, fresh: me.fresh }
+    }
+}
+
+impl From<coverage::LineCoverage> for LineCoverage {
+    fn from(me: coverage::LineCoverage) -> Self {
+        LineCoverage { line: me.line, hits: me.hits }
+    }
+}
+
+impl From<strace::Syscall> for Syscall {
+    fn from(me: strace::Syscall) -> Self {
+        Syscall { name: me.name, calls: me.calls, errors: me.errors }
     }
 }
 
@@ -754,12 +4190,35 @@ impl TryFrom<FormatRequest> for sandbox::FormatRequest {
 
     fn try_from(me: FormatRequest) -> Result<Self> {
         Ok(sandbox::FormatRequest {
-            code: me.code,
+            code: parse_source(me.code)?,
             edition: parse_edition(&me.edition)?,
         })
     }
 }
 
+impl From<minimize::Minimized> for MinimizeResponse {
+    fn from(me: minimize::Minimized) -> Self {
+        MinimizeResponse {
+            code: me.code,
+            attempts: me.attempts,
+            finished: me.finished,
+        }
+    }
+}
+
+impl From<metrics::EntryPoint> for MetaHasMainResponse {
+    fn from(me: metrics::EntryPoint) -> Self {
+        MetaHasMainResponse { has_main: me.has_main, no_main: me.no_main }
+    }
+}
+
+impl From<metrics::Metrics> for MetaMetricsResponse {
+    fn from(me: metrics::Metrics) -> Self {
+        let metrics::Metrics { lines, code_lines, tokens, fns, structs, impls } = me;
+        MetaMetricsResponse { lines, code_lines, tokens, fns, structs, impls }
+    }
+}
+
 impl From<sandbox::FormatResponse> for FormatResponse {
     fn from(me: sandbox::FormatResponse) -> Self {
         FormatResponse {
@@ -767,6 +4226,18 @@ impl From<sandbox::FormatResponse> for FormatResponse {
             code: me.code,
             stdout: me.stdout,
             stderr: me.stderr,
+            line_map: None,
+            files: None,
+        }
+    }
+}
+
+impl From<sandbox::FormatCheckResponse> for FormatCheckResponse {
+    fn from(me: sandbox::FormatCheckResponse) -> Self {
+        FormatCheckResponse {
+            formatted: me.formatted,
+            diff: me.diff,
+            stderr: me.stderr,
         }
     }
 }
@@ -776,9 +4247,11 @@ impl TryFrom<ClippyRequest> for sandbox::ClippyRequest {
 
     fn try_from(me: ClippyRequest) -> Result<Self> {
         Ok(sandbox::ClippyRequest {
-            code: me.code,
+            code: parse_source(me.code)?,
             crate_type: parse_crate_type(&me.crate_type)?,
             edition: parse_edition(&me.edition)?,
+            suggestions: me.patch,
+            user_code_only: me.user_code_only,
         })
     }
 }
@@ -789,6 +4262,42 @@ impl From<sandbox::ClippyResponse> for ClippyResponse {
             success: me.success,
             stdout: me.stdout,
             stderr: me.stderr,
+            patch: None,
+        }
+    }
+}
+
+impl TryFrom<DocJsonRequest> for sandbox::DocJsonRequest {
+    type Error = Error;
+
+    fn try_from(me: DocJsonRequest) -> Result<Self> {
+        let channel = parse_channel(&me.channel)?;
+        if channel != sandbox::Channel::Nightly {
+            IncompatibleOptions { conflict: "rustdoc's JSON output is only available on the nightly channel" }.fail()?;
+        }
+
+        Ok(sandbox::DocJsonRequest {
+            code: parse_source(me.code)?,
+            channel,
+            edition: parse_edition(&me.edition)?,
+            crate_type: parse_crate_type(&me.crate_type)?,
+        })
+    }
+}
+
+// The summary is left out for a layout that isn't recognized, but the
+// document itself is still returned
+impl From<sandbox::DocJsonResponse> for DocJsonResponse {
+    fn from(me: sandbox::DocJsonResponse) -> Self {
+        let summary = me.doc.as_ref().and_then(rustdoc_json::summarize);
+
+        DocJsonResponse {
+            success: me.success,
+            stdout: me.stdout,
+            stderr: me.stderr,
+            format_version: summary.as_ref().map(|s| s.format_version),
+            root_items: summary.map(|s| s.root_items),
+            doc: me.doc,
         }
     }
 }
@@ -798,7 +4307,7 @@ impl TryFrom<MiriRequest> for sandbox::MiriRequest {
 
     fn try_from(me: MiriRequest) -> Result<Self> {
         Ok(sandbox::MiriRequest {
-            code: me.code,
+            code: parse_source(me.code)?,
             edition: parse_edition(&me.edition)?,
         })
     }
@@ -810,10 +4319,65 @@ impl From<sandbox::MiriResponse> for MiriResponse {
             success: me.success,
             stdout: me.stdout,
             stderr: me.stderr,
+            errors: me.errors.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<miri_errors::MiriError> for MiriError {
+    fn from(me: miri_errors::MiriError) -> Self {
+        MiriError {
+            kind: me.kind,
+            message: me.message,
+            span: me.span.map(|span| MiriSpan { file: span.file, line: span.line, column: span.column }),
         }
     }
 }
 
+impl From<&[examples::Category]> for MetaExamplesResponse {
+    fn from(me: &[examples::Category]) -> Self {
+        let categories = me
+            .iter()
+            .map(|category| ExampleCategory {
+                name: category.name.clone(),
+                examples: category
+                    .examples
+                    .iter()
+                    .map(|example| Example { title: example.title.clone(), code: example.code.clone() })
+                    .collect(),
+            })
+            .collect();
+
+        MetaExamplesResponse { categories }
+    }
+}
+
+impl From<sandbox::CheckResponse> for CheckCompileResponse {
+    fn from(me: sandbox::CheckResponse) -> Self {
+        CheckCompileResponse { success: me.success, stdout: me.stdout, stderr: me.stderr }
+    }
+}
+
+impl MetaCrateVersionsResponse {
+    // Crates missing from the lockfile keep the version they were
+    // listed with
+    fn new(crates: Vec<sandbox::CrateInformation>, lock: &cargo_lock::Lockfile) -> Self {
+        let crates = crates.into_iter()
+            .map(|krate| match lock.resolve(&krate.name) {
+                Some(locked) => CrateVersion {
+                    name: krate.name,
+                    id: krate.id,
+                    version: locked.version.clone(),
+                    source: locked.source.clone(),
+                },
+                None => CrateVersion { name: krate.name, id: krate.id, version: krate.version, source: None },
+            })
+            .collect();
+
+        MetaCrateVersionsResponse { crates }
+    }
+}
+
 impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
     fn from(me: Vec<sandbox::CrateInformation>) -> Self {
         let crates = me.into_iter()
@@ -826,6 +4390,98 @@ impl From<Vec<sandbox::CrateInformation>> for MetaCratesResponse {
     }
 }
 
+impl From<&crate_graph::CrateGraph> for TreeJsonResponse {
+    fn from(me: &crate_graph::CrateGraph) -> Self {
+        let nodes = me.nodes()
+            .enumerate()
+            .map(|(id, n)| TreeNode { id, name: n.name.clone(), version: n.version.clone() })
+            .collect();
+        let edges = me.edges()
+            .map(|e| TreeEdge { from: e.from, to: e.to, kind: e.kind.into() })
+            .collect();
+
+        TreeJsonResponse { root: me.root(), nodes, edges }
+    }
+}
+
+impl From<crate_graph::DependencyKind> for DependencyKind {
+    fn from(me: crate_graph::DependencyKind) -> Self {
+        match me {
+            crate_graph::DependencyKind::Normal => DependencyKind::Normal,
+            crate_graph::DependencyKind::Development => DependencyKind::Dev,
+            crate_graph::DependencyKind::Build => DependencyKind::Build,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CrateSort {
+    Name,
+    Version,
+}
+
+fn sort_crates(crates: &mut [sandbox::CrateInformation], sort: CrateSort) {
+    match sort {
+        CrateSort::Name => crates.sort_by(|a, b| a.name.cmp(&b.name)),
+        CrateSort::Version => crates.sort_by(|a, b| {
+            version_key(&a.version)
+                .cmp(&version_key(&b.version))
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+    }
+}
+
+// Compares the numeric components of a version so that `0.10.0`
+// sorts after `0.9.0`. Pre-release and build metadata are ignored.
+fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split(|c| c == '-' || c == '+')
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum GraphFormat {
+    Dot,
+    Rendered(sandbox::GraphFormat),
+}
+
+impl GraphFormat {
+    fn mime(&self) -> iron::mime::Mime {
+        let mime = match *self {
+            GraphFormat::Dot => "text/vnd.graphviz",
+            GraphFormat::Rendered(sandbox::GraphFormat::Svg) => "image/svg+xml",
+            GraphFormat::Rendered(sandbox::GraphFormat::Png) => "image/png",
+        };
+        mime.parse().expect("Invalid graph MIME type")
+    }
+
+    fn extension(&self) -> &'static str {
+        match *self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Rendered(sandbox::GraphFormat::Svg) => "svg",
+            GraphFormat::Rendered(sandbox::GraphFormat::Png) => "png",
+        }
+    }
+}
+
+impl From<circuit_breaker::Status> for MetaCircuitBreakerResponse {
+    fn from(me: circuit_breaker::Status) -> Self {
+        use crate::circuit_breaker::State;
+
+        let state = match me.state {
+            State::Closed => "closed",
+            State::Open => "open",
+            State::HalfOpen => "halfOpen",
+        };
+
+        MetaCircuitBreakerResponse { state, consecutive_failures: me.consecutive_failures }
+    }
+}
+
 impl From<sandbox::Version> for MetaVersionResponse {
     fn from(me: sandbox::Version) -> Self {
         MetaVersionResponse {
@@ -836,16 +4492,114 @@ impl From<sandbox::Version> for MetaVersionResponse {
     }
 }
 
+impl From<Vec<clippy_lints::Lint>> for MetaClippyLintsResponse {
+    fn from(me: Vec<clippy_lints::Lint>) -> Self {
+        let lints = me
+            .into_iter()
+            .map(|lint| ClippyLint {
+                name: lint.name,
+                level: lint.level,
+                groups: lint.groups,
+                description: lint.description,
+            })
+            .collect();
+
+        MetaClippyLintsResponse { lints }
+    }
+}
+
+impl From<sandbox::Capabilities> for MetaChannelCapabilities {
+    fn from(mut me: sandbox::Capabilities) -> Self {
+        // As rustup happens to list them
+        me.components.sort();
+        me.targets.sort();
+        me.toolchains.sort();
+        me.features.sort();
+
+        MetaChannelCapabilities {
+            components: me.components,
+            targets: me.targets,
+            toolchains: me.toolchains,
+            features: me.features,
+        }
+    }
+}
+
 impl From<gist::Gist> for MetaGistResponse {
     fn from(me: gist::Gist) -> Self {
+        // Gists created by hand or by older versions may have no
+        // metadata or metadata that doesn't match; neither is an error
+        let metadata = me.metadata
+            .and_then(|metadata| serde_json::from_str(&metadata).ok())
+            .unwrap_or_default();
+
         MetaGistResponse {
             id: me.id,
             url: me.url,
             code: me.code,
+            metadata,
         }
     }
 }
 
+impl TryFrom<MetaPermalinkRequest> for sandbox::CompileRequest {
+    type Error = Error;
+
+    fn try_from(me: MetaPermalinkRequest) -> Result<Self> {
+        let PermalinkOptions {
+            target, assembly_flavor, demangle_assembly, process_assembly, mir_stage, mir_phase,
+            channel, mode, edition, crate_type, tests, backtrace,
+        } = me.options;
+
+        CompileRequest {
+            target, assembly_flavor, demangle_assembly, process_assembly, mir_stage, mir_phase,
+            channel, mode, edition, crate_type, tests, backtrace,
+            all_flavors: false,
+            suggestions: false,
+            raw_diagnostics: false,
+            build_messages: false,
+            diagnostic_summary: None,
+            diagnostic_groups: false,
+            diagnostic_format: None,
+            instruction_counts: false,
+            wasm_sections: false,
+            debug_artifacts: false,
+            separate_deps_log: false,
+            normalize_paths: false,
+            use_prelude: false,
+            linker: None,
+            incremental: false,
+            session: None,
+            time_passes: false,
+            deny_lint_groups: Vec::new(),
+            with_clippy: false,
+            codegen_units: None,
+            jobs: None,
+            detect_unsafe: false,
+            command_line: false,
+            toolchain_version: None,
+            code: me.code,
+            artifact: false,
+        }.try_into()
+    }
+}
+
+impl TryFrom<gist::Gist> for MetaPermalinkResponse {
+    type Error = Error;
+
+    fn try_from(me: gist::Gist) -> Result<Self> {
+        let metadata = me.metadata.ok_or(Error::PermalinkOptionsMissing)?;
+        let options = serde_json::from_str(&metadata).context(PermalinkOptionsInvalid)?;
+
+        Ok(MetaPermalinkResponse {
+            id: me.id,
+            url: me.url,
+            code: me.code,
+            options,
+        })
+    }
+}
+
 impl TryFrom<EvaluateRequest> for sandbox::ExecuteRequest {
     type Error = Error;
 
@@ -857,7 +4611,24 @@ impl TryFrom<EvaluateRequest> for sandbox::ExecuteRequest {
             crate_type: sandbox::CrateType::Binary,
             tests: me.tests,
             backtrace: false,
-            code: me.code,
+            deterministic: false,
+            stack_size_mb: None,
+            linker: sandbox::Linker::Default,
+            incremental_session: None,
+            seed: None,
+            timezone: None,
+            locale: None,
+            coverage: false,
+            static_target: false,
+            sanitizer: None,
+            trace: false,
+            profile: false,
+            memory_report: false,
+            toolchain_version: None,
+            request_id: None,
+            log_request_id: false,
+            env: Vec::new(),
+            code: parse_source(me.code)?,
         })
     }
 }
@@ -888,14 +4659,27 @@ impl From<sandbox::ExecuteResponse> for EvaluateResponse {
     }
 }
 
+// An empty crate fails deep inside the sandbox with an error about a
+// missing `main`, which doesn't point at the real problem.
+fn parse_source(code: String) -> Result<String> {
+    if code.trim().is_empty() {
+        InvalidSource.fail()
+    } else {
+        Ok(code)
+    }
+}
+
 fn parse_target(s: &str) -> Result<sandbox::CompileTarget> {
     Ok(match s {
         "asm" => sandbox::CompileTarget::Assembly(sandbox::AssemblyFlavor::Att,
                                                   sandbox::DemangleAssembly::Demangle,
                                                   sandbox::ProcessAssembly::Filter),
         "llvm-ir" => sandbox::CompileTarget::LlvmIr,
-        "mir" => sandbox::CompileTarget::Mir,
+        "mir" => sandbox::CompileTarget::Mir(None),
         "wasm" => sandbox::CompileTarget::Wasm,
+        "print-cfg" => sandbox::CompileTarget::Cfg,
+        "dep-info" => sandbox::CompileTarget::DepInfo,
+        "print-target-features" => sandbox::CompileTarget::TargetFeatures,
         value => InvalidTarget { value }.fail()?,
     })
 }
@@ -908,6 +4692,13 @@ fn parse_assembly_flavor(s: &str) -> Result<sandbox::AssemblyFlavor> {
     })
 }
 
+fn assembly_flavor_name(flavor: sandbox::AssemblyFlavor) -> &'static str {
+    match flavor {
+        sandbox::AssemblyFlavor::Att => "att",
+        sandbox::AssemblyFlavor::Intel => "intel",
+    }
+}
+
 fn parse_demangle_assembly(s: &str) -> Result<sandbox::DemangleAssembly> {
     Ok(match s {
         "demangle" => sandbox::DemangleAssembly::Demangle,
@@ -919,11 +4710,169 @@ fn parse_demangle_assembly(s: &str) -> Result<sandbox::DemangleAssembly> {
 fn parse_process_assembly(s: &str) -> Result<sandbox::ProcessAssembly> {
     Ok(match s {
         "filter" => sandbox::ProcessAssembly::Filter,
+        "entry-point" => sandbox::ProcessAssembly::EntryPoint,
         "raw" => sandbox::ProcessAssembly::Raw,
         value => InvalidProcessAssembly { value }.fail()?
     })
 }
 
+fn parse_mir_stage(s: &str) -> Result<sandbox::MirStage> {
+    Ok(match s {
+        "built" => sandbox::MirStage::Built,
+        "optimized" => sandbox::MirStage::Optimized,
+        value => InvalidMirStage { value }.fail()?
+    })
+}
+
+fn parse_diagnostic_format(s: Option<&str>) -> Result<sandbox::DiagnosticFormat> {
+    Ok(match s {
+        None | Some("human") => sandbox::DiagnosticFormat::Human,
+        Some("short") => sandbox::DiagnosticFormat::Short,
+        Some("json") => sandbox::DiagnosticFormat::Json,
+        Some(value) => InvalidDiagnosticFormat { value }.fail()?,
+    })
+}
+
+fn parse_mir_phase(s: &str) -> Result<sandbox::MirStage> {
+    match sandbox::MirPass::named(s) {
+        Some(pass) => Ok(sandbox::MirStage::After(pass)),
+        None => InvalidMirPhase { value: s }.fail(),
+    }
+}
+
+fn parse_crate_sort(s: &str) -> Result<CrateSort> {
+    Ok(match s {
+        "name" => CrateSort::Name,
+        "version" => CrateSort::Version,
+        value => InvalidCrateSort { value }.fail()?,
+    })
+}
+
+fn parse_graph_format(s: &str) -> Result<GraphFormat> {
+    Ok(match s {
+        "dot" => GraphFormat::Dot,
+        "svg" => GraphFormat::Rendered(sandbox::GraphFormat::Svg),
+        "png" => GraphFormat::Rendered(sandbox::GraphFormat::Png),
+        value => InvalidGraphFormat { value }.fail()?,
+    })
+}
+
+fn parse_toolchain_version(version: Option<String>) -> Result<Option<String>> {
+    match version {
+        Some(version) if !sandbox::is_toolchain_version(&version) => InvalidToolchainVersion { version }.fail(),
+        version => Ok(version),
+    }
+}
+
+// Asking an old Docker for the capability would fail the container
+// and count against the circuit breaker
+fn parse_profile(requested: bool) -> Result<bool> {
+    if requested && !profiling_enabled() {
+        ProfilingDisabled.fail()
+    } else if requested && !sandbox::profiling_supported() {
+        ProfilingUnsupported.fail()
+    } else {
+        Ok(requested)
+    }
+}
+
+fn parse_trace(requested: bool) -> Result<bool> {
+    if requested && !tracing_enabled() {
+        TracingDisabled.fail()
+    } else {
+        Ok(requested)
+    }
+}
+
+fn parse_debug_artifacts(requested: bool) -> Result<bool> {
+    if requested && debug_artifacts::store().is_none() {
+        DebugArtifactsDisabled.fail()
+    } else {
+        Ok(requested)
+    }
+}
+
+// Without a session, or with one that has since been evicted, a new
+// session is issued and returned with the response.
+fn parse_incremental_session(incremental: bool, session: Option<String>) -> Result<Option<String>> {
+    if !incremental {
+        return Ok(None);
+    }
+
+    match session {
+        Some(value) if !sandbox::is_incremental_session(&value) => InvalidSession { value }.fail(),
+        session => Ok(Some(sandbox::incremental_session(session.as_deref()))),
+    }
+}
+
+fn parse_seed(seed: Option<String>) -> Result<Option<u64>> {
+    seed.map(|value| value.parse().map_err(|_| Error::InvalidSeed { value })).transpose()
+}
+
+fn parse_request_id(request_id: Option<String>) -> Result<Option<String>> {
+    match request_id {
+        Some(value) if !cancellation::is_valid_id(&value) => InvalidRequestId { value }.fail(),
+        request_id => Ok(request_id),
+    }
+}
+
+fn parse_environment(vars: Vec<EnvironmentVariable>) -> Result<Vec<(String, String)>> {
+    if vars.len() > environment::MAX_VARIABLES {
+        TooManyEnvironmentVariables { count: vars.len(), max: environment::MAX_VARIABLES }.fail()?;
+    }
+
+    vars.into_iter()
+        .map(|EnvironmentVariable { name, value }| {
+            match environment::check(&name, &value, allowed_environment()) {
+                Ok(()) => Ok((name, value)),
+                Err(reason) => InvalidEnvironmentVariable { name, reason }.fail(),
+            }
+        })
+        .collect()
+}
+
+fn parse_timezone(tz: Option<String>) -> Result<Option<String>> {
+    match tz {
+        Some(value) if !sandbox::TIMEZONES.contains(&&*value) => InvalidTimezone { value }.fail(),
+        tz => Ok(tz),
+    }
+}
+
+fn parse_locale(lang: Option<String>) -> Result<Option<String>> {
+    match lang {
+        Some(value) if !sandbox::LOCALES.contains(&&*value) => InvalidLocale { value }.fail(),
+        lang => Ok(lang),
+    }
+}
+
+fn parse_lint_groups(groups: Vec<String>) -> Result<Vec<&'static str>> {
+    groups
+        .into_iter()
+        .map(|value| match sandbox::LINT_GROUPS.iter().find(|&&group| group == value) {
+            Some(&group) => Ok(group),
+            None => InvalidLintGroup { value }.fail(),
+        })
+        .collect()
+}
+
+fn parse_linker(s: Option<&str>) -> Result<sandbox::Linker> {
+    Ok(match s {
+        None | Some("default") => sandbox::Linker::Default,
+        Some("lld") => sandbox::Linker::Lld,
+        Some(value) => InvalidLinker { value }.fail()?,
+    })
+}
+
+fn parse_sanitizer(s: Option<&str>) -> Result<Option<sandbox::Sanitizer>> {
+    Ok(match s {
+        None => None,
+        Some("address") => Some(sandbox::Sanitizer::Address),
+        Some("leak") => Some(sandbox::Sanitizer::Leak),
+        Some("thread") => Some(sandbox::Sanitizer::Thread),
+        Some(value) => InvalidSanitizer { value }.fail()?,
+    })
+}
+
 fn parse_channel(s: &str) -> Result<sandbox::Channel> {
     Ok(match s {
         "stable" => sandbox::Channel::Stable,
@@ -933,6 +4882,14 @@ fn parse_channel(s: &str) -> Result<sandbox::Channel> {
     })
 }
 
+fn channel_name(channel: sandbox::Channel) -> &'static str {
+    match channel {
+        sandbox::Channel::Stable => "stable",
+        sandbox::Channel::Beta => "beta",
+        sandbox::Channel::Nightly => "nightly",
+    }
+}
+
 fn parse_mode(s: &str) -> Result<sandbox::Mode> {
     Ok(match s {
         "debug" => sandbox::Mode::Debug,
@@ -950,6 +4907,13 @@ fn parse_edition(s: &str) -> Result<Option<sandbox::Edition>> {
     })
 }
 
+fn edition_name(edition: sandbox::Edition) -> &'static str {
+    match edition {
+        sandbox::Edition::Rust2015 => "2015",
+        sandbox::Edition::Rust2018 => "2018",
+    }
+}
+
 fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
     use crate::sandbox::{CrateType::*, LibraryType::*};
     Ok(match s {
@@ -967,3 +4931,353 @@ fn parse_crate_type(s: &str) -> Result<sandbox::CrateType> {
 fn default_crate_type() -> String {
     "bin".into()
 }
+
+fn default_channel() -> String {
+    "stable".into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{fs, io::prelude::*, net::{SocketAddr, TcpStream}, thread};
+
+    // Sends one raw HTTP/1.0 request and reads the whole response
+    fn send(socket: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(socket).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn connections_past_the_limit_wait_for_a_free_slot() {
+        let handler = |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "ok")));
+        let mut listening = server(handler, Some(1)).http("127.0.0.1:0").expect("Unable to start server");
+
+        let idle = TcpStream::connect(listening.socket).unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let mut waiting = TcpStream::connect(listening.socket).unwrap();
+        waiting.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+        waiting.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        assert!(waiting.read(&mut [0; 1]).is_err(), "The connection past the limit was handled");
+
+        drop(idle);
+
+        let mut response = String::new();
+        waiting.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        waiting.read_to_string(&mut response).unwrap();
+        assert!(response.contains("200 OK"), "response was: {}", response);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn only_one_runner_can_be_chosen() {
+        let request = |options: &str| -> Result<sandbox::ExecuteRequest> {
+            let json = format!(
+                r#"{{"channel":"stable","mode":"debug","crateType":"bin","tests":false,"backtrace":false,"code":"fn main() {{}}",{}}}"#,
+                options,
+            );
+            serde_json::from_str::<ExecuteRequest>(&json).unwrap().try_into()
+        };
+
+        assert!(request(r#""deterministic":true"#).is_ok());
+        assert_eq!(
+            request(r#""deterministic":true,"memoryReport":true"#).unwrap_err().to_string(),
+            "The requested options are incompatible: deterministic execution cannot be combined with reporting memory use",
+        );
+        assert_eq!(
+            request(r#""deterministic":true,"staticTarget":true"#).unwrap_err().to_string(),
+            "The requested options are incompatible: deterministic execution cannot be combined with the static target",
+        );
+    }
+
+    #[test]
+    fn the_evaluate_endpoint_can_be_disabled() {
+        let mut routes = Routes::new();
+        routes.get("/", |_: &mut Request<'_, '_>| Ok(Response::with(status::NotFound)));
+        routes.post("/execute", |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "executed"))));
+        mount_evaluate(&mut routes, false, |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "evaluated"))));
+        assert!(routes.list().iter().all(|r| r.path != "/evaluate.json"));
+
+        let mut listening = server(routes.into_mount(), None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let post = |path: &str| send(listening.socket, &format!("POST {} HTTP/1.0\r\nContent-Length: 0\r\n\r\n", path));
+
+        let evaluated = post("/evaluate.json");
+        assert!(evaluated.starts_with("HTTP/1.0 404"), "response was: {}", evaluated);
+
+        let executed = post("/execute");
+        assert!(executed.starts_with("HTTP/1.0 200"), "response was: {}", executed);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn overlong_gist_ids_are_rejected() {
+        let handler = |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "gist")));
+        let mut listening = server(PathLimited::new(40, handler), None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let get = |path: &str| send(listening.socket, &format!("GET {} HTTP/1.0\r\n\r\n", path));
+
+        let found = get("/3b2c1a0f9e8d7c6b5a4f3e2d1c0b9a8f");
+        assert!(found.starts_with("HTTP/1.0 200"), "response was: {}", found);
+
+        let overlong = get(&format!("/{}", "3b2c1a0f".repeat(8)));
+        assert!(overlong.starts_with("HTTP/1.0 414"), "response was: {}", overlong);
+        assert!(overlong.contains("at most 40 are allowed"), "response was: {}", overlong);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn a_missing_index_is_explained() {
+        let root = tempdir::TempDir::new("ui-root").unwrap();
+        let handler = |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "asset")));
+        let mut listening = server(IndexRequired::new(root.path(), handler), None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let get = |path: &str| send(listening.socket, &format!("GET {} HTTP/1.0\r\n\r\n", path));
+
+        let index = get("/");
+        assert!(index.starts_with("HTTP/1.0 503"), "response was: {}", index);
+        assert!(index.contains("UI assets not found"), "response was: {}", index);
+
+        let asset = get("/assets/app.js");
+        assert!(asset.starts_with("HTTP/1.0 200"), "response was: {}", asset);
+
+        fs::write(root.path().join("index.html"), "<html></html>").unwrap();
+        let built = get("/");
+        assert!(built.starts_with("HTTP/1.0 200"), "response was: {}", built);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn the_content_type_can_be_removed() {
+        let response = || Response::with((status::Ok, Header(ContentType::json()), "{}"));
+
+        let mut unchanged = response();
+        override_content_type(&mut unchanged, None);
+        assert_eq!(unchanged.headers.get::<ContentType>(), Some(&ContentType::json()));
+
+        let mut removed = response();
+        override_content_type(&mut removed, Some(""));
+        assert_eq!(removed.headers.get::<ContentType>(), None);
+
+        let mut replaced = response();
+        override_content_type(&mut replaced, Some("text/plain; charset=utf-8"));
+        assert_eq!(replaced.headers.get::<ContentType>(), Some(&ContentType::plaintext()));
+    }
+
+    #[test]
+    fn the_null_origin_can_be_rejected() {
+        let handler = |_: &mut Request<'_, '_>| Ok(Response::with((status::Ok, "ok")));
+        let mut chain = Chain::new(handler);
+        chain.link_around(cors(false));
+        let mut listening = server(chain, None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let response = send(listening.socket, "GET / HTTP/1.0\r\nOrigin: null\r\n\r\n");
+
+        assert!(!response.to_lowercase().contains("access-control-allow-origin"), "response was: {}", response);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn message_pack_is_only_sent_when_it_is_acceptable() {
+        let handler = |req: &mut Request<'_, '_>| serialize_to_response(ResponseFormat::negotiate(req), Ok(MetaNightlyFlagsResponse { flags: vec![] }));
+        let mut listening = server(handler, None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let get = |accept: &str| send(listening.socket, &format!("GET / HTTP/1.0\r\nAccept: {}\r\n\r\n", accept)).to_lowercase();
+
+        let packed = get("application/msgpack");
+        assert!(packed.contains("content-type: application/msgpack"), "response was: {}", packed);
+        assert!(packed.contains("vary: accept"), "response was: {}", packed);
+
+        let refused = get("application/msgpack;q=0, application/json");
+        assert!(refused.contains("content-type: application/json"), "response was: {}", refused);
+        assert!(refused.contains("vary: accept"), "response was: {}", refused);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn sandbox_routes_are_unavailable_while_the_breaker_is_open() {
+        // Nothing else in these tests runs the sandbox
+        while sandbox_breaker().status().state != circuit_breaker::State::Open {
+            if let Some(attempt) = sandbox_breaker().allow_request() {
+                attempt.record(false);
+            }
+        }
+
+        let mut routes = Routes::new();
+        routes.get("/tree/json", tree_json);
+        let mut listening = server(routes.into_mount(), None).http("127.0.0.1:0").expect("Unable to start server");
+
+        let response = send(listening.socket, "GET /tree/json HTTP/1.0\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.0 503"), "response was: {}", response);
+        assert!(response.contains("The sandbox is temporarily unavailable"), "response was: {}", response);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+
+    #[test]
+    fn problem_json_errors_describe_the_status() {
+        let err = Error::InvalidChannel { value: "purple".into() };
+        let body = ResponseFormat::ProblemJson.serialize_error(&err).unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(problem, serde_json::json!({
+            "type": "about:blank",
+            "title": "Bad Request",
+            "status": 400,
+            "detail": "The value \"purple\" is not a valid channel",
+        }));
+    }
+
+    #[test]
+    fn capabilities_are_populated_once_within_the_time_to_live() {
+        let cache = SandboxCacheOne::default();
+        let populated = std::cell::Cell::new(0);
+        let populate = || {
+            populated.set(populated.get() + 1);
+            Ok(sandbox::Capabilities { components: vec!["clippy".into()], targets: vec![], toolchains: vec![], features: vec![] })
+        };
+        let time_to_live = Duration::from_secs(CAPABILITIES_CACHE_TIME_TO_LIVE_IN_SECONDS);
+
+        for _ in 0..3 {
+            let capabilities = cache.clone_or_populate_within(time_to_live, populate).unwrap();
+            assert_eq!(capabilities.components, ["clippy"]);
+        }
+        assert_eq!(populated.get(), 1);
+
+        cache.clone_or_populate_within(Duration::from_secs(0), populate).unwrap();
+        assert_eq!(populated.get(), 2);
+    }
+
+    #[test]
+    fn the_sandbox_routes_are_listed_with_their_methods() {
+        let mut routes = Routes::new();
+        mount_sandbox_routes(&mut routes, &None);
+        let list = routes.list();
+
+        let compile = list.iter().find(|r| r.path == "/compile").expect("/compile is not listed");
+        assert_eq!(compile.methods, [Post]);
+        let execute = list.iter().find(|r| r.path == "/execute").expect("/execute is not listed");
+        assert_eq!(execute.methods, [Get, Post]);
+        let compiles = list.iter().find(|r| r.path == "/compiles").expect("/compiles is not listed");
+        assert_eq!(compiles.methods, [Post]);
+    }
+
+    #[test]
+    fn simultaneous_cold_version_lookups_are_populated_once() {
+        use std::sync::{atomic::{AtomicUsize, Ordering}, Barrier};
+
+        const REQUESTS: usize = 8;
+        let cache = Arc::new(SandboxCacheOne::default());
+        let populated = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(REQUESTS));
+
+        let requests: Vec<_> = (0..REQUESTS)
+            .map(|_| {
+                let (cache, populated, barrier) = (cache.clone(), populated.clone(), barrier.clone());
+                thread::spawn(move || {
+                    barrier.wait();
+                    cache.clone_or_populate(|| {
+                        populated.fetch_add(1, Ordering::SeqCst);
+                        // As slow as starting a container, so the others arrive while it runs
+                        thread::sleep(Duration::from_millis(100));
+                        Ok(sandbox::Version {
+                            release: "1.42.0".into(),
+                            commit_hash: "b8cedc004".into(),
+                            commit_date: "2020-03-09".into(),
+                        })
+                    })
+                })
+            })
+            .collect();
+
+        for request in requests {
+            let version = request.join().unwrap().unwrap();
+            assert_eq!(version.release, "1.42.0");
+        }
+        assert_eq!(populated.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn components_are_reported_with_or_without_their_preview_suffix() {
+        let capabilities = sandbox::Capabilities {
+            components: vec!["cargo".into(), "llvm-tools-preview".into(), "rustfmt".into()],
+            targets: vec![],
+            toolchains: vec![],
+            features: vec![],
+        };
+
+        let components = installed_components(&capabilities);
+
+        assert!(components["rustfmt"]);
+        assert!(components["llvm-tools"]);
+        assert!(!components["miri"]);
+        assert!(!components.contains_key("cargo"));
+    }
+
+    #[test]
+    fn oversized_responses_can_be_rejected() {
+        let limit = response_limit::Limit { max_bytes: 16, overflow: response_limit::Overflow::Reject };
+        let err = ResponseFormat::Json.serialize_within(&ErrorJson { error: "x".repeat(32) }, Some(limit)).unwrap_err();
+
+        assert_eq!(err.status(), status::UnprocessableEntity);
+        assert_eq!(err.to_string(), "The response would be 44 bytes long, but at most 16 are allowed");
+    }
+
+    #[test]
+    fn gists_with_too_many_files_are_rejected_locally() {
+        let limits = gist::Limits { max_files: 1, max_bytes: DEFAULT_MAX_GIST_BYTES };
+        let err = check_gist_limits(limits, "fn main() {}", Some("{}")).unwrap_err();
+
+        assert_eq!(err.status(), status::PayloadTooLarge);
+        assert_eq!(err.to_string(), "The gist would have 2 files, but at most 1 are allowed");
+    }
+
+    #[test]
+    fn gist_metadata_round_trips() {
+        let request: MetaGistCreateRequest = serde_json::from_str(
+            r#"{"code": "fn main() {}", "channel": "nightly", "edition": "2018", "mode": "release"}"#,
+        ).unwrap();
+        let stored = serde_json::to_string(&request.metadata).unwrap();
+
+        let gist = gist::Gist {
+            id: "abc123".into(),
+            url: "https://gist.github.com/abc123".into(),
+            code: request.code,
+            metadata: Some(stored),
+        };
+        let response = serde_json::to_value(MetaGistResponse::from(gist)).unwrap();
+
+        assert_eq!(response["channel"], "nightly");
+        assert_eq!(response["edition"], "2018");
+        assert_eq!(response["mode"], "release");
+    }
+
+    #[test]
+    fn gists_without_metadata_are_loaded() {
+        let gist = gist::Gist {
+            id: "abc123".into(),
+            url: "https://gist.github.com/abc123".into(),
+            code: "fn main() {}".into(),
+            metadata: None,
+        };
+        let response = serde_json::to_value(MetaGistResponse::from(gist)).unwrap();
+
+        assert!(response.get("channel").is_none());
+    }
+}
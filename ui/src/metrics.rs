@@ -0,0 +1,427 @@
+// Simple static measurements of a snippet for analytics and for the
+// frontend. A small lexer is enough for these, so no container needs
+// to be started.
+
+use std::collections::BTreeSet;
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub lines: usize,
+    /// Lines with at least one token; blank and comment-only lines
+    /// are not counted
+    pub code_lines: usize,
+    pub tokens: usize,
+    pub fns: usize,
+    pub structs: usize,
+    pub impls: usize,
+}
+
+pub fn measure(code: &str) -> Metrics {
+    let tokens = Lexer::new(code).tokenize();
+    let code_lines: BTreeSet<_> = tokens.iter().map(|t| t.line).collect();
+
+    let mut metrics = Metrics {
+        lines: code.lines().count(),
+        code_lines: code_lines.len(),
+        tokens: tokens.len(),
+        ..Metrics::default()
+    };
+
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.kind != Kind::Ident {
+            continue;
+        }
+
+        // `fn(u8)` types and `impl Trait` types are not items
+        let named = tokens.get(idx + 1).map(|t| t.kind) == Some(Kind::Ident);
+        let starts_item = match idx.checked_sub(1).map(|prev| tokens[prev].text) {
+            None | Some("{") | Some("}") | Some(";") | Some("]") | Some("unsafe") => true,
+            Some(_) => false,
+        };
+
+        match token.text {
+            "fn" if named => metrics.fns += 1,
+            "struct" if named => metrics.structs += 1,
+            "impl" if starts_item => metrics.impls += 1,
+            _ => {}
+        }
+    }
+
+    metrics
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct UnsafeUsage {
+    pub blocks: u32,
+    pub functions: u32,
+    pub impls: u32,
+    pub traits: u32,
+}
+
+impl UnsafeUsage {
+    pub fn total(&self) -> u32 {
+        self.blocks + self.functions + self.impls + self.traits
+    }
+}
+
+/// Counts the `unsafe` keywords in the code by what they apply to.
+pub fn unsafe_usage(code: &str) -> UnsafeUsage {
+    let tokens = Lexer::new(code).tokenize();
+    let mut usage = UnsafeUsage::default();
+
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.kind != Kind::Ident || token.text != "unsafe" {
+            continue;
+        }
+
+        // `unsafe extern "C" fn` is still a function
+        let applies_to = tokens[idx + 1..]
+            .iter()
+            .find(|t| t.text != "extern" && t.kind != Kind::Literal)
+            .map(|t| t.text);
+
+        match applies_to {
+            Some("{") => usage.blocks += 1,
+            Some("fn") => usage.functions += 1,
+            Some("impl") => usage.impls += 1,
+            Some("trait") | Some("auto") => usage.traits += 1,
+            _ => {}
+        }
+    }
+
+    usage
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct EntryPoint {
+    /// A `fn main` at the root of the crate
+    pub has_main: bool,
+    /// The crate doesn't need a `main`, through `#![no_main]`
+    pub no_main: bool,
+}
+
+/// Lets the frontend decide whether the code needs to be wrapped in
+/// a `main`. Functions in modules and blocks are not the entry point.
+pub fn entry_point(code: &str) -> EntryPoint {
+    let tokens = Lexer::new(code).tokenize();
+    let text = |idx: usize| tokens.get(idx).map(|t| t.text);
+    let mut entry_point = EntryPoint::default();
+    let mut depth = 0_usize;
+
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.text {
+            "{" => depth += 1,
+            "}" => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            "fn" if text(idx + 1) == Some("main") => entry_point.has_main = true,
+            "no_main" if idx >= 3 && [text(idx - 3), text(idx - 2), text(idx - 1)] == [Some("#"), Some("!"), Some("[")] => {
+                entry_point.no_main = true;
+            }
+            _ => {}
+        }
+    }
+
+    entry_point
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Lifetime,
+    Literal,
+    Punct,
+}
+
+#[derive(Debug)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    line: usize,
+}
+
+// Operators that are made of several characters; longer ones first
+const OPERATORS: &[&str] = &[
+    "...", "..=", "<<=", ">>=",
+    "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=", "-=",
+    "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>", "..",
+];
+
+struct Lexer<'a> {
+    code: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(code: &'a str) -> Self {
+        Lexer { code, pos: 0, line: 1 }
+    }
+
+    fn tokenize(mut self) -> Vec<Token<'a>> {
+        let mut tokens = Vec::new();
+
+        while let Some(c) = self.peek(0) {
+            let start = self.pos;
+            let line = self.line;
+
+            let kind = if c.is_whitespace() {
+                self.bump();
+                continue;
+            } else if self.rest().starts_with("//") {
+                self.eat_while(|c| c != '\n');
+                continue;
+            } else if self.rest().starts_with("/*") {
+                self.block_comment();
+                continue;
+            } else if let Some(prefix_len) = self.string_prefix() {
+                self.string(prefix_len);
+                Kind::Literal
+            } else if c == '\'' {
+                self.char_or_lifetime()
+            } else if c.is_ascii_digit() {
+                self.number();
+                Kind::Literal
+            } else if is_ident_start(c) {
+                self.eat_while(is_ident_continue);
+                Kind::Ident
+            } else {
+                self.punct();
+                Kind::Punct
+            };
+
+            tokens.push(Token { kind, text: &self.code[start..self.pos], line });
+        }
+
+        tokens
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.code[self.pos..]
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn eat_while(&mut self, mut f: impl FnMut(char) -> bool) {
+        while self.peek(0).map(&mut f) == Some(true) {
+            self.bump();
+        }
+    }
+
+    fn block_comment(&mut self) {
+        let mut depth = 0;
+
+        loop {
+            if self.rest().starts_with("/*") {
+                depth += 1;
+                self.bump();
+                self.bump();
+            } else if self.rest().starts_with("*/") {
+                depth -= 1;
+                self.bump();
+                self.bump();
+                if depth == 0 {
+                    return;
+                }
+            } else if self.bump().is_none() {
+                return;
+            }
+        }
+    }
+
+    /// The length of `b`, `r#`, `br##`, etc. before a string's quote
+    fn string_prefix(&self) -> Option<usize> {
+        let rest = self.rest();
+        let after_b = if rest.starts_with('b') { 1 } else { 0 };
+        let after_r = if rest[after_b..].starts_with('r') { after_b + 1 } else { after_b };
+        let hashes = if after_r > after_b { rest[after_r..].chars().take_while(|&c| c == '#').count() } else { 0 };
+
+        if rest[after_r + hashes..].starts_with('"') {
+            Some(after_r + hashes)
+        } else {
+            None
+        }
+    }
+
+    fn string(&mut self, prefix_len: usize) {
+        let prefix = &self.rest()[..prefix_len];
+        let raw = prefix.contains('r');
+        let closing = format!("\"{}", "#".repeat(prefix.matches('#').count()));
+
+        for _ in 0..=prefix_len {
+            self.bump();
+        }
+
+        loop {
+            if self.rest().starts_with(&closing) {
+                for _ in 0..closing.len() {
+                    self.bump();
+                }
+                return;
+            }
+
+            match self.bump() {
+                Some('\\') if !raw => {
+                    self.bump();
+                }
+                Some(_) => {}
+                None => return,
+            }
+        }
+    }
+
+    fn char_or_lifetime(&mut self) -> Kind {
+        self.bump();
+
+        // A lifetime is never followed by a closing quote
+        let is_lifetime = self.peek(0).map(is_ident_start) == Some(true) && self.peek(1) != Some('\'');
+        if is_lifetime {
+            self.eat_while(is_ident_continue);
+            return Kind::Lifetime;
+        }
+
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    self.bump();
+                }
+                Some('\'') | Some('\n') | None => return Kind::Literal,
+                Some(_) => {}
+            }
+        }
+    }
+
+    fn number(&mut self) {
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(c), _) if is_ident_continue(c) => {}
+                // `1.5` but not `1..5` or `1.max(2)`
+                (Some('.'), Some(d)) if d.is_ascii_digit() => {}
+                _ => return,
+            }
+            self.bump();
+        }
+    }
+
+    fn punct(&mut self) {
+        let len = OPERATORS
+            .iter()
+            .find(|op| self.rest().starts_with(*op))
+            .map_or(1, |op| op.len());
+
+        for _ in 0..len {
+            self.bump();
+        }
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn functions_are_counted() {
+        let code = r#"
+            fn helper(f: impl Fn(u8) -> u8) -> u8 {
+                f(1)
+            }
+
+            // fn commented_out() {}
+            fn main() {
+                let g: fn(u8) -> u8 = |x| x;
+                println!("fn {}", helper(g));
+            }
+        "#;
+
+        let metrics = measure(code);
+
+        assert_eq!(metrics.fns, 2);
+        assert_eq!(metrics.impls, 0);
+    }
+
+    #[test]
+    fn unsafe_blocks_are_counted() {
+        let code = r#"
+            fn main() {
+                let x = 5;
+                // unsafe { this is a comment }
+                let y = unsafe { *(&x as *const i32) };
+                println!("{} unsafe", y);
+            }
+        "#;
+
+        let usage = unsafe_usage(code);
+
+        assert_eq!(usage, UnsafeUsage { blocks: 1, ..UnsafeUsage::default() });
+        assert_eq!(usage.total(), 1);
+    }
+
+    #[test]
+    fn unsafe_items_are_counted() {
+        let code = "unsafe trait T {}\nunsafe impl T for u8 {}\nunsafe extern \"C\" fn f() {}\n";
+
+        assert_eq!(unsafe_usage(code), UnsafeUsage { blocks: 0, functions: 1, impls: 1, traits: 1 });
+    }
+
+    #[test]
+    fn main_is_found_at_the_root_of_the_crate() {
+        let code = "use std::fmt;\n\n/// Starts here\npub fn main() {\n    println!(\"hi\");\n}\n";
+
+        assert_eq!(entry_point(code), EntryPoint { has_main: true, no_main: false });
+    }
+
+    #[test]
+    fn main_is_not_found_elsewhere() {
+        let code = r#"
+            // fn main() {}
+            mod inner {
+                fn main() {}
+            }
+            impl Server {
+                fn main(&self) {}
+            }
+            fn mainly() { let _ = "fn main() {}"; }
+        "#;
+
+        assert_eq!(entry_point(code), EntryPoint { has_main: false, no_main: false });
+    }
+
+    #[test]
+    fn no_main_is_recognized() {
+        let code = "#![no_std]\n#![no_main]\n\n#[no_mangle]\npub extern \"C\" fn _start() {}\n";
+
+        assert_eq!(entry_point(code), EntryPoint { has_main: false, no_main: true });
+    }
+
+    #[test]
+    fn items_lines_and_tokens_are_counted() {
+        let code = "struct S<'a>(&'a str);\n\nimpl S<'_> {\n    fn new() -> Self { S(\"}\") }\n}\n";
+
+        assert_eq!(measure(code), Metrics {
+            lines: 5,
+            code_lines: 4,
+            tokens: 30,
+            fns: 1,
+            structs: 1,
+            impls: 1,
+        });
+    }
+}
@@ -0,0 +1,171 @@
+// Shrinks a snippet while it keeps failing in the same way, much like
+// a very simple creduce
+
+use std::ops::Range;
+
+/// Finds the lines that a pass will try to remove
+type Candidates = fn(&[&str]) -> Vec<Range<usize>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimized {
+    pub code: String,
+    pub attempts: u32,
+    /// No more lines could be removed before running out of attempts
+    pub finished: bool,
+}
+
+/// The original code must already satisfy `still_fails`. Whole
+/// top-level items are removed first, followed by single lines that
+/// don't open or close a block.
+pub fn minimize<E>(
+    code: &str,
+    max_attempts: u32,
+    mut still_fails: impl FnMut(&str) -> Result<bool, E>,
+) -> Result<Minimized, E> {
+    let mut lines: Vec<&str> = code.lines().collect();
+    let mut attempts = 0;
+    let passes: &[Candidates] = &[top_level_items, balanced_lines];
+
+    loop {
+        let mut progress = false;
+
+        for pass in passes {
+            let mut idx = 0;
+
+            while let Some(range) = pass(&lines).get(idx).cloned() {
+                if attempts == max_attempts {
+                    return Ok(Minimized { code: join(&lines), attempts, finished: false });
+                }
+                attempts += 1;
+
+                let mut candidate = lines.clone();
+                candidate.drain(range);
+
+                if still_fails(&join(&candidate))? {
+                    // The next candidate has moved into this position
+                    lines = candidate;
+                    progress = true;
+                } else {
+                    idx += 1;
+                }
+            }
+        }
+
+        if !progress {
+            return Ok(Minimized { code: join(&lines), attempts, finished: true });
+        }
+    }
+}
+
+fn join(lines: &[&str]) -> String {
+    let mut code = String::new();
+    let mut previous_blank = true;
+
+    // Removing items leaves their surrounding blank lines behind
+    for line in lines {
+        let blank = line.trim().is_empty();
+        if !(blank && previous_blank) {
+            code.push_str(line);
+            code.push('\n');
+        }
+        previous_blank = blank;
+    }
+
+    code
+}
+
+// Braces inside of strings and comments are counted too, which only
+// means that some candidates are skipped or rejected.
+fn brace_delta(line: &str) -> i32 {
+    line.chars().fold(0, |delta, c| match c {
+        '{' => delta + 1,
+        '}' => delta - 1,
+        _ => delta,
+    })
+}
+
+fn top_level_items(lines: &[&str]) -> Vec<Range<usize>> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+
+    for (idx, line) in lines.iter().enumerate() {
+        if depth == 0 && start.is_none() && !line.trim().is_empty() {
+            start = Some(idx);
+        }
+
+        depth += brace_delta(line);
+
+        // Attributes and doc comments belong to the following item
+        let trimmed = line.trim();
+        let is_prefix = trimmed.starts_with("#[") || trimmed.starts_with("///");
+
+        if depth == 0 && !is_prefix {
+            if let Some(start) = start.take() {
+                items.push(start..idx + 1);
+            }
+        }
+    }
+
+    items
+}
+
+fn balanced_lines(lines: &[&str]) -> Vec<Range<usize>> {
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter(|(_, line)| brace_delta(line) == 0)
+        .map(|(idx, _)| idx..idx + 1)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Stands in for the compiler: the error remains as long as the
+    // mismatched assignment is still inside of `main`.
+    fn still_mismatched(code: &str) -> Result<bool, ()> {
+        let balanced = code.lines().map(brace_delta).sum::<i32>() == 0;
+        Ok(balanced && code.contains("fn main() {") && code.contains(r#"let x: i32 = "";"#))
+    }
+
+    #[test]
+    fn unused_helpers_are_removed() {
+        let code = r#"
+use std::fmt;
+
+/// Formats things
+fn helper(f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "hello")
+}
+
+struct Unused {
+    field: u8,
+}
+
+fn main() {
+    let y = 42;
+    let x: i32 = "";
+    println!("{}", y);
+}
+"#;
+
+        let minimized = minimize(code, 100, still_mismatched).unwrap();
+
+        assert_eq!(minimized.code, "fn main() {\n    let x: i32 = \"\";\n}\n");
+        assert!(minimized.finished);
+    }
+
+    #[test]
+    fn attempts_are_bounded() {
+        let code = "fn a() {}\nfn b() {}\nfn main() {\n    let x: i32 = \"\";\n}\n";
+
+        let minimized = minimize(code, 1, still_mismatched).unwrap();
+
+        assert_eq!(minimized.code, "fn b() {}\nfn main() {\n    let x: i32 = \"\";\n}\n");
+        assert_eq!(minimized.attempts, 1);
+        assert!(!minimized.finished);
+    }
+}
@@ -0,0 +1,102 @@
+// Finds the errors that Miri reports, such as undefined behavior, so
+// that the editor can point at the code that caused them.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// Compilation errors that happen before Miri runs are left out
+const MIRI_ERROR_KINDS: &[&str] = &[
+    "Undefined Behavior",
+    "unsupported operation",
+    "resource exhaustion",
+    "deadlock",
+    "memory leaked",
+    "abnormal termination",
+    "Miri evaluation error",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiriError {
+    /// Such as `Undefined Behavior`
+    pub kind: String,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+/// Lines and columns are 1-based
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+pub fn parse(stderr: &str) -> Vec<MiriError> {
+    lazy_static! {
+        static ref ERROR_REGEX: Regex = Regex::new(r"^error: ([^:]+): (.+)$").unwrap();
+        static ref SPAN_REGEX: Regex = Regex::new(r"^\s*--> (.+?):(\d+):(\d+)$").unwrap();
+    }
+
+    let mut errors: Vec<MiriError> = Vec::new();
+    // Only the first location after an error is where it happened
+    let mut awaiting_span = false;
+
+    for line in stderr.lines() {
+        if let Some(caps) = ERROR_REGEX.captures(line) {
+            awaiting_span = MIRI_ERROR_KINDS.contains(&&caps[1]);
+            if awaiting_span {
+                errors.push(MiriError { kind: caps[1].into(), message: caps[2].into(), span: None });
+            }
+        } else if let Some(caps) = SPAN_REGEX.captures(line) {
+            if awaiting_span {
+                if let Some(error) = errors.last_mut() {
+                    error.span = Some(Span {
+                        file: caps[1].into(),
+                        line: caps[2].parse().unwrap_or(0),
+                        column: caps[3].parse().unwrap_or(0),
+                    });
+                }
+            }
+            awaiting_span = false;
+        } else if line.starts_with("error") {
+            awaiting_span = false;
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn undefined_behavior_is_reported_with_its_span() {
+        let stderr = r#"   Compiling playground v0.0.1 (/playground)
+error: Undefined Behavior: accessing memory with alignment 1, but alignment 4 is required
+ --> src/main.rs:4:22
+  |
+4 |     let value = unsafe { *pointer };
+  |                          ^^^^^^^^ accessing memory with alignment 1, but alignment 4 is required
+  |
+  = help: this indicates a bug in the program: it performed an invalid operation, and caused Undefined Behavior
+  = note: inside `main` at src/main.rs:4:22
+
+error: aborting due to previous error
+
+"#;
+
+        assert_eq!(parse(stderr), [MiriError {
+            kind: "Undefined Behavior".into(),
+            message: "accessing memory with alignment 1, but alignment 4 is required".into(),
+            span: Some(Span { file: "src/main.rs".into(), line: 4, column: 22 }),
+        }]);
+    }
+
+    #[test]
+    fn compilation_errors_are_not_miri_errors() {
+        let stderr = "error[E0425]: cannot find value `x` in this scope\n --> src/main.rs:1:13\n";
+
+        assert_eq!(parse(stderr), []);
+    }
+}
@@ -0,0 +1,87 @@
+// Builds `multipart/mixed` bodies (RFC 2046) so that a client can
+// receive several documents from a single request.
+
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+const BOUNDARY_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Part {
+    pub content_type: String,
+    /// Suggests a name when the part is saved
+    pub file_name: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// Long enough that it won't appear in any of the parts by chance
+pub fn boundary() -> String {
+    thread_rng().sample_iter(&Alphanumeric).take(BOUNDARY_LEN).collect()
+}
+
+pub fn content_type(boundary: &str) -> String {
+    format!("multipart/mixed; boundary={}", boundary)
+}
+
+pub fn encode(boundary: &str, parts: &[Part]) -> Vec<u8> {
+    let mut body = Vec::new();
+
+    for part in parts {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(format!("Content-Type: {}\r\n", part.content_type).as_bytes());
+        if let Some(file_name) = &part.file_name {
+            body.extend_from_slice(format!("Content-Disposition: attachment; filename=\"{}\"\r\n", file_name).as_bytes());
+        }
+        body.extend_from_slice(b"\r\n");
+        body.extend_from_slice(&part.body);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    body
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Splits a body back into each part's headers and content
+    fn decode(boundary: &str, body: &str) -> Vec<(Vec<String>, String)> {
+        let delimiter = format!("--{}", boundary);
+        let (parts, rest) = body.split_at(body.rfind(&format!("{}--", delimiter)).unwrap());
+        assert_eq!(rest, format!("{}--\r\n", delimiter));
+
+        parts
+            .split(&delimiter)
+            .skip(1)
+            .map(|part| {
+                assert!(part.starts_with("\r\n") && part.ends_with("\r\n"), "part was: {:?}", part);
+                let part = &part["\r\n".len()..part.len() - "\r\n".len()];
+                let (headers, content) = part.split_at(part.find("\r\n\r\n").unwrap());
+                let headers = headers.split("\r\n").map(String::from).collect();
+                (headers, content["\r\n\r\n".len()..].to_string())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parts_are_separated_by_the_boundary() {
+        let boundary = boundary();
+        let body = encode(&boundary, &[
+            Part { content_type: "application/json".into(), file_name: None, body: br#"{"success":true}"#.to_vec() },
+            Part { content_type: "text/plain".into(), file_name: Some("playground.ll".into()), body: b"define void @main()\n".to_vec() },
+        ]);
+
+        let parts = decode(&boundary, std::str::from_utf8(&body).unwrap());
+
+        assert_eq!(parts, [
+            (vec!["Content-Type: application/json".to_string()], r#"{"success":true}"#.to_string()),
+            (
+                vec![
+                    "Content-Type: text/plain".to_string(),
+                    r#"Content-Disposition: attachment; filename="playground.ll""#.to_string(),
+                ],
+                "define void @main()\n".to_string(),
+            ),
+        ]);
+    }
+}
@@ -0,0 +1,271 @@
+// Turns the compiler's suggestions into a single patch that a client
+// can apply in one go.
+
+use std::{cmp, ops::Range};
+
+use crate::{diagnostics::Suggestion, line_map};
+
+const MACHINE_APPLICABLE: &str = "MachineApplicable";
+// The same amount of context that `diff -u` shows
+const CONTEXT_LINES: usize = 3;
+
+/// Applies every suggestion that the compiler is certain about. When
+/// suggestions overlap, only the first is applied.
+pub fn apply(code: &str, suggestions: &[Suggestion]) -> String {
+    let mut replacements: Vec<_> = suggestions
+        .iter()
+        .filter(|suggestion| suggestion.applicability == MACHINE_APPLICABLE)
+        .filter_map(|suggestion| Some((byte_range(code, suggestion)?, suggestion.replacement.as_str())))
+        .collect();
+    replacements.sort_by_key(|(range, _)| (range.start, range.end));
+    replacements.dedup();
+
+    let mut patched = String::with_capacity(code.len());
+    let mut position = 0;
+    for (range, replacement) in replacements {
+        if range.start < position {
+            continue;
+        }
+        patched.push_str(&code[position..range.start]);
+        patched.push_str(replacement);
+        position = range.end;
+    }
+    patched.push_str(&code[position..]);
+
+    patched
+}
+
+// Lines and columns are 1-based, with columns counted in characters
+fn byte_range(code: &str, suggestion: &Suggestion) -> Option<Range<usize>> {
+    let span = &suggestion.span;
+    let start = byte_offset(code, span.line_start, span.column_start)?;
+    let end = byte_offset(code, span.line_end, span.column_end)?;
+    if start <= end { Some(start..end) } else { None }
+}
+
+fn byte_offset(code: &str, line: u32, column: u32) -> Option<usize> {
+    let line_start: usize = lines(code)
+        .into_iter()
+        .take(line.checked_sub(1)? as usize)
+        .map(str::len)
+        .sum();
+    let line_text = code[line_start..].split('\n').next()?;
+
+    let column = column.checked_sub(1)? as usize;
+    let in_line = line_text
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .chain(Some(line_text.len()))
+        .nth(column)?;
+
+    Some(line_start + in_line)
+}
+
+// Each line keeps its newline so that a missing final newline is
+// part of the difference
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let end = rest.find('\n').map_or(rest.len(), |idx| idx + 1);
+        let (line, tail) = rest.split_at(end);
+        lines.push(line);
+        rest = tail;
+    }
+
+    lines
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Edit<'a> {
+    Keep(&'a str),
+    Remove(&'a str),
+    Add(&'a str),
+}
+
+/// A unified diff from `original` to `modified`, empty when they are
+/// the same.
+pub fn unified_diff(path: &str, original: &str, modified: &str) -> String {
+    if original == modified {
+        return String::new();
+    }
+
+    let old = lines(original);
+    let new = lines(modified);
+    let edits = edits(&old, &new);
+
+    // How many lines of each side come before each edit
+    let mut before = Vec::with_capacity(edits.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        before.push((old_line, new_line));
+        match edit {
+            Edit::Keep(_) => { old_line += 1; new_line += 1 }
+            Edit::Remove(_) => old_line += 1,
+            Edit::Add(_) => new_line += 1,
+        }
+    }
+    before.push((old_line, new_line));
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for hunk in hunks(&edits) {
+        let (old_start, new_start) = before[hunk.start];
+        let (old_end, new_end) = before[hunk.end];
+        let (old_len, new_len) = (old_end - old_start, new_end - new_start);
+        // An empty side refers to the line before the hunk
+        let start = |start, len| if len == 0 { start } else { start + 1 };
+
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            start(old_start, old_len), old_len, start(new_start, new_len), new_len,
+        ));
+
+        for edit in &edits[hunk] {
+            let (prefix, line) = match edit {
+                Edit::Keep(line) => (' ', line),
+                Edit::Remove(line) => ('-', line),
+                Edit::Add(line) => ('+', line),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    diff
+}
+
+// The longest common subsequence, with removals before additions. When
+// the changed part is too large to compare line by line, all of it is
+// replaced.
+fn edits<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    let matches = if old_middle.len().saturating_mul(new_middle.len()) <= line_map::MAX_COMPARISONS {
+        line_map::matching_lines(old_middle, new_middle)
+    } else {
+        vec![None; old_middle.len()]
+    };
+
+    let mut edits = Vec::with_capacity(cmp::max(old.len(), new.len()));
+    edits.extend(old[..prefix].iter().map(|line| Edit::Keep(line)));
+
+    let mut next_new = 0;
+    for (line, matched) in old_middle.iter().zip(matches) {
+        match matched {
+            Some(idx) => {
+                edits.extend(new_middle[next_new..idx].iter().map(|line| Edit::Add(line)));
+                edits.push(Edit::Keep(line));
+                next_new = idx + 1;
+            }
+            None => edits.push(Edit::Remove(line)),
+        }
+    }
+    edits.extend(new_middle[next_new..].iter().map(|line| Edit::Add(line)));
+
+    edits.extend(old[old.len() - suffix..].iter().map(|line| Edit::Keep(line)));
+    edits
+}
+
+// Changes that are close enough to share their context are grouped
+fn hunks(edits: &[Edit<'_>]) -> Vec<Range<usize>> {
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+
+    for (idx, edit) in edits.iter().enumerate() {
+        if let Edit::Keep(_) = edit {
+            continue;
+        }
+
+        let start = idx.saturating_sub(CONTEXT_LINES);
+        let end = cmp::min(idx + 1 + CONTEXT_LINES, edits.len());
+
+        match hunks.last_mut() {
+            Some(hunk) if hunk.end >= start => hunk.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    hunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diagnostics::Span;
+
+    fn suggestion(line: u32, columns: Range<u32>, replacement: &str, applicability: &str) -> Suggestion {
+        Suggestion {
+            span: Span { line_start: line, column_start: columns.start, line_end: line, column_end: columns.end },
+            replacement: replacement.into(),
+            applicability: applicability.into(),
+        }
+    }
+
+    #[test]
+    fn only_machine_applicable_suggestions_are_applied() {
+        let code = "fn main() {\n    let é = 1;\n    if é == true {}\n    let _ = 0.0 / 0.0;\n}\n";
+        let suggestions = [
+            suggestion(3, 8..17, "é", MACHINE_APPLICABLE),
+            // Reported twice, such as by both of the compilations
+            suggestion(3, 8..17, "é", MACHINE_APPLICABLE),
+            suggestion(4, 13..22, "f64::NAN", "MaybeIncorrect"),
+        ];
+
+        assert_eq!(
+            apply(code, &suggestions),
+            "fn main() {\n    let é = 1;\n    if é {}\n    let _ = 0.0 / 0.0;\n}\n",
+        );
+    }
+
+    #[test]
+    fn distant_changes_are_separate_hunks() {
+        let original: String = (1..=10).map(|n| format!("{}\n", n)).collect();
+        let modified = original.replace("2\n", "two\n").replace("10\n", "ten");
+
+        assert_eq!(unified_diff("src/main.rs", &original, &modified), "\
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -7,4 +7,4 @@
+ 7
+ 8
+ 9
+-10
++ten
+\\ No newline at end of file
+");
+    }
+
+    #[test]
+    fn large_changes_replace_every_changed_line() {
+        let original: String = (0..3000).map(|n| format!("{}\n", n)).collect();
+        let modified: String = (0..3000).map(|n| format!("line {}\n", n)).collect();
+
+        let diff = unified_diff("src/main.rs", &original, &modified);
+
+        assert!(diff.starts_with("--- a/src/main.rs\n+++ b/src/main.rs\n@@ -1,3000 +1,3000 @@\n-0\n-1\n"));
+        assert_eq!(diff.lines().filter(|line| line.starts_with('-')).count(), 3001);
+        assert_eq!(diff.lines().filter(|line| line.starts_with('+')).count(), 3001);
+    }
+
+    #[test]
+    fn unchanged_code_has_an_empty_patch() {
+        assert_eq!(unified_diff("src/main.rs", "fn main() {}\n", "fn main() {}\n"), "");
+    }
+}
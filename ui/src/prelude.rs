@@ -0,0 +1,253 @@
+// Adds operator-chosen items, usually `use` statements, to the
+// user's code and hides the extra lines from the diagnostics
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::{env, fs, iter};
+
+/// Only available when `PLAYGROUND_PRELUDE` names a readable file.
+pub fn configured() -> Option<&'static str> {
+    lazy_static! {
+        static ref PRELUDE: Option<String> = env::var_os("PLAYGROUND_PRELUDE").and_then(|path| {
+            fs::read_to_string(&path)
+                .map_err(|e| log::error!("Unable to read the prelude {:?}: {}", path, e))
+                .ok()
+        });
+    }
+
+    PRELUDE.as_ref().map(String::as_str)
+}
+
+/// Where the prelude was placed in the user's code
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Insertion {
+    after_line: u32,
+    len: u32,
+}
+
+/// The prelude goes after any leading inner attributes and inner doc
+/// comments, which must stay at the start of the crate.
+pub fn insert(code: &str, prelude: &str) -> (String, Insertion) {
+    let after_line = inner_attributes_lines(code);
+
+    let mut combined = String::new();
+    let mut len = 0;
+
+    for line in code.lines().take(after_line) {
+        combined.push_str(line);
+        combined.push('\n');
+    }
+    for line in prelude.lines() {
+        // The user may not need everything in the prelude
+        if line.trim_start().starts_with("use ") {
+            combined.push_str("#[allow(unused_imports)] ");
+        }
+        combined.push_str(line);
+        combined.push('\n');
+        len += 1;
+    }
+    for line in code.lines().skip(after_line) {
+        combined.push_str(line);
+        combined.push('\n');
+    }
+
+    (combined, Insertion { after_line: after_line as u32, len })
+}
+
+// How many lines the leading inner attributes take up. An attribute
+// may span several lines, and the line it ends on belongs to it.
+fn inner_attributes_lines(code: &str) -> usize {
+    let mut end = 0;
+    let mut rest = code;
+
+    loop {
+        let trimmed = rest.trim_start();
+        let offset = code.len() - trimmed.len();
+        let line_end = offset + trimmed.find('\n').unwrap_or(trimmed.len());
+
+        if trimmed.starts_with("//!") {
+            end = line_end;
+            rest = &code[end..];
+        } else if trimmed.starts_with("//") {
+            // Ordinary comments may come before or between the
+            // attributes without moving the prelude
+            rest = &code[line_end..];
+        } else if trimmed.starts_with("#![") {
+            match attribute_len(trimmed) {
+                Some(len) => end = offset + len,
+                None => break,
+            }
+            rest = &code[end..];
+        } else {
+            break;
+        }
+    }
+
+    if end == 0 {
+        0
+    } else {
+        code[..end].matches('\n').count() + 1
+    }
+}
+
+// The length of the attribute at the start of `code`, up to and
+// including its closing bracket. Brackets inside of strings don't
+// count.
+fn attribute_len(code: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (idx, c) in code.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+impl Insertion {
+    /// Lines inside of the prelude are left as they are.
+    pub fn user_line(&self, line: u32) -> u32 {
+        if line > self.after_line + self.len {
+            line - self.len
+        } else {
+            line
+        }
+    }
+
+    /// Corrects the file locations and the line number gutters in
+    /// the compiler's rendered diagnostics.
+    pub fn correct_rendered(&self, rendered: &str) -> String {
+        lazy_static! {
+            static ref GUTTER_REGEX: Regex = Regex::new(r"(?m)^( *)(\d+)( \|)").unwrap();
+        }
+
+        let rendered = self.correct_locations(rendered);
+
+        let rendered = GUTTER_REGEX.replace_all(&rendered, |caps: &Captures<'_>| {
+            // Keep the gutter the same width so the source stays aligned
+            let width = caps[1].len() + caps[2].len();
+            format!("{:>width$}{}", self.correct_number(&caps[2]), &caps[3], width = width)
+        });
+
+        rendered.into_owned()
+    }
+
+    /// Corrects only the file locations, such as those in panic
+    /// messages, leaving the rest of the program's output alone.
+    pub fn correct_locations(&self, output: &str) -> String {
+        lazy_static! {
+            static ref LOCATION_REGEX: Regex = Regex::new(r"(src/(?:main|lib)\.rs):(\d+)").unwrap();
+        }
+
+        LOCATION_REGEX
+            .replace_all(output, |caps: &Captures<'_>| {
+                format!("{}:{}", &caps[1], self.correct_number(&caps[2]))
+            })
+            .into_owned()
+    }
+
+    /// Cargo's output, up until it starts running the program, holds
+    /// the compiler's diagnostics; everything after is the program's.
+    pub fn correct_execution(&self, stderr: &str) -> String {
+        let line_starts = stderr.match_indices('\n').map(|(idx, _)| idx + 1);
+        let run_start = iter::once(0)
+            .chain(line_starts)
+            .find(|&idx| stderr[idx..].trim_start_matches(' ').starts_with("Running "))
+            .unwrap_or(stderr.len());
+        let (build, run) = stderr.split_at(run_start);
+
+        self.correct_rendered(build) + &self.correct_locations(run)
+    }
+
+    fn correct_number(&self, line: &str) -> String {
+        match line.parse() {
+            Ok(line) => self.user_line(line).to_string(),
+            Err(_) => line.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PRELUDE: &str = "use std::collections::HashMap;\nuse std::fmt;\n";
+
+    #[test]
+    fn prelude_follows_inner_attributes() {
+        let (code, insertion) = insert("#![allow(dead_code)]\nfn main() {}\n", PRELUDE);
+
+        assert_eq!(code, "#![allow(dead_code)]\n\
+                          #[allow(unused_imports)] use std::collections::HashMap;\n\
+                          #[allow(unused_imports)] use std::fmt;\n\
+                          fn main() {}\n");
+        assert_eq!(insertion.user_line(4), 2);
+        assert_eq!(insertion.user_line(1), 1);
+    }
+
+    #[test]
+    fn diagnostics_point_at_the_users_code() {
+        let (_, insertion) = insert("fn main() {\n    let x: i32 = \"\";\n}\n", PRELUDE);
+
+        let rendered = "error[E0308]: mismatched types\n \
+                        --> src/main.rs:4:18\n  \
+                        |\n\
+                        4 |     let x: i32 = \"\";\n  \
+                        |                  ^^ expected `i32`, found `&str`\n";
+
+        let corrected = insertion.correct_rendered(rendered);
+
+        assert!(corrected.contains(" --> src/main.rs:2:18\n"), "was: {}", corrected);
+        assert!(corrected.contains("\n2 |     let x: i32"), "was: {}", corrected);
+    }
+
+    #[test]
+    fn prelude_follows_inner_attributes_spanning_lines() {
+        let code = "//! Docs\n#![allow(\n    dead_code,\n)]\n#![doc = \"]\"]\nfn main() {}\n";
+        let (code, insertion) = insert(code, PRELUDE);
+
+        assert_eq!(code, "//! Docs\n#![allow(\n    dead_code,\n)]\n#![doc = \"]\"]\n\
+                          #[allow(unused_imports)] use std::collections::HashMap;\n\
+                          #[allow(unused_imports)] use std::fmt;\n\
+                          fn main() {}\n");
+        assert_eq!(insertion.user_line(8), 6);
+    }
+
+    #[test]
+    fn only_the_compilers_gutters_are_corrected() {
+        let (_, insertion) = insert("fn main() {\n    panic!();\n}\n", PRELUDE);
+
+        let stderr = "   Compiling playground v0.0.1 (/playground)\n\
+                      4 |     let x = 1;\n\
+                      \x20    Running `target/debug/playground`\n\
+                      4 | of the program's own output\n\
+                      thread 'main' panicked at 'explicit panic', src/main.rs:4:5\n";
+
+        let corrected = insertion.correct_execution(stderr);
+
+        assert!(corrected.contains("\n2 |     let x = 1;\n"), "was: {}", corrected);
+        assert!(corrected.contains("\n4 | of the program's own output\n"), "was: {}", corrected);
+        assert!(corrected.contains("src/main.rs:2:5"), "was: {}", corrected);
+    }
+}
@@ -0,0 +1,98 @@
+// `perf script` prints each sample as a header line followed by its
+// stack, innermost frame first, and a blank line, such as:
+//
+// playground    42 1234.567890:    1001001 cpu-clock:
+//             55d0c0a1b2c3 playground::fib+0x13 (/playground/target/debug/playground)
+//             55d0c0a1b2d4 playground::main+0x20 (/playground/target/debug/playground)
+//
+// Flame graph tools take "folded" stacks instead: one line for each
+// distinct stack, outermost frame first and separated by semicolons,
+// followed by how many samples had that stack.
+
+use std::collections::BTreeMap;
+
+pub fn fold(perf_script: &str) -> String {
+    let mut stacks = BTreeMap::new();
+    let mut frames = Vec::new();
+
+    for line in perf_script.lines() {
+        if line.starts_with(char::is_whitespace) && !line.trim().is_empty() {
+            frames.push(frame_name(line.trim()));
+        } else {
+            count(&mut stacks, &mut frames);
+        }
+    }
+    count(&mut stacks, &mut frames);
+
+    stacks
+        .into_iter()
+        .map(|(stack, samples)| format!("{} {}\n", stack, samples))
+        .collect()
+}
+
+fn count(stacks: &mut BTreeMap<String, u64>, frames: &mut Vec<&str>) {
+    if frames.is_empty() {
+        return;
+    }
+
+    frames.reverse();
+    *stacks.entry(frames.join(";")).or_insert(0) += 1;
+    frames.clear();
+}
+
+// A frame is the address, the symbol and its offset, and the binary
+// it's in. Symbols can contain spaces, such as `<T as Trait>::f`.
+fn frame_name(frame: &str) -> &str {
+    let symbol = match frame.find(' ') {
+        Some(i) => &frame[i + 1..],
+        None => return frame,
+    };
+    let symbol = match symbol.rfind(" (") {
+        Some(i) => &symbol[..i],
+        None => symbol,
+    };
+
+    match symbol.rfind("+0x") {
+        Some(i) => &symbol[..i],
+        None => symbol,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn identical_stacks_are_counted_together() {
+        let script = "\
+playground    42 1234.567890:    1001001 cpu-clock:
+            55d0c0a1b2c3 playground::fib+0x13 (/playground/target/debug/playground)
+            55d0c0a1b2d4 playground::main+0x20 (/playground/target/debug/playground)
+
+playground    42 1234.568891:    1001001 cpu-clock:
+            55d0c0a1b2c7 playground::fib+0x17 (/playground/target/debug/playground)
+            55d0c0a1b2d4 playground::main+0x20 (/playground/target/debug/playground)
+
+playground    42 1234.569892:    1001001 cpu-clock:
+            55d0c0a1b2f0 <u32 as core::ops::Add>::add+0x5 (/playground/target/debug/playground)
+            55d0c0a1b2d4 playground::main+0x20 (/playground/target/debug/playground)
+";
+
+        assert_eq!(
+            fold(script),
+            "playground::main;<u32 as core::ops::Add>::add 1\nplayground::main;playground::fib 2\n",
+        );
+    }
+
+    #[test]
+    fn unknown_frames_are_kept() {
+        let script = "\
+playground    42 1234.567890:    1001001 cpu-clock:
+                       0 [unknown] ([unknown])
+            7f0011223344 __libc_start_main+0xe7 (/lib/x86_64-linux-gnu/libc-2.27.so)
+";
+
+        assert_eq!(fold(script), "__libc_start_main;[unknown] 1\n");
+        assert_eq!(fold(""), "");
+    }
+}
@@ -0,0 +1,57 @@
+// The code is built in `/playground` inside of the container, which
+// means nothing to the person who wrote it. Paths within the project
+// are made relative to it instead, such as `src/main.rs`.
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+
+pub fn normalize(output: &str) -> Cow<'_, str> {
+    lazy_static! {
+        // Neither `/playground-result`, where the sandbox collects its
+        // output, nor the project's URL in Cargo's package ids
+        static ref PROJECT_PATH: Regex = Regex::new(r"(^|[^/\w])/playground(?:/|([^\w/-]|$))").unwrap();
+    }
+
+    PROJECT_PATH.replace_all(output, |caps: &Captures<'_>| {
+        match caps.get(2) {
+            // The project itself, as in `Compiling playground (/playground)`
+            Some(end) => format!("{}.{}", &caps[1], end.as_str()),
+            None => caps[1].to_string(),
+        }
+    })
+}
+
+pub fn normalize_in_place(output: &mut String) {
+    if let Cow::Owned(normalized) = normalize(output) {
+        *output = normalized;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn no_sandbox_paths_remain() {
+        let stderr = "   Compiling playground v0.0.1 (/playground)\n\
+                      thread 'main' panicked at 'boom', /playground/src/main.rs:2:5\n\
+                      error: could not compile `playground`\n\
+                      Running `/playground/target/debug/playground`";
+
+        let normalized = normalize(stderr);
+
+        assert!(!normalized.contains("/playground/"), "was: {}", normalized);
+        assert_eq!(normalized, "   Compiling playground v0.0.1 (.)\n\
+                                thread 'main' panicked at 'boom', src/main.rs:2:5\n\
+                                error: could not compile `playground`\n\
+                                Running `target/debug/playground`");
+    }
+
+    #[test]
+    fn other_paths_are_left_alone() {
+        let output = "-o /playground-result/compilation path+file:///playground /tmp/playground/x";
+
+        assert_eq!(normalize(output), output);
+    }
+}
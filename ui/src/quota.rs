@@ -0,0 +1,107 @@
+// Limits how many requests each client can make in a day, which
+// catches sustained abuse that slips under any per-second limits
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: u64 = 60 * 60 * 24;
+
+#[derive(Debug)]
+pub struct DailyQuota {
+    limit: u32,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    day: u64,
+    used: HashMap<IpAddr, u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Exhausted;
+
+impl DailyQuota {
+    pub fn new(limit: u32) -> Self {
+        DailyQuota { limit, inner: Mutex::default() }
+    }
+
+    pub fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Counts a request that runs `units` sandboxes against the
+    /// client's quota, returning how many the client has left today.
+    /// Nothing is counted unless all of them are left. Every client's
+    /// quota is restored at midnight UTC.
+    pub fn take(&self, client: IpAddr, units: u32, now: SystemTime) -> Result<u32, Exhausted> {
+        let day = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() / SECONDS_PER_DAY);
+
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        if inner.day != day {
+            inner.day = day;
+            inner.used.clear();
+        }
+
+        let used = inner.used.entry(client).or_insert(0);
+        if units > self.limit - *used {
+            return Err(Exhausted);
+        }
+
+        *used += units;
+        Ok(self.limit - *used)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const ALICE: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 1));
+    const BOB: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(192, 0, 2, 2));
+
+    fn noon(day: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(day * SECONDS_PER_DAY + SECONDS_PER_DAY / 2)
+    }
+
+    #[test]
+    fn requests_past_the_quota_are_rejected() {
+        let quota = DailyQuota::new(2);
+
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Ok(1));
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Ok(0));
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Err(Exhausted));
+    }
+
+    #[test]
+    fn clients_have_separate_quotas() {
+        let quota = DailyQuota::new(1);
+
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Ok(0));
+        assert_eq!(quota.take(BOB, 1, noon(1)), Ok(0));
+    }
+
+    #[test]
+    fn quotas_are_restored_the_next_day() {
+        let quota = DailyQuota::new(1);
+
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Ok(0));
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Err(Exhausted));
+        assert_eq!(quota.take(ALICE, 1, noon(2)), Ok(0));
+    }
+
+    #[test]
+    fn requests_running_several_sandboxes_count_as_that_many() {
+        let quota = DailyQuota::new(4);
+
+        assert_eq!(quota.take(ALICE, 3, noon(1)), Ok(1));
+        assert_eq!(quota.take(ALICE, 3, noon(1)), Err(Exhausted));
+        assert_eq!(quota.take(ALICE, 1, noon(1)), Ok(0));
+    }
+}
@@ -0,0 +1,109 @@
+// Remembers the most recent problems running the sandbox so that
+// operators can spot a broken image or toolchain. Only the kind of
+// failure is kept, never the code that caused it.
+
+use lazy_static::lazy_static;
+use std::{collections::VecDeque, sync::Mutex, time::SystemTime};
+
+const MAX_RECENT_FAILURES: usize = 100;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Category {
+    /// The container didn't exit, even after being killed
+    Timeout,
+    /// The container was killed for using too much time or memory
+    Killed,
+    /// The compiler panicked instead of reporting an error
+    CompilerCrash,
+    /// Docker itself failed
+    ContainerRuntime,
+}
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Timeout => "timeout",
+            Category::Killed => "killed",
+            Category::CompilerCrash => "compiler-crash",
+            Category::ContainerRuntime => "container-runtime",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub category: Category,
+    pub at: SystemTime,
+}
+
+#[derive(Debug)]
+pub struct RecentFailures {
+    capacity: usize,
+    failures: Mutex<VecDeque<Failure>>,
+}
+
+impl RecentFailures {
+    pub fn new(capacity: usize) -> Self {
+        RecentFailures { capacity, failures: Mutex::default() }
+    }
+
+    pub fn record(&self, category: Category, at: SystemTime) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+
+        while failures.len() >= self.capacity {
+            failures.pop_front();
+        }
+        if self.capacity > 0 {
+            failures.push_back(Failure { category, at });
+        }
+    }
+
+    /// The newest failure is first
+    pub fn list(&self) -> Vec<Failure> {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        failures.iter().rev().cloned().collect()
+    }
+}
+
+pub fn global() -> &'static RecentFailures {
+    lazy_static! {
+        static ref RECENT_FAILURES: RecentFailures = RecentFailures::new(MAX_RECENT_FAILURES);
+    }
+
+    &RECENT_FAILURES
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn recorded_failures_are_listed_newest_first() {
+        let recent = RecentFailures::new(10);
+
+        recent.record(Category::Timeout, at(1));
+        recent.record(Category::CompilerCrash, at(2));
+
+        assert_eq!(recent.list(), [
+            Failure { category: Category::CompilerCrash, at: at(2) },
+            Failure { category: Category::Timeout, at: at(1) },
+        ]);
+    }
+
+    #[test]
+    fn the_oldest_failures_are_dropped() {
+        let recent = RecentFailures::new(2);
+
+        recent.record(Category::Timeout, at(1));
+        recent.record(Category::Killed, at(2));
+        recent.record(Category::ContainerRuntime, at(3));
+
+        let categories: Vec<_> = recent.list().into_iter().map(|f| f.category).collect();
+        assert_eq!(categories, [Category::ContainerRuntime, Category::Killed]);
+    }
+}
@@ -0,0 +1,61 @@
+// Lets clients ask for only the parts of a response that they use,
+// such as `?fields=stdout,success`
+
+use serde::Serialize;
+use serde_derive::Serialize;
+use serde_json::Value;
+
+/// Field names are separated by commas; empty names are ignored.
+pub fn parse(fields: &str) -> Vec<String> {
+    fields
+        .split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Projected<T> {
+    All(T),
+    Fields(Value),
+}
+
+/// Unknown field names are ignored. Responses that are not objects
+/// are returned unchanged.
+pub fn project<T: Serialize>(response: T, fields: Option<&[String]>) -> serde_json::Result<Projected<T>> {
+    let fields = match fields {
+        Some(fields) => fields,
+        None => return Ok(Projected::All(response)),
+    };
+
+    let value = match serde_json::to_value(&response)? {
+        Value::Object(all) => all.into_iter().filter(|(key, _)| fields.contains(key)).collect(),
+        other => other,
+    };
+
+    Ok(Projected::Fields(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Response {
+        success: bool,
+        stdout: String,
+        stderr: String,
+    }
+
+    #[test]
+    fn only_the_requested_fields_are_kept() {
+        let response = Response { success: true, stdout: "hello".into(), stderr: "".into() };
+        let fields = parse("success");
+
+        let projected = project(response, Some(&fields)).unwrap();
+
+        assert_eq!(serde_json::to_value(projected).unwrap(), serde_json::json!({ "success": true }));
+    }
+}
@@ -0,0 +1,128 @@
+// Keeps serialized responses below a configured size. The largest
+// strings, such as the generated code or the compiler's output, are
+// cut until the response fits.
+
+use serde_json::Value;
+use std::cmp;
+
+/// Added to object responses that had to be cut
+const TRUNCATED_FIELD: &str = "truncated";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Overflow {
+    Truncate,
+    Reject,
+}
+
+impl Overflow {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "truncate" => Some(Overflow::Truncate),
+            "reject" => Some(Overflow::Reject),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Limit {
+    pub max_bytes: usize,
+    pub overflow: Overflow,
+}
+
+/// Cuts the longest string until `size` reports that the value fits,
+/// returning if anything was removed. The value may still be too
+/// large if there are no strings left to cut.
+pub fn truncate(value: &mut Value, max_bytes: usize, size: impl Fn(&Value) -> usize) -> bool {
+    let mut current = size(value);
+    if current <= max_bytes {
+        return false;
+    }
+
+    if let Value::Object(fields) = value {
+        fields.insert(TRUNCATED_FIELD.into(), Value::Bool(true));
+        current = size(value);
+    }
+
+    while current > max_bytes {
+        let longest = match longest_string(value) {
+            Some(longest) if !longest.is_empty() => longest,
+            _ => break,
+        };
+
+        // Escaping makes the string larger once serialized, so the
+        // excess is scaled down to the bytes of the string itself
+        let excess = current - max_bytes;
+        let encoded = cmp::max(size(&Value::String(longest.clone())), 1);
+        let raw_excess = excess * longest.len() / encoded + 1;
+        let end = longest.len().saturating_sub(raw_excess);
+        cut_at_line(longest, end);
+
+        current = size(value);
+    }
+
+    true
+}
+
+fn longest_string(value: &mut Value) -> Option<&mut String> {
+    let candidates: Box<dyn Iterator<Item = &mut String> + '_> = match value {
+        Value::String(s) => return Some(s),
+        Value::Array(values) => Box::new(values.iter_mut().filter_map(longest_string)),
+        Value::Object(fields) => Box::new(fields.iter_mut().filter_map(|(_, v)| longest_string(v))),
+        _ => return None,
+    };
+
+    candidates.max_by_key(|s| s.len())
+}
+
+// Like the generated code, the string ends with the last complete
+// line that fits
+fn cut_at_line(s: &mut String, max_bytes: usize) {
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = s[..end].rfind('\n').map_or(end, |newline| newline + 1);
+
+    s.truncate(end);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn json_size(value: &Value) -> usize {
+        serde_json::to_vec(value).unwrap().len()
+    }
+
+    #[test]
+    fn oversized_llvm_ir_is_truncated_and_flagged() {
+        let ir = "define void @main() {\n  ret void\n}\n".repeat(1000);
+        let mut response = json!({
+            "success": true,
+            "code": ir,
+            "stdout": "",
+            "stderr": "   Compiling playground v0.0.1 (/playground)\n",
+        });
+
+        assert!(truncate(&mut response, 1024, json_size));
+
+        assert!(json_size(&response) <= 1024);
+        assert_eq!(response["truncated"], true);
+        assert_eq!(response["success"], true);
+        assert_eq!(response["stderr"], "   Compiling playground v0.0.1 (/playground)\n");
+        let code = response["code"].as_str().unwrap();
+        assert!(code.starts_with("define void @main()"));
+        assert!(code.ends_with('\n'));
+    }
+
+    #[test]
+    fn responses_that_fit_are_unchanged() {
+        let mut response = json!({ "success": true, "code": "define void @main()\n" });
+        let original = response.clone();
+
+        assert!(!truncate(&mut response, 1024, json_size));
+        assert_eq!(response, original);
+    }
+}
@@ -0,0 +1,75 @@
+// Sends each part of a response as soon as it is ready instead of
+// waiting for the slowest one. Every part is a line of its own, so
+// clients can read one JSON object per line as the lines arrive.
+
+use iron::response::WriteBody;
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+pub type Task = Box<dyn FnOnce() -> Vec<u8> + Send>;
+
+pub struct ResultStream {
+    lines: Receiver<Vec<u8>>,
+}
+
+impl ResultStream {
+    /// Each task runs on its own thread
+    pub fn spawn(tasks: Vec<Task>) -> Self {
+        let (tx, lines) = mpsc::channel();
+
+        for task in tasks {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                // The client may have gone away
+                let _ = tx.send(task());
+            });
+        }
+
+        ResultStream { lines }
+    }
+}
+
+impl WriteBody for ResultStream {
+    fn write_body(&mut self, res: &mut dyn Write) -> io::Result<()> {
+        for line in self.lines.iter() {
+            res.write_all(&line)?;
+            res.write_all(b"\n")?;
+            res.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn task(line: &'static str, delay_ms: u64) -> Task {
+        Box::new(move || {
+            thread::sleep(Duration::from_millis(delay_ms));
+            line.as_bytes().to_vec()
+        })
+    }
+
+    #[test]
+    fn parts_are_written_as_they_finish() {
+        let mut stream = ResultStream::spawn(vec![
+            task(r#"{"part":"compile"}"#, 300),
+            task(r#"{"part":"clippy"}"#, 0),
+            task(r#"{"part":"format"}"#, 150),
+        ]);
+
+        let mut body = Vec::new();
+        stream.write_body(&mut body).unwrap();
+
+        assert_eq!(
+            String::from_utf8(body).unwrap(),
+            "{\"part\":\"clippy\"}\n{\"part\":\"format\"}\n{\"part\":\"compile\"}\n",
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Records what is mounted where, so that the list of routes reflects
+// how the server was actually configured, including the endpoints that
+// were turned off. A mounted handler only sees the methods it was
+// mounted with.
+
+use iron::{headers::Allow, method::Method, modifiers::Header, prelude::*, status, Handler};
+use mount::Mount;
+use router::Router;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub path: String,
+    pub methods: Vec<Method>,
+}
+
+pub struct Routes {
+    mount: Mount,
+    routes: Vec<Route>,
+}
+
+impl Routes {
+    pub fn new() -> Self {
+        Routes { mount: Mount::new(), routes: Vec::new() }
+    }
+
+    pub fn mount<H: Handler>(&mut self, path: &str, methods: &[Method], handler: H) -> &mut Self {
+        self.record(path.to_string(), methods.to_vec());
+        self.mount.mount(path, AllowedMethods { methods: methods.to_vec(), handler });
+        self
+    }
+
+    pub fn get<H: Handler>(&mut self, path: &str, handler: H) -> &mut Self {
+        self.mount(path, &[Method::Get], handler)
+    }
+
+    pub fn post<H: Handler>(&mut self, path: &str, handler: H) -> &mut Self {
+        self.mount(path, &[Method::Post], handler)
+    }
+
+    /// Answers every request for the path with 404 and leaves it out
+    /// of the list, as if it had never been mounted. Otherwise a
+    /// handler mounted above it, such as for the UI files, would
+    /// answer instead.
+    pub fn mount_not_found(&mut self, path: &str) -> &mut Self {
+        self.mount.mount(path, |_: &mut Request<'_, '_>| Ok(Response::with(status::NotFound)));
+        self
+    }
+
+    /// `wrap` can add handlers around the router, such as to limit
+    /// the length of the paths it sees
+    pub fn mount_router<H, F>(&mut self, path: &str, router: RecordedRouter, wrap: F) -> &mut Self
+    where
+        H: Handler,
+        F: FnOnce(Router) -> H,
+    {
+        let RecordedRouter { router, routes } = router;
+
+        for (method, glob) in routes {
+            let full = match glob.as_str() {
+                "/" => path.to_string(),
+                glob => format!("{}{}", path, glob),
+            };
+            self.record(full, vec![method]);
+        }
+
+        self.mount.mount(path, wrap(router));
+        self
+    }
+
+    /// Mounts a handler that is given every route, including its own
+    pub fn mount_listing<H, F>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        H: Handler,
+        F: FnOnce(Vec<Route>) -> H,
+    {
+        self.record(path.to_string(), vec![Method::Get]);
+        let handler = handler(self.list());
+        self.mount.mount(path, AllowedMethods { methods: vec![Method::Get], handler });
+        self
+    }
+
+    // A path handled by several methods is listed once
+    fn record(&mut self, path: String, methods: Vec<Method>) {
+        match self.routes.iter_mut().find(|r| r.path == path) {
+            Some(route) => route.methods.extend(methods),
+            None => self.routes.push(Route { path, methods }),
+        }
+    }
+
+    pub fn list(&self) -> Vec<Route> {
+        let mut routes = self.routes.clone();
+        routes.sort_by(|a, b| a.path.cmp(&b.path));
+        routes
+    }
+
+    pub fn into_mount(self) -> Mount {
+        self.mount
+    }
+}
+
+/// Answers the methods that the handler wasn't mounted with with 405.
+/// `HEAD` is allowed wherever `GET` is.
+struct AllowedMethods<H> {
+    methods: Vec<Method>,
+    handler: H,
+}
+
+impl<H> AllowedMethods<H> {
+    fn allows(&self, method: &Method) -> bool {
+        self.methods.contains(method) || (*method == Method::Head && self.methods.contains(&Method::Get))
+    }
+}
+
+impl<H: Handler> Handler for AllowedMethods<H> {
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        if self.allows(&req.method) {
+            self.handler.handle(req)
+        } else {
+            Ok(Response::with((status::MethodNotAllowed, Header(Allow(self.methods.clone())))))
+        }
+    }
+}
+
+/// A `Router` that remembers the method and path of each route
+pub struct RecordedRouter {
+    router: Router,
+    routes: Vec<(Method, String)>,
+}
+
+impl RecordedRouter {
+    pub fn new() -> Self {
+        RecordedRouter { router: Router::new(), routes: Vec::new() }
+    }
+
+    pub fn get<H: Handler>(&mut self, glob: &str, handler: H, id: &str) -> &mut Self {
+        self.routes.push((Method::Get, glob.to_string()));
+        self.router.get(glob, handler, id);
+        self
+    }
+
+    pub fn post<H: Handler>(&mut self, glob: &str, handler: H, id: &str) -> &mut Self {
+        self.routes.push((Method::Post, glob.to_string()));
+        self.router.post(glob, handler, id);
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iron::Iron;
+    use std::{io::prelude::*, net::TcpStream};
+
+    fn handler(_: &mut Request<'_, '_>) -> IronResult<Response> {
+        Ok(Response::with(status::Ok))
+    }
+
+    #[test]
+    fn router_paths_are_listed_below_their_mount() {
+        let mut gist = RecordedRouter::new();
+        gist.post("/", handler, "gist_create");
+        gist.get("/:id", handler, "gist_get");
+
+        let mut routes = Routes::new();
+        routes.get("/meta/crates", handler);
+        routes.mount_router("/meta/gist", gist, |router| router);
+
+        assert_eq!(routes.list(), vec![
+            Route { path: "/meta/crates".into(), methods: vec![Method::Get] },
+            Route { path: "/meta/gist".into(), methods: vec![Method::Post] },
+            Route { path: "/meta/gist/:id".into(), methods: vec![Method::Get] },
+        ]);
+    }
+
+    #[test]
+    fn only_the_mounted_methods_reach_the_handler() {
+        let mut routes = Routes::new();
+        routes.post("/compile", handler);
+        routes.get("/meta/crates", handler);
+
+        let mut listening = Iron::new(routes.into_mount()).http("127.0.0.1:0").expect("Unable to start server");
+
+        let request = |method: &str, path: &str| {
+            let mut stream = TcpStream::connect(listening.socket).unwrap();
+            write!(stream, "{} {} HTTP/1.0\r\nContent-Length: 0\r\n\r\n", method, path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let posted = request("POST", "/compile");
+        assert!(posted.starts_with("HTTP/1.0 200"), "response was: {}", posted);
+
+        let fetched = request("GET", "/compile");
+        assert!(fetched.starts_with("HTTP/1.0 405"), "response was: {}", fetched);
+        assert!(fetched.contains("Allow: POST\r\n"), "response was: {}", fetched);
+
+        let head = request("HEAD", "/meta/crates");
+        assert!(head.starts_with("HTTP/1.0 200"), "response was: {}", head);
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+// rustdoc's JSON output is unstable and its layout changes between
+// format versions. The document is passed on to clients untouched;
+// only the crate's root module is read here, in a way that works for
+// both the older and newer layouts.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    pub format_version: u64,
+    /// The names of the items in the crate's root module
+    pub root_items: Vec<String>,
+}
+
+/// `None` when the document doesn't look like rustdoc's output
+pub fn summarize(doc: &Value) -> Option<Summary> {
+    let format_version = doc.get("format_version")?.as_u64()?;
+    let index = doc.get("index")?;
+    let root = lookup(index, doc.get("root")?)?;
+
+    let root_items = module_items(root)?
+        .iter()
+        .filter_map(|id| lookup(index, id))
+        .filter_map(|item| item.get("name")?.as_str())
+        .map(Into::into)
+        .collect();
+
+    Some(Summary { format_version, root_items })
+}
+
+// Ids used to be strings such as `"0:0"` and are now numbers, but the
+// keys of the index are always strings
+fn lookup<'a>(index: &'a Value, id: &Value) -> Option<&'a Value> {
+    match id {
+        Value::String(id) => index.get(id),
+        Value::Number(id) => index.get(id.to_string()),
+        _ => None,
+    }
+}
+
+// Older versions put a module's fields directly within `inner`, next
+// to a separate `kind`; newer ones nest them under the kind's name.
+fn module_items(item: &Value) -> Option<&Vec<Value>> {
+    let inner = item.get("inner")?;
+    let module = inner.get("module").unwrap_or(inner);
+    module.get("items")?.as_array()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn root_items_are_read_from_the_older_layout() {
+        let doc = json!({
+            "format_version": 10,
+            "root": "0:0",
+            "index": {
+                "0:0": { "name": "playground", "kind": "module", "inner": { "is_crate": true, "items": ["0:3", "0:4"] } },
+                "0:3": { "name": "Point", "kind": "struct", "inner": {} },
+                "0:4": { "name": "origin", "kind": "function", "inner": {} },
+            },
+        });
+
+        assert_eq!(summarize(&doc), Some(Summary {
+            format_version: 10,
+            root_items: vec!["Point".into(), "origin".into()],
+        }));
+    }
+
+    #[test]
+    fn root_items_are_read_from_the_newer_layout() {
+        let doc = json!({
+            "format_version": 39,
+            "root": 0,
+            "index": {
+                "0": { "name": "playground", "inner": { "module": { "is_crate": true, "items": [1, 2, 3] } } },
+                "1": { "name": "Point", "inner": { "struct": {} } },
+                "2": { "name": null, "inner": { "use": {} } },
+                "3": { "name": "origin", "inner": { "function": {} } },
+            },
+        });
+
+        assert_eq!(summarize(&doc), Some(Summary {
+            format_version: 39,
+            root_items: vec!["Point".into(), "origin".into()],
+        }));
+    }
+
+    #[test]
+    fn other_documents_are_not_summarized() {
+        assert_eq!(summarize(&json!({ "root": 0, "index": {} })), None);
+        assert_eq!(summarize(&json!({ "format_version": 39, "root": 0, "index": {} })), None);
+    }
+}
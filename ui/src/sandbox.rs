@@ -1,21 +1,328 @@
+use lazy_static::lazy_static;
+use rand::Rng;
 use serde_derive::Deserialize;
 use snafu::{ResultExt, Snafu};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
+    env,
     ffi::OsStr,
     fmt,
     fs::{self, File},
     io::{self, prelude::*, BufReader, ErrorKind},
+    iter,
+    mem,
     os::unix::fs::PermissionsExt,
     path::{Path, PathBuf},
+    process::Stdio,
     string,
-    time::Duration,
+    sync::Mutex,
+    thread,
+    time::{Duration, SystemTime},
 };
 use tempdir::TempDir;
 use tokio::process::Command;
 
-const DOCKER_PROCESS_TIMEOUT_SOFT: Duration = Duration::from_secs(10);
-const DOCKER_PROCESS_TIMEOUT_HARD: Duration = Duration::from_secs(12);
+use super::{cancellation::Registration, warm_pool::{UserRotation, WarmPool}};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_TIMEOUT_GRACE: Duration = Duration::from_secs(1);
+// How long the container has to exit once the soft timeout expires
+const DOCKER_PROCESS_TIMEOUT_GRACE: Duration = Duration::from_secs(2);
+const CANCELLATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// `docker run` exits with this status when it couldn't start the
+// container at all, such as when the daemon is down.
+const DOCKER_RUN_FAILURE_STATUS: i32 = 125;
+// What the entrypoint prints before anything else runs, so that
+// Docker's failures can be told apart from the program's own
+const CONTAINER_STARTED_MARKER: &[u8] = b"playground: the container started\n";
+// `docker exec` exits with this when the container isn't running, but
+// so could the user's program.
+const DOCKER_EXEC_FAILURE_STATUS: i32 = 1;
+// The entrypoint's `timeout --signal=KILL` and the kernel's OOM killer
+// both use SIGKILL, which the shell reports as this status. A program
+// can exit with it too, so it only counts while building.
+const KILLED_STATUS: i32 = 128 + 9;
+// `timeout` exits with this when the build stopped on its own during
+// the grace period.
+const TERMINATED_STATUS: i32 = 124;
+const COMPILER_CRASH_MARKERS: &[&[u8]] = &[b"error: internal compiler error", b"thread 'rustc' panicked"];
+// Macros and monomorphization report reaching the limit differently
+const RECURSION_LIMIT_MARKERS: &[&[u8]] = &[b"recursion limit reached while expanding", b"reached the recursion limit"];
+// What `std::thread::spawn` panics with when the pids limit is reached
+const THREAD_LIMIT_MARKERS: &[&[u8]] = &[b"failed to spawn thread"];
+// How Cargo reports that the crate itself, or its tests, didn't build
+const BUILD_FAILURE_MARKERS: &[&[u8]] = &[b"error: could not compile"];
+const REQUEST_ID_LABEL: &str = "playground.request-id";
+// Used when `PLAYGROUND_MAX_THREADS` is not set
+const DEFAULT_PIDS_LIMIT: u32 = 512;
+
+// The soft timeout for each kind of operation, in seconds. Operations
+// without their own setting use `PLAYGROUND_TIMEOUT_DEFAULT`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Timeouts {
+    default: Duration,
+    compile: Duration,
+    execute: Duration,
+    format: Duration,
+    clippy: Duration,
+    miri: Duration,
+    // How long a program has to exit after being asked to stop
+    grace: Duration,
+}
+
+impl Timeouts {
+    fn from_env(var: impl Fn(&str) -> Option<String>) -> Self {
+        let seconds = |name: &str| var(name).and_then(|v| v.parse().ok()).map(Duration::from_secs);
+
+        let default = seconds("PLAYGROUND_TIMEOUT_DEFAULT").unwrap_or(DEFAULT_TIMEOUT);
+        let timeout = |name: &str| seconds(name).unwrap_or(default);
+
+        Timeouts {
+            default,
+            compile: timeout("PLAYGROUND_TIMEOUT_COMPILE"),
+            execute: timeout("PLAYGROUND_TIMEOUT_EXECUTE"),
+            format: timeout("PLAYGROUND_TIMEOUT_FORMAT"),
+            clippy: timeout("PLAYGROUND_TIMEOUT_CLIPPY"),
+            miri: timeout("PLAYGROUND_TIMEOUT_MIRI"),
+            grace: seconds("PLAYGROUND_TIMEOUT_GRACE").unwrap_or(DEFAULT_TIMEOUT_GRACE),
+        }
+    }
+}
+
+const DEFAULT_MAX_STACK_SIZE_MB: u32 = 64;
+const DEFAULT_MAX_CODEGEN_UNITS: u32 = 16;
+const DEFAULT_MAX_JOBS: u32 = 4;
+const DEFAULT_MAX_CODE_OUTPUT_KB: usize = 1024;
+
+lazy_static! {
+    static ref TIMEOUTS: Timeouts = Timeouts::from_env(|name| env::var(name).ok());
+
+    static ref MAX_STACK_SIZE_MB: u32 = env::var("PLAYGROUND_MAX_STACK_SIZE_MB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_STACK_SIZE_MB);
+
+    static ref MAX_CODEGEN_UNITS: u32 = env::var("PLAYGROUND_MAX_CODEGEN_UNITS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CODEGEN_UNITS);
+
+    static ref MAX_JOBS: u32 = env::var("PLAYGROUND_MAX_JOBS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_JOBS);
+
+    // Applies to the assembly, LLVM IR, and MIR that is returned
+    static ref MAX_CODE_OUTPUT_BYTES: usize = env::var("PLAYGROUND_MAX_CODE_OUTPUT_KB")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CODE_OUTPUT_KB)
+        .saturating_mul(1024);
+
+    // A fraction of a core, as understood by `docker run --cpus`
+    static ref CPU_QUOTA: Option<f64> = env::var("PLAYGROUND_CPU_QUOTA")
+        .ok()
+        .and_then(|v| parse_cpu_quota(&v));
+
+    // Every process and thread in the container counts, including
+    // the compiler's own
+    static ref MAX_THREADS: Option<u32> = env::var("PLAYGROUND_MAX_THREADS")
+        .ok()
+        .and_then(|v| v.parse().ok());
+
+    // Cargo is only given these dependencies, so code can't use any
+    // other crate however it refers to it
+    static ref ALLOWED_CRATES: Option<String> = env::var("PLAYGROUND_ALLOWED_CRATES").ok();
+
+    static ref INCREMENTAL_CACHE_DIR: PathBuf = env::var_os("PLAYGROUND_INCREMENTAL_CACHE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join("playground-incremental"));
+
+    // How many running builds have each session's directory mounted
+    static ref INCREMENTAL_SESSIONS_IN_USE: Mutex<BTreeMap<PathBuf, usize>> = Default::default();
+
+    // How many containers each channel keeps started ahead of time
+    static ref WARM_POOL: WarmPool<Channel, WarmContainer> = WarmPool::new(
+        env::var("PLAYGROUND_WARM_POOL_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0)
+    );
+
+    // Programs in warm containers each run as the next of these users
+    static ref EXECUTION_USERS: Option<UserRotation> = env::var("PLAYGROUND_EXECUTION_UIDS")
+        .ok()
+        .and_then(|v| UserRotation::parse(&v));
+}
+
+fn parse_cpu_quota(value: &str) -> Option<f64> {
+    value.parse().ok().filter(|&cpus: &f64| cpus.is_finite() && cpus > 0.0)
+}
+
+const INCREMENTAL_CACHE_MAX_AGE: Duration = Duration::from_secs(30 * 60);
+const INCREMENTAL_CACHE_MAX_SESSIONS: usize = 100;
+const INCREMENTAL_LAST_USED: &str = ".last-used";
+const INCREMENTAL_SESSION_LEN: usize = 32;
+
+// Lets the containers that an earlier server left behind be found
+const WARM_CONTAINER_LABEL: &str = "org.rust-lang.playground.warm";
+// Resetting only runs a few short commands, and the container is
+// replaced rather than waited on when it takes any longer
+const WARM_CONTAINER_RESET_TIMEOUT: Duration = Duration::from_secs(3);
+const WARM_CONTAINER_ENTRYPOINT: &str = "/playground/tools/entrypoint.sh";
+// Runs an already built program with the entrypoint's timeouts. The
+// program's files are only readable by the user it runs as.
+const RUN_AS_USER_SCRIPT: &str = r#"
+echo "playground: the container started" >&2
+printf '     Running `%s`\n' "$3" >&2
+umask 077
+if [ "$1" -eq 0 ]; then
+    exec timeout --signal=KILL "$2" "$3"
+fi
+exec timeout --signal=TERM --kill-after="$1" "$2" "$3"
+"#;
+// Runs once the last program's processes have been killed and its
+// changes have been checked by `is_resettable_change`. The search path
+// is fixed so that nothing the program left behind is run.
+const WARM_CONTAINER_RESET_SCRIPT: &str = r#"
+set -eu
+export PATH=/usr/bin:/bin
+cd /playground
+
+for mode in debug release; do
+    rm -rf \
+       "target/${mode}/playground" \
+       "target/${mode}/playground.d" \
+       "target/${mode}/deps/playground-"* \
+       "target/${mode}/.fingerprint/playground-"* \
+       "target/${mode}/incremental/playground-"*
+done
+rm -f target/.rustc_info.json
+
+cp tools/Cargo.toml.orig Cargo.toml
+cp tools/Cargo.lock.orig Cargo.lock
+
+find /dev/shm -mindepth 1 -delete
+ipcrm --all
+"#;
+
+// Arbitrary unstable flags are a security concern, so every `-Z` flag
+// that the sandbox passes to the compiler must be listed here.
+pub const NIGHTLY_FLAGS: &[&str] = &[
+    NIGHTLY_FLAG_ASM_COMMENTS,
+    NIGHTLY_FLAG_MIR_OPT_LEVEL,
+    NIGHTLY_FLAG_INSTRUMENT_COVERAGE,
+    NIGHTLY_FLAG_TIME_PASSES,
+    NIGHTLY_FLAG_SANITIZER,
+    NIGHTLY_FLAG_DUMP_MIR,
+    NIGHTLY_FLAG_DUMP_MIR_DIR,
+    NIGHTLY_FLAG_UNSTABLE_OPTIONS,
+];
+
+const NIGHTLY_FLAG_ASM_COMMENTS: &str = "asm-comments";
+const NIGHTLY_FLAG_MIR_OPT_LEVEL: &str = "mir-opt-level";
+const NIGHTLY_FLAG_INSTRUMENT_COVERAGE: &str = "instrument-coverage";
+const NIGHTLY_FLAG_TIME_PASSES: &str = "time-passes";
+const NIGHTLY_FLAG_SANITIZER: &str = "sanitizer";
+const NIGHTLY_FLAG_DUMP_MIR: &str = "dump-mir";
+const NIGHTLY_FLAG_DUMP_MIR_DIR: &str = "dump-mir-dir";
+const NIGHTLY_FLAG_UNSTABLE_OPTIONS: &str = "unstable-options";
+
+const CARGO_JSON_MESSAGES: &str = "--message-format=json";
+
+const DEBUG_ARTIFACTS_NAME: &str = "build.tar.gz";
+
+// Written by the cargo-coverage wrapper in lcov format
+const COVERAGE_NAME: &str = "coverage";
+
+// Written by the trace runner as `strace -c` summarizes it
+const STRACE_NAME: &str = "strace";
+
+// Written by the profile runner as `perf script` prints the samples
+const PERF_SCRIPT_NAME: &str = "perf-script";
+
+// Written by the memory-report runner, in kilobytes
+const PEAK_RSS_NAME: &str = "peak-rss";
+
+// Written by the cargo-build-run wrapper; the marker only exists once
+// the build has succeeded and the program is about to run
+const BUILD_STDOUT_NAME: &str = "build-stdout";
+const BUILD_STDERR_NAME: &str = "build-stderr";
+const BUILD_SUCCEEDED_NAME: &str = "build-succeeded";
+
+// The sources, manifest, and build script logs; the compiled
+// dependencies are the same for every request.
+const DEBUG_ARTIFACTS_SCRIPT: &str = "status=$?; \
+    tar --create --gzip --ignore-failed-read --file /playground-result/build.tar.gz \
+    Cargo.toml Cargo.lock src $(find target -path '*/build/*' \\( -name output -o -name stderr \\)) 2>/dev/null; \
+    exit $status";
+
+// rustdoc always writes into the target directory, so the document
+// is copied out once it has been generated
+const DOC_JSON_NAME: &str = "doc.json";
+const DOC_JSON_COPY: &str = "cp target/doc/playground.json /playground-result/doc.json";
+
+// The compiler writes a file for each function that the pass ran on
+const MIR_DUMP_NAME: &str = "mir-dump";
+const MIR_DUMP_DIR_FLAG: &str = "dump-mir-dir=/playground-result/mir-dump";
+
+// Written by the cargo-wasm wrapper next to the textual format
+const WASM_MODULE_NAME: &str = "compilation.wasm";
+
+const COMPILATION_OUTPUT_NAME: &str = "compilation";
+const COMPILATION_OUTPUT: &str = "/playground-result/compilation";
+
+// The base manifest doesn't specify these, so Cargo's defaults apply
+// unless the request overrides them.
+const MANIFEST_DEFAULT_EDITION: Edition = Edition::Rust2015;
+const MANIFEST_RESOLVER: &str = "1";
+
+// 2020-01-01T00:00:00Z
+const DETERMINISTIC_EPOCH: u64 = 1_577_836_800;
+
+// Every timezone in the container's `tzdata` works, but a short list
+// keeps the requests easy to validate.
+pub const TIMEZONES: &[&str] = &[
+    "UTC",
+    "America/Los_Angeles",
+    "America/New_York",
+    "America/Sao_Paulo",
+    "Asia/Kolkata",
+    "Asia/Shanghai",
+    "Asia/Tokyo",
+    "Australia/Sydney",
+    "Europe/Berlin",
+    "Europe/London",
+    "Europe/Moscow",
+    "Pacific/Auckland",
+];
+
+// The groups that can be denied in addition to the compiler's own
+// defaults. Each name must be known to the oldest supported compiler.
+pub const LINT_GROUPS: &[&str] = &[
+    "future_incompatible",
+    "nonstandard_style",
+    "rust_2018_compatibility",
+    "rust_2018_idioms",
+    "unused",
+    "warnings",
+];
+
+// Other than the C locales, these must be generated when the
+// container is built; see `compiler/base/Dockerfile`.
+pub const LOCALES: &[&str] = &[
+    "C",
+    "C.UTF-8",
+    "de_DE.UTF-8",
+    "en_GB.UTF-8",
+    "en_US.UTF-8",
+    "es_ES.UTF-8",
+    "fr_FR.UTF-8",
+    "ja_JP.UTF-8",
+    "pt_BR.UTF-8",
+    "ru_RU.UTF-8",
+    "zh_CN.UTF-8",
+];
 
 #[derive(Debug, Deserialize)]
 struct CrateInformationInner {
@@ -45,6 +352,80 @@ pub struct Version {
     pub commit_date: String,
 }
 
+const HOST_TARGET_SUFFIX: &str = "-x86_64-unknown-linux-gnu";
+
+/// What has been installed into a channel's container
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    pub components: Vec<String>,
+    pub targets: Vec<String>,
+    /// Exact versions, such as `1.42.0`, installed next to the channel
+    pub toolchains: Vec<String>,
+    /// The unstable compiler features that the channel's compiler
+    /// actually accepts, such as `FEATURE_DOC_JSON`
+    pub features: Vec<String>,
+}
+
+// The components that the playground's features rely on
+pub const TOOLCHAIN_COMPONENTS: &[&str] = &["clippy", "llvm-tools", "miri", "rust-src", "rustfmt"];
+
+pub const FEATURE_DOC_JSON: &str = "doc-json";
+pub const FEATURE_COVERAGE: &str = "coverage";
+
+// Unstable options come and go between nightlies, so each one that a
+// feature relies on is tried out rather than assumed from the channel.
+// Each probe prints the feature's name when it works.
+fn feature_probes() -> String {
+    format!(
+        "cd /tmp; echo 'pub fn probe() {{}}' > probe.rs; \
+         rustdoc -Z {unstable} --output-format json -o probe-doc probe.rs >/dev/null 2>&1 && \
+         test -f probe-doc/probe.json && echo {doc_json}; \
+         rustc -Z help 2>/dev/null | grep -q -- '{instrument_coverage}' && \
+         test -x \"$(rustc --print sysroot)/lib/rustlib/x86_64-unknown-linux-gnu/bin/llvm-profdata\" && echo {coverage}; \
+         true",
+        unstable = NIGHTLY_FLAG_UNSTABLE_OPTIONS,
+        doc_json = FEATURE_DOC_JSON,
+        instrument_coverage = NIGHTLY_FLAG_INSTRUMENT_COVERAGE,
+        coverage = FEATURE_COVERAGE,
+    )
+}
+
+impl Capabilities {
+    /// Some components are only available with a `-preview` suffix
+    pub fn has_component(&self, name: &str) -> bool {
+        self.components.iter().any(|component| {
+            component == name || component.trim_end_matches("-preview") == name
+        })
+    }
+
+    pub fn has_toolchain(&self, version: &str) -> bool {
+        self.toolchains.iter().any(|toolchain| toolchain == version)
+    }
+
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.iter().any(|feature| feature == name)
+    }
+}
+
+/// Only exact versions can be pinned, not channels or dated nightlies
+pub fn is_toolchain_version(version: &str) -> bool {
+    let parts: Vec<_> = version.split('.').collect();
+    parts.len() == 3 && parts.iter().all(|part| {
+        !part.is_empty() && part.len() <= 4 && part.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+// Toolchains are listed along with the host's target triple, and the
+// channel's own is marked as the default or as an override
+fn parse_toolchain_list(list: &str) -> Vec<String> {
+    list.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|toolchain| toolchain.trim_end_matches(HOST_TARGET_SUFFIX))
+        .filter(|toolchain| is_toolchain_version(toolchain))
+        .map(String::from)
+        .collect()
+}
+
 #[derive(Debug, Snafu)]
 pub enum Error {
     #[snafu(display("Unable to create temporary directory: {}", source))]
@@ -59,12 +440,44 @@ pub enum Error {
     UnableToSetSourcePermissions { source: io::Error },
     #[snafu(display("Unable to execute the compiler: {}", source))]
     UnableToExecuteCompiler { source: io::Error },
+    #[snafu(display("The container runtime failed: {}", stderr))]
+    ContainerRuntimeFailed { stderr: String },
     #[snafu(display("Compiler execution took longer than {} ms", timeout.as_millis()))]
     CompilerExecutionTimedOut { source: tokio::time::Elapsed, timeout: Duration },
+    #[snafu(display("The request was cancelled"))]
+    Cancelled,
+    #[snafu(display("A request with the id {:?} is already running", id))]
+    RequestIdInUse { id: String },
+    #[snafu(display("Unable to start a warm container: {}", stderr))]
+    WarmContainerStartFailed { stderr: String },
+    #[snafu(display("The warm container is no longer running: {}", stderr))]
+    WarmContainerUnavailable { stderr: String },
+    #[snafu(display("The warm container changed {}, so it cannot be reused", path))]
+    WarmContainerTainted { path: String },
+    #[snafu(display("Unable to reset the warm container: {}", stderr))]
+    WarmContainerResetFailed { stderr: String },
+    #[snafu(display("{} processes of the last program are still running in the warm container", count))]
+    WarmContainerProcessesSurvived { count: usize },
+    #[snafu(display("Unable to create incremental cache directory: {}", source))]
+    UnableToCreateIncrementalDir { source: io::Error },
     #[snafu(display("Unable to read output file: {}", source))]
     UnableToReadOutput { source: io::Error },
+    #[snafu(display("The MIR pass {} did not run; some passes only run in release mode", pass))]
+    MirPassNotRun { pass: &'static str },
     #[snafu(display("Unable to read crate information: {}", source))]
     UnableToParseCrateInformation { source: ::serde_json::Error },
+    #[snafu(display("Unable to read the crates' lockfile: {}", source))]
+    UnableToParseCargoLock { source: ::toml::de::Error },
+    #[snafu(display("Unable to read rustdoc's JSON output: {}", source))]
+    UnableToParseDocJson { source: ::serde_json::Error },
+    #[snafu(display("Unable to read cargo metadata: {}", source))]
+    UnableToParseCargoMetadata { source: ::serde_json::Error },
+    #[snafu(display("Unable to write the dependency graph: {}", source))]
+    UnableToWriteGraph { source: io::Error },
+    #[snafu(display("Graphviz is not installed in the sandbox"))]
+    GraphvizUnavailable,
+    #[snafu(display("Unable to render the dependency graph: {}", stderr))]
+    GraphRenderingFailed { stderr: String },
     #[snafu(display("Output was not valid UTF-8: {}", source))]
     OutputNotUtf8 { source: string::FromUtf8Error },
     #[snafu(display("Output was missing"))]
@@ -77,6 +490,21 @@ pub enum Error {
     VersionDateMissing,
 }
 
+impl Error {
+    /// Problems with the host rather than with the user's code; see
+    /// the circuit breaker in `main`.
+    pub fn is_runtime_failure(&self) -> bool {
+        use self::Error::*;
+
+        match *self {
+            UnableToCreateTempDir { .. } |
+            UnableToExecuteCompiler { .. } |
+            ContainerRuntimeFailed { .. } => true,
+            _ => false,
+        }
+    }
+}
+
 pub type Result<T, E = Error> = ::std::result::Result<T, E>;
 
 pub struct Sandbox {
@@ -117,27 +545,108 @@ impl Sandbox {
     }
 
     pub fn compile(&self, req: &CompileRequest) -> Result<CompileResponse> {
-        self.write_source_code(&req.code)?;
+        self.compile_reporting(req, None)
+    }
 
-        let command = self.compile_command(req.target, req.channel, req.mode, req.tests, req);
+    /// Reports each crate as Cargo finishes building it, including
+    /// the dependencies that were already built. The build is stopped
+    /// once `on_progress` returns false.
+    pub fn compile_with_progress(&self, req: &CompileRequest, on_progress: &mut dyn FnMut(Progress) -> bool) -> Result<CompileResponse> {
+        // Cargo only describes what it builds in its JSON messages
+        let with_messages = CompileRequest { build_messages: true, ..req.clone() };
+        let mut resp = self.compile_reporting(&with_messages, Some(on_progress))?;
 
-        let output = run_command_with_timeout(command)?;
+        if !req.build_messages {
+            resp.build_messages = None;
+        }
+        Ok(resp)
+    }
 
-        // The compiler writes the file to a name like
-        // `compilation-3b75174cac3d47fb.ll`, so we just find the
-        // first with the right extension.
-        let file =
-            fs::read_dir(&self.output_dir)
-            .context(UnableToReadOutput)?
-            .flat_map(|entry| entry)
-            .map(|entry| entry.path())
-            .find(|path| path.extension() == Some(req.target.extension()));
+    fn compile_reporting(&self, req: &CompileRequest, on_progress: Option<&mut dyn FnMut(Progress) -> bool>) -> Result<CompileResponse> {
+        self.write_source_code(&req.code)?;
+        let _in_use = prepare_incremental_dir(req)?;
+
+        // A reported build is named so that it can be stopped
+        let container = on_progress.as_ref().map(|_| progress_container_name());
+        let (command, command_line) = if req.all_flavors {
+            self.compile_all_flavors_command(req, container.as_deref())
+        } else {
+            self.compile_command(req.target, req.channel, req.mode, req.tests, container.as_deref(), req)
+        };
 
-        let stdout = vec_to_str(output.stdout)?;
+        let output = match (on_progress, &container) {
+            (Some(on_progress), Some(container)) => {
+                let on_line = |line: &str| match super::diagnostics::artifact(line) {
+                    Some(artifact) => on_progress(Progress { target: artifact.target, fresh: artifact.fresh }),
+                    None => true,
+                };
+                run_command_reporting_lines(command, container, TIMEOUTS.compile, on_line)?
+            }
+            _ => run_command_with_timeout(command, TIMEOUTS.compile)?,
+        };
+
+        let mut stdout = vec_to_str(output.stdout)?;
         let mut stderr = vec_to_str(output.stderr)?;
 
+        let mut suggestions = Vec::new();
+        let mut raw_diagnostics = None;
+        let mut build_messages = None;
+        let mut diagnostic_summary = None;
+        let mut diagnostic_groups = None;
+
+        if req.wants_json_messages() {
+            let diagnostics = super::diagnostics::parse(&stdout);
+            stderr = super::diagnostics::splice_rendered(&stderr, &diagnostics.rendered);
+            if let Some(lines) = req.diagnostic_summary {
+                diagnostic_summary = Some(diagnostics.summarize(&stderr, lines));
+            }
+            stdout = diagnostics.other;
+            if req.suggestions {
+                suggestions = diagnostics.suggestions;
+            }
+            if req.raw_diagnostics {
+                raw_diagnostics = Some(diagnostics.raw);
+            }
+            if req.build_messages {
+                build_messages = Some(diagnostics.build);
+            }
+            if req.diagnostic_groups {
+                diagnostic_groups = Some(diagnostics.groups);
+            }
+        }
+
+        let mut flavors = Vec::new();
+
+        if let (CompileTarget::Assembly(_, demangle, process), true) = (req.target, req.all_flavors) {
+            for &flavor in &[AssemblyFlavor::Att, AssemblyFlavor::Intel] {
+                let code = self.read_compilation_output(flavor.output_name(), req.target)?;
+                let code = postprocess_assembly(code.unwrap_or_default(), demangle, process);
+                flavors.push((flavor, code));
+            }
+        }
+
+        let output_name = match req.target {
+            CompileTarget::Assembly(flavor, _, _) if req.all_flavors => flavor.output_name(),
+            _ => COMPILATION_OUTPUT_NAME,
+        };
+
+        let file = match req.target {
+            CompileTarget::Cfg | CompileTarget::TargetFeatures if output.status.success() => Some(mem::take(&mut stdout)),
+            CompileTarget::Mir(Some(MirStage::After(pass))) => match self.read_mir_dump(pass, "after")? {
+                None if output.status.success() => return MirPassNotRun { pass: pass.name() }.fail(),
+                dump => dump,
+            },
+            _ => self.read_compilation_output(output_name, req.target)?,
+        };
+
+        let artifact = match req.target {
+            _ if !req.artifact => None,
+            CompileTarget::Wasm => self.read_wasm_module()?,
+            _ => file.clone().map(String::into_bytes),
+        };
+
         let mut code = match file {
-            Some(file) => read(&file)?.unwrap_or_else(String::new),
+            Some(code) => code,
             None => {
                 // If we didn't find the file, it's *most* likely that
                 // the user's code was invalid. Tack on our own error
@@ -150,43 +659,259 @@ impl Sandbox {
             }
         };
 
-        if let CompileTarget::Assembly(_, demangle, process) = req.target {
+        let instruction_counts = match req.target {
+            CompileTarget::Assembly(..) if req.instruction_counts => Some(super::asm_cleanup::instruction_counts(&code)),
+            _ => None,
+        };
 
-            if demangle == DemangleAssembly::Demangle {
-                code = super::asm_cleanup::demangle_asm(&code);
-            }
+        let wasm_sections = match req.target {
+            CompileTarget::Wasm if req.wasm_sections => self.read_wasm_sections()?,
+            _ => None,
+        };
 
-            if process == ProcessAssembly::Filter {
-                code = super::asm_cleanup::filter_asm(&code);
+        if let CompileTarget::Assembly(_, demangle, process) = req.target {
+            code = postprocess_assembly(code, demangle, process);
+        }
+
+        let mut truncated = false;
+        if req.target.is_generated_code() {
+            truncated |= truncate_output(&mut code, *MAX_CODE_OUTPUT_BYTES);
+            for (_, code) in &mut flavors {
+                truncated |= truncate_output(code, *MAX_CODE_OUTPUT_BYTES);
             }
         }
 
+        let debug_artifacts = if req.debug_artifacts {
+            self.read_debug_artifacts()?
+        } else {
+            None
+        };
+
+        let passes = if req.time_passes {
+            Some(super::time_passes::parse(&stdout))
+        } else {
+            None
+        };
+
+        let success = output.status.success();
+        let error_category = if success { None } else { error_category(&stderr) };
+        record_build_failure(output.status, &stderr);
+
         Ok(CompileResponse {
-            success: output.status.success(),
+            success,
+            error_category,
             code,
             stdout,
             stderr,
+            flavors,
+            suggestions,
+            raw_diagnostics,
+            build_messages,
+            diagnostic_summary,
+            diagnostic_groups,
+            instruction_counts,
+            wasm_sections,
+            build_info: BuildInfo::new(req),
+            command_line: if req.command_line { Some(command_line) } else { None },
+            debug_artifacts,
+            artifact,
+            passes,
+            truncated,
         })
     }
 
+    // The dumps are named like `playground.main.005-000.ConstProp.after.mir`
+    // and are joined in name order, which keeps each function's together.
+    // The MIR is dumped both `before` and `after` the pass. A pass that
+    // didn't run, such as inlining in debug mode, leaves neither.
+    fn read_mir_dump(&self, pass: MirPass, when: &str) -> Result<Option<String>> {
+        let suffix = format!(".{}.{}.mir", pass.name(), when);
+
+        let entries = match fs::read_dir(self.output_dir.join(MIR_DUMP_NAME)) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            e => e.context(UnableToReadOutput)?,
+        };
+
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.to_string_lossy().ends_with(&suffix))
+            .collect();
+
+        if files.is_empty() {
+            return Ok(None);
+        }
+        files.sort();
+
+        let mut dump = Vec::new();
+        for file in files {
+            dump.push(read(&file)?.unwrap_or_default());
+        }
+
+        Ok(Some(dump.join("\n")))
+    }
+
+    // The compiler writes the file to a name like
+    // `compilation-3b75174cac3d47fb.ll`, so we just find the first
+    // with the right name and extension.
+    fn read_compilation_output(&self, name: &str, target: CompileTarget) -> Result<Option<String>> {
+        let file =
+            fs::read_dir(&self.output_dir)
+            .context(UnableToReadOutput)?
+            .flat_map(|entry| entry)
+            .map(|entry| entry.path())
+            .find(|path| {
+                path.extension() == Some(target.extension()) &&
+                    path.file_name().map_or(false, |f| f.to_string_lossy().starts_with(name))
+            });
+
+        match file {
+            Some(file) => Ok(Some(read(&file)?.unwrap_or_else(String::new))),
+            None => Ok(None),
+        }
+    }
+
+    fn read_debug_artifacts(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.output_dir.join(DEBUG_ARTIFACTS_NAME)) {
+            Ok(tarball) => Ok(Some(tarball)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(UnableToReadOutput),
+        }
+    }
+
+    fn read_wasm_sections(&self) -> Result<Option<Vec<super::wasm_sections::Section>>> {
+        Ok(self.read_wasm_module()?.map(|module| super::wasm_sections::parse(&module)))
+    }
+
+    // Missing when the build failed
+    fn read_wasm_module(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.output_dir.join(WASM_MODULE_NAME)) {
+            Ok(module) => Ok(Some(module)),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context(UnableToReadOutput),
+        }
+    }
+
+    fn read_coverage(&self) -> Result<Option<Vec<super::coverage::LineCoverage>>> {
+        let lcov = read(&self.output_dir.join(COVERAGE_NAME))?;
+        Ok(lcov.map(|lcov| super::coverage::parse_lcov(&lcov)))
+    }
+
+    // Missing when the program never ran or strace isn't installed
+    fn read_syscalls(&self) -> Result<Option<Vec<super::strace::Syscall>>> {
+        let summary = read(&self.output_dir.join(STRACE_NAME))?;
+        Ok(summary.map(|summary| super::strace::parse(&summary)))
+    }
+
+    // Missing when the program never ran or perf couldn't sample it
+    fn read_profile(&self) -> Result<Option<String>> {
+        let script = read(&self.output_dir.join(PERF_SCRIPT_NAME))?;
+        Ok(script.map(|script| super::profile::fold(&script)))
+    }
+
+    // Missing when the program never ran or time isn't installed. GNU
+    // time writes a note before the number when the program fails.
+    fn read_peak_rss(&self) -> Result<Option<u64>> {
+        let report = read(&self.output_dir.join(PEAK_RSS_NAME))?;
+        Ok(report.and_then(|report| report.lines().last().and_then(|kb| kb.trim().parse().ok())))
+    }
+
     pub fn execute(&self, req: &ExecuteRequest) -> Result<ExecuteResponse> {
-        self.write_source_code(&req.code)?;
-        let command = self.execute_command(req.channel, req.mode, req.tests, req);
+        let output = match execute_in_warm_container(req) {
+            Some(output) => output?,
+            None => {
+                self.write_source_code(&req.code)?;
+                let _in_use = prepare_incremental_dir(req)?;
+                let command = self.execute_command(req.channel, req.mode, req.tests, false, req);
+
+                run_execution_command(command, req)?
+            }
+        };
+
+        let coverage = if req.coverage { self.read_coverage()? } else { None };
+        let syscalls = if req.trace { self.read_syscalls()? } else { None };
+        let profile = if req.profile { self.read_profile()? } else { None };
+        let peak_rss_kb = if req.memory_report { self.read_peak_rss()? } else { None };
+        let stderr = vec_to_str(output.stderr)?;
+        let backtrace = if req.backtrace { super::backtrace::parse(&stderr) } else { Vec::new() };
 
-        let output = run_command_with_timeout(command)?;
+        let success = output.status.success();
+        let error_category = if success { None } else { error_category(&stderr) };
+        let compiled = success || !build_failed(&stderr);
+        record_build_failure(output.status, &stderr);
+        let tests_passed = tests_passed(req.tests, compiled, success);
 
         Ok(ExecuteResponse {
-            success: output.status.success(),
+            success,
+            compiled,
+            tests_passed,
+            error_category,
             stdout: vec_to_str(output.stdout)?,
-            stderr: vec_to_str(output.stderr)?,
+            stderr,
+            coverage,
+            syscalls,
+            profile,
+            peak_rss_kb,
+            backtrace,
+        })
+    }
+
+    /// Only runs the code if it builds, keeping the compiler's output
+    /// separate from the program's.
+    pub fn build_and_run(&self, req: &ExecuteRequest) -> Result<BuildRunResponse> {
+        self.write_source_code(&req.code)?;
+        let _in_use = prepare_incremental_dir(req)?;
+        let command = self.execute_command(req.channel, req.mode, req.tests, true, req);
+
+        let output = run_execution_command(command, req)?;
+
+        let mut build_stdout = read(&self.output_dir.join(BUILD_STDOUT_NAME))?.unwrap_or_default();
+        let mut build_stderr = read(&self.output_dir.join(BUILD_STDERR_NAME))?.unwrap_or_default();
+        let stdout = vec_to_str(output.stdout)?;
+        let stderr = vec_to_str(output.stderr)?;
+
+        if !self.output_dir.join(BUILD_SUCCEEDED_NAME).exists() {
+            // Anything that failed before the wrapper started, such
+            // as a missing target, belongs to the build
+            build_stdout.push_str(&stdout);
+            build_stderr.push_str(&stderr);
+
+            return Ok(BuildRunResponse {
+                build: BuildOutput { success: false, stdout: build_stdout, stderr: build_stderr },
+                run: None,
+            });
+        }
+
+        let syscalls = if req.trace { self.read_syscalls()? } else { None };
+        let profile = if req.profile { self.read_profile()? } else { None };
+        let peak_rss_kb = if req.memory_report { self.read_peak_rss()? } else { None };
+        let success = output.status.success();
+
+        Ok(BuildRunResponse {
+            build: BuildOutput { success: true, stdout: build_stdout, stderr: build_stderr },
+            run: Some(ExecuteResponse {
+                success,
+                compiled: true,
+                tests_passed: tests_passed(req.tests, true, success),
+                // Only the build can reach the recursion limit
+                error_category: None,
+                stdout,
+                backtrace: if req.backtrace { super::backtrace::parse(&stderr) } else { Vec::new() },
+                stderr,
+                coverage: None,
+                syscalls,
+                profile,
+                peak_rss_kb,
+            }),
         })
     }
 
     pub fn format(&self, req: &FormatRequest) -> Result<FormatResponse> {
         self.write_source_code(&req.code)?;
-        let command = self.format_command(req);
+        let command = self.format_command(req, false);
 
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.format)?;
 
         Ok(FormatResponse {
             success: output.status.success(),
@@ -196,16 +921,79 @@ impl Sandbox {
         })
     }
 
+    pub fn format_check(&self, req: &FormatRequest) -> Result<FormatCheckResponse> {
+        self.write_source_code(&req.code)?;
+        let command = self.format_command(req, true);
+
+        let output = run_command_with_timeout(command, TIMEOUTS.format)?;
+
+        Ok(FormatCheckResponse {
+            formatted: output.status.success(),
+            diff: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
     pub fn clippy(&self, req: &ClippyRequest) -> Result<ClippyResponse> {
         self.write_source_code(&req.code)?;
         let command = self.clippy_command(req);
 
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.clippy)?;
+
+        let mut stdout = vec_to_str(output.stdout)?;
+        let mut stderr = vec_to_str(output.stderr)?;
+        let mut suggestions = Vec::new();
+
+        if req.suggestions || req.user_code_only {
+            if req.user_code_only {
+                stdout = super::diagnostics::retain_user_code(&stdout);
+            }
+
+            let diagnostics = super::diagnostics::parse(&stdout);
+            stderr = super::diagnostics::splice_rendered(&stderr, &diagnostics.rendered);
+            stdout = diagnostics.other;
+            if req.suggestions {
+                suggestions = diagnostics.suggestions;
+            }
+        }
 
         Ok(ClippyResponse {
+            success: output.status.success(),
+            stdout,
+            stderr,
+            suggestions,
+        })
+    }
+
+    pub fn check(&self, req: &CheckRequest) -> Result<CheckResponse> {
+        self.write_source_code(&req.code)?;
+        let command = self.check_command(req);
+
+        let output = run_command_with_timeout(command, TIMEOUTS.compile)?;
+
+        Ok(CheckResponse {
+            success: output.status.success(),
+            stdout: vec_to_str(output.stdout)?,
+            stderr: vec_to_str(output.stderr)?,
+        })
+    }
+
+    pub fn doc_json(&self, req: &DocJsonRequest) -> Result<DocJsonResponse> {
+        self.write_source_code(&req.code)?;
+        let command = self.doc_json_command(req);
+
+        let output = run_command_with_timeout(command, TIMEOUTS.compile)?;
+
+        let doc = match read(&self.output_dir.join(DOC_JSON_NAME))? {
+            Some(doc) => Some(::serde_json::from_str(&doc).context(UnableToParseDocJson)?),
+            None => None,
+        };
+
+        Ok(DocJsonResponse {
             success: output.status.success(),
             stdout: vec_to_str(output.stdout)?,
             stderr: vec_to_str(output.stderr)?,
+            doc,
         })
     }
 
@@ -213,22 +1001,24 @@ impl Sandbox {
         self.write_source_code(&req.code)?;
         let command = self.miri_command(req);
 
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.miri)?;
+        let stderr = vec_to_str(output.stderr)?;
 
         Ok(MiriResponse {
             success: output.status.success(),
             stdout: vec_to_str(output.stdout)?,
-            stderr: vec_to_str(output.stderr)?,
+            errors: super::miri_errors::parse(&stderr),
+            stderr,
         })
     }
 
     pub fn crates(&self) -> Result<Vec<CrateInformation>> {
-        let mut command = basic_secure_docker_command();
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
         command.args(&[Channel::Nightly.container_name()]);
         command.args(&["cat", "crate-information.json"]);
 
         log::debug!("crates command is {:?}", command);
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
 
         let crate_info: Vec<CrateInformationInner> = ::serde_json::from_slice(&output.stdout).context(UnableToParseCrateInformation)?;
 
@@ -239,12 +1029,85 @@ impl Sandbox {
         Ok(crates)
     }
 
+    // The lockfile that every build starts from
+    pub fn cargo_lock(&self) -> Result<super::cargo_lock::Lockfile> {
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        command.args(&[Channel::Nightly.container_name()]);
+        command.args(&["cat", "Cargo.lock"]);
+
+        log::debug!("Cargo.lock command is {:?}", command);
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+        let contents = vec_to_str(output.stdout)?;
+
+        super::cargo_lock::Lockfile::parse(&contents).context(UnableToParseCargoLock)
+    }
+
+    pub fn clippy_lints(&self) -> Result<Vec<super::clippy_lints::Lint>> {
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        command.args(&["clippy", "clippy-driver", "-W", "help"]);
+
+        log::debug!("Clippy lints command is {:?}", command);
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+        let listing = vec_to_str(output.stdout)?;
+
+        Ok(super::clippy_lints::parse(&listing))
+    }
+
+    /// How the crates that code can use depend on each other
+    pub fn crate_graph(&self) -> Result<super::crate_graph::CrateGraph> {
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        command.args(&[Channel::Nightly.container_name()]);
+        command.args(&["cargo", "metadata", "--format-version", "1", "--offline"]);
+
+        log::debug!("crate graph command is {:?}", command);
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+
+        super::crate_graph::CrateGraph::from_metadata(&output.stdout)
+            .context(UnableToParseCargoMetadata)
+    }
+
+    pub fn render_graph(&self, dot: &str, format: GraphFormat) -> Result<Vec<u8>> {
+        let dot_file = self.output_dir.join("crates.dot");
+        fs::write(&dot_file, dot).context(UnableToWriteGraph)?;
+
+        let mut mount_output_dir = self.output_dir.as_os_str().to_os_string();
+        mount_output_dir.push(":");
+        mount_output_dir.push("/playground-result");
+
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        command.arg("--volume").arg(&mount_output_dir);
+        command.args(&[Channel::Nightly.container_name()]);
+        command.args(&["dot", format.dot_flag(), "/playground-result/crates.dot"]);
+
+        log::debug!("Graph rendering command is {:?}", command);
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+
+        // `timeout` exits with 127 when the command cannot be found
+        match output.status.code() {
+            Some(0) => Ok(output.stdout),
+            Some(127) => GraphvizUnavailable.fail(),
+            _ => GraphRenderingFailed { stderr: vec_to_str(output.stderr)? }.fail(),
+        }
+    }
+
     pub fn version(&self, channel: Channel) -> Result<Version> {
-        let mut command = basic_secure_docker_command();
+        self.rustc_version(channel, None)
+    }
+
+    /// The version of a toolchain installed next to the channel
+    pub fn pinned_version(&self, channel: Channel, toolchain_version: &str) -> Result<Version> {
+        self.rustc_version(channel, Some(toolchain_version))
+    }
+
+    fn rustc_version(&self, channel: Channel, toolchain_version: Option<&str>) -> Result<Version> {
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        if let Some(toolchain_version) = toolchain_version {
+            command.args(&["--env", &format!("RUSTUP_TOOLCHAIN={}", toolchain_version)]);
+        }
         command.args(&[channel.container_name()]);
         command.args(&["rustc", "--version", "--verbose"]);
 
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
         let version_output = vec_to_str(output.stdout)?;
 
         let mut info: BTreeMap<String, String> = version_output.lines().skip(1).filter_map(|line| {
@@ -262,28 +1125,57 @@ impl Sandbox {
         Ok(Version { release, commit_hash, commit_date })
     }
 
+    pub fn capabilities(&self, channel: Channel) -> Result<Capabilities> {
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
+        command.args(&[channel.container_name()]);
+        let script = format!(
+            "rustup component list --installed && echo --- && rustup target list --installed && echo --- && rustup toolchain list && echo --- && {}",
+            feature_probes(),
+        );
+        command.args(&["sh", "-c", &script]);
+
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+        let capabilities_output = vec_to_str(output.stdout)?;
+
+        let mut sections = capabilities_output.splitn(4, "---\n");
+        let components = sections.next().unwrap_or("");
+        let targets = sections.next().unwrap_or("");
+        let toolchains = sections.next().unwrap_or("");
+        let features = sections.next().unwrap_or("");
+
+        // Components are listed along with the host's target triple
+        let components = components
+            .lines()
+            .map(|component| component.trim_end_matches(HOST_TARGET_SUFFIX).to_string())
+            .collect();
+        let targets = targets.lines().map(String::from).collect();
+        let toolchains = parse_toolchain_list(toolchains);
+        let features = features.lines().map(String::from).collect();
+
+        Ok(Capabilities { components, targets, toolchains, features })
+    }
 
     pub fn version_rustfmt(&self) -> Result<Version> {
-        let mut command = basic_secure_docker_command();
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
         command.args(&["rustfmt", "cargo", "fmt", "--version"]);
         self.cargo_tool_version(command)
     }
 
     pub fn version_clippy(&self) -> Result<Version> {
-        let mut command = basic_secure_docker_command();
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
         command.args(&["clippy", "cargo", "clippy", "--version"]);
         self.cargo_tool_version(command)
     }
 
     pub fn version_miri(&self) -> Result<Version> {
-        let mut command = basic_secure_docker_command();
+        let mut command = basic_secure_docker_command(TIMEOUTS.default);
         command.args(&["miri", "cargo", "miri", "--version"]);
         self.cargo_tool_version(command)
     }
 
     // Parses versions of the shape `toolname 0.0.0 (0000000 0000-00-00)`
     fn cargo_tool_version(&self, command: Command) -> Result<Version> {
-        let output = run_command_with_timeout(command)?;
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
         let version_output = vec_to_str(output.stdout)?;
         let mut parts = version_output.split_whitespace().fuse().skip(1);
 
@@ -302,61 +1194,213 @@ impl Sandbox {
         Ok(())
     }
 
-    fn compile_command(&self, target: CompileTarget, channel: Channel, mode: Mode, tests: bool, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest) -> Command {
-        let mut cmd = self.docker_command(Some(req.crate_type()));
+    fn compile_command(&self, target: CompileTarget, channel: Channel, mode: Mode, tests: bool, container: Option<&str>, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest + LinkerRequest + IncrementalRequest + JsonMessagesRequest + DebugArtifactsRequest + TimePassesRequest + LintGroupsRequest + WithClippyRequest + ParallelismRequest + ToolchainRequest + DiagnosticFormatRequest) -> (Command, String) {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.compile);
+        cmd.apply_container_name(container);
         set_execution_environment(&mut cmd, Some(target), &req);
+        cmd.apply_toolchain(&req);
+        cmd.apply_incremental(&req, mode);
+        cmd.apply_with_clippy(&req);
+        cmd.apply_jobs(&req);
+
+        let codegen_units = codegen_units_flag(&req);
+        let dump_mir = dump_mir_flag(target);
+        let mut execution_cmd: Vec<&str> = build_execution_command(Some(target), channel, mode, &req, tests);
+
+        if req.wants_json_messages() {
+            execution_cmd.insert(2, CARGO_JSON_MESSAGES);
+        } else if let Some(flag) = req.diagnostic_format().message_format_flag() {
+            execution_cmd.insert(2, flag);
+        }
 
-        let execution_cmd = build_execution_command(Some(target), channel, mode, &req, tests);
+        // Only the playground crate is compiled, so the report only
+        // covers the user's code
+        if req.time_passes() {
+            execution_cmd.extend(&["-Z", NIGHTLY_FLAG_TIME_PASSES]);
+        }
 
-        cmd.arg(&channel.container_name()).args(&execution_cmd);
+        for &group in req.deny_lint_groups() {
+            execution_cmd.extend(&["-D", group]);
+        }
+
+        if let Some(codegen_units) = &codegen_units {
+            execution_cmd.extend(&["-C", codegen_units]);
+        }
+
+        if let Some(dump_mir) = &dump_mir {
+            execution_cmd.extend(&["-Z", dump_mir, "-Z", MIR_DUMP_DIR_FLAG]);
+        }
+
+        let command_line = execution_cmd.join(" ");
+
+        cmd.arg(&channel.container_name());
+
+        if req.debug_artifacts() {
+            let script = format!("{}; {}", command_line, DEBUG_ARTIFACTS_SCRIPT);
+            cmd.args(&["bash", "-c", &script]);
+        } else {
+            cmd.args(&execution_cmd);
+        }
 
         log::debug!("Compilation command is {:?}", cmd);
 
-        cmd
+        (cmd, command_line)
     }
 
-    fn execute_command(&self, channel: Channel, mode: Mode, tests: bool, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest) -> Command {
-        let mut cmd = self.docker_command(Some(req.crate_type()));
-        set_execution_environment(&mut cmd, None, &req);
+    // Compiles each assembly flavor in turn inside of one container;
+    // only the playground crate itself is rebuilt the second time.
+    fn compile_all_flavors_command(&self, req: &CompileRequest, container: Option<&str>) -> (Command, String) {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.compile);
+        cmd.apply_container_name(container);
+        set_execution_environment(&mut cmd, Some(req.target), req);
+        cmd.apply_toolchain(req);
+        cmd.apply_incremental(req, req.mode);
+        cmd.apply_with_clippy(req);
+        cmd.apply_jobs(req);
+
+        let codegen_units = codegen_units_flag(req);
+        let script = [AssemblyFlavor::Att, AssemblyFlavor::Intel]
+            .iter()
+            .enumerate()
+            .map(|(idx, &flavor)| {
+                let target = match req.target {
+                    CompileTarget::Assembly(_, demangle, process) => CompileTarget::Assembly(flavor, demangle, process),
+                    target => target,
+                };
+
+                let mut execution_cmd: Vec<&str> = build_execution_command(Some(target), req.channel, req.mode, req, req.tests);
+
+                // Both compilations report the same diagnostics
+                if idx == 0 && req.wants_json_messages() {
+                    execution_cmd.insert(2, CARGO_JSON_MESSAGES);
+                } else if let Some(flag) = req.diagnostic_format.message_format_flag() {
+                    execution_cmd.insert(2, flag);
+                }
+
+                for &group in &req.deny_lint_groups {
+                    execution_cmd.extend(&["-D", group]);
+                }
+
+                if let Some(codegen_units) = &codegen_units {
+                    execution_cmd.extend(&["-C", codegen_units]);
+                }
+
+                execution_cmd
+                    .into_iter()
+                    .map(|arg| if arg == COMPILATION_OUTPUT { flavor.output_path() } else { arg })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(" && ");
+
+        let command_line = script.clone();
+        let script = if req.debug_artifacts {
+            format!("{}; {}", script, DEBUG_ARTIFACTS_SCRIPT)
+        } else {
+            script
+        };
+
+        cmd.arg(&req.channel.container_name()).args(&["bash", "-c", &script]);
+
+        log::debug!("Compilation command is {:?}", cmd);
 
-        let execution_cmd = build_execution_command(None, channel, mode, &req, tests);
+        (cmd, command_line)
+    }
+
+    fn execute_command(&self, channel: Channel, mode: Mode, tests: bool, build_first: bool, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest + LinkerRequest + DeterministicRequest + StackSizeRequest + IncrementalRequest + SeedRequest + LocaleRequest + CoverageRequest + StaticTargetRequest + SanitizerRequest + TraceRequest + ProfileRequest + MemoryReportRequest + ToolchainRequest + CancellationRequest + LoggedRequestIdRequest + EnvironmentRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.execute);
+        set_execution_environment(&mut cmd, None, &req);
+        cmd.apply_toolchain(&req);
+        cmd.apply_deterministic(&req);
+        cmd.apply_stack_size(&req);
+        cmd.apply_incremental(&req, mode);
+        cmd.apply_seed(&req);
+        cmd.apply_locale(&req);
+        cmd.apply_static_target(&req);
+        cmd.apply_sanitizer(&req);
+        cmd.apply_trace(&req);
+        cmd.apply_profile(&req);
+        cmd.apply_memory_report(&req);
+        cmd.apply_cancellation(&req);
+        cmd.apply_logged_request_id(&req);
+        cmd.apply_environment(&req);
+
+        let execution_cmd = build_wrapped_execution_command(channel, mode, tests, build_first, &req);
 
         cmd.arg(&channel.container_name()).args(&execution_cmd);
 
-        log::debug!("Execution command is {:?}", cmd);
+        match req.logged_request_id() {
+            Some(id) => log::debug!("[{}] Execution command is {:?}", id, cmd),
+            None => log::debug!("Execution command is {:?}", cmd),
+        }
 
         cmd
     }
 
-    fn format_command(&self, req: impl EditionRequest) -> Command {
+    fn format_command(&self, req: impl EditionRequest, check: bool) -> Command {
         let crate_type = CrateType::Binary;
 
-        let mut cmd = self.docker_command(Some(crate_type));
+        let mut cmd = self.docker_command(Some(crate_type), TIMEOUTS.format);
 
         cmd.apply_edition(req);
 
         cmd.arg("rustfmt").args(&["cargo", "fmt"]);
 
+        if check {
+            cmd.args(&["--", "--check"]);
+        }
+
         log::debug!("Formatting command is {:?}", cmd);
 
         cmd
     }
 
-    fn clippy_command(&self, req: impl CrateTypeRequest + EditionRequest) -> Command {
-        let mut cmd = self.docker_command(Some(req.crate_type()));
+    fn clippy_command(&self, req: impl CrateTypeRequest + EditionRequest + SuggestionsRequest + UserCodeRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.clippy);
 
         cmd.apply_crate_type(&req);
         cmd.apply_edition(&req);
 
         cmd.arg("clippy").args(&["cargo", "clippy"]);
+        if req.suggestions() || req.user_code_only() {
+            cmd.arg(CARGO_JSON_MESSAGES);
+        }
 
         log::debug!("Clippy command is {:?}", cmd);
 
         cmd
     }
 
+    fn check_command(&self, req: &CheckRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.compile);
+
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        cmd.arg(req.channel.container_name()).args(&["cargo", "check"]);
+
+        log::debug!("Check command is {:?}", cmd);
+
+        cmd
+    }
+
+    fn doc_json_command(&self, req: &DocJsonRequest) -> Command {
+        let mut cmd = self.docker_command(Some(req.crate_type()), TIMEOUTS.compile);
+
+        cmd.apply_crate_type(req);
+        cmd.apply_edition(req);
+
+        let script = format!("cargo rustdoc -- -Z {} --output-format json && {}", NIGHTLY_FLAG_UNSTABLE_OPTIONS, DOC_JSON_COPY);
+        cmd.arg(req.channel.container_name()).args(&["bash", "-c", &script]);
+
+        log::debug!("Doc JSON command is {:?}", cmd);
+
+        cmd
+    }
+
     fn miri_command(&self, req: impl EditionRequest) -> Command {
-        let mut cmd = self.docker_command(None);
+        let mut cmd = self.docker_command(None, TIMEOUTS.miri);
         cmd.apply_edition(req);
 
         cmd.arg("miri").args(&["cargo", "miri-playground"]);
@@ -366,7 +1410,7 @@ impl Sandbox {
         cmd
     }
 
-    fn docker_command(&self, crate_type: Option<CrateType>) -> Command {
+    fn docker_command(&self, crate_type: Option<CrateType>, timeout: Duration) -> Command {
         let crate_type = crate_type.unwrap_or(CrateType::Binary);
 
         let mut mount_input_file = self.input_file.as_os_str().to_os_string();
@@ -378,7 +1422,7 @@ impl Sandbox {
         mount_output_dir.push(":");
         mount_output_dir.push("/playground-result");
 
-        let mut cmd = basic_secure_docker_command();
+        let mut cmd = basic_secure_docker_command(timeout);
 
         cmd
             .arg("--volume").arg(&mount_input_file)
@@ -388,12 +1432,432 @@ impl Sandbox {
     }
 }
 
-fn basic_secure_docker_command() -> Command {
-    let mut cmd = Command::new("docker");
+fn build_wrapped_execution_command(channel: Channel, mode: Mode, tests: bool, build_first: bool, req: impl CrateTypeRequest + CoverageRequest + StaticTargetRequest + SanitizerRequest) -> Vec<&'static str> {
+    let mut execution_cmd = if req.coverage() && !build_first {
+        build_coverage_command(mode)
+    } else {
+        build_execution_command(None, channel, mode, &req, tests)
+    };
 
-    cmd
-        .arg("run")
-        .arg("--rm")
+    // The cargo-build-run wrapper wraps the original subcommand
+    if build_first {
+        execution_cmd.insert(1, "build-run");
+    }
+
+    // The cargo-static wrapper adds the musl target
+    if req.static_target() {
+        execution_cmd.insert(1, "static");
+    }
+
+    // The cargo-sanitize wrapper adds the host target
+    if req.sanitizer().is_some() {
+        execution_cmd.insert(1, "sanitize");
+    }
+
+    execution_cmd
+}
+
+lazy_static! {
+    static ref PERFMON_SUPPORTED: bool = probe_perfmon();
+}
+
+/// Docker only knows the capability that profiling needs from 20.10
+/// on, and refuses to start any container that asks for it before
+/// then. Docker is only asked the first time.
+pub fn profiling_supported() -> bool {
+    *PERFMON_SUPPORTED
+}
+
+fn probe_perfmon() -> bool {
+    let mut command = Command::new("docker");
+    command
+        .args(&["run", "--rm", "--cap-drop=ALL", "--cap-add=PERFMON"])
+        .args(&["--entrypoint", "/bin/true"])
+        .arg(Channel::Stable.container_name());
+
+    match run_command_with_timeout(command, TIMEOUTS.default) {
+        Ok(output) => output.status.success(),
+        Err(e) => {
+            log::info!("Docker refused the PERFMON capability: {}", e);
+            false
+        }
+    }
+}
+
+/// Starts the warm pool's containers for every channel, stopping any
+/// that an earlier server left running.
+pub fn fill_warm_pool() {
+    if WARM_POOL.size() == 0 {
+        return;
+    }
+
+    if let Err(e) = stop_stale_warm_containers() {
+        log::warn!("Unable to stop the warm containers of an earlier server: {}", e);
+    }
+
+    for &channel in &[Channel::Stable, Channel::Beta, Channel::Nightly] {
+        for _ in 0..WARM_POOL.size() {
+            start_warm_container(channel);
+        }
+    }
+}
+
+fn stop_stale_warm_containers() -> Result<()> {
+    let mut list = Command::new("docker");
+    list.args(&["ps", "--quiet", "--filter", &format!("label={}", WARM_CONTAINER_LABEL)]);
+    let output = run_command_with_timeout(list, TIMEOUTS.default)?;
+
+    let ids = vec_to_str(output.stdout)?;
+    let ids: Vec<_> = ids.split_whitespace().collect();
+    if !ids.is_empty() {
+        let mut kill = Command::new("docker");
+        kill.arg("kill").args(&ids);
+        run_command_with_timeout(kill, TIMEOUTS.default)?;
+    }
+
+    Ok(())
+}
+
+fn start_warm_container(channel: Channel) {
+    match WarmContainer::start(channel) {
+        // A full pool drops, and so stops, the container
+        Ok(container) => drop(WARM_POOL.put(channel, container)),
+        Err(e) => log::warn!("Unable to start a warm container for {:?}: {}", channel, e),
+    }
+}
+
+// Only options that are set through the environment can be given to a
+// container that is already running. A program that runs as another
+// user is run without Cargo, which some options rely on.
+fn can_use_warm_container(req: &ExecuteRequest) -> bool {
+    let runs_without_cargo = !req.tests && !req.deterministic && !req.static_target && req.sanitizer.is_none();
+
+    req.crate_type == CrateType::Binary &&
+        req.stack_size_mb.is_none() &&
+        req.incremental_session.is_none() &&
+        req.request_id.is_none() &&
+        !req.coverage &&
+        !req.trace &&
+        !req.profile &&
+        !req.memory_report &&
+        req.toolchain_version.is_none() &&
+        (EXECUTION_USERS.is_none() || runs_without_cargo)
+}
+
+/// `None` when there is no warm container to use, so the program
+/// needs a new container instead.
+fn execute_in_warm_container(req: &ExecuteRequest) -> Option<Result<std::process::Output>> {
+    if !can_use_warm_container(req) {
+        return None;
+    }
+
+    let container = WARM_POOL.take(&req.channel)?;
+    let user = EXECUTION_USERS.as_ref().map(UserRotation::next);
+    let output = container.execute(req, user);
+    recycle_warm_container(container);
+
+    match output {
+        Err(Error::WarmContainerUnavailable { .. }) => None,
+        output => Some(output),
+    }
+}
+
+// The reset happens in the background so that the response isn't
+// delayed by it. A container that can't be reset is replaced.
+fn recycle_warm_container(container: WarmContainer) {
+    thread::spawn(move || {
+        let channel = container.channel;
+
+        match container.reset() {
+            Ok(()) => drop(WARM_POOL.put(channel, container)),
+            Err(e) => {
+                log::info!("Replacing the warm container {}: {}", container.name, e);
+                drop(container);
+                start_warm_container(channel);
+            }
+        }
+    });
+}
+
+/// A container that was started ahead of time and waits for programs
+/// to run. It is stopped when dropped.
+#[derive(Debug)]
+struct WarmContainer {
+    name: String,
+    channel: Channel,
+    #[allow(dead_code)]
+    scratch: TempDir,
+    input_file: PathBuf,
+    output_dir: PathBuf,
+    // The users that programs have run as since the last reset
+    users: Mutex<BTreeSet<u32>>,
+}
+
+impl WarmContainer {
+    fn start(channel: Channel) -> Result<Self> {
+        let scratch = TempDir::new("playground-warm").context(UnableToCreateTempDir)?;
+        let input_file = scratch.path().join("input.rs");
+        let output_dir = scratch.path().join("output");
+
+        fs::write(&input_file, "").context(UnableToCreateSourceFile)?;
+        fs::set_permissions(&input_file, wide_open_permissions()).context(UnableToSetSourcePermissions)?;
+        fs::create_dir(&output_dir).context(UnableToCreateOutputDir)?;
+        fs::set_permissions(&output_dir, wide_open_permissions()).context(UnableToSetOutputPermissions)?;
+
+        let unique = scratch.path().file_name().unwrap_or_default().to_string_lossy();
+        let name = format!("playground-warm-{}", unique.replace('.', "-"));
+
+        let mut mount_input_file = input_file.as_os_str().to_os_string();
+        mount_input_file.push(":");
+        mount_input_file.push("/playground/");
+        mount_input_file.push(CrateType::Binary.file_name());
+
+        let mut mount_output_dir = output_dir.as_os_str().to_os_string();
+        mount_output_dir.push(":");
+        mount_output_dir.push("/playground-result");
+
+        let mut command = basic_secure_docker_command(TIMEOUTS.execute);
+        command
+            .arg("--detach")
+            .args(&["--name", &name])
+            .args(&["--label", WARM_CONTAINER_LABEL])
+            .arg("--volume").arg(&mount_input_file)
+            .arg("--volume").arg(&mount_output_dir)
+            .args(&["--entrypoint", "sleep"])
+            .arg(channel.container_name())
+            .arg("infinity");
+
+        log::debug!("Warm container command is {:?}", command);
+        let output = run_command_with_timeout(command, TIMEOUTS.default)?;
+
+        if !output.status.success() {
+            let stderr = vec_to_str(output.stderr)?;
+            return WarmContainerStartFailed { stderr }.fail();
+        }
+
+        Ok(WarmContainer { name, channel, scratch, input_file, output_dir, users: Mutex::default() })
+    }
+
+    fn execute(&self, req: &ExecuteRequest, user: Option<u32>) -> Result<std::process::Output> {
+        fs::write(&self.input_file, &req.code).context(UnableToCreateSourceFile)?;
+
+        let user = match user {
+            Some(user) => user,
+            None => {
+                let execution_cmd = build_wrapped_execution_command(req.channel, req.mode, req.tests, false, req);
+                return self.exec(req, None, WARM_CONTAINER_ENTRYPOINT, &execution_cmd);
+            }
+        };
+
+        self.users.lock().unwrap_or_else(|e| e.into_inner()).insert(user);
+
+        // Cargo writes to the playground's own files, so only the
+        // program itself runs as the user
+        let mut build_cmd = build_execution_command(None, req.channel, req.mode, req, false);
+        build_cmd[1] = "build";
+        let build = self.exec(req, None, WARM_CONTAINER_ENTRYPOINT, &build_cmd)?;
+        if !build.status.success() {
+            return Ok(build);
+        }
+
+        let grace = TIMEOUTS.grace.as_secs().to_string();
+        let timeout = TIMEOUTS.execute.as_secs().to_string();
+        let program = format!("/playground/target/{}/playground", req.mode.target_dir());
+        let run_cmd = ["-c", RUN_AS_USER_SCRIPT, "sh", &grace, &timeout, &program];
+        let mut run = self.exec(req, Some(user), "/bin/sh", &run_cmd)?;
+
+        // Cargo's messages come first, as they do with `cargo run`
+        let mut stderr = build.stderr;
+        stderr.append(&mut run.stderr);
+        run.stderr = stderr;
+
+        Ok(run)
+    }
+
+    fn exec(&self, req: &ExecuteRequest, user: Option<u32>, program: &str, args: &[&str]) -> Result<std::process::Output> {
+        let mut cmd = Command::new("docker");
+        cmd
+            .arg("exec")
+            .args(&["--workdir", "/playground"])
+            .args(&["--env", &format!("PLAYGROUND_TIMEOUT={}", TIMEOUTS.execute.as_secs())])
+            .args(&["--env", &format!("PLAYGROUND_TIMEOUT_GRACE={}", TIMEOUTS.grace.as_secs())]);
+        if let Some(user) = user {
+            cmd.args(&["--user", &format!("{0}:{0}", user)]);
+        }
+        apply_allowed_crates(&mut cmd);
+        set_execution_environment(&mut cmd, None, req);
+        cmd.apply_deterministic(req);
+        cmd.apply_seed(req);
+        cmd.apply_locale(req);
+        cmd.apply_static_target(req);
+        cmd.apply_sanitizer(req);
+        cmd.apply_environment(req);
+        cmd.kill_on_drop(true);
+
+        cmd.arg(&self.name).arg(program).args(args);
+
+        log::debug!("Warm execution command is {:?}", cmd);
+        let output = run_command_with_timeout(cmd, TIMEOUTS.execute)?;
+
+        // The user's program could print the same, but then it only
+        // loses the warm container.
+        if output.status.code() == Some(DOCKER_EXEC_FAILURE_STATUS) && output.stderr.starts_with(b"Error response from daemon: ") {
+            let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+            return WarmContainerUnavailable { stderr }.fail();
+        }
+
+        Ok(output)
+    }
+
+    /// Stops everything the last program left running and undoes its
+    /// changes, refusing when it changed something that can't be
+    /// undone.
+    fn reset(&self) -> Result<()> {
+        // Every capability was dropped, so not even root may signal
+        // another user's processes; each user stops its own instead.
+        // The container's init process ignores the signal.
+        let users = mem::take(&mut *self.users.lock().unwrap_or_else(|e| e.into_inner()));
+        let users = iter::once(None).chain(users.into_iter().map(Some));
+        for user in users {
+            let mut kill = Command::new("docker");
+            kill.arg("exec");
+            if let Some(user) = user {
+                kill.args(&["--user", &format!("{0}:{0}", user)]);
+            }
+            kill.args(&[&self.name, "/bin/kill", "-KILL", "-1"]);
+            run_command_with_timeout(kill, WARM_CONTAINER_RESET_TIMEOUT)?;
+        }
+
+        let running = self.running_processes()?;
+        if running > 1 {
+            return WarmContainerProcessesSurvived { count: running - 1 }.fail();
+        }
+
+        let mut diff = Command::new("docker");
+        diff.args(&["diff", &self.name]);
+        let output = run_command_with_timeout(diff, WARM_CONTAINER_RESET_TIMEOUT)?;
+        if !output.status.success() {
+            let stderr = vec_to_str(output.stderr)?;
+            return WarmContainerResetFailed { stderr }.fail();
+        }
+
+        // Each line is the kind of change, then the path
+        let changes = vec_to_str(output.stdout)?;
+        if let Some(path) = changes.lines().filter_map(|line| line.get(2..)).find(|path| !is_resettable_change(path)) {
+            return WarmContainerTainted { path }.fail();
+        }
+
+        let mut reset = Command::new("docker");
+        reset.args(&["exec", &self.name, "/bin/sh", "-c", WARM_CONTAINER_RESET_SCRIPT]);
+        let output = run_command_with_timeout(reset, WARM_CONTAINER_RESET_TIMEOUT)?;
+        if !output.status.success() {
+            let stderr = vec_to_str(output.stderr)?;
+            return WarmContainerResetFailed { stderr }.fail();
+        }
+
+        fs::write(&self.input_file, "").context(UnableToCreateSourceFile)?;
+        for entry in fs::read_dir(&self.output_dir).context(UnableToReadOutput)? {
+            let path = entry.context(UnableToReadOutput)?.path();
+            let removed = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+            removed.context(UnableToCreateOutputDir)?;
+        }
+
+        Ok(())
+    }
+
+    // Killed processes stay behind as zombies, as the init process
+    // doesn't reap them, but they can no longer do anything
+    fn running_processes(&self) -> Result<usize> {
+        let mut top = Command::new("docker");
+        top.args(&["top", &self.name, "-eo", "pid,stat"]);
+        let output = run_command_with_timeout(top, WARM_CONTAINER_RESET_TIMEOUT)?;
+        if !output.status.success() {
+            let stderr = vec_to_str(output.stderr)?;
+            return WarmContainerResetFailed { stderr }.fail();
+        }
+
+        let processes = vec_to_str(output.stdout)?;
+        let running = processes
+            .lines()
+            .skip(1)
+            .filter(|line| !line.split_whitespace().nth(1).unwrap_or("").starts_with('Z'))
+            .count();
+
+        Ok(running)
+    }
+}
+
+impl Drop for WarmContainer {
+    fn drop(&mut self) {
+        let mut kill = Command::new("docker");
+        kill.args(&["kill", &self.name]);
+
+        // The container was started with `--rm`, so stopping it also
+        // removes it
+        if let Err(e) = run_command_with_timeout(kill, TIMEOUTS.default) {
+            log::warn!("Unable to stop the warm container {}: {}", self.name, e);
+        }
+    }
+}
+
+// The changes that running the user's crate makes, all of which the
+// reset script undoes. Cargo's own artifacts for the crate are removed
+// so that the next build can't reuse them.
+fn is_resettable_change(path: &str) -> bool {
+    const DIRECTORIES: &[&str] = &["/tmp", "/playground", "/playground/.cargo", "/playground/src", "/playground/target"];
+    const RESTORED: &[&str] = &[
+        "/playground/Cargo.lock",
+        "/playground/Cargo.toml",
+        "/playground/.cargo/.package-cache",
+        // Where the input file is mounted
+        "/playground/src/main.rs",
+        "/playground/target/.rustc_info.json",
+    ];
+    const TARGET_DIR: &str = "/playground/target/";
+
+    if DIRECTORIES.contains(&path) || RESTORED.contains(&path) {
+        return true;
+    }
+
+    if !path.starts_with(TARGET_DIR) {
+        return false;
+    }
+
+    let mut components = path[TARGET_DIR.len()..].splitn(3, '/');
+    match components.next() {
+        Some("debug") | Some("release") => {}
+        _ => return false,
+    }
+
+    match (components.next(), components.next()) {
+        (None, _) => true,
+        (Some("playground"), None) | (Some("playground.d"), None) | (Some(".cargo-lock"), None) => true,
+        (Some("deps"), None) | (Some(".fingerprint"), None) | (Some("incremental"), None) => true,
+        (Some("deps"), Some(entry)) | (Some(".fingerprint"), Some(entry)) | (Some("incremental"), Some(entry)) => {
+            entry.starts_with("playground-")
+        }
+        _ => false,
+    }
+}
+
+fn run_execution_command(command: Command, req: &ExecuteRequest) -> Result<std::process::Output> {
+    match &req.request_id {
+        Some(id) => {
+            let registration = super::cancellation::global()
+                .register(id)
+                .map_err(|_| Error::RequestIdInUse { id: id.clone() })?;
+            run_cancellable_command(command, TIMEOUTS.execute, &registration)
+        }
+        None => run_command_with_timeout(command, TIMEOUTS.execute),
+    }
+}
+
+fn basic_secure_docker_command(timeout: Duration) -> Command {
+    let mut cmd = Command::new("docker");
+
+    cmd
+        .arg("run")
+        .arg("--rm")
         .arg("--cap-drop=ALL")
         // Needed to allow overwriting the file
         .arg("--cap-add=DAC_OVERRIDE")
@@ -402,17 +1866,48 @@ fn basic_secure_docker_command() -> Command {
         .args(&["--net", "none"])
         .args(&["--memory", "256m"])
         .args(&["--memory-swap", "320m"])
-        .args(&["--env", &format!("PLAYGROUND_TIMEOUT={}", DOCKER_PROCESS_TIMEOUT_SOFT.as_secs())]);
+        .args(&["--env", &format!("PLAYGROUND_TIMEOUT={}", timeout.as_secs())])
+        .args(&["--env", &format!("PLAYGROUND_TIMEOUT_GRACE={}", TIMEOUTS.grace.as_secs())]);
+
+    if let Some(max_threads) = *MAX_THREADS {
+        cmd.args(&["--pids-limit", &max_threads.to_string()]);
+    } else if cfg!(feature = "fork-bomb-prevention") {
+        cmd.args(&["--pids-limit", &DEFAULT_PIDS_LIMIT.to_string()]);
+    }
 
-    if cfg!(feature = "fork-bomb-prevention") {
-        cmd.args(&["--pids-limit", "512"]);
+    if let Some(cpus) = *CPU_QUOTA {
+        cmd.args(&["--cpus", &cpus.to_string()]);
     }
 
+    apply_allowed_crates(&mut cmd);
+
     cmd.kill_on_drop(true);
 
     cmd
 }
 
+fn apply_allowed_crates(cmd: &mut Command) {
+    if let Some(crates) = &*ALLOWED_CRATES {
+        cmd.args(&["--env", &format!("PLAYGROUND_ALLOWED_CRATES={}", crates)]);
+    }
+}
+
+// Only the playground crate is affected; the dependencies were
+// built ahead of time
+fn codegen_units_flag(req: impl ParallelismRequest) -> Option<String> {
+    req.codegen_units()
+        .map(|units| format!("codegen-units={}", units.max(1).min(*MAX_CODEGEN_UNITS)))
+}
+
+// The pass name is chosen by the user, so it can't be part of the
+// static command
+fn dump_mir_flag(target: CompileTarget) -> Option<String> {
+    match target {
+        CompileTarget::Mir(Some(MirStage::After(pass))) => Some(format!("{}={}", NIGHTLY_FLAG_DUMP_MIR, pass.name())),
+        _ => None,
+    }
+}
+
 fn build_execution_command(target: Option<CompileTarget>, channel: Channel, mode: Mode, req: impl CrateTypeRequest, tests: bool) -> Vec<&'static str> {
     use self::CompileTarget::*;
     use self::CrateType::*;
@@ -433,7 +1928,7 @@ fn build_execution_command(target: Option<CompileTarget>, channel: Channel, mode
     }
 
     if let Some(target) = target {
-        cmd.extend(&["--", "-o", "/playground-result/compilation"]);
+        cmd.extend(&["--", "-o", COMPILATION_OUTPUT]);
 
         match target {
             Assembly(flavor, _, _) => {
@@ -444,7 +1939,7 @@ fn build_execution_command(target: Option<CompileTarget>, channel: Channel, mode
                 // Enable extra assembly comments for nightly builds
                 if let Channel::Nightly = channel {
                     cmd.push("-Z");
-                    cmd.push("asm-comments");
+                    cmd.push(NIGHTLY_FLAG_ASM_COMMENTS);
                 }
 
                 cmd.push("-C");
@@ -454,15 +1949,72 @@ fn build_execution_command(target: Option<CompileTarget>, channel: Channel, mode
                 }
             },
             LlvmIr => cmd.push("--emit=llvm-ir"),
-            Mir => cmd.push("--emit=mir"),
+            Mir(stage) => {
+                cmd.push("--emit=mir");
+
+                if let Some(opt_level) = stage.and_then(|s| s.mir_opt_level()) {
+                    cmd.push("-Z");
+                    cmd.push(opt_level);
+                }
+            },
             Wasm => { /* handled by cargo-wasm wrapper */ },
+            DepInfo => cmd.push("--emit=dep-info"),
+            // rustc prints these to stdout instead of compiling
+            Cfg => cmd.extend(&["--print", "cfg"]),
+            TargetFeatures => cmd.extend(&["--print", "target-features"]),
          }
     }
 
     cmd
 }
 
-fn set_execution_environment(cmd: &mut Command, target: Option<CompileTarget>, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest) {
+// The cargo-coverage wrapper builds and runs the tests, so coverage
+// is only available for test runs on the nightly channel. The wrapper
+// passes the arguments on to rustc, including the instrumentation flag.
+fn build_coverage_command(mode: Mode) -> Vec<&'static str> {
+    let mut cmd = vec!["cargo", "coverage"];
+
+    if mode == Mode::Release {
+        cmd.push("--release");
+    }
+
+    cmd.extend(&["--", "-Z", NIGHTLY_FLAG_INSTRUMENT_COVERAGE]);
+
+    cmd
+}
+
+fn postprocess_assembly(mut code: String, demangle: DemangleAssembly, process: ProcessAssembly) -> String {
+    if demangle == DemangleAssembly::Demangle {
+        code = super::asm_cleanup::demangle_asm(&code);
+    }
+
+    match process {
+        ProcessAssembly::Filter => code = super::asm_cleanup::filter_asm(&code),
+        ProcessAssembly::EntryPoint => code = super::asm_cleanup::entry_point_asm(&code),
+        ProcessAssembly::Raw => {}
+    }
+
+    code
+}
+
+/// Cuts the output at the last complete line that fits, returning if
+/// anything was removed.
+fn truncate_output(output: &mut String, max_bytes: usize) -> bool {
+    if output.len() <= max_bytes {
+        return false;
+    }
+
+    let mut end = max_bytes;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = output[..end].rfind('\n').map_or(end, |newline| newline + 1);
+
+    output.truncate(end);
+    true
+}
+
+fn set_execution_environment(cmd: &mut Command, target: Option<CompileTarget>, req: impl CrateTypeRequest + EditionRequest + BacktraceRequest + LinkerRequest) {
     use self::CompileTarget::*;
 
     if let Some(Wasm) = target {
@@ -473,6 +2025,107 @@ fn set_execution_environment(cmd: &mut Command, target: Option<CompileTarget>, r
     cmd.apply_crate_type(&req);
     cmd.apply_edition(&req);
     cmd.apply_backtrace(&req);
+    cmd.apply_linker(&req);
+}
+
+/// Whether the value has the form of a session that this server issues
+pub fn is_incremental_session(session: &str) -> bool {
+    session.len() == INCREMENTAL_SESSION_LEN && session.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Sessions name a directory on the host, so they are issued by the
+/// server as 128 random bits rather than chosen by the client. A
+/// requested session is only resumed if its directory still exists;
+/// otherwise a new one is issued.
+pub fn incremental_session(requested: Option<&str>) -> String {
+    match requested {
+        Some(session) if is_incremental_session(session) && INCREMENTAL_CACHE_DIR.join(session).is_dir() => session.into(),
+        _ => format!("{:032x}", rand::thread_rng().gen::<u128>()),
+    }
+}
+
+// Each session gets its own directory so that one user's incremental
+// artifacts are never visible to another. The directory can't be
+// evicted until the returned value is dropped.
+fn prepare_incremental_dir(req: impl IncrementalRequest) -> Result<Option<IncrementalSessionInUse>> {
+    let session = match req.incremental_session() {
+        Some(session) => session,
+        None => return Ok(None),
+    };
+
+    let root = &*INCREMENTAL_CACHE_DIR;
+    fs::create_dir_all(root).context(UnableToCreateIncrementalDir)?;
+
+    let dir = root.join(session);
+    let in_use = {
+        let mut in_use = INCREMENTAL_SESSIONS_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+        *in_use.entry(dir.clone()).or_insert(0) += 1;
+        evict_incremental_dirs(root, INCREMENTAL_CACHE_MAX_AGE, INCREMENTAL_CACHE_MAX_SESSIONS, &in_use);
+        IncrementalSessionInUse(dir.clone())
+    };
+
+    fs::create_dir_all(&dir).context(UnableToCreateIncrementalDir)?;
+    fs::set_permissions(&dir, wide_open_permissions()).context(UnableToCreateIncrementalDir)?;
+    fs::write(dir.join(INCREMENTAL_LAST_USED), "").context(UnableToCreateIncrementalDir)?;
+
+    Ok(Some(in_use))
+}
+
+struct IncrementalSessionInUse(PathBuf);
+
+impl Drop for IncrementalSessionInUse {
+    fn drop(&mut self) {
+        let mut in_use = INCREMENTAL_SESSIONS_IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = in_use.get_mut(&self.0) {
+            *count -= 1;
+            if *count == 0 {
+                in_use.remove(&self.0);
+            }
+        }
+    }
+}
+
+// Removes sessions that have not been used recently, then the least
+// recently used sessions until at most `max_sessions` remain. Sessions
+// that a running build has mounted are left alone.
+fn evict_incremental_dirs(root: &Path, max_age: Duration, max_sessions: usize, in_use: &BTreeMap<PathBuf, usize>) {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Unable to read incremental cache directory: {}", e);
+            return;
+        }
+    };
+
+    let last_used = |dir: &Path| {
+        fs::metadata(dir.join(INCREMENTAL_LAST_USED))
+            .or_else(|_| fs::metadata(dir))
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    };
+
+    let mut sessions: Vec<_> = entries
+        .flat_map(|entry| entry)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !in_use.contains_key(path))
+        .map(|path| (last_used(&path), path))
+        .collect();
+
+    sessions.sort();
+    let max_sessions = max_sessions.saturating_sub(in_use.len());
+
+    let now = SystemTime::now();
+    let stale = sessions
+        .iter()
+        .take_while(|(used, _)| now.duration_since(*used).map_or(false, |age| age >= max_age))
+        .count();
+    let stale = stale.max(sessions.len().saturating_sub(max_sessions));
+
+    for (_, path) in &sessions[..stale] {
+        if let Err(e) = fs::remove_dir_all(path) {
+            log::warn!("Unable to evict incremental cache {}: {}", path.display(), e);
+        }
+    }
 }
 
 fn read(path: &Path) -> Result<Option<String>> {
@@ -489,74 +2142,410 @@ fn read(path: &Path) -> Result<Option<String>> {
 }
 
 #[tokio::main]
-async fn run_command_with_timeout(mut command: Command) -> Result<std::process::Output> {
-    let timeout = DOCKER_PROCESS_TIMEOUT_HARD;
+async fn run_command_with_timeout(command: Command, timeout: Duration) -> Result<std::process::Output> {
+    run_command(command, timeout, None).await
+}
+
+// Each line of stdout is reported as soon as it is read
+#[tokio::main]
+// Stops the named container as soon as `on_line` returns false, such
+// as when the client that the lines are reported to has gone away.
+async fn run_command_reporting_lines(mut command: Command, container: &str, timeout: Duration, mut on_line: impl FnMut(&str) -> bool) -> Result<std::process::Output> {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let timeout = timeout + TIMEOUTS.grace + DOCKER_PROCESS_TIMEOUT_GRACE;
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    let stopped = std::cell::Cell::new(false);
+
+    let output = tokio::time::timeout(timeout, async {
+        let mut child = command.spawn()?;
+        let mut stdout = tokio::io::BufReader::new(child.stdout.take().expect("stdout was not piped"));
+        let mut stderr = child.stderr.take().expect("stderr was not piped");
+
+        let read_stdout = async {
+            let mut all = Vec::new();
+            let mut line = Vec::new();
+            while stdout.read_until(b'\n', &mut line).await? != 0 {
+                if !on_line(String::from_utf8_lossy(&line).trim_end_matches('\n')) {
+                    stopped.set(true);
+                    return Err(ErrorKind::BrokenPipe.into());
+                }
+                all.extend_from_slice(&line);
+                line.clear();
+            }
+            Ok::<_, io::Error>(all)
+        };
+        let read_stderr = async {
+            let mut all = Vec::new();
+            stderr.read_to_end(&mut all).await?;
+            Ok(all)
+        };
 
-    tokio::time::timeout(timeout, command.output())
-        .await
-        .context(CompilerExecutionTimedOut { timeout })?
-        .context(UnableToExecuteCompiler)
+        let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+        let status = child.await?;
+        Ok(std::process::Output { status, stdout, stderr })
+    }).await;
+
+    if stopped.get() {
+        // Stopping the `docker` client leaves the container running
+        kill_container(container).await;
+        return Cancelled.fail();
+    }
+
+    check_command_output(output, timeout)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum AssemblyFlavor {
-    Att,
-    Intel,
+#[tokio::main]
+async fn run_cancellable_command(command: Command, timeout: Duration, registration: &Registration<'_>) -> Result<std::process::Output> {
+    run_command(command, timeout, Some(registration)).await
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum DemangleAssembly {
-    Demangle,
-    Mangle,
+async fn run_command(mut command: Command, timeout: Duration, registration: Option<&Registration<'_>>) -> Result<std::process::Output> {
+    let timeout = timeout + TIMEOUTS.grace + DOCKER_PROCESS_TIMEOUT_GRACE;
+    let output = tokio::time::timeout(timeout, command.output());
+
+    let output = match registration {
+        Some(registration) => tokio::select! {
+            output = output => output,
+            _ = cancellation_requested(registration) => {
+                // Stopping the `docker` client leaves the container running
+                kill_container(&cancellable_container_name(registration.id())).await;
+                return Cancelled.fail();
+            }
+        },
+        None => output.await,
+    };
+
+    check_command_output(output, timeout)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum ProcessAssembly {
-    Filter,
-    Raw,
+fn check_command_output(
+    output: std::result::Result<io::Result<std::process::Output>, tokio::time::Elapsed>,
+    timeout: Duration,
+) -> Result<std::process::Output> {
+    let output = output
+        .context(CompilerExecutionTimedOut { timeout })
+        .and_then(|output| output.context(UnableToExecuteCompiler))
+        .and_then(|mut output| {
+            // Once the container has started, the status and the output
+            // could have come from the user's program.
+            let started = strip_started_marker(&mut output.stderr);
+            if !started && output.status.code() == Some(DOCKER_RUN_FAILURE_STATUS) {
+                let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+                return ContainerRuntimeFailed { stderr }.fail();
+            }
+            Ok(output)
+        });
+
+    if let Err(e) = &output {
+        if let Some(category) = failure_category(e) {
+            super::recent_failures::global().record(category, SystemTime::now());
+        }
+    }
+
+    output
 }
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum CompileTarget {
-    Assembly(AssemblyFlavor, DemangleAssembly, ProcessAssembly),
-    LlvmIr,
-    Mir,
-    Wasm,
+
+fn strip_started_marker(stderr: &mut Vec<u8>) -> bool {
+    if stderr.starts_with(CONTAINER_STARTED_MARKER) {
+        stderr.drain(..CONTAINER_STARTED_MARKER.len());
+        true
+    } else {
+        false
+    }
 }
 
-impl CompileTarget {
-    fn extension(&self) -> &'static OsStr {
-        let ext = match *self {
-            CompileTarget::Assembly(_, _, _) => "s",
-            CompileTarget::LlvmIr            => "ll",
-            CompileTarget::Mir               => "mir",
-            CompileTarget::Wasm              => "wat",
-        };
-        OsStr::new(ext)
+async fn cancellation_requested(registration: &Registration<'_>) {
+    while !registration.is_cancelled() {
+        tokio::time::delay_for(CANCELLATION_POLL_INTERVAL).await;
     }
 }
 
-impl fmt::Display for CompileTarget {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use self::CompileTarget::*;
+async fn kill_container(name: &str) {
+    let killed = Command::new("docker").args(&["kill", name]).output().await;
 
-        match *self {
-            Assembly(_, _, _) => "assembly".fmt(f),
-            LlvmIr            => "LLVM IR".fmt(f),
-            Mir               => "Rust MIR".fmt(f),
-            Wasm              => "WebAssembly".fmt(f),
-        }
+    if let Err(e) = killed {
+        log::warn!("Unable to kill the container {}: {}", name, e);
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub enum Channel {
-    Stable,
-    Beta,
-    Nightly,
+fn cancellable_container_name(id: &str) -> String {
+    format!("playground-request-{}", id)
 }
 
-impl Channel {
-    fn container_name(&self) -> &'static str {
+fn progress_container_name() -> String {
+    format!("playground-progress-{:032x}", rand::thread_rng().gen::<u128>())
+}
+
+// Only what the sandbox saw for itself: its own timer running out, or
+// Docker failing before the container started
+fn failure_category(error: &Error) -> Option<super::recent_failures::Category> {
+    use super::recent_failures::Category;
+
+    match error {
+        Error::CompilerExecutionTimedOut { .. } => Some(Category::Timeout),
+        e if e.is_runtime_failure() => Some(Category::ContainerRuntime),
+        _ => None,
+    }
+}
+
+// Once the program has started, the status and the output are its own
+// to choose, so only a build that never got that far is categorized.
+fn build_failure_category(status: std::process::ExitStatus, stderr: &str) -> Option<super::recent_failures::Category> {
+    use super::recent_failures::Category;
+
+    if stderr.lines().any(is_running_line) {
+        return None;
+    }
+
+    match status.code() {
+        Some(KILLED_STATUS) | Some(TERMINATED_STATUS) => Some(Category::Killed),
+        _ if contains_any(stderr.as_bytes(), COMPILER_CRASH_MARKERS) => Some(Category::CompilerCrash),
+        _ => None,
+    }
+}
+
+fn record_build_failure(status: std::process::ExitStatus, stderr: &str) {
+    if let Some(category) = build_failure_category(status, stderr) {
+        super::recent_failures::global().record(category, SystemTime::now());
+    }
+}
+
+// A crash of the compiler can mention anything, so it's checked first
+fn error_category(stderr: &str) -> Option<ErrorCategory> {
+    if contains_any(stderr.as_bytes(), COMPILER_CRASH_MARKERS) {
+        Some(ErrorCategory::CompilerIce)
+    } else if contains_any(stderr.as_bytes(), RECURSION_LIMIT_MARKERS) {
+        Some(ErrorCategory::RecursionLimit)
+    } else if contains_any(stderr.as_bytes(), THREAD_LIMIT_MARKERS) {
+        Some(ErrorCategory::ThreadLimit)
+    } else {
+        None
+    }
+}
+
+// Only what Cargo reported before it started running anything counts;
+// the program itself is free to print the same words.
+fn build_failed(stderr: &str) -> bool {
+    stderr
+        .lines()
+        .take_while(|line| !is_running_line(line))
+        .any(|line| contains_any(line.as_bytes(), BUILD_FAILURE_MARKERS))
+}
+
+fn is_running_line(line: &str) -> bool {
+    line.trim_start().starts_with("Running ")
+}
+
+// `cargo test` exits the same way whether the tests didn't build or
+// didn't pass
+fn tests_passed(tests: bool, compiled: bool, success: bool) -> Option<bool> {
+    if tests && compiled {
+        Some(success)
+    } else {
+        None
+    }
+}
+
+/// The one line of a compiler crash that says what went wrong, such
+/// as `internal compiler error: unexpected panic`
+pub fn compiler_ice_summary(stderr: &str) -> Option<String> {
+    const ICE_PREFIX: &str = "error: internal compiler error: ";
+    const PANIC_PREFIX: &str = "thread 'rustc' panicked at ";
+
+    stderr
+        .lines()
+        .find(|line| line.starts_with(ICE_PREFIX))
+        .map(|line| line["error: ".len()..].trim_end().to_string())
+        .or_else(|| {
+            stderr
+                .lines()
+                .find(|line| line.starts_with(PANIC_PREFIX))
+                .map(|line| format!("the compiler panicked at {}", line[PANIC_PREFIX.len()..].trim_end()))
+        })
+}
+
+fn contains_any(haystack: &[u8], needles: &[&[u8]]) -> bool {
+    needles.iter().any(|needle| haystack.windows(needle.len()).any(|w| w == *needle))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AssemblyFlavor {
+    Att,
+    Intel,
+}
+
+impl AssemblyFlavor {
+    fn output_name(&self) -> &'static str {
+        match *self {
+            AssemblyFlavor::Att => "att",
+            AssemblyFlavor::Intel => "intel",
+        }
+    }
+
+    fn output_path(&self) -> &'static str {
+        match *self {
+            AssemblyFlavor::Att => "/playground-result/att",
+            AssemblyFlavor::Intel => "/playground-result/intel",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DemangleAssembly {
+    Demangle,
+    Mangle,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProcessAssembly {
+    Filter,
+    /// Filtered down to `main` and the functions that it calls
+    EntryPoint,
+    Raw,
+}
+/// Which point of the MIR pipeline to show. All require nightly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MirStage {
+    /// As built from the source, before any optimizations
+    Built,
+    /// After all of the MIR optimizations have run
+    Optimized,
+    /// Right after one pass, at the default optimization level
+    After(MirPass),
+}
+
+impl MirStage {
+    fn mir_opt_level(&self) -> Option<&'static str> {
+        match *self {
+            MirStage::Built => Some("mir-opt-level=0"),
+            MirStage::Optimized => Some("mir-opt-level=2"),
+            MirStage::After(_) => None,
+        }
+    }
+}
+
+/// The MIR passes that can be dumped. The names are the ones the
+/// nightly compiler uses in its dump files.
+pub const MIR_PASSES: &[&str] = &[
+    "AddRetag",
+    "ConstProp",
+    "Deaggregator",
+    "ElaborateDrops",
+    "Inline",
+    "InstCombine",
+    "RemoveNoopLandingPads",
+    "SimplifyArmIdentity",
+    "SimplifyBranchSame",
+    "SimplifyLocals",
+    "StateTransform",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MirPass(&'static str);
+
+impl MirPass {
+    pub fn named(name: &str) -> Option<Self> {
+        MIR_PASSES.iter().find(|&&pass| pass == name).map(|&pass| MirPass(pass))
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompileTarget {
+    Assembly(AssemblyFlavor, DemangleAssembly, ProcessAssembly),
+    LlvmIr,
+    Mir(Option<MirStage>),
+    Wasm,
+    /// The configuration options that are active for the crate
+    Cfg,
+    /// The Makefile-style listing of the files the crate was built from
+    DepInfo,
+    /// Every target feature that the compiler knows about for the
+    /// target, such as those used with `#[target_feature]`
+    TargetFeatures,
+}
+
+impl CompileTarget {
+    /// The output is a translation of the user's code, which can be
+    /// many times larger than the code itself
+    fn is_generated_code(&self) -> bool {
+        use self::CompileTarget::*;
+
+        match *self {
+            Assembly(..) | LlvmIr | Mir(_) => true,
+            Wasm | Cfg | DepInfo | TargetFeatures => false,
+        }
+    }
+
+    fn extension(&self) -> &'static OsStr {
+        OsStr::new(self.file_extension())
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match *self {
+            CompileTarget::Assembly(_, _, _) => "s",
+            CompileTarget::LlvmIr            => "ll",
+            CompileTarget::Mir(_)            => "mir",
+            CompileTarget::Wasm              => "wat",
+            CompileTarget::Cfg               => "cfg",
+            CompileTarget::DepInfo           => "d",
+            CompileTarget::TargetFeatures    => "features",
+        }
+    }
+
+    /// The WebAssembly artifact is the module itself
+    pub fn artifact_extension(&self) -> &'static str {
+        match *self {
+            CompileTarget::Wasm => "wasm",
+            _ => self.file_extension(),
+        }
+    }
+}
+
+impl fmt::Display for CompileTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::CompileTarget::*;
+
+        match *self {
+            Assembly(_, _, _) => "assembly".fmt(f),
+            LlvmIr            => "LLVM IR".fmt(f),
+            Mir(_)            => "Rust MIR".fmt(f),
+            Wasm              => "WebAssembly".fmt(f),
+            Cfg               => "cfg values".fmt(f),
+            DepInfo           => "dependency information".fmt(f),
+            TargetFeatures    => "target features".fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GraphFormat {
+    Svg,
+    Png,
+}
+
+impl GraphFormat {
+    fn dot_flag(&self) -> &'static str {
+        match *self {
+            GraphFormat::Svg => "-Tsvg",
+            GraphFormat::Png => "-Tpng",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Stable,
+    Beta,
+    Nightly,
+}
+
+impl Channel {
+    fn container_name(&self) -> &'static str {
         use self::Channel::*;
 
         match *self {
@@ -573,6 +2562,29 @@ pub enum Mode {
     Release,
 }
 
+impl Mode {
+    fn cargo_profile(&self) -> &'static str {
+        match *self {
+            Mode::Debug => "dev",
+            Mode::Release => "release",
+        }
+    }
+
+    fn target_dir(&self) -> &'static str {
+        match *self {
+            Mode::Debug => "debug",
+            Mode::Release => "release",
+        }
+    }
+
+    fn incremental_dir(&self) -> &'static str {
+        match *self {
+            Mode::Debug => "/playground/target/debug/incremental",
+            Mode::Release => "/playground/target/release/incremental",
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Edition {
     Rust2015,
@@ -590,6 +2602,52 @@ impl Edition {
     }
 }
 
+/// How Cargo prints the compiler's errors and warnings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// The usual multi-line messages with code snippets
+    Human,
+    /// One line for each message
+    Short,
+    /// One JSON message per line, in `stdout`
+    Json,
+}
+
+impl DiagnosticFormat {
+    fn message_format_flag(self) -> Option<&'static str> {
+        match self {
+            DiagnosticFormat::Human => None,
+            DiagnosticFormat::Short => Some("--message-format=short"),
+            DiagnosticFormat::Json => Some(CARGO_JSON_MESSAGES),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Linker {
+    Default,
+    Lld,
+}
+
+/// Instruments the program to detect memory and threading bugs at
+/// runtime. Requires nightly.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Sanitizer {
+    Address,
+    Leak,
+    Thread,
+}
+
+impl Sanitizer {
+    fn name(self) -> &'static str {
+        match self {
+            Sanitizer::Address => "address",
+            Sanitizer::Leak => "leak",
+            Sanitizer::Thread => "thread",
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum CrateType {
     Binary,
@@ -597,7 +2655,7 @@ pub enum CrateType {
 }
 
 impl CrateType {
-    fn file_name(&self) -> &'static str {
+    pub fn file_name(&self) -> &'static str {
         use self::CrateType::*;
 
         match *self {
@@ -636,6 +2694,24 @@ trait DockerCommandExt {
     fn apply_crate_type(&mut self, req: impl CrateTypeRequest);
     fn apply_edition(&mut self, req: impl EditionRequest);
     fn apply_backtrace(&mut self, req: impl BacktraceRequest);
+    fn apply_linker(&mut self, req: impl LinkerRequest);
+    fn apply_deterministic(&mut self, req: impl DeterministicRequest);
+    fn apply_stack_size(&mut self, req: impl StackSizeRequest);
+    fn apply_incremental(&mut self, req: impl IncrementalRequest, mode: Mode);
+    fn apply_seed(&mut self, req: impl SeedRequest);
+    fn apply_locale(&mut self, req: impl LocaleRequest);
+    fn apply_static_target(&mut self, req: impl StaticTargetRequest);
+    fn apply_sanitizer(&mut self, req: impl SanitizerRequest);
+    fn apply_trace(&mut self, req: impl TraceRequest);
+    fn apply_profile(&mut self, req: impl ProfileRequest);
+    fn apply_memory_report(&mut self, req: impl MemoryReportRequest);
+    fn apply_toolchain(&mut self, req: impl ToolchainRequest);
+    fn apply_cancellation(&mut self, req: impl CancellationRequest);
+    fn apply_container_name(&mut self, name: Option<&str>);
+    fn apply_logged_request_id(&mut self, req: impl LoggedRequestIdRequest);
+    fn apply_with_clippy(&mut self, req: impl WithClippyRequest);
+    fn apply_jobs(&mut self, req: impl ParallelismRequest);
+    fn apply_environment(&mut self, req: impl EnvironmentRequest);
 }
 
 impl DockerCommandExt for Command {
@@ -656,6 +2732,163 @@ impl DockerCommandExt for Command {
             self.args(&["--env", "RUST_BACKTRACE=1"]);
         }
     }
+
+    // Only the final link changes, so the precompiled dependencies
+    // are still used.
+    fn apply_linker(&mut self, req: impl LinkerRequest) {
+        if let Linker::Lld = req.linker() {
+            self.args(&["--env", "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER=/playground/tools/lld-linker"]);
+        }
+    }
+
+    // Best-effort: the clock seen by the user's program is frozen and
+    // tests run on a single thread so their output is not interleaved.
+    fn apply_deterministic(&mut self, req: impl DeterministicRequest) {
+        if req.deterministic() {
+            self.args(&["--env", &format!("SOURCE_DATE_EPOCH={}", DETERMINISTIC_EPOCH)]);
+            self.args(&["--env", "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER=/playground/tools/deterministic"]);
+            self.args(&["--env", "RUST_TEST_THREADS=1"]);
+        }
+    }
+
+    // The stack ulimit sizes the main thread while `RUST_MIN_STACK`
+    // covers any threads the program spawns.
+    fn apply_stack_size(&mut self, req: impl StackSizeRequest) {
+        if let Some(mb) = req.stack_size_mb() {
+            let bytes = u64::from(mb.min(*MAX_STACK_SIZE_MB)) * 1024 * 1024;
+            self.args(&["--ulimit", &format!("stack={}:{}", bytes, bytes)]);
+            self.args(&["--env", &format!("RUST_MIN_STACK={}", bytes)]);
+        }
+    }
+
+    // The session's directory replaces the one that Cargo would
+    // otherwise create fresh inside of every container.
+    fn apply_incremental(&mut self, req: impl IncrementalRequest, mode: Mode) {
+        if let Some(session) = req.incremental_session() {
+            let mut mount_incremental_dir = INCREMENTAL_CACHE_DIR.join(session).into_os_string();
+            mount_incremental_dir.push(":");
+            mount_incremental_dir.push(mode.incremental_dir());
+
+            self.arg("--volume").arg(&mount_incremental_dir);
+            self.args(&["--env", "CARGO_INCREMENTAL=1"]);
+        }
+    }
+
+    fn apply_seed(&mut self, req: impl SeedRequest) {
+        if let Some(seed) = req.seed() {
+            self.args(&["--env", &format!("PLAYGROUND_SEED={}", seed)]);
+        }
+    }
+
+    // The name lets the container be killed if the request is cancelled
+    fn apply_cancellation(&mut self, req: impl CancellationRequest) {
+        if let Some(id) = req.request_id() {
+            self.args(&["--name", &cancellable_container_name(id)]);
+        }
+    }
+
+    fn apply_container_name(&mut self, name: Option<&str>) {
+        if let Some(name) = name {
+            self.args(&["--name", name]);
+        }
+    }
+
+    // The label is kept in `docker inspect` and events, and log drivers
+    // started with `--log-opt labels=playground.request-id` add it to
+    // each line that the container writes
+    fn apply_logged_request_id(&mut self, req: impl LoggedRequestIdRequest) {
+        if let Some(id) = req.logged_request_id() {
+            self.args(&["--env", &format!("PLAYGROUND_REQUEST_ID={}", id)]);
+            self.args(&["--label", &format!("{}={}", REQUEST_ID_LABEL, id)]);
+        }
+    }
+
+    // Clippy runs in place of rustc for the playground crate only, so
+    // its lints are reported by the same build and the precompiled
+    // dependencies are still used.
+    fn apply_with_clippy(&mut self, req: impl WithClippyRequest) {
+        if req.with_clippy() {
+            self.args(&["--env", "RUSTC_WORKSPACE_WRAPPER=clippy-driver"]);
+        }
+    }
+
+    fn apply_jobs(&mut self, req: impl ParallelismRequest) {
+        if let Some(jobs) = req.jobs() {
+            self.args(&["--env", &format!("CARGO_BUILD_JOBS={}", jobs.max(1).min(*MAX_JOBS))]);
+        }
+    }
+
+    // Only the standard library is available for the musl target,
+    // much like WebAssembly.
+    fn apply_static_target(&mut self, req: impl StaticTargetRequest) {
+        if req.static_target() {
+            self.args(&["--env", "PLAYGROUND_NO_DEPENDENCIES=true"]);
+        }
+    }
+
+    // Like the static target, the explicit target means that the
+    // precompiled dependencies can't be used.
+    fn apply_sanitizer(&mut self, req: impl SanitizerRequest) {
+        if let Some(sanitizer) = req.sanitizer() {
+            self.args(&["--env", "PLAYGROUND_NO_DEPENDENCIES=true"]);
+            self.args(&["--env", &format!("RUSTFLAGS=-Z{}={}", NIGHTLY_FLAG_SANITIZER, sanitizer.name())]);
+        }
+    }
+
+    // strace needs to ptrace the program, which every other container
+    // is denied. The runner only wraps the program, not cargo.
+    fn apply_trace(&mut self, req: impl TraceRequest) {
+        if req.trace() {
+            self.arg("--cap-add=SYS_PTRACE");
+            self.args(&["--env", "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER=/playground/tools/trace"]);
+        }
+    }
+
+    // The dependencies were precompiled by the channel's compiler,
+    // which no other compiler can use, and rebuilding them wouldn't
+    // finish in time.
+    fn apply_toolchain(&mut self, req: impl ToolchainRequest) {
+        if let Some(version) = req.toolchain_version() {
+            self.args(&["--env", &format!("RUSTUP_TOOLCHAIN={}", version)]);
+            self.args(&["--env", "PLAYGROUND_NO_DEPENDENCIES=true"]);
+        }
+    }
+
+    // Sampling needs `perf_event_open`, which the container runtime
+    // only allows with this capability. The runner only wraps the
+    // program, not cargo. Check `profiling_supported` first.
+    fn apply_profile(&mut self, req: impl ProfileRequest) {
+        if req.profile() {
+            self.arg("--cap-add=PERFMON");
+            self.args(&["--env", "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER=/playground/tools/profile"]);
+        }
+    }
+
+    fn apply_memory_report(&mut self, req: impl MemoryReportRequest) {
+        if req.memory_report() {
+            self.args(&["--env", "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUNNER=/playground/tools/memory-report"]);
+        }
+    }
+
+    // `LC_ALL` takes precedence over any of the other `LC_*` variables
+    // that the image might set.
+    fn apply_locale(&mut self, req: impl LocaleRequest) {
+        if let Some(timezone) = req.timezone() {
+            self.args(&["--env", &format!("TZ={}", timezone)]);
+        }
+        if let Some(locale) = req.locale() {
+            self.args(&["--env", &format!("LANG={}", locale)]);
+            self.args(&["--env", &format!("LC_ALL={}", locale)]);
+        }
+    }
+
+    // The names have been checked against the ones the playground
+    // itself relies on, so these can't override them.
+    fn apply_environment(&mut self, req: impl EnvironmentRequest) {
+        for (name, value) in req.env() {
+            self.args(&["--env", &format!("{}={}", name, value)]);
+        }
+    }
 }
 
 trait CrateTypeRequest {
@@ -682,65 +2915,535 @@ impl<R: BacktraceRequest> BacktraceRequest for &'_ R {
     fn backtrace(&self) -> bool { (*self).backtrace() }
 }
 
-#[derive(Debug, Clone)]
-pub struct CompileRequest {
-    pub target: CompileTarget,
-    pub channel: Channel,
-    pub crate_type: CrateType,
-    pub mode: Mode,
-    pub edition: Option<Edition>,
-    pub tests: bool,
-    pub backtrace: bool,
-    pub code: String,
+trait LinkerRequest {
+    fn linker(&self) -> Linker;
 }
 
-impl CrateTypeRequest for CompileRequest {
-    fn crate_type(&self) -> CrateType { self.crate_type }
+impl<R: LinkerRequest> LinkerRequest for &'_ R {
+    fn linker(&self) -> Linker { (*self).linker() }
 }
 
-impl EditionRequest for CompileRequest {
-    fn edition(&self) -> Option<Edition> { self.edition }
+trait DeterministicRequest {
+    fn deterministic(&self) -> bool;
 }
 
-impl BacktraceRequest for CompileRequest {
-    fn backtrace(&self) -> bool { self.backtrace }
+impl<R: DeterministicRequest> DeterministicRequest for &'_ R {
+    fn deterministic(&self) -> bool { (*self).deterministic() }
 }
 
-#[derive(Debug, Clone)]
-pub struct CompileResponse {
-    pub success: bool,
-    pub code: String,
-    pub stdout: String,
-    pub stderr: String,
+trait StackSizeRequest {
+    fn stack_size_mb(&self) -> Option<u32>;
 }
 
-#[derive(Debug, Clone)]
-pub struct ExecuteRequest {
-    pub channel: Channel,
-    pub mode: Mode,
-    pub edition: Option<Edition>,
-    pub crate_type: CrateType,
-    pub tests: bool,
-    pub backtrace: bool,
-    pub code: String,
+impl<R: StackSizeRequest> StackSizeRequest for &'_ R {
+    fn stack_size_mb(&self) -> Option<u32> { (*self).stack_size_mb() }
 }
 
-impl CrateTypeRequest for ExecuteRequest {
-    fn crate_type(&self) -> CrateType { self.crate_type }
+trait ParallelismRequest {
+    fn codegen_units(&self) -> Option<u32>;
+    fn jobs(&self) -> Option<u32>;
 }
 
-impl EditionRequest for ExecuteRequest {
-    fn edition(&self) -> Option<Edition> { self.edition }
+impl<R: ParallelismRequest> ParallelismRequest for &'_ R {
+    fn codegen_units(&self) -> Option<u32> { (*self).codegen_units() }
+    fn jobs(&self) -> Option<u32> { (*self).jobs() }
 }
 
-impl BacktraceRequest for ExecuteRequest {
-    fn backtrace(&self) -> bool { self.backtrace }
+trait SuggestionsRequest {
+    fn suggestions(&self) -> bool;
 }
 
-#[derive(Debug, Clone)]
-pub struct ExecuteResponse {
-    pub success: bool,
-    pub stdout: String,
+impl<R: SuggestionsRequest> SuggestionsRequest for &'_ R {
+    fn suggestions(&self) -> bool { (*self).suggestions() }
+}
+
+trait UserCodeRequest {
+    fn user_code_only(&self) -> bool;
+}
+
+impl<R: UserCodeRequest> UserCodeRequest for &'_ R {
+    fn user_code_only(&self) -> bool { (*self).user_code_only() }
+}
+
+/// Whether the compiler's messages must be read as JSON, which is
+/// the case for every option that inspects individual diagnostics.
+pub trait JsonMessagesRequest {
+    fn wants_json_messages(&self) -> bool;
+}
+
+impl<R: JsonMessagesRequest> JsonMessagesRequest for &'_ R {
+    fn wants_json_messages(&self) -> bool { (*self).wants_json_messages() }
+}
+
+trait DebugArtifactsRequest {
+    fn debug_artifacts(&self) -> bool;
+}
+
+impl<R: DebugArtifactsRequest> DebugArtifactsRequest for &'_ R {
+    fn debug_artifacts(&self) -> bool { (*self).debug_artifacts() }
+}
+
+trait SeedRequest {
+    fn seed(&self) -> Option<u64>;
+}
+
+impl<R: SeedRequest> SeedRequest for &'_ R {
+    fn seed(&self) -> Option<u64> { (*self).seed() }
+}
+
+trait CoverageRequest {
+    fn coverage(&self) -> bool;
+}
+
+impl<R: CoverageRequest> CoverageRequest for &'_ R {
+    fn coverage(&self) -> bool { (*self).coverage() }
+}
+
+trait StaticTargetRequest {
+    fn static_target(&self) -> bool;
+}
+
+impl<R: StaticTargetRequest> StaticTargetRequest for &'_ R {
+    fn static_target(&self) -> bool { (*self).static_target() }
+}
+
+trait SanitizerRequest {
+    fn sanitizer(&self) -> Option<Sanitizer>;
+}
+
+impl<R: SanitizerRequest> SanitizerRequest for &'_ R {
+    fn sanitizer(&self) -> Option<Sanitizer> { (*self).sanitizer() }
+}
+
+trait TraceRequest {
+    fn trace(&self) -> bool;
+}
+
+impl<R: TraceRequest> TraceRequest for &'_ R {
+    fn trace(&self) -> bool { (*self).trace() }
+}
+
+trait ToolchainRequest {
+    fn toolchain_version(&self) -> Option<&str>;
+}
+
+impl<R: ToolchainRequest> ToolchainRequest for &'_ R {
+    fn toolchain_version(&self) -> Option<&str> { (*self).toolchain_version() }
+}
+
+trait ProfileRequest {
+    fn profile(&self) -> bool;
+}
+
+impl<R: ProfileRequest> ProfileRequest for &'_ R {
+    fn profile(&self) -> bool { (*self).profile() }
+}
+
+trait MemoryReportRequest {
+    fn memory_report(&self) -> bool;
+}
+
+impl<R: MemoryReportRequest> MemoryReportRequest for &'_ R {
+    fn memory_report(&self) -> bool { (*self).memory_report() }
+}
+
+trait LoggedRequestIdRequest {
+    fn logged_request_id(&self) -> Option<&str>;
+}
+
+impl<R: LoggedRequestIdRequest> LoggedRequestIdRequest for &'_ R {
+    fn logged_request_id(&self) -> Option<&str> { (*self).logged_request_id() }
+}
+
+trait CancellationRequest {
+    fn request_id(&self) -> Option<&str>;
+}
+
+impl<R: CancellationRequest> CancellationRequest for &'_ R {
+    fn request_id(&self) -> Option<&str> { (*self).request_id() }
+}
+
+trait TimePassesRequest {
+    fn time_passes(&self) -> bool;
+}
+
+impl<R: TimePassesRequest> TimePassesRequest for &'_ R {
+    fn time_passes(&self) -> bool { (*self).time_passes() }
+}
+
+trait DiagnosticFormatRequest {
+    fn diagnostic_format(&self) -> DiagnosticFormat;
+}
+
+impl<R: DiagnosticFormatRequest> DiagnosticFormatRequest for &'_ R {
+    fn diagnostic_format(&self) -> DiagnosticFormat { (*self).diagnostic_format() }
+}
+
+trait LintGroupsRequest {
+    fn deny_lint_groups(&self) -> &[&'static str];
+}
+
+impl<R: LintGroupsRequest> LintGroupsRequest for &'_ R {
+    fn deny_lint_groups(&self) -> &[&'static str] { (*self).deny_lint_groups() }
+}
+
+trait WithClippyRequest {
+    fn with_clippy(&self) -> bool;
+}
+
+impl<R: WithClippyRequest> WithClippyRequest for &'_ R {
+    fn with_clippy(&self) -> bool { (*self).with_clippy() }
+}
+
+trait LocaleRequest {
+    fn timezone(&self) -> Option<&str>;
+    fn locale(&self) -> Option<&str>;
+}
+
+impl<R: LocaleRequest> LocaleRequest for &'_ R {
+    fn timezone(&self) -> Option<&str> { (*self).timezone() }
+    fn locale(&self) -> Option<&str> { (*self).locale() }
+}
+
+trait EnvironmentRequest {
+    fn env(&self) -> &[(String, String)];
+}
+
+impl<R: EnvironmentRequest> EnvironmentRequest for &'_ R {
+    fn env(&self) -> &[(String, String)] { (*self).env() }
+}
+
+trait IncrementalRequest {
+    fn incremental_session(&self) -> Option<&str>;
+}
+
+impl<R: IncrementalRequest> IncrementalRequest for &'_ R {
+    fn incremental_session(&self) -> Option<&str> { (*self).incremental_session() }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileRequest {
+    pub target: CompileTarget,
+    pub channel: Channel,
+    pub crate_type: CrateType,
+    pub mode: Mode,
+    pub edition: Option<Edition>,
+    pub tests: bool,
+    pub backtrace: bool,
+    pub all_flavors: bool,
+    pub suggestions: bool,
+    pub raw_diagnostics: bool,
+    /// Cargo's artifact and build-finished messages
+    pub build_messages: bool,
+    /// How many of the last lines of output the summary includes
+    pub diagnostic_summary: Option<usize>,
+    /// The messages as errors, warnings, and notes
+    pub diagnostic_groups: bool,
+    /// Ignored when structured diagnostics or progress are requested,
+    /// as both need Cargo's JSON messages
+    pub diagnostic_format: DiagnosticFormat,
+    /// Only for assembly
+    pub instruction_counts: bool,
+    /// Only for WebAssembly
+    pub wasm_sections: bool,
+    pub debug_artifacts: bool,
+    /// The output file as the compiler wrote it
+    pub artifact: bool,
+    pub linker: Linker,
+    pub incremental_session: Option<String>,
+    pub time_passes: bool,
+    /// Each one is from `LINT_GROUPS`
+    pub deny_lint_groups: Vec<&'static str>,
+    pub with_clippy: bool,
+    /// Clamped to `PLAYGROUND_MAX_CODEGEN_UNITS`
+    pub codegen_units: Option<u32>,
+    /// Clamped to `PLAYGROUND_MAX_JOBS`
+    pub jobs: Option<u32>,
+    pub command_line: bool,
+    /// An installed toolchain, such as `1.42.0`, used instead of the channel's
+    pub toolchain_version: Option<String>,
+    pub code: String,
+}
+
+impl CrateTypeRequest for CompileRequest {
+    fn crate_type(&self) -> CrateType { self.crate_type }
+}
+
+impl EditionRequest for CompileRequest {
+    fn edition(&self) -> Option<Edition> { self.edition }
+}
+
+impl BacktraceRequest for CompileRequest {
+    fn backtrace(&self) -> bool { self.backtrace }
+}
+
+impl LinkerRequest for CompileRequest {
+    fn linker(&self) -> Linker { self.linker }
+}
+
+impl JsonMessagesRequest for CompileRequest {
+    fn wants_json_messages(&self) -> bool {
+        self.suggestions || self.raw_diagnostics || self.build_messages || self.diagnostic_summary.is_some() || self.diagnostic_groups
+    }
+}
+
+impl DebugArtifactsRequest for CompileRequest {
+    fn debug_artifacts(&self) -> bool { self.debug_artifacts }
+}
+
+impl TimePassesRequest for CompileRequest {
+    fn time_passes(&self) -> bool { self.time_passes }
+}
+
+impl DiagnosticFormatRequest for CompileRequest {
+    fn diagnostic_format(&self) -> DiagnosticFormat { self.diagnostic_format }
+}
+
+impl LintGroupsRequest for CompileRequest {
+    fn deny_lint_groups(&self) -> &[&'static str] { &self.deny_lint_groups }
+}
+
+impl WithClippyRequest for CompileRequest {
+    fn with_clippy(&self) -> bool { self.with_clippy }
+}
+
+impl ParallelismRequest for CompileRequest {
+    fn codegen_units(&self) -> Option<u32> { self.codegen_units }
+    fn jobs(&self) -> Option<u32> { self.jobs }
+}
+
+impl ToolchainRequest for CompileRequest {
+    fn toolchain_version(&self) -> Option<&str> { self.toolchain_version.as_deref() }
+}
+
+impl IncrementalRequest for CompileRequest {
+    fn incremental_session(&self) -> Option<&str> { self.incremental_session.as_deref() }
+}
+
+/// A crate that Cargo has finished building
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub target: String,
+    /// Cargo reused it from an earlier build
+    pub fresh: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileResponse {
+    pub success: bool,
+    pub error_category: Option<ErrorCategory>,
+    pub code: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub flavors: Vec<(AssemblyFlavor, String)>,
+    pub suggestions: Vec<super::diagnostics::Suggestion>,
+    /// Cargo's JSON messages, when requested
+    pub raw_diagnostics: Option<String>,
+    /// What Cargo built, when requested
+    pub build_messages: Option<super::diagnostics::BuildMessages>,
+    /// The error counts and the end of the output, when requested
+    pub diagnostic_summary: Option<super::diagnostics::Summary>,
+    /// The messages by their level, when requested
+    pub diagnostic_groups: Option<super::diagnostics::Groups>,
+    /// Counted before the assembly is filtered, when requested
+    pub instruction_counts: Option<Vec<super::asm_cleanup::InstructionCount>>,
+    /// The size of each section of the module, when requested
+    pub wasm_sections: Option<Vec<super::wasm_sections::Section>>,
+    pub build_info: BuildInfo,
+    /// A gzipped tarball of the build directory
+    pub debug_artifacts: Option<Vec<u8>>,
+    /// The output before it was processed or truncated, when requested.
+    /// For WebAssembly, this is the module rather than its text.
+    pub artifact: Option<Vec<u8>>,
+    /// The compiler's pass timings, when requested
+    pub passes: Option<Vec<super::time_passes::Pass>>,
+    /// The code was cut short at `PLAYGROUND_MAX_CODE_OUTPUT_KB`
+    pub truncated: bool,
+    /// The Cargo invocation run in the container, when requested
+    pub command_line: Option<String>,
+}
+
+/// Failures that are worth explaining beyond the compiler's own
+/// message
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// A bug in the compiler rather than in the user's code
+    CompilerIce,
+    /// Usually a macro that expands to itself without end
+    RecursionLimit,
+    /// The program started more threads than the container allows
+    ThreadLimit,
+}
+
+/// A summary of how the crate was built, matching the manifest that
+/// the sandbox generates for the request.
+#[derive(Debug, Clone)]
+pub struct BuildInfo {
+    pub resolver: &'static str,
+    pub profile: &'static str,
+    pub edition: Edition,
+    pub channel: Channel,
+}
+
+impl BuildInfo {
+    fn new(req: &CompileRequest) -> Self {
+        BuildInfo {
+            resolver: MANIFEST_RESOLVER,
+            profile: req.mode.cargo_profile(),
+            edition: req.edition.unwrap_or(MANIFEST_DEFAULT_EDITION),
+            channel: req.channel,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecuteRequest {
+    pub channel: Channel,
+    pub mode: Mode,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+    pub tests: bool,
+    pub backtrace: bool,
+    pub deterministic: bool,
+    pub stack_size_mb: Option<u32>,
+    pub linker: Linker,
+    pub incremental_session: Option<String>,
+    pub seed: Option<u64>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub coverage: bool,
+    pub static_target: bool,
+    pub sanitizer: Option<Sanitizer>,
+    /// Runs the program under strace to summarize its system calls
+    pub trace: bool,
+    /// Samples the program's stacks with perf
+    pub profile: bool,
+    /// Measures the program's peak resident set size
+    pub memory_report: bool,
+    /// An installed toolchain, such as `1.42.0`, used instead of the channel's
+    pub toolchain_version: Option<String>,
+    /// Chosen by the client so that it can cancel the request
+    pub request_id: Option<String>,
+    /// Labels the container and the server's logs with the request id
+    pub log_request_id: bool,
+    /// Names and values that have already been validated
+    pub env: Vec<(String, String)>,
+    pub code: String,
+}
+
+impl CrateTypeRequest for ExecuteRequest {
+    fn crate_type(&self) -> CrateType { self.crate_type }
+}
+
+impl EditionRequest for ExecuteRequest {
+    fn edition(&self) -> Option<Edition> { self.edition }
+}
+
+impl BacktraceRequest for ExecuteRequest {
+    fn backtrace(&self) -> bool { self.backtrace }
+}
+
+impl LinkerRequest for ExecuteRequest {
+    fn linker(&self) -> Linker { self.linker }
+}
+
+impl DeterministicRequest for ExecuteRequest {
+    fn deterministic(&self) -> bool { self.deterministic }
+}
+
+impl StackSizeRequest for ExecuteRequest {
+    fn stack_size_mb(&self) -> Option<u32> { self.stack_size_mb }
+}
+
+impl IncrementalRequest for ExecuteRequest {
+    fn incremental_session(&self) -> Option<&str> { self.incremental_session.as_deref() }
+}
+
+impl SeedRequest for ExecuteRequest {
+    fn seed(&self) -> Option<u64> { self.seed }
+}
+
+impl LocaleRequest for ExecuteRequest {
+    fn timezone(&self) -> Option<&str> { self.timezone.as_deref() }
+    fn locale(&self) -> Option<&str> { self.locale.as_deref() }
+}
+
+impl CoverageRequest for ExecuteRequest {
+    fn coverage(&self) -> bool { self.coverage }
+}
+
+impl StaticTargetRequest for ExecuteRequest {
+    fn static_target(&self) -> bool { self.static_target }
+}
+
+impl SanitizerRequest for ExecuteRequest {
+    fn sanitizer(&self) -> Option<Sanitizer> { self.sanitizer }
+}
+
+impl TraceRequest for ExecuteRequest {
+    fn trace(&self) -> bool { self.trace }
+}
+
+impl ToolchainRequest for ExecuteRequest {
+    fn toolchain_version(&self) -> Option<&str> { self.toolchain_version.as_deref() }
+}
+
+impl ProfileRequest for ExecuteRequest {
+    fn profile(&self) -> bool { self.profile }
+}
+
+impl MemoryReportRequest for ExecuteRequest {
+    fn memory_report(&self) -> bool { self.memory_report }
+}
+
+impl CancellationRequest for ExecuteRequest {
+    fn request_id(&self) -> Option<&str> { self.request_id.as_deref() }
+}
+
+impl LoggedRequestIdRequest for ExecuteRequest {
+    fn logged_request_id(&self) -> Option<&str> {
+        self.request_id.as_deref().filter(|_| self.log_request_id)
+    }
+}
+
+impl EnvironmentRequest for ExecuteRequest {
+    fn env(&self) -> &[(String, String)] { &self.env }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecuteResponse {
+    pub success: bool,
+    /// False when the failure was in building the code, rather than in
+    /// running it
+    pub compiled: bool,
+    /// Only when tests were requested and they built
+    pub tests_passed: Option<bool>,
+    pub error_category: Option<ErrorCategory>,
+    pub stdout: String,
+    pub stderr: String,
+    pub coverage: Option<Vec<super::coverage::LineCoverage>>,
+    /// Only when tracing was requested and strace is available
+    pub syscalls: Option<Vec<super::strace::Syscall>>,
+    /// Folded stacks for a flame graph, one `outer;inner samples` per
+    /// line; only when profiling was requested and perf could sample
+    pub profile: Option<String>,
+    /// Only when a memory report was requested and time is available
+    pub peak_rss_kb: Option<u64>,
+    /// The frames of a panic's backtrace, when backtraces are enabled
+    pub backtrace: Vec<super::backtrace::Frame>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildRunResponse {
+    pub build: BuildOutput,
+    /// Missing when the build failed
+    pub run: Option<ExecuteResponse>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildOutput {
+    pub success: bool,
+    pub stdout: String,
     pub stderr: String,
 }
 
@@ -762,11 +3465,21 @@ pub struct FormatResponse {
     pub stderr: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct FormatCheckResponse {
+    pub formatted: bool,
+    pub diff: String,
+    pub stderr: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct ClippyRequest {
     pub code: String,
     pub edition: Option<Edition>,
     pub crate_type: CrateType,
+    pub suggestions: bool,
+    /// Leaves out the lints about code outside of the user's files
+    pub user_code_only: bool,
 }
 
 impl CrateTypeRequest for ClippyRequest {
@@ -777,328 +3490,2001 @@ impl EditionRequest for ClippyRequest {
     fn edition(&self) -> Option<Edition> { self.edition }
 }
 
+impl SuggestionsRequest for ClippyRequest {
+    fn suggestions(&self) -> bool { self.suggestions }
+}
+
+impl UserCodeRequest for ClippyRequest {
+    fn user_code_only(&self) -> bool { self.user_code_only }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClippyResponse {
     pub success: bool,
     pub stdout: String,
     pub stderr: String,
+    pub suggestions: Vec<super::diagnostics::Suggestion>,
 }
 
 #[derive(Debug, Clone)]
-pub struct MiriRequest {
+pub struct CheckRequest {
     pub code: String,
+    pub channel: Channel,
     pub edition: Option<Edition>,
+    pub crate_type: CrateType,
 }
 
-impl EditionRequest for MiriRequest {
+impl CrateTypeRequest for CheckRequest {
+    fn crate_type(&self) -> CrateType { self.crate_type }
+}
+
+impl EditionRequest for CheckRequest {
     fn edition(&self) -> Option<Edition> { self.edition }
 }
 
 #[derive(Debug, Clone)]
-pub struct MiriResponse {
+pub struct CheckResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// rustdoc's JSON output requires the nightly channel
+#[derive(Debug, Clone)]
+pub struct DocJsonRequest {
+    pub code: String,
+    pub channel: Channel,
+    pub edition: Option<Edition>,
+    pub crate_type: CrateType,
+}
+
+impl CrateTypeRequest for DocJsonRequest {
+    fn crate_type(&self) -> CrateType { self.crate_type }
+}
+
+impl EditionRequest for DocJsonRequest {
+    fn edition(&self) -> Option<Edition> { self.edition }
+}
+
+#[derive(Debug, Clone)]
+pub struct DocJsonResponse {
     pub success: bool,
     pub stdout: String,
     pub stderr: String,
+    /// Missing when the documentation could not be generated
+    pub doc: Option<::serde_json::Value>,
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+#[derive(Debug, Clone)]
+pub struct MiriRequest {
+    pub code: String,
+    pub edition: Option<Edition>,
+}
+
+impl EditionRequest for MiriRequest {
+    fn edition(&self) -> Option<Edition> { self.edition }
+}
+
+#[derive(Debug, Clone)]
+pub struct MiriResponse {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+    pub errors: Vec<super::miri_errors::MiriError>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HELLO_WORLD_CODE: &'static str = r#"
+    fn main() {
+        println!("Hello, world!");
+    }
+    "#;
+
+    impl Default for ExecuteRequest {
+        fn default() -> Self {
+            ExecuteRequest {
+                channel: Channel::Stable,
+                crate_type: CrateType::Binary,
+                mode: Mode::Debug,
+                tests: false,
+                code: HELLO_WORLD_CODE.to_string(),
+                edition: None,
+                backtrace: false,
+                deterministic: false,
+                stack_size_mb: None,
+                linker: Linker::Default,
+                incremental_session: None,
+                seed: None,
+                timezone: None,
+                locale: None,
+                coverage: false,
+                static_target: false,
+                sanitizer: None,
+                trace: false,
+                profile: false,
+                memory_report: false,
+                toolchain_version: None,
+                request_id: None,
+                log_request_id: false,
+                env: Vec::new(),
+            }
+        }
+    }
+
+    impl Default for CompileRequest {
+        fn default() -> Self {
+            CompileRequest {
+                target: CompileTarget::LlvmIr,
+                channel: Channel::Stable,
+                crate_type: CrateType::Binary,
+                mode: Mode::Debug,
+                tests: false,
+                code: HELLO_WORLD_CODE.to_string(),
+                edition: None,
+                backtrace: false,
+                all_flavors: false,
+                suggestions: false,
+                raw_diagnostics: false,
+                build_messages: false,
+                diagnostic_summary: None,
+                diagnostic_groups: false,
+                diagnostic_format: DiagnosticFormat::Human,
+                instruction_counts: false,
+                wasm_sections: false,
+                debug_artifacts: false,
+                artifact: false,
+                linker: Linker::Default,
+                incremental_session: None,
+                time_passes: false,
+                deny_lint_groups: Vec::new(),
+                with_clippy: false,
+                codegen_units: None,
+                jobs: None,
+                command_line: false,
+                toolchain_version: None,
+            }
+        }
+    }
+
+    impl Default for ClippyRequest {
+        fn default() -> Self {
+            ClippyRequest {
+                code: HELLO_WORLD_CODE.to_string(),
+                crate_type: CrateType::Binary,
+                edition: None,
+                suggestions: false,
+                user_code_only: false,
+            }
+        }
+    }
+
+    impl Default for CheckRequest {
+        fn default() -> Self {
+            CheckRequest {
+                code: HELLO_WORLD_CODE.to_string(),
+                channel: Channel::Stable,
+                crate_type: CrateType::Binary,
+                edition: None,
+            }
+        }
+    }
+
+    #[test]
+    fn basic_functionality() {
+        let req = ExecuteRequest::default();
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("Hello, world!"));
+    }
+
+    const COMPILATION_MODE_CODE: &'static str = r#"
+    #[cfg(debug_assertions)]
+    fn main() {
+        println!("Compiling in debug mode");
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn main() {
+        println!("Compiling in release mode");
+    }
+    "#;
+
+    #[test]
+    fn debug_mode() {
+        let req = ExecuteRequest {
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("debug mode"));
+    }
+
+    #[test]
+    fn release_mode() {
+        let req = ExecuteRequest {
+            mode: Mode::Release,
+            code: COMPILATION_MODE_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("release mode"));
+    }
+
+    #[test]
+    fn raw_diagnostics_are_json_lines() -> Result<()> {
+        let req = CompileRequest {
+            code: "fn main() {\n    let unused = 1;\n}\n".to_string(),
+            raw_diagnostics: true,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let raw = resp.raw_diagnostics.expect("no raw diagnostics");
+        let messages: Vec<serde_json::Value> = raw
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{} in {:?}", e, line)))
+            .collect();
+        assert!(messages.iter().any(|message| message["reason"] == "compiler-message"), "was: {}", raw);
+        assert!(resp.stderr.contains("unused variable"), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostic_summary_counts_all_errors() -> Result<()> {
+        let code = r#"
+            fn main() {
+                let a: u8 = "one";
+                let b: u8 = "two";
+                let c: u8 = "three";
+                let unused = 4;
+            }
+        "#;
+
+        let req = CompileRequest {
+            code: code.to_string(),
+            diagnostic_summary: Some(3),
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let summary = resp.diagnostic_summary.expect("no summary");
+        assert_eq!(summary.errors, 3);
+        assert!(summary.first_error.as_deref().unwrap_or_default().contains("mismatched types"), "was: {:?}", summary.first_error);
+        assert!(summary.last_lines.len() <= 3);
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics_can_be_grouped_by_level() -> Result<()> {
+        let code = r#"
+            fn main() {
+                let unused = 1;
+                let a: u8 = "one";
+            }
+        "#;
+
+        let req = CompileRequest {
+            code: code.to_string(),
+            diagnostic_groups: true,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let groups = resp.diagnostic_groups.expect("no groups");
+        assert_eq!(groups.errors.len(), 1, "errors were: {:?}", groups.errors);
+        assert_eq!(groups.errors[0].code.as_deref(), Some("E0308"));
+        assert_eq!(groups.warnings.len(), 1, "warnings were: {:?}", groups.warnings);
+        assert!(groups.warnings[0].message.contains("unused variable"), "was: {}", groups.warnings[0].message);
+        Ok(())
+    }
+
+    #[test]
+    fn build_messages_report_the_binary() -> Result<()> {
+        let req = CompileRequest {
+            build_messages: true,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let build = resp.build_messages.expect("no build messages");
+        let playground = build.artifacts
+            .iter()
+            .find(|artifact| artifact.target == "playground")
+            .expect("no artifact for the playground crate");
+        assert_eq!(playground.kinds, ["bin"]);
+        assert!(playground.filenames.iter().any(|name| name.ends_with("/playground")), "was: {:?}", playground.filenames);
+        Ok(())
+    }
+
+    #[test]
+    fn progress_is_reported_for_each_crate() -> Result<()> {
+        let req = CompileRequest {
+            code: "fn main() { let _: u8 = rand::random(); }".to_string(),
+            ..CompileRequest::default()
+        };
+
+        let mut progress = Vec::new();
+        let resp = Sandbox::new()?.compile_with_progress(&req, &mut |p| {
+            progress.push(p);
+            true
+        })?;
+
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(resp.build_messages.is_none());
+        assert!(progress.iter().any(|p| p.target == "rand"), "progress was: {:?}", progress);
+        assert_eq!(progress.last().map(|p| p.target.as_str()), Some("playground"));
+        Ok(())
+    }
+
+    #[test]
+    fn a_build_whose_progress_is_unwanted_is_stopped() -> Result<()> {
+        let req = CompileRequest {
+            code: "fn main() { let _: u8 = rand::random(); }".to_string(),
+            ..CompileRequest::default()
+        };
+
+        match Sandbox::new()?.compile_with_progress(&req, &mut |_| false) {
+            Err(Error::Cancelled) => {}
+            other => panic!("The build was not stopped: {:?}", other.map(|r| r.success)),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn suggestions_for_a_mistyped_method() -> Result<()> {
+        let req = CompileRequest {
+            code: "fn main() {\n    let mut v = Vec::new();\n    v.pusj(1);\n}\n".to_string(),
+            suggestions: true,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let suggestion = resp.suggestions.iter().find(|s| s.replacement == "push");
+        let suggestion = suggestion.unwrap_or_else(|| panic!("no suggestion in {:?}", resp.suggestions));
+        assert_eq!(suggestion.span.line_start, 3);
+        assert!(resp.stderr.contains("E0599"), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn debug_artifacts_contain_the_build_directory() -> Result<()> {
+        let req = CompileRequest {
+            debug_artifacts: true,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+        let tarball = resp.debug_artifacts.expect("No debug artifacts were created");
+
+        let dir = TempDir::new("debug-artifacts").context(UnableToCreateTempDir)?;
+        let path = dir.path().join(DEBUG_ARTIFACTS_NAME);
+        fs::write(&path, tarball).context(UnableToCreateTempDir)?;
+
+        let listing = std::process::Command::new("tar")
+            .arg("--list")
+            .arg("--file")
+            .arg(&path)
+            .output()
+            .context(UnableToExecuteCompiler)?;
+        let listing = vec_to_str(listing.stdout)?;
+
+        assert!(listing.lines().any(|l| l == "Cargo.toml"), "was: {}", listing);
+        assert!(listing.lines().any(|l| l == "src/main.rs"), "was: {}", listing);
+        Ok(())
+    }
+
+    #[test]
+    fn build_info_for_release_mode() -> Result<()> {
+        let req = CompileRequest {
+            mode: Mode::Release,
+            edition: Some(Edition::Rust2018),
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        assert_eq!(resp.build_info.profile, "release");
+        assert_eq!(resp.build_info.edition, Edition::Rust2018);
+        assert_eq!(resp.build_info.channel, Channel::Stable);
+        Ok(())
+    }
+
+    static VERSION_CODE: &'static str = r#"
+    use std::process::Command;
+
+    fn main() {
+        let output = Command::new("rustc").arg("--version").output().unwrap();
+        let output = String::from_utf8(output.stdout).unwrap();
+        println!("{}", output);
+    }
+    "#;
+
+    #[test]
+    fn stable_channel() {
+        let req = ExecuteRequest {
+            channel: Channel::Stable,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[test]
+    fn beta_channel() {
+        let req = ExecuteRequest {
+            channel: Channel::Beta,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(resp.stdout.contains("beta"));
+        assert!(!resp.stdout.contains("nightly"));
+    }
+
+    #[test]
+    fn nightly_channel() {
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(resp.stdout.contains("rustc"));
+        assert!(!resp.stdout.contains("beta"));
+        assert!(resp.stdout.contains("nightly"));
+    }
+
+    // The image is built with this toolchain installed by default
+    const PINNED_TOOLCHAIN: &str = "1.42.0";
+
+    #[test]
+    fn pinned_toolchain() -> Result<()> {
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            toolchain_version: Some(PINNED_TOOLCHAIN.to_string()),
+            code: VERSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new()?;
+        let resp = sb.execute(&req)?;
+
+        assert!(resp.stdout.contains("rustc 1.42.0 "), "stdout was: {}", resp.stdout);
+        assert!(!resp.stdout.contains("nightly"), "stdout was: {}", resp.stdout);
+
+        let version = sb.pinned_version(Channel::Nightly, PINNED_TOOLCHAIN)?;
+        assert_eq!(version.release, "1.42.0");
+
+        let capabilities = sb.capabilities(Channel::Nightly)?;
+        assert!(capabilities.has_toolchain(PINNED_TOOLCHAIN), "toolchains were: {:?}", capabilities.toolchains);
+        Ok(())
+    }
+
+    // Code that will only work in Rust 2015
+    const EDITION_CODE: &str = r#"
+    fn main() {
+        let async = true;
+    }
+    "#;
+
+    const EDITION_ERROR: &str = "found keyword `async`";
+
+    #[test]
+    fn rust_edition_default() -> Result<()> {
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn rust_edition_2015() -> Result<()> {
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2015),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn rust_edition_2018() -> Result<()> {
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            code: EDITION_CODE.to_string(),
+            edition: Some(Edition::Rust2018),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    const BACKTRACE_CODE: &str = r#"
+    fn trigger_the_problem() {
+        None::<u8>.unwrap();
+    }
+
+    fn main() {
+        trigger_the_problem()
+    }
+    "#;
+
+    const BACKTRACE_NOTE: &str = "run with `RUST_BACKTRACE=1` environment variable to display a backtrace";
+
+    #[test]
+    fn backtrace_disabled() -> Result<()> {
+        let req = ExecuteRequest {
+            code: BACKTRACE_CODE.to_string(),
+            backtrace: false,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new()?;
+        let resp = sb.execute(&req)?;
+
+        assert!(resp.stderr.contains(BACKTRACE_NOTE), "Was: {}", resp.stderr);
+        assert!(!resp.stderr.contains("stack backtrace:"), "Was: {}", resp.stderr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backtrace_enabled() -> Result<()> {
+        let req = ExecuteRequest {
+            code: BACKTRACE_CODE.to_string(),
+            backtrace: true,
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new()?;
+        let resp = sb.execute(&req)?;
+
+        assert!(!resp.stderr.contains(BACKTRACE_NOTE), "Was: {}", resp.stderr);
+        assert!(resp.stderr.contains("stack backtrace:"), "Was: {}", resp.stderr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backtrace_frames_identify_the_panicking_line() -> Result<()> {
+        let req = ExecuteRequest {
+            code: BACKTRACE_CODE.to_string(),
+            backtrace: true,
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        let frame = resp.backtrace.iter()
+            .find(|frame| frame.symbol == "playground::trigger_the_problem")
+            .unwrap_or_else(|| panic!("No frame for the panicking function in {:?}", resp.backtrace));
+        assert_eq!(frame.location.as_deref(), Some("src/main.rs:3"));
+
+        Ok(())
+    }
+
+    const TIME_CODE: &str = r#"
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn main() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        println!("{:?}", now);
+    }
+    "#;
+
+    #[test]
+    fn deterministic_execution() -> Result<()> {
+        let req = ExecuteRequest {
+            code: TIME_CODE.to_string(),
+            deterministic: true,
+            ..ExecuteRequest::default()
+        };
+
+        let first = Sandbox::new()?.execute(&req)?;
+        let second = Sandbox::new()?.execute(&req)?;
+
+        assert!(first.success, "stderr was: {}", first.stderr);
+        assert_eq!(first.stdout, second.stdout);
+        assert!(first.stdout.contains(&DETERMINISTIC_EPOCH.to_string()), "was: {}", first.stdout);
+        Ok(())
+    }
+
+    const RANDOM_CODE: &str = r#"
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    fn main() {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(42);
+        println!("{}", hasher.finish());
+    }
+    "#;
+
+    #[test]
+    fn seeded_randomness_is_repeatable() -> Result<()> {
+        let seeded = |seed| ExecuteRequest {
+            code: RANDOM_CODE.to_string(),
+            deterministic: true,
+            seed: Some(seed),
+            ..ExecuteRequest::default()
+        };
+
+        let first = Sandbox::new()?.execute(&seeded(7))?;
+        let second = Sandbox::new()?.execute(&seeded(7))?;
+        let other = Sandbox::new()?.execute(&seeded(8))?;
+
+        assert!(first.success, "stderr was: {}", first.stderr);
+        assert_eq!(first.stdout, second.stdout);
+        assert_ne!(first.stdout, other.stdout);
+        Ok(())
+    }
+
+    const DEEP_RECURSION_CODE: &str = r#"
+    fn recurse(n: u64) -> u64 {
+        let frame = [n as u8; 1024];
+        if n == 0 { 0 } else { recurse(n - 1) + u64::from(frame[0]) }
+    }
+
+    fn main() {
+        println!("{}", recurse(20_000));
+    }
+    "#;
+
+    #[test]
+    fn default_stack_size_overflows() -> Result<()> {
+        let req = ExecuteRequest {
+            code: DEEP_RECURSION_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.stderr.contains("has overflowed its stack"), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn larger_stack_size() -> Result<()> {
+        let req = ExecuteRequest {
+            code: DEEP_RECURSION_CODE.to_string(),
+            stack_size_mb: Some(48),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(!resp.stderr.contains("has overflowed its stack"), "was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn execute_timeout_is_independent_of_compile_timeout() {
+        let timeouts = Timeouts::from_env(|name| match name {
+            "PLAYGROUND_TIMEOUT_EXECUTE" => Some("30".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(timeouts.execute, Duration::from_secs(30));
+        assert_eq!(timeouts.compile, DEFAULT_TIMEOUT);
+    }
+
+    #[test]
+    fn cpu_quota_must_be_positive() {
+        assert_eq!(parse_cpu_quota("0.5"), Some(0.5));
+        assert_eq!(parse_cpu_quota("0"), None);
+        assert_eq!(parse_cpu_quota("-1"), None);
+        assert_eq!(parse_cpu_quota("NaN"), None);
+    }
+
+    #[test]
+    fn only_exact_versions_are_toolchain_versions() {
+        assert!(is_toolchain_version("1.42.0"));
+        assert!(is_toolchain_version("1.100.10"));
+        assert!(!is_toolchain_version("1.42"));
+        assert!(!is_toolchain_version("1.42.0-beta.1"));
+        assert!(!is_toolchain_version("nightly-2020-03-01"));
+        assert!(!is_toolchain_version("1..0"));
+        assert!(!is_toolchain_version("1.42.0/../../stable"));
+    }
+
+    #[test]
+    fn installed_toolchains_are_listed_by_version() {
+        let list = "nightly-2020-03-01-x86_64-unknown-linux-gnu (default)\n\
+                    1.41.1-x86_64-unknown-linux-gnu\n\
+                    1.42.0-x86_64-unknown-linux-gnu\n";
+
+        assert_eq!(parse_toolchain_list(list), ["1.41.1", "1.42.0"]);
+    }
+
+    fn status(code: i32) -> std::process::ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+
+        std::process::ExitStatus::from_raw(code << 8)
+    }
+
+    fn output(code: i32, stderr: &str) -> Result<std::process::Output> {
+        Ok(std::process::Output {
+            status: status(code),
+            stdout: Vec::new(),
+            stderr: stderr.into(),
+        })
+    }
+
+    #[test]
+    fn recursion_limit_errors_are_categorized() {
+        let stderr = "error: recursion limit reached while expanding `recurse!`\n";
+        assert_eq!(error_category(stderr), Some(ErrorCategory::RecursionLimit));
+        assert_eq!(error_category("error[E0308]: mismatched types\n"), None);
+    }
+
+    #[test]
+    fn compiler_crashes_are_categorized_and_summarized() {
+        let stderr = "   Compiling playground v0.0.1 (/playground)
+error: internal compiler error: src/librustc_mir/monomorphize/collector.rs:802: reached the recursion limit while instantiating `foo`
+
+thread 'rustc' panicked at 'Box<Any>', src/librustc_errors/lib.rs:912:9
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+note: the compiler unexpectedly panicked. this is a bug.
+";
+
+        assert_eq!(error_category(stderr), Some(ErrorCategory::CompilerIce));
+        assert_eq!(
+            compiler_ice_summary(stderr).as_deref(),
+            Some("internal compiler error: src/librustc_mir/monomorphize/collector.rs:802: reached the recursion limit while instantiating `foo`"),
+        );
+
+        let stderr = "thread 'rustc' panicked at 'index out of bounds', src/librustc/ty/mod.rs:10:5\n";
+        assert_eq!(
+            compiler_ice_summary(stderr).as_deref(),
+            Some("the compiler panicked at 'index out of bounds', src/librustc/ty/mod.rs:10:5"),
+        );
+        assert_eq!(compiler_ice_summary("error[E0308]: mismatched types\n"), None);
+    }
+
+    #[test]
+    fn build_failures_are_only_read_from_cargo_output() {
+        let stderr = "   Compiling playground v0.0.1 (/playground)
+error[E0308]: mismatched types
+error: could not compile `playground`.
+";
+        assert!(build_failed(stderr));
+
+        let stderr = "   Compiling playground v0.0.1 (/playground)
+    Finished dev [unoptimized + debuginfo] target(s) in 0.50s
+     Running `target/debug/playground`
+error: could not compile
+";
+        assert!(!build_failed(stderr));
+    }
+
+    #[test]
+    fn test_results_are_only_reported_for_built_tests() {
+        assert_eq!(tests_passed(true, true, false), Some(false));
+        assert_eq!(tests_passed(true, true, true), Some(true));
+        assert_eq!(tests_passed(true, false, false), None);
+        assert_eq!(tests_passed(false, true, false), None);
+    }
+
+    #[test]
+    fn thread_limit_errors_are_categorized() {
+        let stderr = "thread 'main' panicked at 'failed to spawn thread: Os { code: 11, kind: WouldBlock, message: \"Resource temporarily unavailable\" }', src/libcore/result.rs:1188:5\n";
+        assert_eq!(error_category(stderr), Some(ErrorCategory::ThreadLimit));
+    }
+
+    #[test]
+    fn failures_are_categorized() {
+        use super::super::recent_failures::Category;
+
+        assert_eq!(build_failure_category(status(0), ""), None);
+        assert_eq!(build_failure_category(status(101), "error[E0308]: mismatched types"), None);
+        assert_eq!(build_failure_category(status(KILLED_STATUS), "   Compiling playground v0.0.1\nKilled"), Some(Category::Killed));
+        assert_eq!(build_failure_category(status(TERMINATED_STATUS), "Terminated"), Some(Category::Killed));
+        assert_eq!(
+            build_failure_category(status(101), "error: internal compiler error: unexpected panic"),
+            Some(Category::CompilerCrash),
+        );
+        assert_eq!(
+            failure_category(&ContainerRuntimeFailed { stderr: "docker: daemon down" }.build()),
+            Some(Category::ContainerRuntime),
+        );
+    }
+
+    #[test]
+    fn programs_cannot_claim_a_failure_of_the_sandbox() {
+        let ran = "    Finished dev [unoptimized + debuginfo] target(s) in 0.5s\n     Running `target/debug/playground`\n";
+        assert_eq!(build_failure_category(status(KILLED_STATUS), ran), None);
+        assert_eq!(build_failure_category(status(TERMINATED_STATUS), ran), None);
+        assert_eq!(
+            build_failure_category(status(101), &format!("{}error: internal compiler error: unexpected panic\n", ran)),
+            None,
+        );
+    }
+
+    #[test]
+    fn only_docker_fails_before_the_container_starts() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let checked = |stderr: &[u8]| {
+            let output = std::process::Output {
+                status: std::process::ExitStatus::from_raw(DOCKER_RUN_FAILURE_STATUS << 8),
+                stdout: Vec::new(),
+                stderr: stderr.into(),
+            };
+            check_command_output(Ok(Ok(output)), DEFAULT_TIMEOUT)
+        };
+
+        match checked(b"docker: Error response from daemon: unknown capability") {
+            Err(Error::ContainerRuntimeFailed { .. }) => {}
+            other => panic!("Docker's failure was not reported: {:?}", other),
+        }
+
+        let mut stderr = CONTAINER_STARTED_MARKER.to_vec();
+        stderr.extend_from_slice(b"docker: Error response from daemon: fake");
+        let output = checked(&stderr).expect("The program's own status was reported as Docker's");
+        assert_eq!(output.stderr, b"docker: Error response from daemon: fake");
+    }
+
+    #[test]
+    fn programs_cannot_claim_that_docker_failed() -> Result<()> {
+        let req = ExecuteRequest {
+            code: r#"fn main() { eprint!("docker: "); std::process::exit(125); }"#.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.success, "stderr was: {}", resp.stderr);
+        assert!(!resp.stderr.contains("the container started"), "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn cancelling_an_execution_kills_the_container() -> Result<()> {
+        let code = r#"
+        fn main() {
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+        "#;
+
+        let req = ExecuteRequest {
+            request_id: Some("cancel-test".to_string()),
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let running = std::thread::spawn(move || Sandbox::new()?.execute(&req));
+
+        // Wait for the program to be built and running
+        std::thread::sleep(Duration::from_secs(5));
+        assert!(super::super::cancellation::global().cancel("cancel-test"));
+
+        match running.join().expect("The execution panicked") {
+            Err(Error::Cancelled) => {}
+            other => panic!("The execution was not cancelled: {:?}", other.map(|r| r.success)),
+        }
+
+        // Docker removes the killed container in the background
+        std::thread::sleep(Duration::from_secs(1));
+
+        let name = cancellable_container_name("cancel-test");
+        let remaining = std::process::Command::new("docker")
+            .args(&["ps", "--all", "--quiet", "--filter", &format!("name={}", name)])
+            .output()
+            .expect("Unable to list containers");
+        assert!(remaining.stdout.is_empty(), "The container is still present");
+        Ok(())
+    }
+
+    #[test]
+    fn build_and_run_stops_when_the_build_fails() -> Result<()> {
+        let req = ExecuteRequest {
+            code: r#"fn main() { println!("never") }; nope"#.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.build_and_run(&req)?;
+
+        assert!(!resp.build.success);
+        assert!(resp.build.stderr.contains("error"), "was: {}", resp.build.stderr);
+        assert!(resp.run.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn build_and_run_separates_the_build_output() -> Result<()> {
+        let req = ExecuteRequest::default();
+
+        let resp = Sandbox::new()?.build_and_run(&req)?;
+
+        assert!(resp.build.success);
+        assert!(resp.build.stderr.contains("Compiling playground"), "was: {}", resp.build.stderr);
+
+        let run = resp.run.expect("The program did not run");
+        assert!(run.success);
+        assert!(run.stdout.contains("Hello, world!"));
+        Ok(())
+    }
+
+    #[test]
+    fn killed_containers_are_listed_as_recent_failures() -> Result<()> {
+        // Allocates more than the container's memory limit
+        let code = r#"
+        fn main() {
+            let mut chunks = Vec::new();
+            loop {
+                chunks.push(vec![1u8; 16 * 1024 * 1024]);
+            }
+        }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let before = SystemTime::now();
+        let resp = Sandbox::new()?.execute(&req)?;
+        assert!(!resp.success);
+
+        let failures = super::super::recent_failures::global().list();
+        assert!(
+            failures.iter().any(|f| f.category == super::super::recent_failures::Category::Killed && f.at >= before),
+            "failures were: {:?}", failures,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn output_is_truncated_at_a_line() {
+        let mut output = String::from("first\nsecond\nthird\n");
+        assert!(truncate_output(&mut output, 15));
+        assert_eq!(output, "first\nsecond\n");
+
+        let mut output = String::from("short\n");
+        assert!(!truncate_output(&mut output, 15));
+        assert_eq!(output, "short\n");
+
+        // A single long line is cut without splitting a character
+        let mut output = String::from("ééééé");
+        assert!(truncate_output(&mut output, 5));
+        assert_eq!(output, "éé");
+    }
+
+    #[test]
+    fn large_llvm_ir_is_truncated() -> Result<()> {
+        // Every byte of the array is written out in the IR
+        let code = r#"
+        #[no_mangle]
+        pub static DATA: [u8; 4_000_000] = [1; 4_000_000];
+
+        fn main() {
+            println!("{}", DATA[0]);
+        }
+        "#;
+
+        let req = CompileRequest {
+            code: code.to_string(),
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        assert!(resp.truncated);
+        assert!(resp.code.len() <= *MAX_CODE_OUTPUT_BYTES);
+        assert!(resp.code.contains("ModuleID"));
+        Ok(())
+    }
+
+    #[test]
+    fn cpu_bound_code_is_throttled_by_the_cpu_quota() -> Result<()> {
+        // Later flags override earlier ones, whatever the
+        // configured quota is.
+        let mut cmd = basic_secure_docker_command(TIMEOUTS.default);
+        cmd.args(&["--cpus", "0.1"]);
+        cmd.arg(Channel::Stable.container_name());
+        cmd.args(&["bash", "-c", "TIMEFORMAT='%R %U'; time (i=0; while [ $i -lt 200000 ]; do i=$((i+1)); done)"]);
+
+        let output = run_command_with_timeout(cmd, TIMEOUTS.default)?;
+        let stderr = vec_to_str(output.stderr)?;
+
+        let times: Vec<f64> = stderr.split_whitespace().filter_map(|t| t.parse().ok()).collect();
+        assert_eq!(times.len(), 2, "stderr was: {}", stderr);
+        let (real, user) = (times[0], times[1]);
+
+        assert!(real > 3.0 * user, "{}s elapsed for {}s of CPU time", real, user);
+        Ok(())
+    }
+
+    #[test]
+    fn timeouts_fall_back_to_the_global_default() {
+        let timeouts = Timeouts::from_env(|name| match name {
+            "PLAYGROUND_TIMEOUT_DEFAULT" => Some("20".to_string()),
+            "PLAYGROUND_TIMEOUT_FORMAT" => Some("5".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(timeouts.format, Duration::from_secs(5));
+        assert_eq!(timeouts.compile, Duration::from_secs(20));
+        assert_eq!(timeouts.miri, Duration::from_secs(20));
+        assert_eq!(timeouts.grace, DEFAULT_TIMEOUT_GRACE);
+    }
+
+    #[test]
+    fn seed_is_visible_to_the_program() -> Result<()> {
+        let req = ExecuteRequest {
+            code: r#"fn main() { println!("{}", std::env::var("PLAYGROUND_SEED").unwrap()); }"#.to_string(),
+            seed: Some(42),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert_eq!(resp.stdout.trim(), "42");
+        Ok(())
+    }
+
+    #[test]
+    fn prelude_items_are_available() -> Result<()> {
+        let code = "fn main() {\n    let m: HashMap<u8, u8> = HashMap::new();\n    let _x: i32 = m;\n}\n";
+        let (code, insertion) = crate::prelude::insert(code, "use std::collections::HashMap;\n");
+
+        let req = ExecuteRequest { code, ..ExecuteRequest::default() };
+        let resp = Sandbox::new()?.execute(&req)?;
+        let stderr = insertion.correct_execution(&resp.stderr);
+
+        assert!(!stderr.contains("cannot find type `HashMap`"), "stderr was: {}", stderr);
+        assert!(stderr.contains("mismatched types"), "stderr was: {}", stderr);
+        assert!(stderr.contains("src/main.rs:3:"), "stderr was: {}", stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_reports_hits_for_covered_lines() -> Result<()> {
+        let code = r#"
+            pub fn covered() -> u8 {
+                1
+            }
+
+            pub fn uncovered() -> u8 {
+                2
+            }
+
+            #[test]
+            fn it_works() {
+                assert_eq!(covered(), 1);
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            crate_type: CrateType::Library(LibraryType::Lib),
+            tests: true,
+            coverage: true,
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+        let coverage = resp.coverage.expect("Coverage was not recorded");
+        let hits = |line| coverage.iter().find(|c| c.line == line).map(|c| c.hits);
+
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(hits(3).unwrap_or(0) > 0, "coverage was: {:?}", coverage);
+        assert_eq!(hits(7), Some(0), "coverage was: {:?}", coverage);
+        Ok(())
+    }
+
+    #[test]
+    fn failing_tests_are_told_apart_from_failing_builds() -> Result<()> {
+        let execute = |code: &str| {
+            let req = ExecuteRequest {
+                crate_type: CrateType::Library(LibraryType::Lib),
+                tests: true,
+                code: code.to_string(),
+                ..ExecuteRequest::default()
+            };
+            Sandbox::new()?.execute(&req)
+        };
+
+        let resp = execute("#[test] fn fails() { assert_eq!(1 + 1, 3); }")?;
+        assert!(!resp.success, "stderr was: {}", resp.stderr);
+        assert!(resp.compiled, "stderr was: {}", resp.stderr);
+        assert_eq!(resp.tests_passed, Some(false));
+
+        let resp = execute("#[test] fn fails() { assert_eq!(1 + 1, \"3\"); }")?;
+        assert!(!resp.compiled, "stderr was: {}", resp.stderr);
+        assert_eq!(resp.tests_passed, None);
+        Ok(())
+    }
+
+    #[test]
+    fn programs_printing_build_errors_still_compiled() -> Result<()> {
+        let req = ExecuteRequest {
+            code: r#"fn main() { eprintln!("error: could not compile"); std::process::exit(1); }"#.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.success, "stderr was: {}", resp.stderr);
+        assert!(resp.compiled, "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn warm_containers_run_programs_sooner_than_new_ones() -> Result<()> {
+        let req = ExecuteRequest {
+            code: HELLO_WORLD_CODE.to_string(),
+            ..ExecuteRequest::default()
+        };
+        let container = WarmContainer::start(req.channel)?;
+
+        let start = std::time::Instant::now();
+        let cold = Sandbox::new()?.execute(&req)?;
+        let cold_elapsed = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let warm = container.execute(&req, None)?;
+        let warm_elapsed = start.elapsed();
+
+        assert!(cold.stdout.contains("Hello, world!"));
+        assert!(vec_to_str(warm.stdout)?.contains("Hello, world!"));
+        assert!(warm_elapsed < cold_elapsed, "warm took {:?}, cold took {:?}", warm_elapsed, cold_elapsed);
+        Ok(())
+    }
+
+    #[test]
+    fn warm_containers_do_not_leak_state_between_uses() -> Result<()> {
+        let code = r#"
+            use std::{fs, path::Path, process::{Command, Stdio}};
+
+            fn main() {
+                if Path::new("/dev/shm/secret").exists() {
+                    println!("leaked a file");
+                } else if Command::new("pgrep").args(&["-f", "sleep 1000"]).status().unwrap().success() {
+                    println!("leaked a process");
+                } else {
+                    println!("clean");
+                }
+
+                fs::write("/dev/shm/secret", "hunter2").unwrap();
+                Command::new("sleep").arg("1000").stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+            }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+        let container = WarmContainer::start(req.channel)?;
+
+        let first = container.execute(&req, None)?;
+        container.reset()?;
+        let second = container.execute(&req, None)?;
+
+        assert_eq!(vec_to_str(first.stdout)?.trim(), "clean");
+        assert_eq!(vec_to_str(second.stdout)?.trim(), "clean");
+        Ok(())
+    }
+
+    #[test]
+    fn warm_containers_stop_processes_of_other_users() -> Result<()> {
+        let code = r#"
+            use std::process::{Command, Stdio};
+
+            fn main() {
+                Command::new("sleep").arg("1000").stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap();
+            }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+        let container = WarmContainer::start(req.channel)?;
+
+        container.execute(&req, Some(20001))?;
+        assert_eq!(container.running_processes()?, 2);
+
+        container.reset()?;
+        assert_eq!(container.running_processes()?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn warm_container_users_cannot_read_each_others_files() -> Result<()> {
+        let code = r#"
+            use std::{fs, path::Path};
+
+            fn main() {
+                let path = Path::new("/tmp/secret");
+                if path.exists() {
+                    match fs::read_to_string(path) {
+                        Ok(_) => println!("read the file"),
+                        Err(_) => println!("denied"),
+                    }
+                } else {
+                    fs::write(path, "hunter2").unwrap();
+                    println!("wrote the file");
+                }
+            }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+        let container = WarmContainer::start(req.channel)?;
+
+        let first = container.execute(&req, Some(20001))?;
+        let second = container.execute(&req, Some(20002))?;
+
+        assert_eq!(vec_to_str(first.stdout)?.trim(), "wrote the file");
+        assert_eq!(vec_to_str(second.stdout)?.trim(), "denied");
+        Ok(())
+    }
+
+    #[test]
+    fn warm_containers_are_not_reused_after_unexpected_changes() -> Result<()> {
+        let code = r#"
+            fn main() {
+                std::fs::write("/playground/.cargo/bin/rustc", "exit 0").unwrap();
+            }
+        "#;
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+        let container = WarmContainer::start(req.channel)?;
+
+        container.execute(&req, None)?;
+
+        match container.reset() {
+            Err(Error::WarmContainerTainted { path }) => assert_eq!(path, "/playground/.cargo/bin/rustc"),
+            other => panic!("The container should not be reusable, but was {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_crates_own_artifacts_are_resettable() {
+        assert!(is_resettable_change("/playground/Cargo.toml"));
+        assert!(is_resettable_change("/playground/target/debug"));
+        assert!(is_resettable_change("/playground/target/debug/playground"));
+        assert!(is_resettable_change("/playground/target/release/deps/playground-0123456789abcdef"));
+        assert!(is_resettable_change("/playground/target/debug/incremental/playground-1a2b/s-abc/query-cache.bin"));
+
+        assert!(!is_resettable_change("/tmp/secret"));
+        assert!(!is_resettable_change("/playground/.cargo/bin/cargo"));
+        assert!(!is_resettable_change("/playground/target/debug/deps/librand-0123456789abcdef.rlib"));
+        assert!(!is_resettable_change("/playground/target/debug/build/playground-1a2b"));
+        assert!(!is_resettable_change("/playground/target/wasm32-unknown-unknown"));
+    }
+
+    #[test]
+    fn address_sanitizer_reports_use_after_free() -> Result<()> {
+        let code = r#"
+            fn main() {
+                let boxed = Box::new(42);
+                let pointer: *const i32 = &*boxed;
+                drop(boxed);
+                println!("{}", unsafe { *pointer });
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            channel: Channel::Nightly,
+            sanitizer: Some(Sanitizer::Address),
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(!resp.success);
+        assert!(resp.stderr.contains("ERROR: AddressSanitizer: heap-use-after-free"), "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn tracing_reports_the_programs_system_calls() -> Result<()> {
+        let code = r#"
+            fn main() {
+                let hosts = std::fs::read_to_string("/etc/hosts").unwrap();
+                println!("{}", hosts.len());
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            trace: true,
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        let syscalls = resp.syscalls.expect("The program was not traced");
+        assert!(syscalls.iter().any(|s| s.name == "openat" && s.calls > 0), "syscalls were: {:?}", syscalls);
+        Ok(())
+    }
+
+    #[test]
+    fn profiling_samples_a_busy_program() -> Result<()> {
+        // Docker before 20.10 can't give the container what perf needs
+        if !profiling_supported() {
+            return Ok(());
+        }
+
+        let code = r#"
+            fn fib(n: u64) -> u64 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            fn main() { println!("{}", fib(32)); }
+        "#;
+
+        let req = ExecuteRequest {
+            profile: true,
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        let profile = resp.profile.expect("The program was not profiled");
+        assert!(profile.lines().any(|l| l.contains("playground::fib")), "profile was: {}", profile);
+        Ok(())
+    }
+
+    #[test]
+    fn allocating_raises_the_peak_memory_use() -> Result<()> {
+        let peak_rss_kb = |code: &str| -> Result<u64> {
+            let req = ExecuteRequest {
+                memory_report: true,
+                code: code.to_string(),
+                ..ExecuteRequest::default()
+            };
+
+            let resp = Sandbox::new()?.execute(&req)?;
+            assert!(resp.success, "stderr was: {}", resp.stderr);
+            Ok(resp.peak_rss_kb.expect("The memory use was not reported"))
+        };
+
+        let trivial = peak_rss_kb("fn main() {}")?;
+        let allocating = peak_rss_kb(r#"
+            fn main() {
+                let buffer = vec![1u8; 64 * 1024 * 1024];
+                println!("{}", buffer.iter().map(|&b| u64::from(b)).sum::<u64>());
+            }
+        "#)?;
+
+        assert!(allocating > trivial + 32 * 1024, "allocating used {} KB, trivial used {} KB", allocating, trivial);
+        Ok(())
+    }
+
+    #[test]
+    fn static_target_produces_a_static_binary() -> Result<()> {
+        let code = r#"
+            use std::process::Command;
+
+            fn main() {
+                let exe = std::env::current_exe().unwrap();
+                let output = Command::new("file").arg(exe).output().unwrap();
+                print!("{}", String::from_utf8_lossy(&output.stdout));
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            static_target: true,
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert!(resp.stdout.contains("statically linked"), "stdout was: {}", resp.stdout);
+        assert!(!resp.stdout.contains("interpreter"), "stdout was: {}", resp.stdout);
+        Ok(())
+    }
+
+    #[test]
+    fn command_line_shows_how_the_options_become_flags() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Intel, DemangleAssembly::Demangle, ProcessAssembly::Filter),
+            mode: Mode::Release,
+            command_line: true,
+            ..CompileRequest::default()
+        };
+
+        let (_, command_line) = Sandbox::new()?.compile_command(req.target, req.channel, req.mode, req.tests, None, &req);
+
+        assert!(command_line.starts_with("cargo rustc --release"), "command line was: {}", command_line);
+        assert!(command_line.contains("--emit=asm"), "command line was: {}", command_line);
+        assert!(command_line.contains("llvm-args=-x86-asm-syntax=intel"), "command line was: {}", command_line);
+        Ok(())
+    }
+
+    #[test]
+    fn request_ids_are_only_logged_when_asked() -> Result<()> {
+        let req = ExecuteRequest {
+            request_id: Some("report-42".into()),
+            ..ExecuteRequest::default()
+        };
+
+        let cmd = Sandbox::new()?.execute_command(req.channel, req.mode, req.tests, false, &req);
+        let cmd = format!("{:?}", cmd);
+        assert!(!cmd.contains("PLAYGROUND_REQUEST_ID"), "command was: {}", cmd);
+
+        let req = ExecuteRequest { log_request_id: true, ..req };
+        let cmd = Sandbox::new()?.execute_command(req.channel, req.mode, req.tests, false, &req);
+        let cmd = format!("{:?}", cmd);
+        assert!(cmd.contains("PLAYGROUND_REQUEST_ID=report-42"), "command was: {}", cmd);
+        assert!(cmd.contains("playground.request-id=report-42"), "command was: {}", cmd);
+        Ok(())
+    }
+
+    #[test]
+    fn lld_linker_reaches_cargo() -> Result<()> {
+        let req = ExecuteRequest {
+            linker: Linker::Lld,
+            ..ExecuteRequest::default()
+        };
+
+        let cmd = Sandbox::new()?.execute_command(req.channel, req.mode, req.tests, false, &req);
+        let cmd = format!("{:?}", cmd);
+
+        assert!(cmd.contains("CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_LINKER=/playground/tools/lld-linker"), "command was: {}", cmd);
+        Ok(())
+    }
+
+    #[test]
+    fn timezone_is_visible_to_the_program() -> Result<()> {
+        let code = r#"
+            extern crate chrono;
+            fn main() { println!("{}", chrono::Local::now().offset()); }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            timezone: Some("Asia/Kolkata".to_string()),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert_eq!(resp.stdout.trim(), "+05:30");
+        Ok(())
+    }
+
+    #[test]
+    fn environment_variables_are_visible_to_the_program() -> Result<()> {
+        let code = r#"
+            fn main() { println!("{}", std::env::var("GREETING").unwrap()); }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            env: vec![("GREETING".to_string(), "Hello, environment!".to_string())],
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+
+        assert_eq!(resp.stdout.trim(), "Hello, environment!");
+        Ok(())
+    }
+
+    // rustc hard links the object files of unchanged codegen units into
+    // the new session, so they keep the time they were first written
+    fn object_files(dir: &Path) -> Result<BTreeMap<PathBuf, SystemTime>> {
+        let mut files = BTreeMap::new();
+        for entry in fs::read_dir(dir).context(UnableToReadOutput)? {
+            let path = entry.context(UnableToReadOutput)?.path();
+            if path.is_dir() {
+                files.extend(object_files(&path)?);
+            } else if path.extension() == Some(OsStr::new("o")) {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).context(UnableToReadOutput)?;
+                files.insert(PathBuf::from(path.file_name().unwrap_or_default()), modified);
+            }
+        }
+        Ok(files)
+    }
+
+    #[test]
+    fn incremental_artifacts_are_reused() -> Result<()> {
+        let session = incremental_session(None);
+        let session_dir = INCREMENTAL_CACHE_DIR.join(&session);
+
+        let code = r#"
+            mod unchanged {
+                #[inline(never)]
+                pub fn greeting() -> &'static str { "Hello" }
+            }
+
+            fn main() {
+                println!("{}, world!", unchanged::greeting());
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            incremental_session: Some(session.clone()),
+            ..ExecuteRequest::default()
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+
+        let first = object_files(&session_dir)?;
+        assert!(!first.is_empty(), "no object files were saved");
+        assert_eq!(incremental_session(Some(&session)), session);
+
+        thread::sleep(Duration::from_millis(50));
+
+        let req = ExecuteRequest {
+            code: code.replace("world!", "incremental!"),
+            ..req
+        };
+
+        let resp = Sandbox::new()?.execute(&req)?;
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(resp.stdout.contains("Hello, incremental!"));
 
-    const HELLO_WORLD_CODE: &'static str = r#"
-    fn main() {
-        println!("Hello, world!");
+        let second = object_files(&session_dir)?;
+        let reused = second.iter().filter(|&(name, modified)| first.get(name) == Some(modified)).count();
+        assert!(reused > 0, "nothing was reused from {:?} in {:?}", first, second);
+        Ok(())
     }
-    "#;
 
-    impl Default for ExecuteRequest {
-        fn default() -> Self {
-            ExecuteRequest {
-                channel: Channel::Stable,
-                crate_type: CrateType::Binary,
-                mode: Mode::Debug,
-                tests: false,
-                code: HELLO_WORLD_CODE.to_string(),
-                edition: None,
-                backtrace: false,
-            }
-        }
-    }
+    #[test]
+    fn incremental_sessions_are_issued_by_the_server() {
+        let session = incremental_session(None);
 
-    impl Default for CompileRequest {
-        fn default() -> Self {
-            CompileRequest {
-                target: CompileTarget::LlvmIr,
-                channel: Channel::Stable,
-                crate_type: CrateType::Binary,
-                mode: Mode::Debug,
-                tests: false,
-                code: HELLO_WORLD_CODE.to_string(),
-                edition: None,
-                backtrace: false,
-            }
-        }
-    }
+        assert!(is_incremental_session(&session));
+        assert_ne!(incremental_session(None), session);
+        assert_ne!(incremental_session(Some("chosen-by-the-client")), "chosen-by-the-client");
 
-    impl Default for ClippyRequest {
-        fn default() -> Self {
-            ClippyRequest {
-                code: HELLO_WORLD_CODE.to_string(),
-                crate_type: CrateType::Binary,
-                edition: None,
-            }
-        }
+        let unknown = "0123456789abcdef0123456789abcdef";
+        assert_ne!(incremental_session(Some(unknown)), unknown);
     }
 
     #[test]
-    fn basic_functionality() {
-        let req = ExecuteRequest::default();
+    fn stale_incremental_sessions_are_evicted() -> Result<()> {
+        let root = TempDir::new("incremental").context(UnableToCreateTempDir)?;
+        fs::create_dir(root.path().join("a")).context(UnableToCreateTempDir)?;
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        evict_incremental_dirs(root.path(), Duration::from_secs(0), 10, &BTreeMap::new());
 
-        assert!(resp.stdout.contains("Hello, world!"));
+        assert!(!root.path().join("a").exists());
+        Ok(())
     }
 
-    const COMPILATION_MODE_CODE: &'static str = r#"
-    #[cfg(debug_assertions)]
-    fn main() {
-        println!("Compiling in debug mode");
+    #[test]
+    fn incremental_sessions_in_use_are_not_evicted() -> Result<()> {
+        let root = TempDir::new("incremental").context(UnableToCreateTempDir)?;
+        let dir = root.path().join("a");
+        fs::create_dir(&dir).context(UnableToCreateTempDir)?;
+
+        let mut in_use = BTreeMap::new();
+        in_use.insert(dir.clone(), 1);
+        evict_incremental_dirs(root.path(), Duration::from_secs(0), 0, &in_use);
+
+        assert!(dir.exists());
+        Ok(())
     }
 
-    #[cfg(not(debug_assertions))]
-    fn main() {
-        println!("Compiling in release mode");
+    #[test]
+    fn least_recently_used_incremental_sessions_are_evicted() -> Result<()> {
+        let root = TempDir::new("incremental").context(UnableToCreateTempDir)?;
+        for session in &["old", "new"] {
+            let dir = root.path().join(session);
+            fs::create_dir(&dir).context(UnableToCreateTempDir)?;
+            fs::write(dir.join(INCREMENTAL_LAST_USED), "").context(UnableToCreateTempDir)?;
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        evict_incremental_dirs(root.path(), INCREMENTAL_CACHE_MAX_AGE, 1, &BTreeMap::new());
+
+        assert!(!root.path().join("old").exists());
+        assert!(root.path().join("new").exists());
+        Ok(())
     }
-    "#;
 
     #[test]
-    fn debug_mode() {
-        let req = ExecuteRequest {
-            code: COMPILATION_MODE_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn output_llvm_ir() {
+        let req = CompileRequest {
+            target: CompileTarget::LlvmIr,
+            ..CompileRequest::default()
         };
 
         let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = sb.compile(&req).expect("Unable to compile code");
 
-        assert!(resp.stdout.contains("debug mode"));
+        assert!(resp.code.contains("ModuleID"));
+        assert!(resp.code.contains("target datalayout"));
+        assert!(resp.code.contains("target triple"));
     }
 
     #[test]
-    fn release_mode() {
-        let req = ExecuteRequest {
-            mode: Mode::Release,
-            code: COMPILATION_MODE_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn output_cfg() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::Cfg,
+            ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.compile(&req)?;
 
-        assert!(resp.stdout.contains("release mode"));
+        assert!(resp.code.contains(r#"target_pointer_width="64""#), "code was: {}", resp.code);
+        assert!(resp.code.contains("debug_assertions"), "code was: {}", resp.code);
+        Ok(())
     }
 
-    static VERSION_CODE: &'static str = r#"
-    use std::process::Command;
+    #[test]
+    fn output_target_features() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::TargetFeatures,
+            ..CompileRequest::default()
+        };
 
-    fn main() {
-        let output = Command::new("rustc").arg("--version").output().unwrap();
-        let output = String::from_utf8(output.stdout).unwrap();
-        println!("{}", output);
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        assert!(resp.code.lines().any(|l| l.trim_start().starts_with("sse2 ")), "code was: {}", resp.code);
+        Ok(())
     }
-    "#;
 
     #[test]
-    fn stable_channel() {
-        let req = ExecuteRequest {
-            channel: Channel::Stable,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn time_passes_are_reported() -> Result<()> {
+        let req = CompileRequest {
+            channel: Channel::Nightly,
+            time_passes: true,
+            code: CONSTANT_FOLDING_CODE.to_string(),
+            ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.compile(&req)?;
+        let passes = resp.passes.expect("Pass timings were not returned");
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(!resp.stdout.contains("beta"));
-        assert!(!resp.stdout.contains("nightly"));
+        assert!(!passes.is_empty(), "stdout was: {}", resp.stdout);
+        Ok(())
     }
 
     #[test]
-    fn beta_channel() {
-        let req = ExecuteRequest {
-            channel: Channel::Beta,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn denied_lint_groups_fail_compilation() -> Result<()> {
+        // Floating point literals in patterns only warn by default
+        let code = r#"
+        fn main() {
+            match 1.0 {
+                1.0 => println!("one"),
+                _ => println!("other"),
+            }
+        }
+        "#;
+
+        let allowed = CompileRequest {
+            code: code.to_string(),
+            ..CompileRequest::default()
+        };
+        let denied = CompileRequest {
+            deny_lint_groups: vec!["future_incompatible"],
+            ..allowed.clone()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        assert!(Sandbox::new()?.compile(&allowed)?.success);
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(resp.stdout.contains("beta"));
-        assert!(!resp.stdout.contains("nightly"));
+        let resp = Sandbox::new()?.compile(&denied)?;
+        assert!(!resp.success);
+        assert!(resp.stderr.contains("future_incompatible"), "stderr was: {}", resp.stderr);
+        Ok(())
     }
 
     #[test]
-    fn nightly_channel() {
-        let req = ExecuteRequest {
-            channel: Channel::Nightly,
-            code: VERSION_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn clippy_lints_are_reported_with_the_compilation() -> Result<()> {
+        let code = r#"
+        pub fn is_empty(v: &Vec<u8>) -> bool {
+            v.len() == 0
+        }
+        "#;
+
+        let req = CompileRequest {
+            crate_type: CrateType::Library(LibraryType::Lib),
+            with_clippy: true,
+            code: code.to_string(),
+            ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.execute(&req).expect("Unable to execute code");
+        let resp = Sandbox::new()?.compile(&req)?;
 
-        assert!(resp.stdout.contains("rustc"));
-        assert!(!resp.stdout.contains("beta"));
-        assert!(resp.stdout.contains("nightly"));
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(resp.code.contains("is_empty"), "code was: {}", resp.code);
+        assert!(resp.stderr.contains("clippy::len_zero"), "stderr was: {}", resp.stderr);
+        assert!(resp.stderr.contains("clippy::ptr_arg"), "stderr was: {}", resp.stderr);
+        Ok(())
     }
 
-    // Code that will only work in Rust 2015
-    const EDITION_CODE: &str = r#"
+    #[test]
+    fn output_dep_info() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::DepInfo,
+            ..CompileRequest::default()
+        };
+
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        assert!(resp.code.contains("src/main.rs"), "code was: {}", resp.code);
+        Ok(())
+    }
+
+    const CONSTANT_FOLDING_CODE: &str = r#"
     fn main() {
-        let async = true;
+        let x = 1 + 2;
+        println!("{}", x);
     }
     "#;
 
-    const EDITION_ERROR: &str = "found keyword `async`";
-
     #[test]
-    fn rust_edition_default() -> Result<()> {
-        let req = ExecuteRequest {
-            channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
-            ..ExecuteRequest::default()
+    fn optimized_mir_differs_from_built_mir() -> Result<()> {
+        let mir_at = |stage| -> Result<String> {
+            let req = CompileRequest {
+                target: CompileTarget::Mir(Some(stage)),
+                channel: Channel::Nightly,
+                code: CONSTANT_FOLDING_CODE.to_string(),
+                ..CompileRequest::default()
+            };
+
+            let resp = Sandbox::new()?.compile(&req)?;
+            assert!(resp.success, "stderr was: {}", resp.stderr);
+            Ok(resp.code)
         };
 
-        let resp = Sandbox::new()?.execute(&req)?;
+        let built = mir_at(MirStage::Built)?;
+        let optimized = mir_at(MirStage::Optimized)?;
 
-        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        assert!(built.contains("fn main()"), "was: {}", built);
+        assert_ne!(built, optimized);
         Ok(())
     }
 
     #[test]
-    fn rust_edition_2015() -> Result<()> {
-        let req = ExecuteRequest {
+    fn mir_after_a_pass_differs_from_before_it() -> Result<()> {
+        let const_prop = MirPass::named("ConstProp").expect("ConstProp is not a known pass");
+        let req = CompileRequest {
+            target: CompileTarget::Mir(Some(MirStage::After(const_prop))),
             channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
-            edition: Some(Edition::Rust2015),
-            ..ExecuteRequest::default()
+            code: CONSTANT_FOLDING_CODE.to_string(),
+            ..CompileRequest::default()
         };
 
-        let resp = Sandbox::new()?.execute(&req)?;
+        let sb = Sandbox::new()?;
+        let resp = sb.compile(&req)?;
+        assert!(resp.success, "stderr was: {}", resp.stderr);
 
-        assert!(!resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        let before = sb.read_mir_dump(const_prop, "before")?.expect("The MIR before the pass was not dumped");
+        let after = sb.read_mir_dump(const_prop, "after")?.expect("The MIR after the pass was not dumped");
+
+        assert!(before.contains("fn main()"), "was: {}", before);
+        assert_eq!(resp.code, after);
+        assert_ne!(before, after);
         Ok(())
     }
 
     #[test]
-    fn rust_edition_2018() -> Result<()> {
-        let req = ExecuteRequest {
+    fn mir_of_a_pass_that_did_not_run_is_an_error() -> Result<()> {
+        let inline = MirPass::named("Inline").expect("Inline is not a known pass");
+        let req = CompileRequest {
+            target: CompileTarget::Mir(Some(MirStage::After(inline))),
             channel: Channel::Nightly,
-            code: EDITION_CODE.to_string(),
-            edition: Some(Edition::Rust2018),
-            ..ExecuteRequest::default()
+            mode: Mode::Debug,
+            code: CONSTANT_FOLDING_CODE.to_string(),
+            ..CompileRequest::default()
         };
 
-        let resp = Sandbox::new()?.execute(&req)?;
-
-        assert!(resp.stderr.contains(EDITION_ERROR), "was: {}", resp.stderr);
+        match Sandbox::new()?.compile(&req) {
+            Err(Error::MirPassNotRun { pass }) => assert_eq!(pass, "Inline"),
+            other => panic!("Expected the pass not to run, was: {:?}", other.map(|resp| resp.code)),
+        }
         Ok(())
     }
 
-    const BACKTRACE_CODE: &str = r#"
-    fn trigger_the_problem() {
-        None::<u8>.unwrap();
-    }
+    #[test]
+    fn output_assembly() {
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Mangle, ProcessAssembly::Raw),
+            ..CompileRequest::default()
+        };
 
-    fn main() {
-        trigger_the_problem()
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.code.contains(".text"));
+        assert!(resp.code.contains(".file"));
+        assert!(resp.code.contains(".section"));
+        assert!(resp.code.contains(".p2align"));
     }
-    "#;
 
-    const BACKTRACE_NOTE: &str = "run with `RUST_BACKTRACE=1` environment variable to display a backtrace";
+    #[test]
+    fn output_demangled_assembly() {
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Demangle, ProcessAssembly::Raw),
+            ..CompileRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.compile(&req).expect("Unable to compile code");
+
+        assert!(resp.code.contains("core::fmt::Arguments::new_v1"));
+        assert!(resp.code.contains("std::io::stdio::_print@GOTPCREL"));
+    }
 
     #[test]
-    fn backtrace_disabled() -> Result<()> {
-        let req = ExecuteRequest {
-            code: BACKTRACE_CODE.to_string(),
-            backtrace: false,
-            ..ExecuteRequest::default()
+    fn output_all_assembly_flavors() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Intel, DemangleAssembly::Demangle, ProcessAssembly::Filter),
+            all_flavors: true,
+            ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new()?;
-        let resp = sb.execute(&req)?;
+        let resp = Sandbox::new()?.compile(&req)?;
 
-        assert!(resp.stderr.contains(BACKTRACE_NOTE), "Was: {}", resp.stderr);
-        assert!(!resp.stderr.contains("stack backtrace:"), "Was: {}", resp.stderr);
+        let att = &resp.flavors.iter().find(|(f, _)| *f == AssemblyFlavor::Att).expect("AT&T missing").1;
+        let intel = &resp.flavors.iter().find(|(f, _)| *f == AssemblyFlavor::Intel).expect("Intel missing").1;
+
+        assert!(att.contains("%rsp"), "was: {}", att);
+        assert!(!intel.contains("%rsp"), "was: {}", intel);
+        assert_ne!(att, intel);
+        assert_eq!(&resp.code, intel);
+        Ok(())
+    }
+
+    #[test]
+    fn instruction_counts_for_a_simple_function() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Demangle, ProcessAssembly::Filter),
+            crate_type: CrateType::Library(LibraryType::Rlib),
+            mode: Mode::Release,
+            instruction_counts: true,
+            code: "pub fn add(a: u32, b: u32) -> u32 { a.wrapping_add(b) }".to_string(),
+            ..CompileRequest::default()
+        };
 
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        let counts = resp.instruction_counts.expect("no instruction counts");
+        let add = counts.iter().find(|c| c.function == "playground::add");
+        let add = add.unwrap_or_else(|| panic!("no count for `add` in {:?}", counts));
+        assert!((1..=4).contains(&add.count), "was: {}", add.count);
         Ok(())
     }
 
     #[test]
-    fn backtrace_enabled() -> Result<()> {
-        let req = ExecuteRequest {
-            code: BACKTRACE_CODE.to_string(),
-            backtrace: true,
-            ..ExecuteRequest::default()
+    fn wasm_sections_include_the_code() -> Result<()> {
+        let req = CompileRequest {
+            target: CompileTarget::Wasm,
+            channel: Channel::Nightly,
+            crate_type: CrateType::Library(LibraryType::Cdylib),
+            wasm_sections: true,
+            code: r#"
+                #[no_mangle]
+                pub extern "C" fn fib(n: u32) -> u32 { if n < 2 { n } else { fib(n - 1) + fib(n - 2) } }
+            "#.to_string(),
+            ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new()?;
-        let resp = sb.execute(&req)?;
-
-        assert!(!resp.stderr.contains(BACKTRACE_NOTE), "Was: {}", resp.stderr);
-        assert!(resp.stderr.contains("stack backtrace:"), "Was: {}", resp.stderr);
+        let resp = Sandbox::new()?.compile(&req)?;
+        assert!(resp.success, "stderr was: {}", resp.stderr);
 
+        let sections = resp.wasm_sections.expect("no wasm sections");
+        let code = sections.iter().find(|s| s.name == "code");
+        let code = code.unwrap_or_else(|| panic!("no code section in {:?}", sections));
+        assert!(code.bytes > 0);
         Ok(())
     }
 
     #[test]
-    fn output_llvm_ir() {
+    fn short_diagnostics_are_one_line_each() -> Result<()> {
         let req = CompileRequest {
-            target: CompileTarget::LlvmIr,
+            diagnostic_format: DiagnosticFormat::Short,
+            code: "fn main() { let a: u8 = \"a\"; let b: u8 = \"b\"; }".to_string(),
             ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.compile(&req).expect("Unable to compile code");
+        let resp = Sandbox::new()?.compile(&req)?;
+        assert!(!resp.success);
 
-        assert!(resp.code.contains("ModuleID"));
-        assert!(resp.code.contains("target datalayout"));
-        assert!(resp.code.contains("target triple"));
+        let errors: Vec<_> = resp.stderr.lines().filter(|l| l.contains("error[E0308]")).collect();
+        assert_eq!(errors.len(), 2, "stderr was: {}", resp.stderr);
+        for error in errors {
+            assert!(error.starts_with("src/main.rs:1:"), "was: {}", error);
+        }
+        assert!(!resp.stderr.contains("-->"), "stderr was: {}", resp.stderr);
+        Ok(())
     }
 
     #[test]
-    fn output_assembly() {
-        let req = CompileRequest {
-            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Mangle, ProcessAssembly::Raw),
+    fn one_codegen_unit_changes_the_assembly() -> Result<()> {
+        let code = r#"
+            pub fn double(values: &[u32]) -> Vec<u32> { values.iter().map(|v| helper(*v)).collect() }
+            fn helper(value: u32) -> u32 { value.wrapping_mul(2) }
+            pub fn sum(values: &[u32]) -> u32 { values.iter().copied().map(helper).fold(0, u32::wrapping_add) }
+        "#;
+        let req = |codegen_units| CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Demangle, ProcessAssembly::Filter),
+            crate_type: CrateType::Library(LibraryType::Rlib),
+            mode: Mode::Release,
+            codegen_units: Some(codegen_units),
+            code: code.to_string(),
             ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.compile(&req).expect("Unable to compile code");
+        let one = Sandbox::new()?.compile(&req(1))?;
+        let many = Sandbox::new()?.compile(&req(16))?;
 
-        assert!(resp.code.contains(".text"));
-        assert!(resp.code.contains(".file"));
-        assert!(resp.code.contains(".section"));
-        assert!(resp.code.contains(".p2align"));
+        assert!(one.success, "stderr was: {}", one.stderr);
+        assert!(many.success, "stderr was: {}", many.stderr);
+        assert_ne!(one.code, many.code);
+        Ok(())
     }
 
     #[test]
-    fn output_demangled_assembly() {
-        let req = CompileRequest {
-            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Demangle, ProcessAssembly::Raw),
+    fn raw_assembly_keeps_filtered_directives() -> Result<()> {
+        let raw = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Mangle, ProcessAssembly::Raw),
+            ..CompileRequest::default()
+        };
+        let filtered = CompileRequest {
+            target: CompileTarget::Assembly(AssemblyFlavor::Att, DemangleAssembly::Mangle, ProcessAssembly::Filter),
             ..CompileRequest::default()
         };
 
-        let sb = Sandbox::new().expect("Unable to create sandbox");
-        let resp = sb.compile(&req).expect("Unable to compile code");
+        let raw = Sandbox::new()?.compile(&raw)?;
+        let filtered = Sandbox::new()?.compile(&filtered)?;
 
-        assert!(resp.code.contains("core::fmt::Arguments::new_v1"));
-        assert!(resp.code.contains("std::io::stdio::_print@GOTPCREL"));
+        assert!(raw.code.contains(".cfi_startproc"), "was: {}", raw.code);
+        assert!(!filtered.code.contains(".cfi_startproc"), "was: {}", filtered.code);
+        assert!(raw.code.contains("_ZN10playground4main"), "was: {}", raw.code);
+        Ok(())
     }
 
     #[test]
@@ -1133,6 +5519,34 @@ mod test {
         assert_eq!(lines[2], "}");
     }
 
+    #[test]
+    fn checking_formatted_code() -> Result<()> {
+        let req = FormatRequest {
+            code: "fn foo() {\n    method_call();\n}\n".to_string(),
+            edition: None,
+        };
+
+        let resp = Sandbox::new()?.format_check(&req)?;
+
+        assert!(resp.formatted, "diff was: {}", resp.diff);
+        assert!(resp.diff.is_empty(), "was: {}", resp.diff);
+        Ok(())
+    }
+
+    #[test]
+    fn checking_unformatted_code() -> Result<()> {
+        let req = FormatRequest {
+            code: "fn foo () { method_call(); }".to_string(),
+            edition: None,
+        };
+
+        let resp = Sandbox::new()?.format_check(&req)?;
+
+        assert!(!resp.formatted);
+        assert!(resp.diff.contains("+fn foo() {"), "was: {}", resp.diff);
+        Ok(())
+    }
+
     // Code that is only syntactically valid in Rust 2018
     const FORMAT_IN_EDITION_2018: &str = r#"fn main() { use std::num::ParseIntError; let result: Result<i32, ParseIntError> = try { "1".parse::<i32>()? + "2".parse::<i32>()? + "3".parse::<i32>()? }; assert_eq!(result, Ok(6)); }"#;
 
@@ -1171,6 +5585,62 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn checking_nightly_only_code() -> Result<()> {
+        let code = r#"
+        #![feature(never_type)]
+
+        fn main() {
+            let _never: ! = panic!();
+        }
+        "#;
+
+        let check = |channel| -> Result<CheckResponse> {
+            let req = CheckRequest { code: code.to_string(), channel, ..CheckRequest::default() };
+            Sandbox::new()?.check(&req)
+        };
+
+        assert!(!check(Channel::Stable)?.success);
+        assert!(!check(Channel::Beta)?.success);
+        let nightly = check(Channel::Nightly)?;
+        assert!(nightly.success, "stderr was: {}", nightly.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn runaway_macros_report_the_recursion_limit() -> Result<()> {
+        let code = r#"
+        macro_rules! recurse {
+            () => { recurse!() };
+        }
+
+        fn main() {
+            recurse!();
+        }
+        "#;
+
+        let req = CompileRequest {
+            code: code.to_string(),
+            ..CompileRequest::default()
+        };
+        let resp = Sandbox::new()?.compile(&req)?;
+
+        assert!(!resp.success);
+        assert_eq!(resp.error_category, Some(ErrorCategory::RecursionLimit), "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn clippy_lints_are_listed() -> Result<()> {
+        let lints = Sandbox::new()?.clippy_lints()?;
+
+        let needless_return = lints.iter().find(|lint| lint.name == "clippy::needless_return");
+        let needless_return = needless_return.unwrap_or_else(|| panic!("no needless_return in {:?}", lints));
+        assert_eq!(needless_return.level, "warn");
+        assert!(needless_return.groups.iter().any(|group| group == "clippy::style"), "was: {:?}", needless_return);
+        Ok(())
+    }
+
     #[test]
     fn linting_code() {
         let code = r#"
@@ -1207,6 +5677,8 @@ mod test {
             code: code.to_string(),
             crate_type: CrateType::Library(LibraryType::Rlib),
             edition: Some(Edition::Rust2018),
+            suggestions: false,
+            user_code_only: false,
         };
 
         let sb = Sandbox::new().expect("Unable to create sandbox");
@@ -1216,6 +5688,63 @@ mod test {
         assert!(resp.stderr.contains("warn(clippy::zero_divided_by_zero)"));
     }
 
+    #[test]
+    fn applying_the_clippy_patch_fixes_the_lints() -> Result<()> {
+        let code = r#"fn main() {
+    let enabled = std::env::args().count() > 1;
+    if enabled == true {
+        println!("{}", String::from("enabled").to_string());
+    }
+}
+"#;
+
+        let req = ClippyRequest {
+            code: code.to_string(),
+            suggestions: true,
+            ..ClippyRequest::default()
+        };
+        let resp = Sandbox::new()?.clippy(&req)?;
+        assert!(resp.stderr.contains("warning:"), "stderr was: {}", resp.stderr);
+
+        let patched = super::super::patch::apply(code, &resp.suggestions);
+        let patch = super::super::patch::unified_diff("src/main.rs", code, &patched);
+        assert!(patch.contains("-    if enabled == true {\n+    if enabled {\n"), "patch was: {}", patch);
+
+        let req = ClippyRequest { code: patched, ..req };
+        let resp = Sandbox::new()?.clippy(&req)?;
+        assert!(resp.success, "stderr was: {}", resp.stderr);
+        assert!(!resp.stderr.contains("warning:"), "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
+    #[test]
+    fn only_the_users_code_is_linted() -> Result<()> {
+        let code = r#"
+        use lazy_static::lazy_static;
+
+        lazy_static! {
+            static ref ANSWER: u8 = 42;
+        }
+
+        fn main() {
+            let a = 0.0 / 0.0;
+            println!("{} {}", *ANSWER, a);
+        }
+        "#;
+
+        let req = ClippyRequest {
+            code: code.to_string(),
+            edition: Some(Edition::Rust2018),
+            user_code_only: true,
+            ..ClippyRequest::default()
+        };
+        let resp = Sandbox::new()?.clippy(&req)?;
+
+        assert!(resp.stderr.contains("clippy::zero_divided_by_zero"), "stderr was: {}", resp.stderr);
+        assert!(!resp.stderr.contains(".cargo/registry"), "stderr was: {}", resp.stderr);
+        Ok(())
+    }
+
     #[test]
     fn interpreting_code() -> Result<()> {
         let code = r#"
@@ -1239,6 +5768,119 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn doc_json_is_only_offered_by_compilers_that_support_it() -> Result<()> {
+        let sb = Sandbox::new()?;
+
+        assert!(!sb.capabilities(Channel::Stable)?.has_feature(FEATURE_DOC_JSON));
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_is_only_offered_by_compilers_that_support_it() -> Result<()> {
+        let sb = Sandbox::new()?;
+
+        assert!(!sb.capabilities(Channel::Stable)?.has_feature(FEATURE_COVERAGE));
+        Ok(())
+    }
+
+    #[test]
+    fn doc_json_includes_the_root_module_items() -> Result<()> {
+        let sb = Sandbox::new()?;
+
+        // Not every nightly has the unstable option
+        if !sb.capabilities(Channel::Nightly)?.has_feature(FEATURE_DOC_JSON) {
+            return Ok(());
+        }
+
+        let code = r#"
+        pub struct Point { pub x: i32, pub y: i32 }
+
+        pub fn origin() -> Point { Point { x: 0, y: 0 } }
+        "#;
+
+        let req = DocJsonRequest {
+            code: code.to_string(),
+            channel: Channel::Nightly,
+            edition: None,
+            crate_type: CrateType::Library(LibraryType::Lib),
+        };
+
+        let resp = sb.doc_json(&req)?;
+
+        assert!(resp.success, "stderr: {}", resp.stderr);
+        let doc = resp.doc.expect("No documentation was generated");
+        let summary = super::super::rustdoc_json::summarize(&doc).expect("The documentation was not recognized");
+        assert!(summary.root_items.contains(&"Point".to_string()), "was: {:?}", summary.root_items);
+        assert!(summary.root_items.contains(&"origin".to_string()), "was: {:?}", summary.root_items);
+        Ok(())
+    }
+
+    #[test]
+    fn miri_errors_point_at_the_undefined_behavior() -> Result<()> {
+        let code = r#"
+        fn main() {
+            let bytes = [0u8; 8];
+            let pointer = bytes.as_ptr().wrapping_add(1) as *const u32;
+            let value = unsafe { *pointer };
+            println!("{}", value);
+        }
+        "#;
+
+        let req = MiriRequest {
+            code: code.to_string(),
+            edition: None,
+        };
+
+        let resp = Sandbox::new()?.miri(&req)?;
+
+        let error = resp.errors.first().unwrap_or_else(|| panic!("No errors in {}", resp.stderr));
+        assert_eq!(error.kind, "Undefined Behavior");
+        assert!(error.message.contains("alignment"), "was: {}", error.message);
+        let span = error.span.as_ref().expect("The error had no span");
+        assert_eq!((span.file.as_str(), span.line), ("src/main.rs", 5));
+        Ok(())
+    }
+
+    #[test]
+    fn rendering_the_crate_graph() -> Result<()> {
+        let sb = Sandbox::new()?;
+        let dot = sb.crate_graph()?.to_dot();
+
+        assert!(dot.matches("->").count() >= 2, "was: {}", dot);
+
+        let svg = sb.render_graph(&dot, GraphFormat::Svg)?;
+        let svg = String::from_utf8(svg).expect("SVG was not UTF-8");
+
+        assert!(svg.starts_with("<?xml"), "was: {}", svg);
+        assert!(svg.contains("<svg"), "was: {}", svg);
+        Ok(())
+    }
+
+    #[test]
+    fn the_crate_dependencies_start_at_the_playground() -> Result<()> {
+        let graph = Sandbox::new()?.crate_graph()?;
+
+        let nodes: Vec<_> = graph.nodes().collect();
+        let root = graph.root().expect("The root is missing");
+        assert_eq!(nodes[root].name, "playground");
+        assert!(nodes.iter().any(|n| n.name == "rand"), "nodes were: {:?}", nodes);
+        assert!(graph.edges().any(|e| e.from == root), "The root has no dependencies");
+        Ok(())
+    }
+
+    #[test]
+    fn the_locked_versions_match_the_crate_information() -> Result<()> {
+        let sb = Sandbox::new()?;
+        let crates = sb.crates()?;
+        let lock = sb.cargo_lock()?;
+
+        let rand = crates.iter().find(|c| c.name == "rand").expect("rand is not available");
+        let locked = lock.resolve("rand").expect("rand is not locked");
+        assert_eq!(locked.version, rand.version);
+        Ok(())
+    }
+
     #[test]
     fn network_connections_are_disabled() {
         let code = r#"
@@ -1299,7 +5941,70 @@ mod test {
         let sb = Sandbox::new().expect("Unable to create sandbox");
         let resp = sb.execute(&req).expect("Unable to execute code");
 
-        assert!(resp.stderr.contains("Killed"));
+        assert!(resp.stderr.contains("Terminated"), "stderr was: {}", resp.stderr);
+    }
+
+    #[test]
+    fn programs_can_flush_their_output_when_time_runs_out() {
+        let code = r#"
+            use std::{sync::atomic::{AtomicBool, Ordering}, thread, time::Duration};
+
+            static STOP: AtomicBool = AtomicBool::new(false);
+
+            extern "C" fn stop(_: i32) { STOP.store(true, Ordering::SeqCst) }
+
+            extern "C" { fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize; }
+
+            fn main() {
+                const SIGTERM: i32 = 15;
+                unsafe { signal(SIGTERM, stop) };
+
+                println!("started");
+                while !STOP.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                println!("cleaned up");
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert_eq!(resp.stdout, "started\ncleaned up\n", "stderr was: {}", resp.stderr);
+    }
+
+    #[test]
+    fn programs_ignoring_the_grace_period_are_killed() {
+        let code = r#"
+            extern "C" { fn signal(signum: i32, handler: usize) -> usize; }
+
+            fn main() {
+                const SIGTERM: i32 = 15;
+                const SIG_IGN: usize = 1;
+                unsafe { signal(SIGTERM, SIG_IGN) };
+
+                println!("started");
+                loop {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                }
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert_eq!(resp.stdout, "started\n");
+        assert!(resp.stderr.contains("Killed"), "stderr was: {}", resp.stderr);
     }
 
     #[test]
@@ -1339,6 +6044,31 @@ mod test {
         }
     }
 
+    #[test]
+    fn spawning_too_many_threads_fails_promptly() {
+        let code = r#"
+            use std::{thread, time::Duration};
+
+            fn main() {
+                let threads: Vec<_> = (0..100_000)
+                    .map(|_| thread::spawn(|| thread::sleep(Duration::from_secs(60))))
+                    .collect();
+                println!("{}", threads.len());
+            }
+        "#;
+
+        let req = ExecuteRequest {
+            code: code.to_string(),
+            ..ExecuteRequest::default()
+        };
+
+        let sb = Sandbox::new().expect("Unable to create sandbox");
+        let resp = sb.execute(&req).expect("Unable to execute code");
+
+        assert!(!resp.success);
+        assert_eq!(resp.error_category, Some(ErrorCategory::ThreadLimit), "stderr was: {}", resp.stderr);
+    }
+
     #[test]
     fn number_of_pids_is_limited() {
         let forkbomb = r##"
@@ -0,0 +1,110 @@
+// Warns about requests that take much longer than usual so that
+// pathological snippets can be spotted. Only the method and path are
+// reported; the query string and body may contain the user's code.
+
+use iron::{prelude::*, AroundMiddleware, Handler};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowRequest {
+    pub method: String,
+    pub path: String,
+    pub duration: Duration,
+}
+
+pub struct SlowRequestLogger<F = fn(&SlowRequest)> {
+    threshold: Duration,
+    report: F,
+}
+
+impl SlowRequestLogger {
+    pub fn new(threshold: Duration) -> Self {
+        SlowRequestLogger { threshold, report: log_slow_request }
+    }
+}
+
+fn log_slow_request(req: &SlowRequest) {
+    log::warn!("Slow request: {} {} took {} ms", req.method, req.path, req.duration.as_millis());
+}
+
+impl<F> AroundMiddleware for SlowRequestLogger<F>
+where
+    F: Fn(&SlowRequest) + Send + Sync + 'static,
+{
+    fn around(self, handler: Box<dyn Handler>) -> Box<dyn Handler> {
+        Box::new(SlowRequestHandler { logger: self, handler })
+    }
+}
+
+struct SlowRequestHandler<F> {
+    logger: SlowRequestLogger<F>,
+    handler: Box<dyn Handler>,
+}
+
+impl<F> Handler for SlowRequestHandler<F>
+where
+    F: Fn(&SlowRequest) + Send + Sync + 'static,
+{
+    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
+        let start = Instant::now();
+        let response = self.handler.handle(req);
+        let duration = start.elapsed();
+
+        if duration >= self.logger.threshold {
+            (self.logger.report)(&SlowRequest {
+                method: req.method.to_string(),
+                path: format!("/{}", req.url.path().join("/")),
+                duration,
+            });
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use iron::{status, Chain, Iron};
+    use std::{
+        io::prelude::*,
+        net::TcpStream,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    #[test]
+    fn slow_requests_are_reported_without_the_query() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+
+        let handler = |req: &mut Request<'_, '_>| {
+            if req.url.path() == ["slow"] {
+                thread::sleep(Duration::from_millis(200));
+            }
+            Ok(Response::with((status::Ok, "ok")))
+        };
+        let mut chain = Chain::new(handler);
+        let seen = reported.clone();
+        chain.link_around(SlowRequestLogger {
+            threshold: Duration::from_millis(100),
+            report: move |req: &SlowRequest| seen.lock().unwrap().push(req.clone()),
+        });
+
+        let mut listening = Iron::new(chain).http("127.0.0.1:0").expect("Unable to start server");
+
+        for request in &["GET /fast HTTP/1.0\r\n\r\n", "GET /slow?code=secret HTTP/1.0\r\n\r\n"] {
+            let mut stream = TcpStream::connect(listening.socket).unwrap();
+            stream.write_all(request.as_bytes()).unwrap();
+            stream.read_to_string(&mut String::new()).unwrap();
+        }
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1, "reported: {:?}", reported);
+        assert_eq!(reported[0].method, "GET");
+        assert_eq!(reported[0].path, "/slow");
+        assert!(reported[0].duration >= Duration::from_millis(200));
+
+        // Dropping the server would wait for it to stop, which it never does
+        listening.close().unwrap();
+    }
+}
@@ -0,0 +1,210 @@
+// Problems with the server's configuration are reported to the
+// operator with a hint about how to fix them, instead of a panic.
+
+use snafu::{ResultExt, Snafu};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    #[snafu(display("{} must be set to {}", name, purpose))]
+    VariableMissing { name: &'static str, purpose: &'static str },
+    #[snafu(display("The UI root {} cannot be read: {}. Build the frontend or set PLAYGROUND_UI_ROOT to the directory containing its output", path.display(), source))]
+    UiRootUnreadable { path: PathBuf, source: io::Error },
+    #[snafu(display("The UI root {} is not a directory. Set PLAYGROUND_UI_ROOT to the directory containing the frontend's output", path.display()))]
+    UiRootNotDirectory { path: PathBuf },
+    #[snafu(display("Unable to read the default snippet {} set by PLAYGROUND_DEFAULT_SNIPPET: {}", path.display(), source))]
+    DefaultSnippetUnreadable { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to read the examples in {} set by PLAYGROUND_EXAMPLES_DIR: {}", path.display(), source))]
+    ExamplesUnreadable { path: PathBuf, source: io::Error },
+    #[snafu(display("Unable to open the access log {} set by PLAYGROUND_LOG_FILE: {}", path, source))]
+    AccessLogUnwritable { path: String, source: io::Error },
+    #[snafu(display("Unable to read the configuration file {} set by PLAYGROUND_CONFIG: {}", path.display(), source))]
+    ConfigFileUnreadable { path: PathBuf, source: io::Error },
+    #[snafu(display("The configuration file {} is not valid TOML: {}", path.display(), source))]
+    ConfigFileInvalid { path: PathBuf, source: toml::de::Error },
+    #[snafu(display("The configuration file {} sets {} to a table or array; only strings, numbers, and booleans are allowed", path.display(), key))]
+    ConfigValueUnsupported { path: PathBuf, key: String },
+    #[snafu(display("PLAYGROUND_ASSET_HEADERS must be a JSON object of header names and values: {}", source))]
+    AssetHeadersInvalid { source: serde_json::Error },
+    #[snafu(display("PLAYGROUND_ASSET_HEADERS contains {:?}, which is not a valid header name", name))]
+    AssetHeaderNameInvalid { name: String },
+    #[snafu(display("PLAYGROUND_ASSET_HEADERS sets {} to a value containing control or non-ASCII characters", name))]
+    AssetHeaderValueInvalid { name: String },
+    #[snafu(display("PLAYGROUND_HASHED_ASSET_PATTERN is not a valid regular expression: {}", source))]
+    HashedAssetPatternInvalid { source: regex::Error },
+    #[snafu(display("PLAYGROUND_MAX_THREADS must be a number of processes greater than zero, not {:?}", value))]
+    MaxThreadsInvalid { value: String },
+    #[snafu(display("Unable to listen on {}:{}; check PLAYGROUND_UI_ADDRESS and PLAYGROUND_UI_PORT: {}", address, port, source))]
+    Listen { address: String, port: u16, source: iron::error::HttpError },
+}
+
+pub type Result<T, E = Error> = ::std::result::Result<T, E>;
+
+pub fn ui_root(value: Option<OsString>) -> Result<PathBuf> {
+    let path = PathBuf::from(value.ok_or(Error::VariableMissing {
+        name: "PLAYGROUND_UI_ROOT",
+        purpose: "the path to the HTML, CSS, and Javascript files",
+    })?);
+
+    let metadata = fs::metadata(&path).context(UiRootUnreadable { path: &path })?;
+    if !metadata.is_dir() {
+        return UiRootNotDirectory { path }.fail();
+    }
+
+    Ok(path)
+}
+
+/// Whether the UI root contains the page served for `/`. The server
+/// still starts without it, as the frontend may be built afterwards.
+pub fn ui_index_exists(root: &Path) -> bool {
+    root.join(UI_INDEX).is_file()
+}
+
+pub const UI_INDEX: &str = "index.html";
+
+/// Reads a TOML file whose keys are the names of environment
+/// variables, such as `PLAYGROUND_UI_PORT = 5000`.
+pub fn config_file(path: &Path) -> Result<BTreeMap<String, String>> {
+    let contents = fs::read_to_string(path).context(ConfigFileUnreadable { path })?;
+    let table: toml::value::Table = toml::from_str(&contents).context(ConfigFileInvalid { path })?;
+
+    table
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => return ConfigValueUnsupported { path, key }.fail(),
+            };
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// The values from the configuration file that should be applied;
+/// anything already set in the environment takes precedence.
+pub fn config_overrides(
+    values: BTreeMap<String, String>,
+    is_set: impl Fn(&str) -> bool,
+) -> impl Iterator<Item = (String, String)> {
+    values.into_iter().filter(move |(key, _)| !is_set(key))
+}
+
+/// Reads headers such as `{"Cross-Origin-Opener-Policy": "same-origin"}`
+pub fn asset_headers(value: Option<String>) -> Result<Vec<(String, String)>> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(Vec::new()),
+    };
+    let headers: BTreeMap<String, String> = serde_json::from_str(&value).context(AssetHeadersInvalid)?;
+
+    for (name, value) in &headers {
+        if name.is_empty() || !name.bytes().all(is_header_name_byte) {
+            return AssetHeaderNameInvalid { name: name.clone() }.fail();
+        }
+        if !value.bytes().all(is_header_value_byte) {
+            return AssetHeaderValueInvalid { name: name.clone() }.fail();
+        }
+    }
+
+    Ok(headers.into_iter().collect())
+}
+
+// The token characters from RFC 7230
+fn is_header_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+fn is_header_value_byte(b: u8) -> bool {
+    b == b'\t' || (b' '..=b'~').contains(&b)
+}
+
+/// Matched against the last segment of the path of each UI file
+pub fn hashed_asset_pattern(value: Option<String>) -> Result<regex::Regex> {
+    let pattern = value.unwrap_or_else(|| crate::immutable_assets::DEFAULT_PATTERN.to_string());
+    regex::Regex::new(&pattern).context(HashedAssetPatternInvalid)
+}
+
+/// Docker treats a pids limit of zero as no limit at all, so it is
+/// rejected along with values that are not numbers.
+pub fn check_max_threads(value: Option<String>) -> Result<()> {
+    match value {
+        Some(value) => match value.parse::<u32>() {
+            Ok(max) if max > 0 => Ok(()),
+            _ => MaxThreadsInvalid { value }.fail(),
+        },
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn a_missing_ui_root_names_the_variable() {
+        let err = ui_root(None).unwrap_err();
+
+        assert_eq!(err.to_string(), "PLAYGROUND_UI_ROOT must be set to the path to the HTML, CSS, and Javascript files");
+    }
+
+    #[test]
+    fn a_nonexistent_ui_root_names_the_path() {
+        let err = ui_root(Some("/does/not/exist".into())).unwrap_err();
+
+        assert!(err.to_string().starts_with("The UI root /does/not/exist cannot be read"), "was: {}", err);
+    }
+
+    #[test]
+    fn the_environment_overrides_the_config_file() {
+        let dir = tempdir::TempDir::new("playground-config").unwrap();
+        let path = dir.path().join("playground.toml");
+        fs::write(&path, "PLAYGROUND_UI_PORT = 8080\nPLAYGROUND_UI_ADDRESS = \"0.0.0.0\"\n").unwrap();
+
+        let values = config_file(&path).unwrap();
+        let applied: Vec<_> = config_overrides(values, |key| key == "PLAYGROUND_UI_ADDRESS").collect();
+
+        assert_eq!(applied, [("PLAYGROUND_UI_PORT".to_string(), "8080".to_string())]);
+    }
+
+    #[test]
+    fn asset_headers_are_validated() {
+        let headers = asset_headers(Some(r#"{"Cross-Origin-Opener-Policy": "same-origin"}"#.into())).unwrap();
+        assert_eq!(headers, [("Cross-Origin-Opener-Policy".to_string(), "same-origin".to_string())]);
+
+        let err = asset_headers(Some(r#"{"Bad Name": "x"}"#.into())).unwrap_err();
+        assert_eq!(err.to_string(), r#"PLAYGROUND_ASSET_HEADERS contains "Bad Name", which is not a valid header name"#);
+
+        let err = asset_headers(Some(r#"{"X-Injected": "a\r\nSet-Cookie: b"}"#.into())).unwrap_err();
+        assert!(err.to_string().contains("sets X-Injected to a value"), "was: {}", err);
+    }
+
+    #[test]
+    fn max_threads_must_limit_something() {
+        assert!(check_max_threads(None).is_ok());
+        assert!(check_max_threads(Some("256".into())).is_ok());
+
+        let err = check_max_threads(Some("0".into())).unwrap_err();
+        assert_eq!(err.to_string(), r#"PLAYGROUND_MAX_THREADS must be a number of processes greater than zero, not "0""#);
+        assert!(check_max_threads(Some("lots".into())).is_err());
+    }
+
+    #[test]
+    fn config_file_tables_are_rejected() {
+        let dir = tempdir::TempDir::new("playground-config").unwrap();
+        let path = dir.path().join("playground.toml");
+        fs::write(&path, "[server]\nport = 8080\n").unwrap();
+
+        let err = config_file(&path).unwrap_err();
+
+        assert!(err.to_string().contains("sets server to a table"), "was: {}", err);
+    }
+}
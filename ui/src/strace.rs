@@ -0,0 +1,72 @@
+// `strace -c` summarizes the system calls a program made as a table,
+// such as:
+//
+// % time     seconds  usecs/call     calls    errors syscall
+// ------ ----------- ----------- --------- --------- ----------------
+//  52.17    0.000024           3         8           mmap
+//  47.83    0.000022           5         4         1 openat
+// ------ ----------- ----------- --------- --------- ----------------
+// 100.00    0.000046                    12         1 total
+//
+// The errors column is left empty for calls that never failed.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Syscall {
+    pub name: String,
+    pub calls: u64,
+    pub errors: u64,
+}
+
+pub fn parse(summary: &str) -> Vec<Syscall> {
+    summary.lines().filter_map(parse_row).collect()
+}
+
+// The header, separators and total don't start with a percentage
+fn parse_row(line: &str) -> Option<Syscall> {
+    let columns: Vec<_> = line.split_whitespace().collect();
+
+    let (time, calls, errors, name) = match *columns.as_slice() {
+        [time, _, _, calls, name] => (time, calls, "0", name),
+        [time, _, _, calls, errors, name] => (time, calls, errors, name),
+        _ => return None,
+    };
+
+    time.parse::<f64>().ok()?;
+    if name == "total" {
+        return None;
+    }
+
+    Some(Syscall {
+        name: name.into(),
+        calls: calls.parse().ok()?,
+        errors: errors.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn each_system_call_is_summarized() {
+        let summary = "\
+% time     seconds  usecs/call     calls    errors syscall
+------ ----------- ----------- --------- --------- ----------------
+ 52.17    0.000024           3         8           mmap
+ 47.83    0.000022           5         4         1 openat
+------ ----------- ----------- --------- --------- ----------------
+100.00    0.000046                    12         1 total
+";
+
+        assert_eq!(parse(summary), vec![
+            Syscall { name: "mmap".into(), calls: 8, errors: 0 },
+            Syscall { name: "openat".into(), calls: 4, errors: 1 },
+        ]);
+    }
+
+    #[test]
+    fn anything_else_is_ignored() {
+        assert_eq!(parse(""), vec![]);
+        assert_eq!(parse("strace: ptrace(PTRACE_TRACEME, ...): Operation not permitted\n"), vec![]);
+    }
+}
@@ -0,0 +1,53 @@
+// Reads the report that `-Z time-passes` prints, such as
+// `time: 0.012; rss: 52MB	parsing`
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pass {
+    pub name: String,
+    pub duration_ms: f64,
+}
+
+/// Lines that are not part of the report are skipped. If nothing
+/// could be parsed, the raw report is still in the standard output.
+pub fn parse(stdout: &str) -> Vec<Pass> {
+    stdout.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<Pass> {
+    let line = line.trim();
+    if !line.starts_with("time:") {
+        return None;
+    }
+
+    let mut parts = line["time:".len()..].splitn(2, ';');
+    let seconds: f64 = parts.next()?.trim().parse().ok()?;
+    let rest = parts.next()?;
+
+    // Newer compilers report the memory before and after the pass
+    // with spaces in between, but the name always comes last.
+    let name = match rest.rfind('\t') {
+        Some(idx) => &rest[idx + 1..],
+        None => rest.split_whitespace().last()?,
+    };
+
+    Some(Pass { name: name.trim().to_string(), duration_ms: seconds * 1000.0 })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn passes_are_parsed() {
+        let stdout = "time: 0.002; rss: 45MB\tparsing\n\
+                      Hello, world!\n  \
+                      time: 0.120; rss: 80MB\titem-bodies checking\n\
+                      time:   0.004; rss:   52MB ->   53MB (   +1MB)\tcodegen_crate\n";
+
+        assert_eq!(parse(stdout), [
+            Pass { name: "parsing".into(), duration_ms: 2.0 },
+            Pass { name: "item-bodies checking".into(), duration_ms: 120.0 },
+            Pass { name: "codegen_crate".into(), duration_ms: 4.0 },
+        ]);
+    }
+}
@@ -0,0 +1,129 @@
+// Holds containers that were started ahead of time so that a request
+// doesn't have to wait for one to start. Each key, such as a channel,
+// has its own containers.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+#[derive(Debug)]
+pub struct WarmPool<K, T> {
+    size: usize,
+    idle: Mutex<HashMap<K, Vec<T>>>,
+}
+
+impl<K, T> WarmPool<K, T>
+where
+    K: Hash + Eq,
+{
+    /// A size of zero disables the pool
+    pub fn new(size: usize) -> Self {
+        WarmPool { size, idle: Mutex::default() }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// `None` when every container is in use
+    pub fn take(&self, key: &K) -> Option<T> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.get_mut(key).and_then(Vec::pop)
+    }
+
+    /// Gives the item back when the pool already has enough
+    pub fn put(&self, key: K, item: T) -> Result<(), T> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let items = idle.entry(key).or_default();
+
+        if items.len() >= self.size {
+            return Err(item);
+        }
+
+        items.push(item);
+        Ok(())
+    }
+}
+
+/// Hands out user ids in turn, so that programs that follow each other
+/// in the same container don't run as the same user
+#[derive(Debug)]
+pub struct UserRotation {
+    first: u32,
+    count: u32,
+    next: AtomicU32,
+}
+
+impl UserRotation {
+    /// An inclusive range, such as `20000-20015`
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut bounds = s.splitn(2, '-').map(|bound| bound.trim().parse::<u32>());
+        let first = bounds.next()?.ok()?;
+        let last = bounds.next()?.ok()?;
+
+        // Running as root would defeat the purpose, and `-1` isn't a user
+        if first == 0 || last < first || last.checked_add(1).is_none() {
+            return None;
+        }
+
+        Some(UserRotation { first, count: last - first + 1, next: AtomicU32::new(0) })
+    }
+
+    pub fn next(&self) -> u32 {
+        self.first + self.next.fetch_add(1, Ordering::Relaxed) % self.count
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn items_are_taken_from_their_own_key() {
+        let pool = WarmPool::new(2);
+        pool.put("stable", 1).unwrap();
+        pool.put("nightly", 2).unwrap();
+
+        assert_eq!(pool.take(&"stable"), Some(1));
+        assert_eq!(pool.take(&"stable"), None);
+        assert_eq!(pool.take(&"nightly"), Some(2));
+    }
+
+    #[test]
+    fn full_pools_give_items_back() {
+        let pool = WarmPool::new(1);
+        pool.put("stable", 1).unwrap();
+
+        assert_eq!(pool.put("stable", 2), Err(2));
+        assert_eq!(pool.take(&"stable"), Some(1));
+    }
+
+    #[test]
+    fn users_are_handed_out_in_turn() {
+        let users = UserRotation::parse("20000-20002").unwrap();
+
+        let ids: Vec<_> = (0..4).map(|_| users.next()).collect();
+        assert_eq!(ids, [20000, 20001, 20002, 20000]);
+    }
+
+    #[test]
+    fn user_ranges_must_leave_out_root() {
+        assert!(UserRotation::parse("0-10").is_none());
+        assert!(UserRotation::parse("20-10").is_none());
+        assert!(UserRotation::parse("20000").is_none());
+        assert!(UserRotation::parse("20000-20000").is_some());
+    }
+
+    #[test]
+    fn disabled_pools_hold_nothing() {
+        let pool = WarmPool::new(0);
+
+        assert_eq!(pool.put("stable", 1), Err(1));
+        assert_eq!(pool.take(&"stable"), None);
+    }
+}
@@ -0,0 +1,107 @@
+// A WebAssembly module is an 8-byte header followed by sections, each
+// of which is an id byte, its size as an unsigned LEB128 number, and
+// then that many bytes. Custom sections (id 0) start with their own
+// name, such as `producers` or `.debug_info`.
+
+const HEADER: &[u8] = b"\0asm\x01\0\0\0";
+
+const SECTION_NAMES: &[&str] = &[
+    "custom", "type", "import", "function", "table", "memory", "global",
+    "export", "start", "element", "code", "data", "datacount",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// Custom sections are named like `custom:producers`
+    pub name: String,
+    pub bytes: u64,
+}
+
+/// Stops at the first section that can't be read, so a truncated or
+/// invalid module returns the sections before the problem.
+pub fn parse(module: &[u8]) -> Vec<Section> {
+    let mut sections = Vec::new();
+
+    if !module.starts_with(HEADER) {
+        return sections;
+    }
+    let mut rest = &module[HEADER.len()..];
+
+    while let Some((&id, after_id)) = rest.split_first() {
+        let (size, after_size) = match leb128(after_id) {
+            Some(size) => size,
+            None => break,
+        };
+        if (after_size.len() as u64) < size {
+            break;
+        }
+        let (payload, after_payload) = after_size.split_at(size as usize);
+
+        let name = match (id, SECTION_NAMES.get(usize::from(id))) {
+            (0, _) => match custom_name(payload) {
+                Some(name) => format!("custom:{}", name),
+                None => break,
+            },
+            (_, Some(name)) => name.to_string(),
+            (_, None) => format!("unknown:{}", id),
+        };
+
+        sections.push(Section { name, bytes: size });
+        rest = after_payload;
+    }
+
+    sections
+}
+
+fn custom_name(payload: &[u8]) -> Option<&str> {
+    let (len, rest) = leb128(payload)?;
+    let name = rest.get(..len as usize)?;
+    std::str::from_utf8(name).ok()
+}
+
+fn leb128(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0;
+
+    // Sizes are at most 32 bits, which takes 5 bytes
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn each_section_is_measured() {
+        let mut module = HEADER.to_vec();
+        // A type section of 4 bytes
+        module.extend(&[1, 4, 1, 0x60, 0, 0]);
+        // A code section of 130 bytes, whose size takes two bytes
+        module.extend(&[10, 0x82, 0x01]);
+        module.extend(vec![0; 130]);
+        // A custom section named `name`
+        module.extend(&[0, 6, 4, b'n', b'a', b'm', b'e', 0]);
+
+        assert_eq!(parse(&module), vec![
+            Section { name: "type".into(), bytes: 4 },
+            Section { name: "code".into(), bytes: 130 },
+            Section { name: "custom:name".into(), bytes: 6 },
+        ]);
+    }
+
+    #[test]
+    fn reading_stops_at_a_truncated_section() {
+        let mut module = HEADER.to_vec();
+        module.extend(&[1, 4, 1, 0x60, 0, 0]);
+        module.extend(&[10, 20, 0, 0]);
+
+        assert_eq!(parse(&module), vec![Section { name: "type".into(), bytes: 4 }]);
+        assert_eq!(parse(b"not wasm"), vec![]);
+    }
+}